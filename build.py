@@ -22,7 +22,7 @@ except ImportError:
 
 def main():
   parser = argparse.ArgumentParser('build tool for project-a')
-  parser.add_argument('--module', action='append', choices=['event', 'render'])
+  parser.add_argument('--module', action='append', choices=['dom', 'event', 'render'])
   parser.add_argument('--out-dir')
   args = parser.parse_args()
 
@@ -52,13 +52,10 @@ def main():
 
   build_cmd = [cargo_cmd, '--color', 'always', 'build', '--lib', '-Zunstable-options', '--out-dir={}'.format(os.path.abspath(args.out_dir)), '--features={}'.format(','.join(map(lambda x: 'c-{}'.format(x), args.module)))]
 
-  process = subprocess.Popen(build_cmd)
+  # The build script regenerates include/project-a.h and include/generated.h for the enabled modules.
+  env = dict(os.environ, FRAMEUI_UPDATE_HEADERS='1')
+  process = subprocess.Popen(build_cmd, env=env)
   process.wait()
 
-  f = open('{}/{}'.format(os.path.dirname(os.path.abspath(__file__)), 'include/generated.h'), 'w')
-  for module in args.module:
-    f.write('#define MODULE_{}\n'.format(module.upper()))
-  f.close()
-
 if __name__ == '__main__':
   sys.exit(main())
\ No newline at end of file
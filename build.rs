@@ -0,0 +1,60 @@
+use std::{env, fs, path::PathBuf};
+
+const MODULES: &[&str] = &["dom", "event", "render"];
+
+/// Files declaring what the C header exports.
+const C_API_FILES: &[&str] = &[
+  "src/dom/c_api.rs",
+  "src/dom/lib.rs",
+  "src/event/c_api.rs",
+  "src/render/c_api.rs",
+  "src/render/lib.rs",
+];
+
+/// Set to copy the generated headers to `include/`, where docgen and C projects read them.
+const UPDATE_HEADERS_VAR: &str = "FRAMEUI_UPDATE_HEADERS";
+
+// Generates `project-a.h` and `generated.h` into `$OUT_DIR/include` whenever a C API module is enabled. Build
+// scripts must not write to the source tree, so they're only copied to `include/` if `FRAMEUI_UPDATE_HEADERS`
+// is set, like `build.py` does.
+fn main() {
+  let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+  println!("cargo:rerun-if-changed=cbindgen.toml");
+  for file in C_API_FILES {
+    println!("cargo:rerun-if-changed={}", file);
+  }
+  println!("cargo:rerun-if-env-changed={}", UPDATE_HEADERS_VAR);
+
+  let modules: Vec<_> = MODULES
+    .iter()
+    .filter(|x| env::var_os(format!("CARGO_FEATURE_C_{}", x.to_uppercase())).is_some())
+    .collect();
+
+  if modules.is_empty() {
+    return;
+  }
+
+  let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap()).join("include");
+  fs::create_dir_all(&out_dir).expect("could not create the header directory");
+
+  let generated: String = modules
+    .iter()
+    .map(|x| format!("#define MODULE_{}\n", x.to_uppercase()))
+    .collect();
+  fs::write(out_dir.join("generated.h"), generated).expect("could not write generated.h");
+
+  let config = cbindgen::Config::from_root_or_default(&root);
+  cbindgen::Builder::new()
+    .with_crate(&root)
+    .with_config(config)
+    .generate()
+    .expect("could not generate the C header")
+    .write_to_file(out_dir.join("project-a.h"));
+
+  if env::var_os(UPDATE_HEADERS_VAR).is_some() {
+    for header in &["generated.h", "project-a.h"] {
+      fs::copy(out_dir.join(header), root.join("include").join(header))
+        .unwrap_or_else(|e| panic!("could not copy {} to include/: {}", header, e));
+    }
+  }
+}
@@ -0,0 +1,322 @@
+//! The CSS domain, editing the rules of the document stylesheet where the source map says they're written.
+//!
+//! Devtools addresses a rule by its stylesheet, the index of the file it's written in, and the range of its
+//! declarations. Only where rules start is known, so the range of a rule is empty at its start until it's
+//! edited, and the ranges following an edit move the way devtools moves them. A reload of the document
+//! replaces the stylesheets devtools knows, the ranges start over then.
+
+use ::dom::{tree::Node, CompiledDocument, Element};
+use devtools_protocol as dt;
+use serde_json::{json, Value};
+use style::Declaration;
+
+use super::find_node;
+
+/// A range in a file, lines and columns start at 0 and count UTF-16 code units like devtools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Range {
+  start_line: i64,
+  start_column: i64,
+  end_line: i64,
+  end_column: i64,
+}
+
+impl Range {
+  fn from_protocol(range: &dt::css::SourceRange) -> Self {
+    Self {
+      start_line: range.start_line,
+      start_column: range.start_column,
+      end_line: range.end_line,
+      end_column: range.end_column,
+    }
+  }
+
+  fn to_json(self) -> Value {
+    json!({
+      "startLine": self.start_line,
+      "startColumn": self.start_column,
+      "endLine": self.end_line,
+      "endColumn": self.end_column,
+    })
+  }
+
+  /// Returns the range of `text` once it replaced this range.
+  fn edited(self, text: &str) -> Self {
+    let mut lines = text.split('\n');
+    let first = lines.next().unwrap_or_default();
+    match lines.last() {
+      Some(last) => Self {
+        end_line: self.start_line + text.matches('\n').count() as i64,
+        end_column: last.encode_utf16().count() as i64,
+        ..self
+      },
+      None => Self {
+        end_line: self.start_line,
+        end_column: self.start_column + first.encode_utf16().count() as i64,
+        ..self
+      },
+    }
+  }
+
+  fn follows(self, other: Self) -> bool {
+    (self.start_line, self.start_column) >= (other.end_line, other.end_column)
+  }
+
+  /// Moves the range after `original` was replaced by `edited`.
+  fn rebased(mut self, original: Self, edited: Self) -> Self {
+    if self.start_line == original.end_line {
+      self.start_column += edited.end_column - original.end_column;
+    }
+    if self.end_line == original.end_line {
+      self.end_column += edited.end_column - original.end_column;
+    }
+
+    self.start_line += edited.end_line - original.end_line;
+    self.end_line += edited.end_line - original.end_line;
+    self
+  }
+}
+
+/// Formats a `CSSStyle`, `text` being the declarations as devtools wrote them if it edited them.
+fn style_json(style_sheet: Option<(usize, Range)>, declarations: &[Declaration], text: Option<&str>) -> Value {
+  let properties: Vec<_> = declarations
+    .iter()
+    .map(|declaration| {
+      json!({
+        "name": declaration.name(),
+        "value": declaration.value(),
+        "text": format!("{};", declaration),
+        "implicit": false,
+        "disabled": false,
+        "parsedOk": true,
+      })
+    })
+    .collect();
+
+  let text = text.map_or_else(
+    || {
+      declarations
+        .iter()
+        .map(|declaration| format!("{};", declaration))
+        .collect::<Vec<_>>()
+        .join(" ")
+    },
+    ToString::to_string,
+  );
+
+  let mut style = json!({
+    "cssProperties": properties,
+    "shorthandEntries": [],
+    "cssText": text,
+  });
+  if let Some((file, range)) = style_sheet {
+    style["styleSheetId"] = json!(file.to_string());
+    style["range"] = range.to_json();
+  }
+
+  style
+}
+
+/// The CSS domain of a connection.
+#[derive(Debug, Default)]
+pub struct Session {
+  /// The file and range of the declarations of each rule of the stylesheet, `None` for rules that can't be
+  /// edited. Empty until devtools enables the domain.
+  ranges: Vec<Option<(usize, Range)>>,
+  /// `CompiledDocument::reloads` when the ranges were located, `None` until devtools enables the domain.
+  reloads: Option<u64>,
+}
+
+impl Session {
+  /// Handles a command of the CSS domain, returning its result and the events to send before it.
+  pub fn handle_command(
+    &mut self,
+    view: &CompiledDocument,
+    cmd: dt::css::Command,
+  ) -> Result<(Value, Vec<Value>), String> {
+    let mut events = self.refresh(view);
+    let result = match cmd {
+      dt::css::Command::Enable(..) => {
+        if self.reloads.is_none() {
+          events = self.enable(view);
+        }
+        json!({})
+      }
+
+      dt::css::Command::GetMatchedStylesForNode(params) => {
+        let node = find_node(view, params.node_id)?;
+        self.matched_styles(view, &node)
+      }
+
+      dt::css::Command::SetStyleTexts(params) => self.set_style_texts(view, &params.edits)?,
+
+      _ => return Err("not supported".to_string()),
+    };
+
+    Ok((result, events))
+  }
+
+  /// Locates the rules again if the document was reloaded since, returning the events replacing the
+  /// stylesheets devtools knows.
+  fn refresh(&mut self, view: &CompiledDocument) -> Vec<Value> {
+    match self.reloads {
+      Some(reloads) if reloads != view.reloads() => {}
+      _ => return Vec::new(),
+    }
+
+    let mut files: Vec<_> = self.ranges.iter().flatten().map(|(file, _)| *file).collect();
+    files.sort_unstable();
+    files.dedup();
+
+    let mut events: Vec<_> = files
+      .into_iter()
+      .map(|file| {
+        json!({
+          "method": "CSS.styleSheetRemoved",
+          "params": { "styleSheetId": file.to_string() },
+        })
+      })
+      .collect();
+    events.extend(self.enable(view));
+    events
+  }
+
+  /// Locates the rules of the stylesheet and returns a `styleSheetAdded` event for each file they're in.
+  fn enable(&mut self, view: &CompiledDocument) -> Vec<Value> {
+    self.reloads = Some(view.reloads());
+    let source_map = match view.source_map() {
+      Some(source_map) => source_map,
+      None => {
+        self.ranges.clear();
+        return Vec::new();
+      }
+    };
+
+    let rules = view.stylesheet.read().unwrap().rules.len();
+    self.ranges = (0..rules)
+      .map(|index| {
+        let location = source_map.rules.get(index).copied().flatten()?;
+        let start_line = location.line as i64 - 1;
+        let start_column = location.column as i64 - 1;
+        let range = Range {
+          start_line,
+          start_column,
+          end_line: start_line,
+          end_column: start_column,
+        };
+        Some((location.file, range))
+      })
+      .collect();
+
+    let mut files: Vec<_> = self.ranges.iter().flatten().map(|(file, _)| *file).collect();
+    files.sort_unstable();
+    files.dedup();
+
+    files
+      .into_iter()
+      .map(|file| {
+        json!({
+          "method": "CSS.styleSheetAdded",
+          "params": {
+            "header": {
+              "styleSheetId": file.to_string(),
+              "frameId": "0",
+              "sourceURL": source_map.files[file],
+              "origin": "regular",
+              "title": source_map.files[file],
+              "disabled": false,
+              "isInline": false,
+              "startLine": 0,
+              "startColumn": 0,
+              "length": 0,
+            },
+          },
+        })
+      })
+      .collect()
+  }
+
+  fn matched_styles(&self, view: &CompiledDocument, node: &Node<Element>) -> Value {
+    let stylesheet = view.stylesheet.read().unwrap();
+    let rules: Vec<_> = stylesheet
+      .rules
+      .iter()
+      .enumerate()
+      .filter(|(_, rule)| rule.matches(node))
+      .map(|(index, rule)| {
+        let location = self.ranges.get(index).copied().flatten();
+        let selectors = rule.selector_text();
+        let mut json_rule = json!({
+          "selectorList": {
+            "selectors": [{ "text": selectors }],
+            "text": selectors,
+          },
+          "origin": "regular",
+          "style": style_json(location, &rule.properties, None),
+        });
+        if let Some((file, _)) = location {
+          json_rule["styleSheetId"] = json!(file.to_string());
+        }
+
+        json!({ "rule": json_rule, "matchingSelectors": [0] })
+      })
+      .collect();
+
+    json!({
+      "inlineStyle": style_json(None, &node.inner().style, None),
+      "matchedCSSRules": rules,
+      "inherited": [],
+      "pseudoElements": [],
+    })
+  }
+
+  /// Applies `CSS.setStyleTexts` edits, each replacing the declarations of the rule at its range.
+  fn set_style_texts(
+    &mut self,
+    view: &CompiledDocument,
+    edits: &[dt::css::StyleDeclarationEdit],
+  ) -> Result<Value, String> {
+    let mut parsed = Vec::with_capacity(edits.len());
+    for edit in edits {
+      let range = Range::from_protocol(&edit.range);
+      let index = self
+        .ranges
+        .iter()
+        .position(|x| *x == Some((edit.style_sheet_id.parse().unwrap_or(usize::MAX), range)))
+        .ok_or_else(|| format!("no rule at {:?} of stylesheet {}", range, edit.style_sheet_id))?;
+
+      let mut input = style::StyleSheet::create_parser_input(&edit.text);
+      let declarations = style::parser::parse_declarations(&mut input).map_err(|e| format!("{:?}", e))?;
+      parsed.push((index, edit, declarations));
+    }
+
+    let mut styles = Vec::with_capacity(parsed.len());
+    {
+      let mut stylesheet = view.stylesheet.write().unwrap();
+      for (index, edit, declarations) in parsed {
+        // A reload may have replaced the stylesheet since the ranges were checked.
+        let rule = stylesheet
+          .rules
+          .get_mut(index)
+          .ok_or_else(|| format!("rule {} of stylesheet {} is gone", index, edit.style_sheet_id))?;
+
+        let (file, original) = self.ranges[index].unwrap();
+        let edited = original.edited(&edit.text);
+        for (other_file, range) in self.ranges.iter_mut().flatten() {
+          if *other_file == file && range.follows(original) {
+            *range = range.rebased(original, edited);
+          }
+        }
+        self.ranges[index] = Some((file, edited));
+
+        styles.push(style_json(Some((file, edited)), &declarations, Some(&edit.text)));
+        rule.properties = declarations;
+      }
+    }
+
+    // Computing the styles again starts from the defaults, so removed declarations revert too.
+    view.invalidate();
+
+    Ok(json!({ "styles": styles }))
+  }
+}
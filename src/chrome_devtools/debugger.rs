@@ -0,0 +1,367 @@
+//! The Debugger domain, backed by the debugger of the document.
+//!
+//! Payloads the protocol types would cover are built as JSON, the shape of `Debugger.paused` and
+//! `Debugger.scriptParsed` changes between protocol revisions more often than the fields devtools reads.
+
+use std::{
+  collections::hash_map::DefaultHasher,
+  hash::{Hash, Hasher},
+};
+
+use ::dom::{
+  debugger::{Breakpoint, Event, Script},
+  source_map::SourceMap,
+  CompiledDocument,
+};
+use devtools_protocol as dt;
+use serde_json::{json, Value};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Object id of the global scope of a paused script, the only scope `Runtime.getProperties` knows.
+const GLOBAL_SCOPE_ID: &str = "scope:global";
+
+/// A script of the document, located in the file devtools shows it in.
+#[derive(Debug, Clone)]
+struct ParsedScript {
+  /// Id devtools knows the script by, the scripts of a reload get new ones.
+  id: usize,
+  script: Script,
+  url: String,
+  /// Line and column the script starts at in `url`, starting at 0.
+  start_line: usize,
+  start_column: usize,
+}
+
+impl ParsedScript {
+  fn new(id: usize, script: Script, source_map: Option<&SourceMap>) -> Self {
+    let (url, start_line, start_column) = match (script.location, source_map) {
+      (Some(location), Some(source_map)) => (
+        source_map.files[location.file].clone(),
+        location.line.saturating_sub(1),
+        location.column.saturating_sub(1),
+      ),
+      _ => (format!("script-{}.rhai", id), 0, 0),
+    };
+
+    Self {
+      id,
+      script,
+      url,
+      start_line,
+      start_column,
+    }
+  }
+
+  /// Returns the location of `line` of the script in its file.
+  fn location(&self, line: usize) -> Value {
+    json!({
+      "scriptId": self.id.to_string(),
+      "lineNumber": self.start_line + line,
+      "columnNumber": if line == 0 { self.start_column } else { 0 },
+    })
+  }
+
+  fn parsed_event(&self) -> Value {
+    let mut hasher = DefaultHasher::new();
+    self.script.source.hash(&mut hasher);
+
+    let lines = self.script.source.lines().count().max(1);
+    json!({
+      "method": "Debugger.scriptParsed",
+      "params": {
+        "scriptId": self.id.to_string(),
+        "url": self.url,
+        "startLine": self.start_line,
+        "startColumn": self.start_column,
+        "endLine": self.start_line + lines - 1,
+        "endColumn": self.script.source.lines().last().map_or(0, str::len),
+        "executionContextId": 1,
+        "hash": format!("{:016x}", hasher.finish()),
+        "length": self.script.source.len(),
+      },
+    })
+  }
+}
+
+fn paused_event(script: &ParsedScript, line: usize, breakpoints: Vec<u64>) -> Value {
+  let location = script.location(line);
+  json!({
+    "method": "Debugger.paused",
+    "params": {
+      "callFrames": [{
+        "callFrameId": "0",
+        "functionName": "",
+        "location": location,
+        "url": script.url,
+        "scopeChain": [{
+          "type": "global",
+          "object": {
+            "type": "object",
+            "className": "Object",
+            "description": "Global",
+            "objectId": GLOBAL_SCOPE_ID,
+          },
+        }],
+        "this": { "type": "undefined" },
+      }],
+      "reason": "other",
+      "hitBreakpoints": breakpoints.iter().map(ToString::to_string).collect::<Vec<_>>(),
+    },
+  })
+}
+
+/// Returns the index of the script with the id `id`.
+fn parse_script_id(scripts: &[ParsedScript], id: &str) -> Result<usize, String> {
+  id.parse()
+    .ok()
+    .and_then(|id| scripts.iter().position(|x| x.id == id))
+    .ok_or_else(|| format!("no script with id {}", id))
+}
+
+/// Locates `scripts` in their files, their ids start at `first_id`.
+fn parse_scripts(scripts: Vec<Script>, first_id: usize, view: &CompiledDocument) -> Vec<ParsedScript> {
+  let source_map = view.source_map();
+  scripts
+    .into_iter()
+    .enumerate()
+    .map(|(index, script)| ParsedScript::new(first_id + index, script, source_map.as_ref()))
+    .collect()
+}
+
+fn parse_breakpoint_id(id: &str) -> Result<u64, String> {
+  id.parse().map_err(|_| format!("no breakpoint with id {}", id))
+}
+
+/// Returns the script and its line at `line` of `url`, moved to the first line the debugger can pause at.
+fn find_line(scripts: &[ParsedScript], url: &str, line: usize) -> Option<(usize, usize)> {
+  scripts.iter().enumerate().find_map(|(index, script)| {
+    if script.url != url || line < script.start_line {
+      return None;
+    }
+
+    script
+      .script
+      .breakable_line(line - script.start_line)
+      .map(|line| (index, line))
+  })
+}
+
+/// The Debugger domain of a connection.
+#[derive(Debug, Default)]
+pub struct Session {
+  /// The scripts sent to devtools, `None` until it enables the debugger.
+  scripts: Option<Vec<ParsedScript>>,
+  /// Id of the next script sent to devtools.
+  next_id: usize,
+}
+
+impl Session {
+  #[must_use]
+  pub fn is_enabled(&self) -> bool {
+    self.scripts.is_some()
+  }
+
+  /// Returns the messages to send devtools for an event of the debugger.
+  pub fn event_messages(&mut self, view: &CompiledDocument, event: Event) -> Vec<String> {
+    let scripts = match &self.scripts {
+      Some(scripts) => scripts,
+      None => return Vec::new(),
+    };
+
+    let messages = match event {
+      // A callback started before a reload may still run the functions of a script that's gone.
+      Event::Paused {
+        script,
+        line,
+        breakpoints,
+      } => scripts
+        .get(script)
+        .map(|script| paused_event(script, line, breakpoints))
+        .into_iter()
+        .collect(),
+      Event::Resumed => vec![json!({ "method": "Debugger.resumed", "params": {} })],
+      Event::ScriptsChanged(scripts) => self.set_scripts(view, scripts),
+    };
+
+    messages.iter().map(ToString::to_string).collect()
+  }
+
+  /// Replaces the scripts, returning the `Debugger.scriptParsed` events announcing them.
+  fn set_scripts(&mut self, view: &CompiledDocument, scripts: Vec<Script>) -> Vec<Value> {
+    let scripts = parse_scripts(scripts, self.next_id, view);
+    self.next_id += scripts.len();
+
+    let events = scripts.iter().map(ParsedScript::parsed_event).collect();
+    self.scripts = Some(scripts);
+    events
+  }
+
+  /// Handles a command of the Debugger domain, returning its result and the events to send before it.
+  /// `events` receives the events of the debugger once it's enabled.
+  pub fn handle_command(
+    &mut self,
+    view: &CompiledDocument,
+    events: &UnboundedSender<Event>,
+    cmd: dt::debugger::Command,
+  ) -> Result<(Value, Vec<Value>), String> {
+    if let dt::debugger::Command::Enable(..) = cmd {
+      let mut parsed_events = Vec::new();
+      if self.scripts.is_none() {
+        let events = events.clone();
+        // The connection is gone if sending fails, it disables the debugger once it notices.
+        let scripts = view.enable_debugger(move |event| drop(events.send(event)));
+        parsed_events = self.set_scripts(view, scripts);
+      }
+
+      return Ok((json!({ "debuggerId": "frameui" }), parsed_events));
+    }
+
+    let parsed = self
+      .scripts
+      .as_ref()
+      .ok_or_else(|| "the debugger isn't enabled".to_string())?;
+    let debugger = view.debugger();
+
+    let result = match cmd {
+      dt::debugger::Command::Enable(..) => unreachable!(),
+
+      dt::debugger::Command::Disable(..) => {
+        view.disable_debugger();
+        self.scripts = None;
+        json!({})
+      }
+
+      dt::debugger::Command::GetScriptSource(params) => {
+        let index = parse_script_id(parsed, &params.script_id)?;
+        json!({ "scriptSource": parsed[index].script.source })
+      }
+
+      dt::debugger::Command::SetBreakpointByUrl(params) => {
+        let url = params
+          .url
+          .ok_or_else(|| "only breakpoints by url are supported".to_string())?;
+        let line = params.line_number.max(0) as usize;
+        match find_line(parsed, &url, line) {
+          Some((script, line)) => {
+            let id = debugger.add_breakpoint(Breakpoint { script, line });
+            json!({
+              "breakpointId": id.to_string(),
+              "locations": [parsed[script].location(line)],
+            })
+          }
+          None => return Err(format!("no line to break at from {}:{}", url, line)),
+        }
+      }
+
+      dt::debugger::Command::SetBreakpoint(params) => {
+        let script = parse_script_id(parsed, &params.location.script_id)?;
+        let line = (params.location.line_number.max(0) as usize).saturating_sub(parsed[script].start_line);
+        let line = parsed[script]
+          .script
+          .breakable_line(line)
+          .ok_or_else(|| format!("no line to break at in script {}", script))?;
+
+        let id = debugger.add_breakpoint(Breakpoint { script, line });
+        json!({
+          "breakpointId": id.to_string(),
+          "actualLocation": parsed[script].location(line),
+        })
+      }
+
+      dt::debugger::Command::RemoveBreakpoint(params) => {
+        debugger.remove_breakpoint(parse_breakpoint_id(&params.breakpoint_id)?);
+        json!({})
+      }
+
+      dt::debugger::Command::SetBreakpointsActive(params) => {
+        debugger.set_breakpoints_active(params.active);
+        json!({})
+      }
+
+      dt::debugger::Command::GetPossibleBreakpoints(params) => {
+        let script = parse_script_id(parsed, &params.start.script_id)?;
+        let start_line = parsed[script].start_line;
+        let start = (params.start.line_number.max(0) as usize).saturating_sub(start_line);
+        let end = params.end.map_or(usize::MAX, |end| {
+          (end.line_number.max(0) as usize).saturating_sub(start_line)
+        });
+
+        let locations: Vec<_> = parsed[script]
+          .script
+          .breakable_lines
+          .iter()
+          .filter(|line| **line >= start && **line < end)
+          .map(|line| parsed[script].location(*line))
+          .collect();
+
+        json!({ "locations": locations })
+      }
+
+      dt::debugger::Command::Pause(..) => {
+        debugger.pause();
+        json!({})
+      }
+
+      dt::debugger::Command::Resume(..) | dt::debugger::Command::StepOut(..) => {
+        debugger.resume(false);
+        json!({})
+      }
+
+      dt::debugger::Command::StepOver(..) | dt::debugger::Command::StepInto(..) => {
+        debugger.resume(true);
+        json!({})
+      }
+
+      _ => return Err("not supported".to_string()),
+    };
+
+    Ok((result, Vec::new()))
+  }
+}
+
+/// Handles `Runtime.getProperties`, listing the variables of the global scope of the paused script.
+pub fn get_properties(view: &CompiledDocument, object_id: &str) -> Result<Value, String> {
+  if object_id != GLOBAL_SCOPE_ID {
+    return Err(format!("no object with id {}", object_id));
+  }
+
+  let globals = view
+    .debugger()
+    .paused_globals()
+    .ok_or_else(|| "the debugger isn't paused".to_string())?;
+
+  let properties: Vec<_> = globals
+    .iter()
+    .map(|(name, value)| {
+      json!({
+        "name": name,
+        "value": remote_object(value),
+        "writable": false,
+        "configurable": false,
+        "enumerable": true,
+        "isOwn": true,
+      })
+    })
+    .collect();
+
+  Ok(json!({ "result": properties }))
+}
+
+fn remote_object(value: &rhai::Dynamic) -> Value {
+  match rhai::serde::from_dynamic(value) {
+    Ok(Value::Null) => json!({ "type": "undefined" }),
+    Ok(value @ Value::Bool(..)) => json!({ "type": "boolean", "value": value }),
+    Ok(value @ Value::Number(..)) => json!({ "type": "number", "value": value, "description": value.to_string() }),
+    Ok(value @ Value::String(..)) => json!({ "type": "string", "value": value }),
+    Ok(value) => json!({
+      "type": "object",
+      "className": value.to_string(),
+      "description": value.to_string(),
+    }),
+    Err(..) => json!({
+      "type": "object",
+      "className": value.type_name(),
+      "description": value.to_string(),
+    }),
+  }
+}
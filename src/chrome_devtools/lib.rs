@@ -3,19 +3,22 @@ use std::{sync::Arc, thread};
 use devtools_protocol as dt;
 
 use dashmap::DashMap;
-use futures_util::sink::SinkExt;
+use futures_util::{sink::SinkExt, stream::StreamExt};
 use log::{error, trace};
 use tokio::{
   net::{TcpListener, TcpStream, ToSocketAddrs},
   runtime::Runtime,
-  stream::StreamExt,
+  sync::mpsc,
 };
 use tungstenite::{
   handshake::server::{Request, Response},
   protocol::Message,
 };
 
-use ::dom::{CompiledDocument, Element, ElementData};
+mod css;
+mod debugger;
+
+use ::dom::{source_map::SourceMap, tree::Node, CompiledDocument, Element, ElementData, RawAttributeValue};
 
 #[derive(PartialEq, Debug)]
 #[repr(u16)]
@@ -35,34 +38,54 @@ enum NodeType {
   Notation = 12, // historical
 }
 
-fn node_from_element(node_id: NodeId, parent: Option<NodeId>, elements: &Arena<Element>) -> dt::dom::Node {
-  let children: Vec<dt::dom::Node> = node_id
-    .children(elements)
-    .map(|x| node_from_element(x, Some(node_id), elements))
+fn node_from_element(
+  node: &Node<Element>,
+  source_map: Option<&SourceMap>,
+  next_id: &mut i64,
+  parent: Option<i64>,
+) -> dt::dom::Node {
+  // Node ids are the pre-order index of the node, matching `Node::descendants`.
+  let node_id = *next_id;
+  *next_id += 1;
+
+  let children: Vec<dt::dom::Node> = node
+    .children()
+    .map(|x| node_from_element(&x, source_map, next_id, Some(node_id)))
     .collect();
 
-  let node = elements.get(node_id).unwrap().get();
+  let node = node.inner();
 
   let node_name = node.get_local_name().to_string();
 
-  let node_type = match node.data {
-    ElementData::Root(..) => NodeType::Document,
-    _ => NodeType::Element,
+  let (node_type, node_value) = match &node.data {
+    ElementData::Root(..) => (NodeType::Document, String::new()),
+    ElementData::Text(text) => (NodeType::Text, text.text.clone()),
+    _ => (NodeType::Element, String::new()),
   };
 
-  let node_value = String::new();
+  // Elements show where they come from, the component they were expanded from and their source location.
+  let mut attributes = Vec::new();
+  if let Some(component) = &node.component {
+    attributes.push("component".to_string());
+    attributes.push(component.clone());
+  }
+  if let (Some(source_map), Some(location)) = (source_map, node.source) {
+    attributes.push("source".to_string());
+    attributes.push(source_map.describe(location));
+  }
+  let attributes = if attributes.is_empty() { None } else { Some(attributes) };
 
   dt::dom::Node {
-    node_id: Into::<usize>::into(node_id) as i64,
-    backend_node_id: Into::<usize>::into(node_id) as i64,
+    node_id,
+    backend_node_id: node_id,
     node_type: node_type as i64,
     local_name: node_name.clone(),
     node_name,
     node_value,
     children: Some(children),
-    parent_id: parent.map(|x| Into::<usize>::into(x) as i64),
+    parent_id: parent,
 
-    attributes: None,
+    attributes,
     base_url: None,
     child_node_count: None,
     content_document: None,
@@ -85,6 +108,153 @@ fn node_from_element(node_id: NodeId, parent: Option<NodeId>, elements: &Arena<E
   }
 }
 
+fn find_node(view: &CompiledDocument, node_id: dt::dom::NodeId) -> Result<Node<Element>, String> {
+  if node_id < 0 {
+    return Err(format!("no node with id {}", node_id));
+  }
+
+  view
+    .root
+    .descendants()
+    .nth(node_id as usize)
+    .ok_or_else(|| format!("no node with id {}", node_id))
+}
+
+fn box_edges(get: impl Fn(yoga::Edge) -> f32) -> Box<dt::frameui::BoxEdges> {
+  Box::new(dt::frameui::BoxEdges {
+    top: f64::from(get(yoga::Edge::Top)),
+    right: f64::from(get(yoga::Edge::Right)),
+    bottom: f64::from(get(yoga::Edge::Bottom)),
+    left: f64::from(get(yoga::Edge::Left)),
+  })
+}
+
+fn computed_style_properties(computed: &style::ComputedStyle) -> Vec<dt::frameui::ComputedStyleProperty> {
+  // Formatted by the style crate, so devtools shows values the way `--emit xml` writes them.
+  computed
+    .declarations()
+    .into_iter()
+    .map(|declaration| dt::frameui::ComputedStyleProperty {
+      name: declaration.name().to_string(),
+      value: declaration.value(),
+    })
+    .collect()
+}
+
+fn handle_frameui_command(
+  view: &CompiledDocument,
+  cmd: dt::frameui::Command,
+) -> Result<dt::frameui::CommandResult, String> {
+  Ok(match cmd {
+    dt::frameui::Command::GetLayout(params) => {
+      let node = find_node(view, params.node_id)?;
+      let el = node.inner();
+      let render = el.get_render();
+
+      dt::frameui::CommandResult::GetLayout {
+        layout: Box::new(dt::frameui::Layout {
+          left: f64::from(render.left),
+          top: f64::from(render.top),
+          width: f64::from(render.width),
+          height: f64::from(render.height),
+          right: f64::from(el.yg.get_right()),
+          bottom: f64::from(el.yg.get_bottom()),
+          direction: match el.yg.get_direction() {
+            yoga::Direction::RTL => "rtl".to_string(),
+            _ => "ltr".to_string(),
+          },
+          margin: box_edges(|edge| el.yg.get_layout_margin(edge)),
+          border: box_edges(|edge| el.yg.get_layout_border(edge)),
+          padding: box_edges(|edge| el.yg.get_layout_padding(edge)),
+          dirty: el.yg.is_dirty(),
+        }),
+      }
+    }
+
+    dt::frameui::Command::GetComputedStyle(params) => {
+      let node = find_node(view, params.node_id)?;
+      let properties = computed_style_properties(&node.inner().computed);
+      dt::frameui::CommandResult::GetComputedStyle { properties }
+    }
+
+    dt::frameui::Command::GetScope(..) => {
+      // A script paused by the debugger holds the scope, its variables are the ones from before it ran.
+      let variables: Vec<(String, rhai::Dynamic)> = match view.scope.try_read() {
+        Ok(scope) => scope.iter().map(|(name, value)| (name.to_string(), value)).collect(),
+        Err(..) => view
+          .debugger()
+          .paused_globals()
+          .ok_or_else(|| "the scope is in use".to_string())?,
+      };
+
+      let variables = variables
+        .into_iter()
+        .map(|(name, value)| dt::frameui::ScopeVariable {
+          name,
+          r#type: value.type_name().to_string(),
+          value: rhai::serde::from_dynamic(&value).unwrap_or_else(|_| serde_json::Value::String(value.to_string())),
+        })
+        .collect();
+
+      dt::frameui::CommandResult::GetScope { variables }
+    }
+
+    dt::frameui::Command::GetScripts(..) => {
+      let mut scripts = Vec::new();
+      for (node_id, node) in view.root.descendants().enumerate() {
+        let el = node.inner();
+        let attributes = [("class", &el.raw_attributes.class), ("id", &el.raw_attributes.id)];
+        for (attribute, value) in attributes.iter() {
+          if let Some(RawAttributeValue::Script { script, ast, .. }) = value {
+            scripts.push(dt::frameui::Script {
+              node_id: node_id as dt::dom::NodeId,
+              attribute: attribute.to_string(),
+              source: script.clone(),
+              compiled: ast.is_some(),
+            });
+          }
+        }
+      }
+
+      dt::frameui::CommandResult::GetScripts { scripts }
+    }
+
+    dt::frameui::Command::GetScriptErrors(..) => {
+      let errors = view
+        .script_errors()
+        .errors()
+        .into_iter()
+        .map(|x| dt::frameui::ScriptError {
+          script: x.script,
+          message: x.message,
+          location: x.location,
+        })
+        .collect();
+
+      dt::frameui::CommandResult::GetScriptErrors { errors }
+    }
+  })
+}
+
+/// JSON-RPC "server error" code used for failed commands.
+const SERVER_ERROR: i64 = -32000;
+
+fn command_error(id: u64, message: String) -> serde_json::Result<String> {
+  serde_json::to_string(&dt::CommandError {
+    id,
+    error: dt::CommandErrorData {
+      code: SERVER_ERROR,
+      message,
+    },
+  })
+}
+
+/// What a connection waits for, commands from devtools or events of the debugger to send it.
+enum Input {
+  Message(Result<Message, tungstenite::Error>),
+  Event(::dom::debugger::Event),
+}
+
 pub struct DevTools {
   counter: usize,
   documents: Arc<DashMap<usize, Arc<CompiledDocument>>>,
@@ -128,55 +298,98 @@ impl DevTools {
       }
     };
 
-    match tokio_tungstenite::accept_hdr_async(stream, callback).await {
-      Ok(mut ws_stream) => {
-        while let Some(Ok(msg)) = ws_stream.next().await {
-          if let Message::Text(text) = msg {
-            let msg: Result<dt::Command, _> = serde_json::from_str(&text);
-            trace!("{:#?}", msg);
-
-            match msg {
-              Ok(cmd) => {
-                let id = cmd.id;
-                match cmd.data {
-                  dt::CommandData::DOM(cmd) => match cmd {
-                    dt::dom::Command::GetDocument { .. } => {
-                      let out = {
-                        let view = { Arc::clone(views.get(&idx).unwrap().value()) };
-
-                        // let elements = view.elements.read().unwrap();
-                        let root = node_from_element(view.root, None, &elements);
-
-                        dt::CommandResult {
-                          id,
-                          result: dt::CommandResultData::DOM(dt::dom::CommandResult::GetDocument {
-                            root: Box::new(root),
-                          }),
-                        }
-                      };
-
-                      ws_stream
-                        .send(Message::Text(serde_json::to_string(&out).unwrap()))
-                        .await
-                        .unwrap();
-                    }
-
-                    _ => {}
-                  },
-
-                  _ => {}
-                }
-              }
-
-              Err(e) => {
-                println!("{} {:?}", text, e);
-              }
-            };
+    let ws_stream = match tokio_tungstenite::accept_hdr_async(stream, callback).await {
+      Ok(ws_stream) => ws_stream,
+      Err(e) => return error!("websocket error: {}", e),
+    };
+
+    let view = { Arc::clone(views.get(&idx).unwrap().value()) };
+    let (mut sink, incoming) = ws_stream.split();
+    // Debugger events come from the thread running the scripts, they're sent along with the command results.
+    let (events, event_messages) = mpsc::unbounded_channel();
+    let mut input = futures_util::stream::select(incoming.map(Input::Message), event_messages.map(Input::Event));
+    let mut css = css::Session::default();
+    let mut debugger = debugger::Session::default();
+
+    while let Some(input) = input.next().await {
+      let text = match input {
+        Input::Event(event) => {
+          for text in debugger.event_messages(&view, event) {
+            sink.send(Message::Text(text)).await.unwrap();
           }
+          continue;
         }
-      }
+        Input::Message(Ok(Message::Text(text))) => text,
+        Input::Message(Ok(..)) => continue,
+        Input::Message(Err(..)) => break,
+      };
+
+      let msg: Result<dt::Command, _> = serde_json::from_str(&text);
+      trace!("{:#?}", msg);
+
+      let cmd = match msg {
+        Ok(cmd) => cmd,
+        Err(e) => {
+          println!("{} {:?}", text, e);
+          continue;
+        }
+      };
+
+      let id = cmd.id;
+      let out = match cmd.data {
+        dt::CommandData::DOM(dt::dom::Command::GetDocument { .. }) => {
+          let root = node_from_element(&view.root, view.source_map().as_ref(), &mut 0, None);
+
+          serde_json::to_string(&dt::CommandResult {
+            id,
+            result: dt::CommandResultData::DOM(dt::dom::CommandResult::GetDocument { root: Box::new(root) }),
+          })
+        }
+
+        dt::CommandData::CSS(cmd) => match css.handle_command(&view, cmd) {
+          Ok((result, events)) => {
+            for event in events {
+              sink.send(Message::Text(event.to_string())).await.unwrap();
+            }
+            Ok(serde_json::json!({ "id": id, "result": result }).to_string())
+          }
+          Err(message) => command_error(id, message),
+        },
+
+        dt::CommandData::FrameUI(cmd) => match handle_frameui_command(&view, cmd) {
+          Ok(result) => serde_json::to_string(&dt::CommandResult {
+            id,
+            result: dt::CommandResultData::FrameUI(result),
+          }),
+          Err(message) => command_error(id, message),
+        },
+
+        dt::CommandData::Debugger(cmd) => match debugger.handle_command(&view, &events, cmd) {
+          Ok((result, events)) => {
+            for event in events {
+              sink.send(Message::Text(event.to_string())).await.unwrap();
+            }
+            Ok(serde_json::json!({ "id": id, "result": result }).to_string())
+          }
+          Err(message) => command_error(id, message),
+        },
+
+        dt::CommandData::Runtime(dt::runtime::Command::GetProperties(params)) => {
+          match debugger::get_properties(&view, &params.object_id) {
+            Ok(result) => Ok(serde_json::json!({ "id": id, "result": result }).to_string()),
+            Err(message) => command_error(id, message),
+          }
+        }
+
+        _ => continue,
+      };
+
+      sink.send(Message::Text(out.unwrap())).await.unwrap();
+    }
 
-      Err(e) => error!("websocket error: {}", e),
+    // A script paused by this connection would never resume otherwise.
+    if debugger.is_enabled() {
+      view.disable_debugger();
     }
   }
 
@@ -0,0 +1,198 @@
+//! On-disk cache of the remote resources fetched by the compiler, revalidated with `ETag` and
+//! `Last-Modified` and kept fresh for as long as `Cache-Control` allows.
+
+use std::{
+  collections::hash_map::DefaultHasher,
+  fs,
+  hash::{Hash, Hasher},
+  io,
+  path::{Path, PathBuf},
+  process,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    RwLock,
+  },
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::{
+  header::{HeaderMap, CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+  StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{DiagnosticKind, NetworkPolicy};
+
+struct Settings {
+  dir: Option<PathBuf>,
+  offline: bool,
+}
+
+static SETTINGS: RwLock<Settings> = RwLock::new(Settings {
+  dir: None,
+  offline: false,
+});
+
+/// Caches remote resources in `dir`, nothing is cached without a directory, which is the default.
+pub fn set_cache_dir(dir: Option<PathBuf>) {
+  SETTINGS.write().unwrap().dir = dir;
+}
+
+/// Only serves remote resources from the cache, a resource that isn't cached is an error.
+pub fn set_offline(offline: bool) {
+  SETTINGS.write().unwrap().offline = offline;
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+  url: String,
+  etag: Option<String>,
+  last_modified: Option<String>,
+  /// Seconds since the epoch until which the entry can be used without asking the server.
+  fresh_until: u64,
+}
+
+fn now() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs())
+}
+
+fn header(headers: &HeaderMap, name: reqwest::header::HeaderName) -> Option<String> {
+  headers.get(name).and_then(|x| x.to_str().ok()).map(str::to_string)
+}
+
+/// Returns whether the response can be stored and for how many seconds it stays fresh.
+fn cache_control(headers: &HeaderMap) -> (bool, u64) {
+  let mut store = true;
+  let mut max_age = 0;
+  for directive in header(headers, CACHE_CONTROL).iter().flat_map(|x| x.split(',')) {
+    let directive = directive.trim().to_ascii_lowercase();
+    if directive == "no-store" {
+      store = false;
+    } else if let Some(age) = directive.strip_prefix("max-age=") {
+      max_age = age.trim_matches('"').parse().unwrap_or(0);
+    }
+  }
+
+  // `no-cache` needs no handling, a `max-age` of 0 already revalidates every time.
+  (store, max_age)
+}
+
+struct Paths {
+  entry: PathBuf,
+  body: PathBuf,
+}
+
+fn paths(dir: &Path, url: &Url) -> Paths {
+  let mut hasher = DefaultHasher::new();
+  url.as_str().hash(&mut hasher);
+  let name = format!("{:016x}", hasher.finish());
+
+  Paths {
+    entry: dir.join(format!("{}.json", name)),
+    body: dir.join(format!("{}.body", name)),
+  }
+}
+
+fn load(paths: &Paths, url: &Url) -> Option<(Entry, Vec<u8>)> {
+  let entry: Entry = serde_json::from_slice(&fs::read(&paths.entry).ok()?).ok()?;
+  // Another URL with the same hash.
+  if entry.url != url.as_str() {
+    return None;
+  }
+
+  Some((entry, fs::read(&paths.body).ok()?))
+}
+
+/// Writes `data` to a temporary file renamed to `path`, so compilations running at the same time never read
+/// a partly written file.
+fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+  static NEXT: AtomicUsize = AtomicUsize::new(0);
+  let temp = path.with_extension(format!("{}-{}.tmp", process::id(), NEXT.fetch_add(1, Ordering::SeqCst)));
+
+  fs::write(&temp, data)
+    .and_then(|_| fs::rename(&temp, path))
+    .map_err(|e| {
+      let _ = fs::remove_file(&temp);
+      e
+    })
+}
+
+// The cache is only an optimization, failing to write it isn't an error.
+fn store(dir: &Path, paths: &Paths, entry: &Entry, body: &[u8]) {
+  let _ = fs::create_dir_all(dir)
+    .and_then(|_| write_atomic(&paths.body, body))
+    .and_then(|_| write_atomic(&paths.entry, &serde_json::to_vec(entry).unwrap()));
+}
+
+/// Downloads `url` with the client of `policy`, going through the cache if one is set.
+pub(crate) fn fetch(url: &Url, policy: &NetworkPolicy) -> Result<Vec<u8>, DiagnosticKind<'static>> {
+  let settings = SETTINGS.read().unwrap();
+
+  let dir = match &settings.dir {
+    Some(dir) => dir,
+    None if settings.offline => {
+      return Err(
+        io::Error::new(
+          io::ErrorKind::NotFound,
+          format!("can't fetch `{}` while offline without a cache", url),
+        )
+        .into(),
+      )
+    }
+    None => return Ok(policy.client()?.get(url.clone()).send()?.bytes()?.to_vec()),
+  };
+
+  let paths = paths(dir, url);
+  let cached = load(&paths, url);
+
+  match &cached {
+    Some((entry, body)) if settings.offline || entry.fresh_until > now() => return Ok(body.clone()),
+    None if settings.offline => {
+      return Err(
+        io::Error::new(
+          io::ErrorKind::NotFound,
+          format!("`{}` isn't cached and the compiler is offline", url),
+        )
+        .into(),
+      )
+    }
+    _ => {}
+  }
+
+  let mut request = policy.client()?.get(url.clone());
+  if let Some((entry, _)) = &cached {
+    if let Some(etag) = &entry.etag {
+      request = request.header(IF_NONE_MATCH, etag.as_str());
+    }
+    if let Some(last_modified) = &entry.last_modified {
+      request = request.header(IF_MODIFIED_SINCE, last_modified.as_str());
+    }
+  }
+
+  let response = request.send()?;
+  let (storable, max_age) = cache_control(response.headers());
+
+  if let (StatusCode::NOT_MODIFIED, Some((mut entry, body))) = (response.status(), cached) {
+    entry.fresh_until = now() + max_age;
+    if storable {
+      store(dir, &paths, &entry, &body);
+    }
+    return Ok(body);
+  }
+
+  let success = response.status().is_success();
+  let entry = Entry {
+    url: url.to_string(),
+    etag: header(response.headers(), ETAG),
+    last_modified: header(response.headers(), LAST_MODIFIED),
+    fresh_until: now() + max_age,
+  };
+  let body = response.bytes()?.to_vec();
+
+  if success && storable {
+    store(dir, &paths, &entry, &body);
+  }
+
+  Ok(body)
+}
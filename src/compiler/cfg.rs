@@ -0,0 +1,117 @@
+//! Conditions of `<If cfg="...">` elements, evaluated against the defines of `CompileOptions`.
+//!
+//! The syntax is the one of Rust's `cfg` attribute: `KEY` holds if `KEY` is defined, `KEY='VALUE'` if it's
+//! defined as `VALUE`, and `not(...)`, `all(...)` and `any(...)` combine conditions.
+
+use super::CompileOptions;
+
+/// Evaluates `condition`, returning a description of the problem if it isn't valid.
+pub(crate) fn evaluate(condition: &str, options: &CompileOptions) -> Result<bool, String> {
+  let mut parser = Parser {
+    input: condition,
+    pos: 0,
+  };
+  let result = parser.condition(options)?;
+
+  parser.skip_whitespace();
+  if parser.pos < condition.len() {
+    return Err(format!("unexpected `{}`", &condition[parser.pos..]));
+  }
+
+  Ok(result)
+}
+
+struct Parser<'a> {
+  input: &'a str,
+  pos: usize,
+}
+
+impl<'a> Parser<'a> {
+  fn rest(&self) -> &'a str {
+    &self.input[self.pos..]
+  }
+
+  fn skip_whitespace(&mut self) {
+    let rest = self.rest();
+    self.pos += rest.len() - rest.trim_start().len();
+  }
+
+  fn eat(&mut self, c: char) -> bool {
+    self.skip_whitespace();
+    if self.rest().starts_with(c) {
+      self.pos += c.len_utf8();
+      true
+    } else {
+      false
+    }
+  }
+
+  fn expect(&mut self, c: char) -> Result<(), String> {
+    if self.eat(c) {
+      Ok(())
+    } else {
+      Err(format!("expected `{}`", c))
+    }
+  }
+
+  fn ident(&mut self) -> Result<&'a str, String> {
+    self.skip_whitespace();
+    let rest = self.rest();
+    let len = rest
+      .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == '.'))
+      .unwrap_or(rest.len());
+    if len == 0 {
+      return Err("expected a name".to_string());
+    }
+
+    self.pos += len;
+    Ok(&rest[..len])
+  }
+
+  /// A quoted string, or a bare name since attribute values are already quoted.
+  fn value(&mut self) -> Result<&'a str, String> {
+    self.skip_whitespace();
+    let rest = self.rest();
+    match rest.chars().next() {
+      Some(quote @ '\'') | Some(quote @ '"') => match rest[1..].find(quote) {
+        Some(end) => {
+          self.pos += end + 2;
+          Ok(&rest[1..=end])
+        }
+        None => Err(format!("unterminated string `{}`", rest)),
+      },
+      _ => self.ident(),
+    }
+  }
+
+  fn condition(&mut self, options: &CompileOptions) -> Result<bool, String> {
+    let name = self.ident()?;
+    match name {
+      "not" | "all" | "any" if self.eat('(') => {
+        // Every condition is parsed, so errors are found even if they don't matter to the result.
+        let mut results = Vec::new();
+        while !self.eat(')') {
+          results.push(self.condition(options)?);
+          if !self.eat(',') {
+            self.expect(')')?;
+            break;
+          }
+        }
+
+        match name {
+          "not" if results.len() == 1 => Ok(!results[0]),
+          "not" => Err(format!("`not` takes one condition, found {}", results.len())),
+          "all" => Ok(results.iter().all(|x| *x)),
+          _ => Ok(results.iter().any(|x| *x)),
+        }
+      }
+
+      _ if self.eat('=') => {
+        let value = self.value()?;
+        Ok(options.define(name) == Some(value))
+      }
+
+      _ => Ok(options.define(name).is_some()),
+    }
+  }
+}
@@ -0,0 +1,327 @@
+//! Long explanations of the diagnostic codes, see `DiagnosticKind::code`.
+
+/// Returns the explanation of `code`, with an example of the problem and how to fix it.
+#[must_use]
+pub fn explain(code: &str) -> Option<&'static str> {
+  EXPLANATIONS
+    .iter()
+    .find(|(x, _)| *x == code)
+    .map(|(_, explanation)| *explanation)
+}
+
+const EXPLANATIONS: &[(&str, &str)] = &[
+  (
+    "E0001",
+    r#"An element the compiler doesn't know was used.
+
+Erroneous example:
+
+    <Body>
+      <Div />
+    </Body>
+
+Check the spelling of the element, names are case sensitive. Components have to be
+defined with `<Define>` in the head before they can be used."#,
+  ),
+  (
+    "E0002",
+    r#"A known element was used where it isn't allowed.
+
+Erroneous example:
+
+    <Body>
+      <Style src="main.scss" />
+    </Body>
+
+Move the element where it belongs, here into `<Head>`."#,
+  ),
+  (
+    "E0003",
+    r#"An element has an attribute it doesn't support, or an attribute has an invalid value.
+
+Erroneous example:
+
+    <Style type="less">...</Style>
+
+Remove the attribute or fix its value, here one of `css`, `sass` and `scss`. Unknown
+attributes are notes by default, pass `-D InvalidAttribute` to make them errors."#,
+  ),
+  (
+    "E0004",
+    r#"An element is missing an attribute it requires.
+
+Erroneous example:
+
+    <Include />
+
+Add the attribute:
+
+    <Include src="header.frame" />"#,
+  ),
+  (
+    "E0005",
+    r#"An element that can appear only once was found more than once, or a component was
+defined twice.
+
+Erroneous example:
+
+    <Frame>
+      <Head></Head>
+      <Head></Head>
+    </Frame>
+
+Merge the elements into one, or rename one of the components."#,
+  ),
+  (
+    "E0006",
+    r#"An element is missing a child it requires.
+
+Erroneous example:
+
+    <Frame>
+      <Head></Head>
+    </Frame>
+
+Add the missing element:
+
+    <Frame>
+      <Head></Head>
+      <Body></Body>
+    </Frame>"#,
+  ),
+  (
+    "E0007",
+    r#"A document includes itself, directly or through other documents.
+
+Erroneous example, in `page.frame`:
+
+    <Include src="page.frame" />
+
+The diagnostic lists the documents of the cycle in include order, remove the include that
+closes it."#,
+  ),
+  (
+    "E0008",
+    r#"A component uses itself, directly or through other components.
+
+Erroneous example:
+
+    <Define name="Card">
+      <Card />
+    </Define>
+
+Components are expanded at compile time, so they can't be recursive."#,
+  ),
+  (
+    "E0009",
+    r#"An element that can't have children has a closing tag.
+
+Erroneous example:
+
+    <Include src="header.frame"></Include>
+
+Make it self-closing, which `--fix` does for elements without content:
+
+    <Include src="header.frame" />"#,
+  ),
+  (
+    "E0010",
+    r#"An element that needs a closing tag is self-closing.
+
+Erroneous example:
+
+    <Unstyled />
+
+Give it a closing tag, `--fix` does this for you:
+
+    <Unstyled></Unstyled>"#,
+  ),
+  (
+    "E0011",
+    r#"Text was found where only elements are allowed, e.g. in `<Head>`.
+
+Erroneous example:
+
+    <Head>
+      Title
+    </Head>
+
+Remove the text, or move it into the body."#,
+  ),
+  (
+    "E0012",
+    r#"A CDATA section was found outside of `<Style>` and `<Script>`.
+
+Erroneous example:
+
+    <Body><![CDATA[a < b]]></Body>
+
+Use escaped text instead:
+
+    <Body>a &lt; b</Body>"#,
+  ),
+  (
+    "E0013",
+    r#"An XML declaration was found where it isn't allowed.
+
+Erroneous example:
+
+    <Body>
+      <?xml version="1.0"?>
+    </Body>
+
+A declaration is only allowed as the very first thing in a document, move it there or
+remove it."#,
+  ),
+  (
+    "E0014",
+    r#"A processing instruction was found, documents don't support them.
+
+Erroneous example:
+
+    <?xml-stylesheet href="main.css"?>
+
+Remove it, styles are added with `<Style>`."#,
+  ),
+  (
+    "E0015",
+    r#"A DOCTYPE was found where it isn't allowed.
+
+Erroneous example:
+
+    <Body>
+      <!DOCTYPE frame>
+    </Body>
+
+A DOCTYPE is only allowed once before `<Frame>`, move it there or remove it."#,
+  ),
+  (
+    "E0016",
+    r#"The document ended before all of its elements were closed.
+
+Erroneous example:
+
+    <Frame>
+      <Body>
+
+Close every element that was opened."#,
+  ),
+  (
+    "E0017",
+    r#"A file couldn't be read, e.g. because it doesn't exist.
+
+Check the path, relative paths are resolved against the document they're used in."#,
+  ),
+  (
+    "E0018",
+    r#"A remote resource couldn't be downloaded.
+
+Check the URL and the network connection. With `--offline` only resources that are
+already cached can be used."#,
+  ),
+  (
+    "E0019",
+    r#"The document isn't well-formed XML.
+
+Erroneous example:
+
+    <Unstyled></Body>
+
+Fix the syntax, here by closing the element with its own name."#,
+  ),
+  (
+    "E0020",
+    r#"A URL couldn't be parsed.
+
+Erroneous example:
+
+    <Style src="http://[invalid" />
+
+Fix the URL, or use a path relative to the document."#,
+  ),
+  (
+    "E0021",
+    r#"A stylesheet or `style` attribute has invalid CSS.
+
+Erroneous example:
+
+    <Unstyled style="width: wide"></Unstyled>
+
+Fix the declaration, e.g. `width: 100px`."#,
+  ),
+  (
+    "E0022",
+    r#"A Sass or SCSS stylesheet failed to compile.
+
+The message comes from the Sass compiler, fix the stylesheet accordingly."#,
+  ),
+  (
+    "E0023",
+    r#"A Sass or SCSS stylesheet raised a warning, e.g. with `@warn`.
+
+The message comes from the stylesheet or the Sass compiler."#,
+  ),
+  (
+    "E0024",
+    r#"A script or attribute expression has a syntax error.
+
+Erroneous example:
+
+    <Unstyled :class="['a', 'b'"></Unstyled>
+
+Fix the expression:
+
+    <Unstyled :class="['a', 'b']"></Unstyled>"#,
+  ),
+  (
+    "E0025",
+    r#"A resource was used that the network policy of the compilation doesn't allow reading.
+
+Erroneous example, compiled with `--allow-host example.com`:
+
+    <Style src="https://cdn.example.org/main.css" />
+
+Use a resource from an allowed host, or allow its host or scheme."#,
+  ),
+  (
+    "E0026",
+    r#"The `cfg` condition of an `<If>` isn't valid.
+
+Erroneous example:
+
+    <If cfg="all(debug">...</If>
+
+Conditions are names, which hold if they're defined with `--define`, `name='value'`
+comparisons, and `not(...)`, `all(...)` and `any(...)` combining them:
+
+    <If cfg="all(debug, not(platform='windows'))">...</If>"#,
+  ),
+  (
+    "E0027",
+    r#"A stylesheet rule matches no element of the document.
+
+Erroneous example:
+
+    <Style>.card { width: 100px; }</Style>
+    ...
+    <Body>
+      <Unstyled class="crad"></Unstyled>
+    </Body>
+
+Fix the selector or the element, or remove the rule. Documents with scripts aren't
+checked, as scripts can change the elements at runtime. Rules of stylesheets shared
+through a project manifest aren't checked either."#,
+  ),
+  (
+    "E0028",
+    r#"The XML declaration of a document names an encoding other than UTF-8.
+
+Erroneous example:
+
+    <?xml version="1.0" encoding="ISO-8859-1"?>
+
+Documents are always read as UTF-8, convert the file and fix the declaration:
+
+    <?xml version="1.0" encoding="UTF-8"?>"#,
+  ),
+];
@@ -0,0 +1,147 @@
+//! Turns a compiled document back into a document the compiler reads, for seeing what a `.cframe` contains.
+//!
+//! Compiling loses information, so the output isn't the original source: the stylesheets of the document are
+//! merged into one, includes and components are expanded and embedded resources are only listed.
+
+use std::fmt::Write;
+
+use dom::{tree::Node, CompiledDocument, Element, ElementData, RawAttributeValue};
+use style::Direction;
+
+/// Returns the XML of `doc`, with its stylesheet flattened into a single `<Style>`.
+#[must_use]
+pub fn decompile(doc: &CompiledDocument) -> String {
+  let mut out = String::new();
+  out.push_str("<Frame>\n  <Head>\n");
+
+  let metadata = &doc.metadata;
+  if let Some(title) = &metadata.title {
+    writeln!(out, "    <Title>{}</Title>", escape(title)).unwrap();
+  }
+
+  if metadata.size.is_some() || metadata.resizable.is_some() || metadata.decorations.is_some() {
+    out.push_str("    <Window");
+    if let Some((width, height)) = metadata.size {
+      write!(out, " width=\"{}\" height=\"{}\"", width, height).unwrap();
+    }
+    if let Some(resizable) = metadata.resizable {
+      write!(out, " resizable=\"{}\"", resizable).unwrap();
+    }
+    if let Some(decorations) = metadata.decorations {
+      write!(out, " decorations=\"{}\"", decorations).unwrap();
+    }
+    out.push_str(" />\n");
+  }
+
+  if doc.direction == Direction::RTL {
+    out.push_str("    <Meta name=\"direction\" content=\"rtl\" />\n");
+  }
+
+  let css = doc.stylesheet.read().unwrap().to_css();
+  if !css.is_empty() {
+    writeln!(out, "    <Style type=\"css\">{}</Style>", cdata(&css)).unwrap();
+  }
+
+  for script in doc.scripts.read().unwrap().iter() {
+    writeln!(out, "    <Script>{}</Script>", cdata(script)).unwrap();
+  }
+
+  for (url, data) in &doc.resources {
+    writeln!(out, "    <!-- asset `{}`, {} bytes -->", comment(url), data.len()).unwrap();
+  }
+
+  out.push_str("  </Head>\n  <Body>\n");
+  for child in doc.root.children() {
+    write_element(&mut out, &child, 2);
+  }
+  out.push_str("  </Body>\n</Frame>\n");
+
+  out
+}
+
+fn write_element(out: &mut String, node: &Node<Element>, depth: usize) {
+  let element = node.inner();
+  let indent = "  ".repeat(depth);
+
+  match &element.data {
+    ElementData::Text(text) => {
+      writeln!(out, "{}{}", indent, escape(&text.text)).unwrap();
+      return;
+    }
+
+    // Only the document itself is a root, it's written as `<Body>`.
+    ElementData::Root(..) => return,
+    ElementData::Unstyled(..) => {}
+  }
+
+  if let Some(component) = &element.component {
+    writeln!(out, "{}<!-- component `{}` -->", indent, comment(component)).unwrap();
+  }
+
+  write!(out, "{}<Unstyled", indent).unwrap();
+  let attributes = &element.raw_attributes;
+  for (name, value) in &[
+    ("class", &attributes.class),
+    ("id", &attributes.id),
+    ("style", &attributes.style),
+  ] {
+    match value {
+      Some(RawAttributeValue::Raw { value, .. }) => write!(out, " {}=\"{}\"", name, escape(value)).unwrap(),
+      Some(RawAttributeValue::Script { script, .. }) => write!(out, " :{}=\"{}\"", name, escape(script)).unwrap(),
+      None => {}
+    }
+  }
+  out.push('>');
+
+  let children: Vec<_> = node.children().collect();
+  if children.is_empty() {
+    out.push_str("</Unstyled>\n");
+    return;
+  }
+
+  out.push('\n');
+  for child in children {
+    write_element(out, &child, depth + 1);
+  }
+  writeln!(out, "{}</Unstyled>", indent).unwrap();
+}
+
+fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+/// Wraps `text` in a CDATA section, splitting it where it contains the end of one.
+fn cdata(text: &str) -> String {
+  format!("<![CDATA[{}]]>", text.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+/// Makes `text` safe to put in a comment, which can't contain `--` or end with `-`. A space goes after every
+/// `-` followed by another one or ending the text.
+fn comment(text: &str) -> String {
+  let mut escaped = String::with_capacity(text.len());
+  let mut chars = text.chars().peekable();
+  while let Some(c) = chars.next() {
+    escaped.push(c);
+    if c == '-' && chars.peek().map_or(true, |next| *next == '-') {
+      escaped.push(' ');
+    }
+  }
+
+  escaped
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn comments_never_contain_double_dashes() {
+    assert_eq!(comment("a---b"), "a- - -b");
+    assert_eq!(comment("a-"), "a- ");
+    assert_eq!(comment("a-b"), "a-b");
+  }
+}
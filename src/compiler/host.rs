@@ -0,0 +1,88 @@
+use std::{
+  io,
+  path::PathBuf,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+  },
+};
+
+use dom::CompiledDocument;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use super::{compile, compile_inert, handle_error, CompileOptions, DiagnosticReporter};
+
+/// Keeps a live document in sync with its source file, recompiling and patching it whenever the file changes.
+///
+/// Changes are noticed through the file system events of the directory of the file, editors often save by
+/// replacing it. Reloads are compiled on the rayon pool like `compile_many`, without running the scripts of
+/// the new version, and applied with `CompiledDocument::patch`, so the script scope, scroll offsets and focus
+/// survive them. Failed compilations are reported and leave the document untouched.
+pub struct DocumentHost {
+  doc: Arc<CompiledDocument>,
+  // Watching stops once it's dropped.
+  _watcher: RecommendedWatcher,
+}
+
+impl DocumentHost {
+  /// Compiles `path` with `options` and watches it for changes, `reporter` creates the reporter of each
+  /// compilation.
+  pub fn watch<R, F>(path: impl Into<PathBuf>, options: CompileOptions, reporter: F) -> Result<Self, ()>
+  where
+    R: DiagnosticReporter,
+    F: Fn() -> R + Send + Sync + 'static,
+  {
+    let path = path.into();
+    let mut first_reporter = reporter();
+    let doc = Arc::new(compile(&path, &options, &mut first_reporter)?);
+
+    let path = path.canonicalize().map_err(handle_error!(first_reporter))?;
+    let directory = path.parent().map_or_else(|| path.clone(), |x| x.to_path_buf());
+
+    let options = Arc::new(options);
+    let reporter = Arc::new(reporter);
+    let latest = Arc::new(AtomicU64::new(0));
+    let handler = {
+      let doc = Arc::clone(&doc);
+      move |event: notify::Result<notify::Event>| {
+        let changed = event.map_or(false, |event| {
+          matches!(event.kind, EventKind::Create(..) | EventKind::Modify(..)) && event.paths.contains(&path)
+        });
+        if !changed {
+          return;
+        }
+
+        // Saving can send several events, only the reload of the last one is applied.
+        let generation = latest.fetch_add(1, Ordering::SeqCst) + 1;
+        let doc = Arc::clone(&doc);
+        let path = path.clone();
+        let options = Arc::clone(&options);
+        let reporter = Arc::clone(&reporter);
+        let latest = Arc::clone(&latest);
+        rayon::spawn(move || {
+          if let Ok(new) = compile_inert(&path, &options, &mut reporter()) {
+            if latest.load(Ordering::SeqCst) == generation {
+              doc.patch(new);
+            }
+          }
+        });
+      }
+    };
+
+    let to_io_error = |e: notify::Error| io::Error::new(io::ErrorKind::Other, e);
+    let mut watcher = notify::recommended_watcher(handler)
+      .map_err(to_io_error)
+      .map_err(handle_error!(first_reporter))?;
+    watcher
+      .watch(&directory, RecursiveMode::NonRecursive)
+      .map_err(to_io_error)
+      .map_err(handle_error!(first_reporter))?;
+
+    Ok(Self { doc, _watcher: watcher })
+  }
+
+  #[must_use]
+  pub fn document(&self) -> &Arc<CompiledDocument> {
+    &self.doc
+  }
+}
@@ -1,27 +1,57 @@
 use std::{
+  collections::{BTreeMap, HashMap},
   fmt,
   fs::File,
   io,
   io::{prelude::*, BufReader},
+  ops::Range,
   path::Path,
 };
 
+use cssparser::ToCss;
 use quick_xml::events::{BytesStart, Event};
-use reqwest::blocking::{get, Response};
+use rayon::prelude::*;
 use url::Url;
 
-use dom::{CompiledDocument, Element, ElementData, RootElement, UnstyledElement};
+use dom::{CompiledDocument, DocumentMetadata, Element, ElementData, RootElement, TextElement, UnstyledElement};
 use style::StyleSheet;
 
-use dom::tree::Node;
+use dom::{
+  source_map::{SourceLocation, SourceMap},
+  tree::Node,
+};
 
 #[path = "style.rs"]
 mod _style;
-
+pub mod cache;
+mod cfg;
+pub mod codes;
+pub mod decompile;
+mod host;
+pub mod manifest;
+mod network;
+mod options;
+mod prefetch;
+mod progress;
+pub mod scheme;
+
+pub use host::DocumentHost;
+pub use network::NetworkPolicy;
+pub use options::{CompileOptions, SassOptions, Whitespace};
+pub use progress::{Progress, ProgressObserver};
+
+/// Where a document is compiled from: a `Url`, a string parsed as one, or a path. URLs can use the schemes
+/// registered with `scheme::register_scheme`.
 pub trait IntoUrl {
   fn into_url(&self) -> Result<Url, DiagnosticKind>;
 }
 
+impl IntoUrl for Url {
+  fn into_url(&self) -> Result<Url, DiagnosticKind> {
+    Ok(self.clone())
+  }
+}
+
 impl IntoUrl for &str {
   fn into_url(&self) -> Result<Url, DiagnosticKind> {
     Ok(Url::parse(self)?)
@@ -35,7 +65,7 @@ impl<T: AsRef<Path>> IntoUrl for &T {
   }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Level {
   Bug,
   Error,
@@ -46,8 +76,21 @@ pub enum Level {
 #[derive(Debug)]
 pub struct Diagnostic<'i, FileId: fmt::Debug> {
   pub kind: DiagnosticKind<'i>,
-  pub location: Option<(FileId, usize)>,
+  /// The file and byte range of the offending source.
+  pub location: Option<(FileId, Range<usize>)>,
   pub min_level: Level,
+  /// Changes to the file of `location` that fix the problem, for `--fix` and quick fixes of editors.
+  pub fixes: Vec<Fix>,
+}
+
+/// An edit fixing a diagnostic that can be applied without looking at it, e.g. making a tag self-closing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+  /// Byte range of the source that is replaced.
+  pub range: Range<usize>,
+  pub replacement: String,
+  /// Describes the edit, e.g. "add `</Unstyled>`".
+  pub message: String,
 }
 
 impl<FileId: fmt::Debug> fmt::Display for Diagnostic<'_, FileId> {
@@ -58,11 +101,48 @@ impl<FileId: fmt::Debug> fmt::Display for Diagnostic<'_, FileId> {
 
 #[derive(Debug)]
 pub enum DiagnosticKind<'i> {
-  InvalidElement { el: String },
-  InvalidContext { el: String, parent: String },
-  InvalidAttribute { el: String, attr: String },
-  ExpectedSelfClosing { el: String },
-  ExpectedClosingTag { el: String },
+  InvalidElement {
+    el: String,
+  },
+  InvalidContext {
+    el: String,
+    parent: String,
+  },
+  InvalidAttribute {
+    el: String,
+    attr: String,
+  },
+  MissingAttribute {
+    el: String,
+    attr: String,
+  },
+  DuplicateElement {
+    el: String,
+  },
+  MissingElement {
+    el: String,
+    parent: String,
+  },
+  /// A document including itself, `chain` being the URLs from its first include back to it.
+  CyclicImport {
+    chain: Vec<String>,
+  },
+  RecursiveComponent {
+    name: String,
+  },
+  BlockedUrl {
+    url: String,
+  },
+  ExpectedSelfClosing {
+    el: String,
+  },
+  ExpectedClosingTag {
+    el: String,
+  },
+  /// The XML declaration names an encoding other than UTF-8, which is the only one documents can use.
+  UnsupportedEncoding {
+    encoding: String,
+  },
 
   UnexpectedText,
   UnexpectedCData,
@@ -77,6 +157,88 @@ pub enum DiagnosticKind<'i> {
   UrlParseError(url::ParseError),
   CssParseError(style::Error<'i>),
   SassParseError(String),
+  SassWarning(String),
+  ScriptParseError(String),
+  CfgParseError(String),
+  UnusedRule {
+    selectors: String,
+  },
+}
+
+impl DiagnosticKind<'_> {
+  /// Name of the variant, a stable identifier for tools consuming diagnostics.
+  #[must_use]
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::InvalidElement { .. } => "InvalidElement",
+      Self::InvalidContext { .. } => "InvalidContext",
+      Self::InvalidAttribute { .. } => "InvalidAttribute",
+      Self::MissingAttribute { .. } => "MissingAttribute",
+      Self::DuplicateElement { .. } => "DuplicateElement",
+      Self::MissingElement { .. } => "MissingElement",
+      Self::CyclicImport { .. } => "CyclicImport",
+      Self::RecursiveComponent { .. } => "RecursiveComponent",
+      Self::BlockedUrl { .. } => "BlockedUrl",
+      Self::ExpectedSelfClosing { .. } => "ExpectedSelfClosing",
+      Self::ExpectedClosingTag { .. } => "ExpectedClosingTag",
+      Self::UnsupportedEncoding { .. } => "UnsupportedEncoding",
+
+      Self::UnexpectedText => "UnexpectedText",
+      Self::UnexpectedCData => "UnexpectedCData",
+      Self::UnexpectedDecl => "UnexpectedDecl",
+      Self::UnexpectedPI => "UnexpectedPI",
+      Self::UnexpectedDocType => "UnexpectedDocType",
+      Self::UnexpectedEof => "UnexpectedEof",
+
+      Self::IOError(..) => "IOError",
+      Self::ReqwestError(..) => "ReqwestError",
+      Self::ParseError(..) => "ParseError",
+      Self::UrlParseError(..) => "UrlParseError",
+      Self::CssParseError(..) => "CssParseError",
+      Self::SassParseError(..) => "SassParseError",
+      Self::SassWarning(..) => "SassWarning",
+      Self::ScriptParseError(..) => "ScriptParseError",
+      Self::CfgParseError(..) => "CfgParseError",
+      Self::UnusedRule { .. } => "UnusedRule",
+    }
+  }
+
+  /// Stable code of the variant, `codes::explain` describes it in detail.
+  #[must_use]
+  pub fn code(&self) -> &'static str {
+    match self {
+      Self::InvalidElement { .. } => "E0001",
+      Self::InvalidContext { .. } => "E0002",
+      Self::InvalidAttribute { .. } => "E0003",
+      Self::MissingAttribute { .. } => "E0004",
+      Self::DuplicateElement { .. } => "E0005",
+      Self::MissingElement { .. } => "E0006",
+      Self::CyclicImport { .. } => "E0007",
+      Self::RecursiveComponent { .. } => "E0008",
+      Self::ExpectedSelfClosing { .. } => "E0009",
+      Self::ExpectedClosingTag { .. } => "E0010",
+
+      Self::UnexpectedText => "E0011",
+      Self::UnexpectedCData => "E0012",
+      Self::UnexpectedDecl => "E0013",
+      Self::UnexpectedPI => "E0014",
+      Self::UnexpectedDocType => "E0015",
+      Self::UnexpectedEof => "E0016",
+
+      Self::IOError(..) => "E0017",
+      Self::ReqwestError(..) => "E0018",
+      Self::ParseError(..) => "E0019",
+      Self::UrlParseError(..) => "E0020",
+      Self::CssParseError(..) => "E0021",
+      Self::SassParseError(..) => "E0022",
+      Self::SassWarning(..) => "E0023",
+      Self::ScriptParseError(..) => "E0024",
+      Self::CfgParseError(..) => "E0026",
+      Self::UnusedRule { .. } => "E0027",
+      Self::BlockedUrl { .. } => "E0025",
+      Self::UnsupportedEncoding { .. } => "E0028",
+    }
+  }
 }
 
 impl fmt::Display for DiagnosticKind<'_> {
@@ -85,8 +247,18 @@ impl fmt::Display for DiagnosticKind<'_> {
       Self::InvalidElement { el } => write!(f, "invalid element {}", el),
       Self::InvalidContext { el, parent } => write!(f, "element `{}` is not allowed inside `{}`", el, parent),
       Self::InvalidAttribute { el, attr } => write!(f, "invalid attribute `{}` for `{}`", attr, el),
+      Self::MissingAttribute { el, attr } => write!(f, "`{}` requires the attribute `{}`", el, attr),
+      Self::DuplicateElement { el } => write!(f, "found more than one `{}`", el),
+      Self::MissingElement { el, parent } => write!(f, "`{}` is missing a `{}`", parent, el),
+      Self::CyclicImport { chain } => {
+        let chain = chain.iter().map(|x| format!("`{}`", x)).collect::<Vec<_>>();
+        write!(f, "cyclic import: {}", chain.join(" -> "))
+      }
+      Self::RecursiveComponent { name } => write!(f, "component `{}` uses itself", name),
+      Self::BlockedUrl { url } => write!(f, "the network policy doesn't allow reading `{}`", url),
       Self::ExpectedSelfClosing { el } => write!(f, "childless element `{}` should be self-closing", el),
       Self::ExpectedClosingTag { el } => write!(f, "element `{}` should have explicit closing tag", el),
+      Self::UnsupportedEncoding { encoding } => write!(f, "unsupported encoding `{}`, expected UTF-8", encoding),
 
       Self::UnexpectedText => write!(f, "unexpected text"),
       Self::UnexpectedCData => write!(f, "unexpected CDATA"),
@@ -101,6 +273,10 @@ impl fmt::Display for DiagnosticKind<'_> {
       Self::UrlParseError(e) => e.fmt(f),
       Self::CssParseError(e) => write!(f, "{:?}", e),
       Self::SassParseError(e) => e.fmt(f),
+      Self::SassWarning(e) => e.fmt(f),
+      Self::ScriptParseError(e) => write!(f, "invalid expression: {}", e),
+      Self::CfgParseError(e) => write!(f, "invalid condition: {}", e),
+      Self::UnusedRule { selectors } => write!(f, "rule `{}` matches no element", selectors),
     }
   }
 }
@@ -131,19 +307,22 @@ impl<'i> From<quick_xml::Error> for DiagnosticKind<'i> {
 
 enum Reader {
   File(BufReader<File>),
-  Network(BufReader<Response>),
+  /// Remote resources, read through the cache or prefetched.
+  Memory(io::Cursor<Vec<u8>>),
 }
 
 impl Reader {
-  pub fn get(url: &Url) -> Result<Reader, DiagnosticKind> {
-    if url.scheme() == "file" {
+  pub fn get(url: &Url, policy: &NetworkPolicy) -> Result<Reader, DiagnosticKind<'static>> {
+    policy.check(url)?;
+
+    if let Some(handler) = scheme::handler(url.scheme()) {
+      Ok(Reader::Memory(io::Cursor::new(handler(url)?)))
+    } else if url.scheme() == "file" {
       let file = File::open(url.to_file_path().unwrap())?;
       let buf = BufReader::new(file);
       Ok(Reader::File(buf))
     } else {
-      let resp = get(url.clone())?;
-      let buf = BufReader::new(resp);
-      Ok(Reader::Network(buf))
+      Ok(Reader::Memory(io::Cursor::new(cache::fetch(url, policy)?)))
     }
   }
 }
@@ -152,7 +331,7 @@ impl Read for Reader {
   fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
     match self {
       Reader::File(buf_reader) => buf_reader.read(buf),
-      Reader::Network(buf_reader) => buf_reader.read(buf),
+      Reader::Memory(cursor) => cursor.read(buf),
     }
   }
 }
@@ -161,14 +340,14 @@ impl BufRead for Reader {
   fn consume(&mut self, amt: usize) {
     match self {
       Reader::File(buf) => buf.consume(amt),
-      Reader::Network(buf) => buf.consume(amt),
+      Reader::Memory(cursor) => cursor.consume(amt),
     }
   }
 
   fn fill_buf(&mut self) -> io::Result<&[u8]> {
     match self {
       Reader::File(buf) => buf.fill_buf(),
-      Reader::Network(buf) => buf.fill_buf(),
+      Reader::Memory(cursor) => cursor.fill_buf(),
     }
   }
 }
@@ -184,8 +363,34 @@ pub trait DiagnosticReporter {
 
 struct Context<'r, FileId: fmt::Debug + Clone> {
   root: Node<Element>,
+  /// Where the `<Body>` being compiled goes, the parent of the `<Include>` while compiling an included file.
+  body: Node<Element>,
+  /// The file being compiled and the files including it, in include order, to detect include cycles.
+  includes: Vec<Url>,
+  /// Remote resources downloaded ahead of time, see `prefetch::prefetch`.
+  prefetched: HashMap<Url, Vec<u8>>,
   reporter: &'r mut dyn DiagnosticReporter<FileId = FileId>,
+  options: &'r CompileOptions,
   stylesheet: StyleSheet,
+  direction: style::Direction,
+  metadata: DocumentMetadata,
+  scripts: Vec<String>,
+  resources: BTreeMap<String, Vec<u8>>,
+  /// Where the last event read with `read_event` starts.
+  event_start: usize,
+  /// Source of the file or component being compiled, templates of `<Define>` elements are cut out of it.
+  source: String,
+  /// Templates of the components defined so far, by name.
+  components: HashMap<String, String>,
+  /// Components being expanded, to detect components using themselves.
+  expanding: Vec<String>,
+  source_map: Option<SourceMap>,
+  /// Index of the file being compiled in `source_map`.
+  source_file: usize,
+  /// Where each rule of `stylesheet` starts, as an index into `style_sources` and a location in the CSS
+  /// compiled from it. `None` for rules that aren't checked for being unused.
+  rule_sources: Vec<Option<(usize, cssparser::SourceLocation)>>,
+  style_sources: Vec<_style::SourceMapOrFileId<FileId>>,
 }
 
 #[macro_export]
@@ -193,9 +398,10 @@ macro_rules! handle_error_with_location {
   ($ctx:ident, $file_id:ident, $reader:ident) => {
     |e| {
       $ctx.reporter.add_diagnostic(crate::Diagnostic {
-        location: Some(($file_id.clone(), $reader.buffer_position())),
+        location: Some(($file_id.clone(), $ctx.span($reader))),
         min_level: crate::Level::Error,
         kind: e.into(),
+        fixes: Vec::new(),
       })
     }
   };
@@ -209,6 +415,7 @@ macro_rules! handle_error {
         location: None,
         min_level: crate::Level::Error,
         kind: e.into(),
+        fixes: Vec::new(),
       })
     }
   };
@@ -235,8 +442,9 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
         } else {
           self.reporter.add_diagnostic(Diagnostic {
             min_level: Level::Error,
-            location: Some((file_id.clone(), reader.buffer_position())),
+            location: Some((file_id.clone(), self.span(reader))),
             kind: DiagnosticKind::UnexpectedText,
+            fixes: Vec::new(),
           });
           Err(())
         }
@@ -245,8 +453,9 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
       Event::CData(..) => {
         self.reporter.add_diagnostic(Diagnostic {
           min_level: Level::Error,
-          location: Some((file_id.clone(), reader.buffer_position())),
+          location: Some((file_id.clone(), self.span(reader))),
           kind: DiagnosticKind::UnexpectedCData,
+          fixes: Vec::new(),
         });
         Err(())
       }
@@ -254,8 +463,9 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
       Event::Decl(..) => {
         self.reporter.add_diagnostic(Diagnostic {
           min_level: Level::Error,
-          location: Some((file_id.clone(), reader.buffer_position())),
+          location: Some((file_id.clone(), self.span(reader))),
           kind: DiagnosticKind::UnexpectedDecl,
+          fixes: Vec::new(),
         });
         Err(())
       }
@@ -263,8 +473,9 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
       Event::PI(..) => {
         self.reporter.add_diagnostic(Diagnostic {
           min_level: Level::Error,
-          location: Some((file_id.clone(), reader.buffer_position())),
+          location: Some((file_id.clone(), self.span(reader))),
           kind: DiagnosticKind::UnexpectedPI,
+          fixes: Vec::new(),
         });
         Err(())
       }
@@ -272,8 +483,9 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
       Event::DocType(..) => {
         self.reporter.add_diagnostic(Diagnostic {
           min_level: Level::Error,
-          location: Some((file_id.clone(), reader.buffer_position())),
+          location: Some((file_id.clone(), self.span(reader))),
           kind: DiagnosticKind::UnexpectedDocType,
+          fixes: Vec::new(),
         });
         Err(())
       }
@@ -281,15 +493,257 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
       Event::Eof => {
         self.reporter.add_diagnostic(Diagnostic {
           min_level: Level::Error,
-          location: Some((file_id.clone(), reader.buffer_position())),
+          location: Some((file_id.clone(), self.span(reader))),
           kind: DiagnosticKind::UnexpectedEof,
+          fixes: Vec::new(),
         });
         Err(())
       }
 
       Event::Comment(..) => Ok(()),
 
-      _ => unimplemented!(),
+      Event::Empty(e) => {
+        let name = e.name();
+        let name = reader
+          .decode(&name)
+          .map_err(handle_error_with_location!(self, file_id, reader))?;
+        let kind = match name {
+          "Frame" | "Head" | "Body" | "Unstyled" | "If" => DiagnosticKind::ExpectedClosingTag { el: name.to_string() },
+          _ => DiagnosticKind::InvalidElement { el: name.to_string() },
+        };
+        self.report(kind, reader, file_id);
+        Ok(())
+      }
+
+      Event::Start(..) | Event::End(..) => unreachable!("opening and closing tags are handled by the caller"),
+    }
+  }
+
+  fn read_event<'b, R: BufRead>(
+    &mut self,
+    reader: &mut quick_xml::Reader<R>,
+    buf: &'b mut Vec<u8>,
+    file_id: &FileId,
+  ) -> Result<Event<'b>, ()> {
+    self.event_start = reader.buffer_position();
+    reader
+      .read_event(buf)
+      .map_err(handle_error_with_location!(self, file_id, reader))
+  }
+
+  /// Span of the last event read, up to where `reader` is now.
+  fn span<R: BufRead>(&self, reader: &quick_xml::Reader<R>) -> Range<usize> {
+    self.event_start..reader.buffer_position()
+  }
+
+  /// Span of the attribute `key` of the tag `e` that was just read, or of the whole tag if it can't be found.
+  fn attribute_span<R: BufRead>(&self, e: &BytesStart, key: &[u8], reader: &quick_xml::Reader<R>) -> Range<usize> {
+    let tag: &[u8] = e;
+    let found = (1..tag.len()).find(|&i| {
+      tag[i..].starts_with(key)
+        && tag[i - 1].is_ascii_whitespace()
+        && tag[i + key.len()..].iter().find(|x| !x.is_ascii_whitespace()) == Some(&b'=')
+    });
+
+    match found {
+      Some(i) => {
+        let rest = &tag[i + key.len()..];
+        let value_end = rest
+          .iter()
+          .position(|&x| x == b'"' || x == b'\'')
+          .and_then(|open| {
+            rest[open + 1..]
+              .iter()
+              .position(|&x| x == rest[open])
+              .map(|close| open + close + 2)
+          })
+          .unwrap_or(0);
+
+        // The tag's content starts after its `<`.
+        let start = self.event_start + 1 + i;
+        start..start + key.len() + value_end
+      }
+
+      None => self.span(reader),
+    }
+  }
+
+  /// Opens a resource, taking it from the prefetched ones if it was downloaded already.
+  fn open(&mut self, url: &Url) -> Result<Reader, DiagnosticKind<'static>> {
+    match self.prefetched.remove(url) {
+      Some(data) => Ok(Reader::Memory(io::Cursor::new(data))),
+      None if url.scheme() == "file" => Reader::get(url, self.options.network_policy()),
+      None => {
+        let progress = self.options.progress();
+        progress.report(Progress::Fetching(url));
+        let result = Reader::get(url, self.options.network_policy());
+        progress.report(Progress::Fetched(url));
+        result
+      }
+    }
+  }
+
+  /// `url` as it's stored in the document, see `relative_url`.
+  fn relative(&self, url: &Url) -> String {
+    relative_url(&self.includes[0], url)
+  }
+
+  /// Location of the byte `pos` of the file being compiled, if the document gets a source map.
+  fn source_location(&mut self, pos: usize, file_id: &FileId) -> Option<SourceLocation> {
+    let file = self.source_map.as_ref().map(|_| self.source_file)?;
+    let line = self.reporter.get_line(file_id, pos);
+    let line_start = self.reporter.get_position(file_id, line, 1);
+    Some(SourceLocation {
+      file,
+      line: line + 1,
+      column: pos - line_start + 1,
+    })
+  }
+
+  fn report<R: BufRead>(&mut self, kind: DiagnosticKind, reader: &quick_xml::Reader<R>, file_id: &FileId) {
+    let span = self.span(reader);
+    let fixes = self.fixes(&kind, &span, None);
+    self.reporter.add_diagnostic(Diagnostic {
+      location: Some((file_id.clone(), span)),
+      min_level: Level::Error,
+      kind,
+      fixes,
+    });
+  }
+
+  /// Fixes of `kind` reported for the tag at `span`, `end` being where its element ends if it was skipped.
+  fn fixes(&self, kind: &DiagnosticKind, span: &Range<usize>, end: Option<usize>) -> Vec<Fix> {
+    let tag = match self.source.get(span.clone()) {
+      Some(tag) => tag,
+      None => return Vec::new(),
+    };
+
+    match (kind, end) {
+      (DiagnosticKind::ExpectedClosingTag { el }, _) if tag.ends_with("/>") => {
+        let start = span.start + tag[..tag.len() - 2].trim_end().len();
+        vec![Fix {
+          range: start..span.end,
+          replacement: format!("></{}>", el),
+          message: format!("add `</{}>`", el),
+        }]
+      }
+
+      // Only elements without content, which would be lost.
+      (DiagnosticKind::ExpectedSelfClosing { .. }, Some(end)) => {
+        let content = self.source.get(span.end..end).unwrap_or_default();
+        if !content.rfind("</").map_or(false, |i| content[..i].trim().is_empty()) {
+          return Vec::new();
+        }
+
+        let start = span.start + tag[..tag.len() - 1].trim_end().len();
+        vec![Fix {
+          range: start..end,
+          replacement: " />".to_string(),
+          message: "make the tag self-closing".to_string(),
+        }]
+      }
+
+      _ => Vec::new(),
+    }
+  }
+
+  /// Reports `kind` and skips the element named `tag` that was just opened, so the rest of the document is
+  /// still checked.
+  fn skip_element<R: BufRead>(
+    &mut self,
+    kind: DiagnosticKind,
+    tag: &[u8],
+    reader: &mut quick_xml::Reader<R>,
+    buf: &mut Vec<u8>,
+    file_id: &FileId,
+  ) -> Result<(), ()> {
+    let span = self.span(reader);
+    let result = self.skip_to_end(tag, reader, buf, file_id);
+    let fixes = match result {
+      Ok(()) => self.fixes(&kind, &span, Some(reader.buffer_position())),
+      Err(()) => Vec::new(),
+    };
+
+    self.reporter.add_diagnostic(Diagnostic {
+      location: Some((file_id.clone(), span)),
+      min_level: Level::Error,
+      kind,
+      fixes,
+    });
+    result
+  }
+
+  /// Skips the element named `tag` that was just opened.
+  fn skip_to_end<R: BufRead>(
+    &mut self,
+    tag: &[u8],
+    reader: &mut quick_xml::Reader<R>,
+    buf: &mut Vec<u8>,
+    file_id: &FileId,
+  ) -> Result<(), ()> {
+    buf.clear();
+    reader
+      .read_to_end(tag, buf)
+      .map_err(handle_error_with_location!(self, file_id, reader))
+  }
+
+  /// Reads the text of the element `el` that was just opened, up to its closing tag.
+  ///
+  /// CDATA sections are taken verbatim, so their content doesn't need escaping, and comments are skipped.
+  fn read_text<R: BufRead>(
+    &mut self,
+    el: &str,
+    reader: &mut quick_xml::Reader<R>,
+    buf: &mut Vec<u8>,
+    file_id: &FileId,
+  ) -> Result<String, ()> {
+    let mut text = String::new();
+    loop {
+      buf.clear();
+      match self.read_event(reader, buf, file_id)? {
+        Event::Text(e) => {
+          let unescaped = e
+            .unescaped()
+            .map_err(handle_error_with_location!(self, file_id, reader))?;
+          let decoded = reader
+            .decode(&unescaped)
+            .map_err(handle_error_with_location!(self, file_id, reader))?;
+          text.push_str(decoded);
+        }
+
+        Event::CData(e) => {
+          let decoded = reader
+            .decode(&e)
+            .map_err(handle_error_with_location!(self, file_id, reader))?;
+          text.push_str(decoded);
+        }
+
+        Event::Comment(..) => {}
+
+        // Mismatched closing tags are a syntax error, so this one is `el`'s.
+        Event::End(..) => return Ok(text),
+
+        Event::Start(e) => {
+          let name = e.name();
+          let name = reader
+            .decode(&name)
+            .map_err(handle_error_with_location!(self, file_id, reader))?;
+          let kind = invalid_element(name, el);
+          let tag = e.name().to_vec();
+          self.skip_element(kind, &tag, reader, buf, file_id)?;
+        }
+
+        Event::Empty(e) => {
+          let name = e.name();
+          let name = reader
+            .decode(&name)
+            .map_err(handle_error_with_location!(self, file_id, reader))?;
+          let kind = invalid_element(name, el);
+          self.report(kind, reader, file_id);
+        }
+
+        event => self.handle_event(event, file_id, reader)?,
+      }
     }
   }
 
@@ -303,26 +757,50 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
     buf.clear();
 
     let mut found_frame = false;
+    // Before the first element, where a declaration and a DOCTYPE may be.
+    let mut prologue = true;
+    let mut found_doctype = false;
     loop {
-      match reader
-        .read_event(buf)
-        .map_err(handle_error_with_location!(self, file_id, reader))?
-      {
+      match self.read_event(reader, buf, file_id)? {
+        // A declaration has to be the very first thing in the document.
+        Event::Decl(e) if self.event_start == 0 => {
+          let encoding = e
+            .encoding()
+            .transpose()
+            .map_err(handle_error_with_location!(self, file_id, reader))?;
+          if let Some(encoding) = encoding {
+            let encoding = String::from_utf8_lossy(&encoding).into_owned();
+            if !encoding.eq_ignore_ascii_case("utf-8") && !encoding.eq_ignore_ascii_case("utf8") {
+              self.reporter.add_diagnostic(Diagnostic {
+                location: Some((file_id.clone(), self.span(reader))),
+                min_level: Level::Error,
+                kind: DiagnosticKind::UnsupportedEncoding { encoding },
+                fixes: Vec::new(),
+              });
+            }
+          }
+        }
+
+        Event::DocType(..) if prologue && !found_doctype => found_doctype = true,
+
         Event::Start(e) => {
+          prologue = false;
           let name = e.name();
           let name = reader
             .decode(&name)
             .map_err(handle_error_with_location!(self, file_id, reader))?;
 
-          if name == "Frame" {
-            if found_frame {
-              panic!("found duplicate frame");
-            }
-
+          if name == "Frame" && !found_frame {
             found_frame = true;
             self.compile_frame(reader, buf, url, file_id)?;
           } else {
-            panic!("unknown {}", name);
+            let kind = if name == "Frame" {
+              DiagnosticKind::DuplicateElement { el: name.to_string() }
+            } else {
+              invalid_element(name, "#document")
+            };
+            let tag = e.name().to_vec();
+            self.skip_element(kind, &tag, reader, buf, file_id)?;
           }
         }
 
@@ -347,13 +825,22 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
   ) -> Result<(), ()> {
     buf.clear();
 
+    // The URL relative URLs are resolved against, `<Base>` can change it.
+    let mut base = url.clone();
     let mut found_head = false;
     let mut found_body = false;
+
+    // Shared styles come before the styles of the document, whether it has a head or not. Included documents
+    // get them through the document including them.
+    if self.includes.len() == 1 {
+      let options = self.options;
+      for stylesheet in options.stylesheets() {
+        self.compile_shared_style(stylesheet, reader, file_id)?;
+      }
+    }
+
     loop {
-      match reader
-        .read_event(buf)
-        .map_err(handle_error_with_location!(self, file_id, reader))?
-      {
+      match self.read_event(reader, buf, file_id)? {
         Event::Start(e) => {
           let name = e.name();
           let name = reader
@@ -361,23 +848,24 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
             .map_err(handle_error_with_location!(self, file_id, reader))?;
 
           match name {
-            "Head" => {
-              if found_head {
-                panic!("found duplicate head");
-              }
+            "Head" if !found_head => {
               found_head = true;
-              self.compile_head(reader, buf, url, file_id)?;
+              base = self.compile_head(reader, buf, url, file_id)?;
             }
 
-            "Body" => {
-              if found_body {
-                panic!("found duplicate body");
-              }
+            "Body" if !found_body => {
               found_body = true;
-              self.compile_body(reader, buf, url, file_id)?;
+              self.compile_body(reader, buf, &base, file_id)?;
             }
 
-            _ => panic!("unknown {}", name),
+            _ => {
+              let kind = match name {
+                "Head" | "Body" => DiagnosticKind::DuplicateElement { el: name.to_string() },
+                _ => invalid_element(name, "Frame"),
+              };
+              let tag = e.name().to_vec();
+              self.skip_element(kind, &tag, reader, buf, file_id)?;
+            }
           }
         }
 
@@ -390,26 +878,36 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
     }
 
     if !found_body {
-      panic!("found no body");
+      let kind = DiagnosticKind::MissingElement {
+        el: "Body".to_string(),
+        parent: "Frame".to_string(),
+      };
+      self.report(kind, reader, file_id);
     }
 
     Ok(())
   }
 
+  /// Compiles the head of the document at `url`, returning the URL the rest of the document resolves relative
+  /// URLs against.
   fn compile_head<R: BufRead>(
     &mut self,
     reader: &mut quick_xml::Reader<R>,
     buf: &mut Vec<u8>,
     url: &Url,
     file_id: &FileId,
-  ) -> Result<(), ()> {
+  ) -> Result<Url, ()> {
     buf.clear();
 
+    let mut base = url.clone();
+    let mut found_base = false;
+    let mut found_title = false;
+    let mut found_window = false;
+    // `<If>` elements being compiled, their children are part of the head.
+    let mut depth = 0;
+
     loop {
-      match reader
-        .read_event(buf)
-        .map_err(handle_error_with_location!(self, file_id, reader))?
-      {
+      match self.read_event(reader, buf, file_id)? {
         Event::Start(e) => {
           let name = e.name();
           let name = reader
@@ -418,10 +916,49 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
 
           match name {
             "Style" => {
-              self.compile_style(e.to_owned(), false, reader, buf, url, file_id)?;
+              self.compile_style(e.to_owned(), false, reader, buf, &base, file_id)?;
+            }
+
+            "Script" => {
+              self.compile_script(e.to_owned(), false, reader, buf, &base, file_id)?;
+            }
+
+            "Define" => {
+              self.compile_define(e.to_owned(), reader, buf, file_id)?;
+            }
+
+            "If" => {
+              if self.compile_if(&e, reader, file_id)? {
+                depth += 1;
+              } else {
+                let tag = e.name().to_vec();
+                self.skip_to_end(&tag, reader, buf, file_id)?;
+              }
+            }
+
+            "Title" if found_title => {
+              let kind = DiagnosticKind::DuplicateElement { el: name.to_string() };
+              let tag = e.name().to_vec();
+              self.skip_element(kind, &tag, reader, buf, file_id)?;
             }
 
-            _ => panic!("unknown {}", name),
+            "Title" => {
+              found_title = true;
+              let title = self.read_text("Title", reader, buf, file_id)?;
+              self.metadata.title = Some(title.split_whitespace().collect::<Vec<_>>().join(" "));
+            }
+
+            "Base" | "Window" => {
+              let kind = DiagnosticKind::ExpectedSelfClosing { el: name.to_string() };
+              let tag = e.name().to_vec();
+              self.skip_element(kind, &tag, reader, buf, file_id)?;
+            }
+
+            _ => {
+              let kind = invalid_element(name, "Head");
+              let tag = e.name().to_vec();
+              self.skip_element(kind, &tag, reader, buf, file_id)?;
+            }
           }
         }
 
@@ -433,94 +970,75 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
 
           match name {
             "Style" => {
-              self.compile_style(e.to_owned(), true, reader, buf, url, file_id)?;
+              self.compile_style(e.to_owned(), true, reader, buf, &base, file_id)?;
             }
 
-            _ => panic!("unknown {}", name),
-          }
-        }
-
-        Event::End(..) => break,
+            "Script" => {
+              self.compile_script(e.to_owned(), true, reader, buf, &base, file_id)?;
+            }
 
-        event => self.handle_event(event, file_id, reader)?,
-      }
-      buf.clear();
-    }
+            "Meta" => {
+              self.compile_meta(e.to_owned(), reader, file_id)?;
+            }
 
-    Ok(())
-  }
+            "Asset" => {
+              self.compile_asset(e.to_owned(), reader, &base, file_id)?;
+            }
 
-  fn compile_body<R: BufRead>(
-    &mut self,
-    reader: &mut quick_xml::Reader<R>,
-    buf: &mut Vec<u8>,
-    url: &Url,
-    file_id: &FileId,
-  ) -> Result<(), ()> {
-    buf.clear();
+            "Base" if found_base => {
+              let kind = DiagnosticKind::DuplicateElement { el: name.to_string() };
+              self.report(kind, reader, file_id);
+            }
 
-    self.compile_ui_element(self.root.clone(), reader, buf, url, file_id)
-  }
+            "Base" => {
+              found_base = true;
+              base = self.compile_base(e.to_owned(), reader, url, file_id)?;
+            }
 
-  fn compile_ui_element<R: BufRead>(
-    &mut self,
-    parent: Node<Element>,
-    reader: &mut quick_xml::Reader<R>,
-    buf: &mut Vec<u8>,
-    url: &Url,
-    file_id: &FileId,
-  ) -> Result<(), ()> {
-    buf.clear();
+            "Window" if found_window => {
+              let kind = DiagnosticKind::DuplicateElement { el: name.to_string() };
+              self.report(kind, reader, file_id);
+            }
 
-    loop {
-      match reader
-        .read_event(buf)
-        .map_err(handle_error_with_location!(self, file_id, reader))?
-      {
-        Event::Start(e) => {
-          let name = e.name();
-          let name = reader
-            .decode(&name)
-            .map_err(handle_error_with_location!(self, file_id, reader))?;
+            "Window" => {
+              found_window = true;
+              self.compile_window(e.to_owned(), reader, file_id)?;
+            }
 
-          match name {
-            "Unstyled" => {
-              let e = e.to_owned();
-              self.compile_unstyled(e, parent.clone(), reader, buf, url, file_id)?;
+            "Title" | "If" => {
+              let kind = DiagnosticKind::ExpectedClosingTag { el: name.to_string() };
+              self.report(kind, reader, file_id);
             }
 
-            _ => panic!("unknown {}", name),
+            _ => {
+              let kind = invalid_element(name, "Head");
+              self.report(kind, reader, file_id);
+            }
           }
         }
 
+        Event::End(..) if depth > 0 => depth -= 1,
+
         Event::End(..) => break,
 
         event => self.handle_event(event, file_id, reader)?,
       }
-
       buf.clear();
     }
 
-    Ok(())
+    Ok(base)
   }
 
-  fn compile_unstyled<'a, R: BufRead>(
+  /// Returns the URL `<Base href>` sets, relative URLs of the elements after it are resolved against it
+  /// instead of the URL of the document.
+  fn compile_base<'a, R: BufRead>(
     &mut self,
     e: BytesStart<'a>,
-    parent: Node<Element>,
     reader: &mut quick_xml::Reader<R>,
-    buf: &mut Vec<u8>,
     url: &Url,
     file_id: &FileId,
-  ) -> Result<(), ()> {
-    buf.clear();
-
-    let name = e.name();
-    let name = reader
-      .decode(&name)
-      .map_err(handle_error_with_location!(self, file_id, reader))?;
-
-    let mut raw_attributes = dom::RawElementAttributes::default();
+  ) -> Result<Url, ()> {
+    let mut href = None;
     for attr in e.attributes() {
       let attr = attr.map_err(handle_error_with_location!(self, file_id, reader))?;
       let key = reader
@@ -534,74 +1052,1211 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
         .map_err(handle_error_with_location!(self, file_id, reader))?;
 
       match key {
-        "class" => {
-          raw_attributes.class = Some(dom::RawAttributeValue::Raw {
-            value: value.to_string(),
-            up_to_date: false,
-          });
-        }
+        "href" => href = Some(value.to_string()),
 
-        ":class" => {
-          raw_attributes.class = Some(dom::RawAttributeValue::Script {
-            script: value.to_string(),
-            up_to_date: false,
-            ast: None,
+        _ => {
+          self.reporter.add_diagnostic(Diagnostic {
+            location: Some((file_id.clone(), self.attribute_span(&e, attr.key, reader))),
+            min_level: Level::Info,
+            kind: DiagnosticKind::InvalidAttribute {
+              attr: key.to_string(),
+              el: "Base".to_string(),
+            },
+            fixes: Vec::new(),
           });
         }
+      }
+    }
 
-        "id" => {
-          raw_attributes.id = Some(dom::RawAttributeValue::Raw {
-            value: value.to_string(),
-            up_to_date: false,
-          });
-        }
+    match href {
+      Some(href) => url
+        .join(&href)
+        .map_err(handle_error_with_location!(self, file_id, reader)),
+
+      None => {
+        let kind = DiagnosticKind::MissingAttribute {
+          attr: "href".to_string(),
+          el: "Base".to_string(),
+        };
+        self.report(kind, reader, file_id);
+        Ok(url.clone())
+      }
+    }
+  }
 
-        ":id" => {
-          raw_attributes.id = Some(dom::RawAttributeValue::Script {
-            script: value.to_string(),
-            up_to_date: false,
-            ast: None,
-          });
-        }
+  /// Returns whether the `cfg` condition of an `<If>` holds, a missing or invalid one is reported and doesn't.
+  fn compile_if<R: BufRead>(
+    &mut self,
+    e: &BytesStart,
+    reader: &mut quick_xml::Reader<R>,
+    file_id: &FileId,
+  ) -> Result<bool, ()> {
+    let mut condition = None;
+    for attr in e.attributes() {
+      let attr = attr.map_err(handle_error_with_location!(self, file_id, reader))?;
+      let key = reader
+        .decode(attr.key)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = attr
+        .unescaped_value()
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = reader
+        .decode(&value)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
 
-        "style" => {
-          unimplemented!();
-        }
+      match key {
+        "cfg" => condition = Some((value.to_string(), self.attribute_span(e, attr.key, reader))),
 
         _ => {
           self.reporter.add_diagnostic(Diagnostic {
-            location: Some((file_id.clone(), reader.buffer_position())),
+            location: Some((file_id.clone(), self.attribute_span(e, attr.key, reader))),
             min_level: Level::Info,
             kind: DiagnosticKind::InvalidAttribute {
               attr: key.to_string(),
-              el: name.to_string(),
+              el: "If".to_string(),
             },
+            fixes: Vec::new(),
           });
         }
       }
     }
 
-    let el = Element::new(ElementData::Unstyled(UnstyledElement), raw_attributes);
-    let node = parent.append(el);
-
-    self.compile_ui_element(node, reader, buf, url, file_id)
+    match condition {
+      Some((condition, span)) => match cfg::evaluate(&condition, self.options) {
+        Ok(result) => Ok(result),
+        Err(e) => {
+          self.reporter.add_diagnostic(Diagnostic {
+            location: Some((file_id.clone(), span)),
+            min_level: Level::Error,
+            kind: DiagnosticKind::CfgParseError(e),
+            fixes: Vec::new(),
+          });
+          Ok(false)
+        }
+      },
+
+      None => {
+        let kind = DiagnosticKind::MissingAttribute {
+          attr: "cfg".to_string(),
+          el: "If".to_string(),
+        };
+        self.report(kind, reader, file_id);
+        Ok(false)
+      }
+    }
   }
+
+  fn compile_window<'a, R: BufRead>(
+    &mut self,
+    e: BytesStart<'a>,
+    reader: &mut quick_xml::Reader<R>,
+    file_id: &FileId,
+  ) -> Result<(), ()> {
+    let mut width = None;
+    let mut height = None;
+    for attr in e.attributes() {
+      let attr = attr.map_err(handle_error_with_location!(self, file_id, reader))?;
+      let key = reader
+        .decode(attr.key)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = attr
+        .unescaped_value()
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = reader
+        .decode(&value)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+
+      let valid = match key {
+        "width" | "height" => match value.trim().trim_end_matches("px").parse::<f64>() {
+          Ok(size) if size.is_finite() && size > 0.0 => {
+            if key == "width" {
+              width = Some(size);
+            } else {
+              height = Some(size);
+            }
+            true
+          }
+          _ => false,
+        },
+
+        "resizable" | "decorations" => match value {
+          "true" | "false" => {
+            let flag = Some(value == "true");
+            if key == "resizable" {
+              self.metadata.resizable = flag;
+            } else {
+              self.metadata.decorations = flag;
+            }
+            true
+          }
+          _ => false,
+        },
+
+        _ => {
+          self.reporter.add_diagnostic(Diagnostic {
+            location: Some((file_id.clone(), self.attribute_span(&e, attr.key, reader))),
+            min_level: Level::Info,
+            kind: DiagnosticKind::InvalidAttribute {
+              attr: key.to_string(),
+              el: "Window".to_string(),
+            },
+            fixes: Vec::new(),
+          });
+          true
+        }
+      };
+
+      if !valid {
+        self.reporter.add_diagnostic(Diagnostic {
+          location: Some((file_id.clone(), self.attribute_span(&e, attr.key, reader))),
+          min_level: Level::Warn,
+          kind: DiagnosticKind::InvalidAttribute {
+            attr: key.to_string(),
+            el: "Window".to_string(),
+          },
+          fixes: Vec::new(),
+        });
+      }
+    }
+
+    // A window can't be given only one side.
+    match (width, height) {
+      (Some(width), Some(height)) => self.metadata.size = Some((width, height)),
+      (None, None) => {}
+      (Some(_), None) | (None, Some(_)) => {
+        let attr = if width.is_none() { "width" } else { "height" };
+        self.reporter.add_diagnostic(Diagnostic {
+          location: Some((file_id.clone(), self.span(reader))),
+          min_level: Level::Warn,
+          kind: DiagnosticKind::MissingAttribute {
+            attr: attr.to_string(),
+            el: "Window".to_string(),
+          },
+          fixes: Vec::new(),
+        });
+      }
+    }
+
+    Ok(())
+  }
+
+  fn compile_meta<'a, R: BufRead>(
+    &mut self,
+    e: BytesStart<'a>,
+    reader: &mut quick_xml::Reader<R>,
+    file_id: &FileId,
+  ) -> Result<(), ()> {
+    let mut name = None;
+    let mut content = None;
+    for attr in e.attributes() {
+      let attr = attr.map_err(handle_error_with_location!(self, file_id, reader))?;
+      let key = reader
+        .decode(attr.key)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = attr
+        .unescaped_value()
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = reader
+        .decode(&value)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+
+      match key {
+        "name" => name = Some(value.to_string()),
+        "content" => content = Some(value.to_string()),
+
+        _ => {
+          self.reporter.add_diagnostic(Diagnostic {
+            location: Some((file_id.clone(), self.attribute_span(&e, attr.key, reader))),
+            min_level: Level::Info,
+            kind: DiagnosticKind::InvalidAttribute {
+              attr: key.to_string(),
+              el: "Meta".to_string(),
+            },
+            fixes: Vec::new(),
+          });
+        }
+      }
+    }
+
+    let invalid = match (name.as_deref(), content.as_deref()) {
+      (Some("direction"), Some(content)) if content.eq_ignore_ascii_case("ltr") => {
+        self.direction = style::Direction::LTR;
+        None
+      }
+
+      (Some("direction"), Some(content)) if content.eq_ignore_ascii_case("rtl") => {
+        self.direction = style::Direction::RTL;
+        None
+      }
+
+      (Some("direction"), _) => Some("content"),
+      _ => Some("name"),
+    };
+
+    if let Some(attr) = invalid {
+      self.reporter.add_diagnostic(Diagnostic {
+        location: Some((file_id.clone(), self.attribute_span(&e, attr.as_bytes(), reader))),
+        min_level: Level::Warn,
+        kind: DiagnosticKind::InvalidAttribute {
+          attr: attr.to_string(),
+          el: "Meta".to_string(),
+        },
+        fixes: Vec::new(),
+      });
+    }
+
+    Ok(())
+  }
+
+  /// Adds an inline script or the one at `src` to the document, scripts run in order when it's loaded.
+  ///
+  /// Inline scripts are XML text, so `<` and `&` have to be escaped unless the script is in a CDATA section.
+  fn compile_script<'a, R: BufRead>(
+    &mut self,
+    e: BytesStart<'a>,
+    empty: bool,
+    reader: &mut quick_xml::Reader<R>,
+    buf: &mut Vec<u8>,
+    url: &Url,
+    file_id: &FileId,
+  ) -> Result<(), ()> {
+    buf.clear();
+
+    let mut src = None;
+    for attr in e.attributes() {
+      let attr = attr.map_err(handle_error_with_location!(self, file_id, reader))?;
+      let key = reader
+        .decode(attr.key)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = attr
+        .unescaped_value()
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = reader
+        .decode(&value)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+
+      match key {
+        "src" => src = Some(value.to_string()),
+
+        _ => {
+          self.reporter.add_diagnostic(Diagnostic {
+            location: Some((file_id.clone(), self.attribute_span(&e, attr.key, reader))),
+            min_level: Level::Info,
+            kind: DiagnosticKind::InvalidAttribute {
+              attr: key.to_string(),
+              el: "Script".to_string(),
+            },
+            fixes: Vec::new(),
+          });
+        }
+      }
+    }
+
+    let (source, source_file, line_offset, location) = match (src, empty) {
+      (Some(src), true) => {
+        let url = url
+          .join(&src)
+          .map_err(handle_error_with_location!(self, file_id, reader))?;
+        let mut url_reader = self
+          .open(&url)
+          .map_err(handle_error_with_location!(self, file_id, reader))?;
+        let mut out = String::new();
+        url_reader
+          .read_to_string(&mut out)
+          .map_err(handle_error_with_location!(self, file_id, reader))?;
+
+        let source_file = self.reporter.add_file(url.to_string(), out.clone());
+        let name = self.relative(&url);
+        let location = self.source_map.as_mut().map(|x| SourceLocation {
+          file: x.add_file(&name),
+          line: 1,
+          column: 1,
+        });
+        (out, source_file, 0, location)
+      }
+
+      (None, false) => {
+        let line_offset = self.reporter.get_line(file_id, reader.buffer_position());
+        let location = self.source_location(reader.buffer_position(), file_id);
+        let text = self.read_text("Script", reader, buf, file_id)?;
+        (text, file_id.clone(), line_offset, location)
+      }
+
+      // A script needs exactly one of `src` and a body.
+      (Some(_), false) => {
+        let kind = DiagnosticKind::InvalidAttribute {
+          attr: "src".to_string(),
+          el: "Script".to_string(),
+        };
+        self.report(kind, reader, file_id);
+        return Err(());
+      }
+
+      (None, true) => {
+        let kind = DiagnosticKind::MissingAttribute {
+          attr: "src".to_string(),
+          el: "Script".to_string(),
+        };
+        self.report(kind, reader, file_id);
+        return Err(());
+      }
+    };
+
+    if let Err(e) = rhai::Engine::new().compile(&source) {
+      let location = match (e.1.line(), e.1.position()) {
+        (Some(line), Some(col)) => {
+          let pos = self.reporter.get_position(&source_file, line_offset + line - 1, col);
+          (source_file, pos..pos)
+        }
+
+        _ => (file_id.clone(), self.span(reader)),
+      };
+
+      self.reporter.add_diagnostic(Diagnostic {
+        location: Some(location),
+        min_level: Level::Error,
+        kind: DiagnosticKind::ScriptParseError(e.to_string()),
+        fixes: Vec::new(),
+      });
+    }
+
+    if let Some(source_map) = &mut self.source_map {
+      source_map.scripts.push(location);
+    }
+    self.scripts.push(source);
+    Ok(())
+  }
+
+  /// Embeds the file at `src` into the document, see `CompiledDocument::resources`.
+  fn compile_asset<'a, R: BufRead>(
+    &mut self,
+    e: BytesStart<'a>,
+    reader: &mut quick_xml::Reader<R>,
+    url: &Url,
+    file_id: &FileId,
+  ) -> Result<(), ()> {
+    let mut src = None;
+    for attr in e.attributes() {
+      let attr = attr.map_err(handle_error_with_location!(self, file_id, reader))?;
+      let key = reader
+        .decode(attr.key)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = attr
+        .unescaped_value()
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = reader
+        .decode(&value)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+
+      match key {
+        "src" => src = Some(value.to_string()),
+
+        _ => {
+          self.reporter.add_diagnostic(Diagnostic {
+            location: Some((file_id.clone(), self.attribute_span(&e, attr.key, reader))),
+            min_level: Level::Info,
+            kind: DiagnosticKind::InvalidAttribute {
+              attr: key.to_string(),
+              el: "Asset".to_string(),
+            },
+            fixes: Vec::new(),
+          });
+        }
+      }
+    }
+
+    let src = match src {
+      Some(src) => src,
+      None => {
+        let kind = DiagnosticKind::MissingAttribute {
+          attr: "src".to_string(),
+          el: "Asset".to_string(),
+        };
+        self.report(kind, reader, file_id);
+        return Err(());
+      }
+    };
+
+    let asset_url = url
+      .join(&src)
+      .map_err(handle_error_with_location!(self, file_id, reader))?;
+
+    let mut data = Vec::new();
+    self
+      .open(&asset_url)
+      .map_err(handle_error_with_location!(self, file_id, reader))?
+      .read_to_end(&mut data)
+      .map_err(handle_error_with_location!(self, file_id, reader))?;
+    self.resources.insert(self.relative(&asset_url), data);
+
+    Ok(())
+  }
+
+  /// Registers the content of a `<Define name="...">` as the template of a component, it's compiled where the
+  /// component is used.
+  fn compile_define<'a, R: BufRead>(
+    &mut self,
+    e: BytesStart<'a>,
+    reader: &mut quick_xml::Reader<R>,
+    buf: &mut Vec<u8>,
+    file_id: &FileId,
+  ) -> Result<(), ()> {
+    let mut name = None;
+    for attr in e.attributes() {
+      let attr = attr.map_err(handle_error_with_location!(self, file_id, reader))?;
+      let key = reader
+        .decode(attr.key)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = attr
+        .unescaped_value()
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = reader
+        .decode(&value)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+
+      match key {
+        "name" if ELEMENTS.contains(&value) => {
+          let kind = DiagnosticKind::InvalidAttribute {
+            attr: key.to_string(),
+            el: "Define".to_string(),
+          };
+          let span = self.attribute_span(&e, attr.key, reader);
+          self.reporter.add_diagnostic(Diagnostic {
+            location: Some((file_id.clone(), span)),
+            min_level: Level::Error,
+            kind,
+            fixes: Vec::new(),
+          });
+        }
+
+        "name" => name = Some(value.to_string()),
+
+        _ => {
+          self.reporter.add_diagnostic(Diagnostic {
+            location: Some((file_id.clone(), self.attribute_span(&e, attr.key, reader))),
+            min_level: Level::Info,
+            kind: DiagnosticKind::InvalidAttribute {
+              attr: key.to_string(),
+              el: "Define".to_string(),
+            },
+            fixes: Vec::new(),
+          });
+        }
+      }
+    }
+
+    let start = reader.buffer_position();
+    let define_span = self.span(reader);
+
+    buf.clear();
+    reader
+      .read_to_end(e.name(), buf)
+      .map_err(handle_error_with_location!(self, file_id, reader))?;
+    let end = self.source[..reader.buffer_position()].rfind("</").unwrap_or(start);
+    let template = self.source[start..end].to_string();
+
+    let name = match name {
+      Some(name) => name,
+      None => {
+        self.reporter.add_diagnostic(Diagnostic {
+          location: Some((file_id.clone(), define_span)),
+          min_level: Level::Error,
+          kind: DiagnosticKind::MissingAttribute {
+            attr: "name".to_string(),
+            el: "Define".to_string(),
+          },
+          fixes: Vec::new(),
+        });
+        return Ok(());
+      }
+    };
+
+    if self.components.insert(name.clone(), template).is_some() {
+      self.reporter.add_diagnostic(Diagnostic {
+        location: Some((file_id.clone(), define_span)),
+        min_level: Level::Error,
+        kind: DiagnosticKind::DuplicateElement { el: name },
+        fixes: Vec::new(),
+      });
+    }
+
+    Ok(())
+  }
+
+  /// Compiles the template of the component `name` in place of its use `e`, replacing every `{attribute}` in
+  /// it by the value of that attribute.
+  fn compile_component<'a, R: BufRead>(
+    &mut self,
+    name: &str,
+    e: BytesStart<'a>,
+    parent: Node<Element>,
+    reader: &mut quick_xml::Reader<R>,
+    url: &Url,
+    file_id: &FileId,
+  ) -> Result<(), ()> {
+    if self.expanding.iter().any(|x| x == name) {
+      let kind = DiagnosticKind::RecursiveComponent { name: name.to_string() };
+      self.report(kind, reader, file_id);
+      return Err(());
+    }
+
+    let mut template = self.components[name].clone();
+    for attr in e.attributes() {
+      let attr = attr.map_err(handle_error_with_location!(self, file_id, reader))?;
+      let key = reader
+        .decode(attr.key)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      // The value is substituted into XML, so it's kept escaped.
+      let value = reader
+        .decode(&attr.value)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      template = template.replace(&format!("{{{}}}", key), value);
+    }
+
+    // The template is wrapped, so it ends with a closing tag like the content of any other element.
+    let source = format!("<{0}>{1}</{0}>", name, template);
+    let template_file_id = self
+      .reporter
+      .add_file(format!("{} (component `{}`)", url, name), source.clone());
+    let mut template_reader = quick_xml::Reader::from_str(&source);
+    template_reader.check_comments(true);
+
+    let mut buf = Vec::new();
+    self.read_event(&mut template_reader, &mut buf, &template_file_id)?;
+
+    let outer_source = std::mem::replace(&mut self.source, source.clone());
+    let source_file = self.source_file;
+    let file_name = format!("{} (component `{}`)", self.relative(url), name);
+    if let Some(source_map) = &mut self.source_map {
+      self.source_file = source_map.add_file(&file_name);
+    }
+
+    let count = parent.children().count();
+    self.expanding.push(name.to_string());
+    let result = self.compile_ui_element(parent.clone(), &mut template_reader, &mut buf, url, &template_file_id);
+    self.expanding.pop();
+    self.source_file = source_file;
+    self.source = outer_source;
+
+    for child in parent.children().skip(count) {
+      child.inner_mut().component.get_or_insert_with(|| name.to_string());
+    }
+
+    result
+  }
+
+  fn compile_body<R: BufRead>(
+    &mut self,
+    reader: &mut quick_xml::Reader<R>,
+    buf: &mut Vec<u8>,
+    url: &Url,
+    file_id: &FileId,
+  ) -> Result<(), ()> {
+    buf.clear();
+
+    self.compile_ui_element(self.body.clone(), reader, buf, url, file_id)
+  }
+
+  /// Compiles the document at `src` as if its body was written in place of the `<Include>`, its head adds
+  /// to the head of the including document.
+  fn compile_include<'a, R: BufRead>(
+    &mut self,
+    e: BytesStart<'a>,
+    parent: Node<Element>,
+    reader: &mut quick_xml::Reader<R>,
+    url: &Url,
+    file_id: &FileId,
+  ) -> Result<(), ()> {
+    let mut src = None;
+    for attr in e.attributes() {
+      let attr = attr.map_err(handle_error_with_location!(self, file_id, reader))?;
+      let key = reader
+        .decode(attr.key)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = attr
+        .unescaped_value()
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = reader
+        .decode(&value)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+
+      match key {
+        "src" => src = Some(value.to_string()),
+
+        _ => {
+          self.reporter.add_diagnostic(Diagnostic {
+            location: Some((file_id.clone(), self.attribute_span(&e, attr.key, reader))),
+            min_level: Level::Info,
+            kind: DiagnosticKind::InvalidAttribute {
+              attr: key.to_string(),
+              el: "Include".to_string(),
+            },
+            fixes: Vec::new(),
+          });
+        }
+      }
+    }
+
+    let src = match src {
+      Some(src) => src,
+      None => {
+        let kind = DiagnosticKind::MissingAttribute {
+          attr: "src".to_string(),
+          el: "Include".to_string(),
+        };
+        self.report(kind, reader, file_id);
+        return Err(());
+      }
+    };
+
+    let include_url = url
+      .join(&src)
+      .map_err(handle_error_with_location!(self, file_id, reader))?;
+    if let Some(start) = self.includes.iter().position(|x| *x == include_url) {
+      let chain = self.includes[start..].iter().chain(std::iter::once(&include_url));
+      let kind = DiagnosticKind::CyclicImport {
+        chain: chain.map(Url::to_string).collect(),
+      };
+      self.report(kind, reader, file_id);
+      return Err(());
+    }
+
+    let mut out = String::new();
+    self
+      .open(&include_url)
+      .map_err(handle_error_with_location!(self, file_id, reader))?
+      .read_to_string(&mut out)
+      .map_err(handle_error_with_location!(self, file_id, reader))?;
+    let include_file_id = self.reporter.add_file(include_url.to_string(), out.clone());
+    self.options.progress().report(Progress::Document(&include_url));
+    self
+      .prefetched
+      .extend(prefetch::prefetch(&out, &include_url, self.options));
+
+    let mut include_reader = quick_xml::Reader::from_str(&out);
+    include_reader.check_comments(true);
+
+    // The direction and the window are set by the including document.
+    let direction = self.direction;
+    let metadata = self.metadata.clone();
+    let body = std::mem::replace(&mut self.body, parent);
+    let source = std::mem::replace(&mut self.source, out.clone());
+    let source_file = self.source_file;
+    let file_name = self.relative(&include_url);
+    if let Some(source_map) = &mut self.source_map {
+      self.source_file = source_map.add_file(&file_name);
+    }
+    self.includes.push(include_url.clone());
+
+    let result = self.compile_root(&mut include_reader, &mut Vec::new(), &include_url, &include_file_id);
+
+    self.includes.pop();
+    self.source_file = source_file;
+    self.source = source;
+    self.body = body;
+    self.direction = direction;
+    self.metadata = metadata;
+
+    result
+  }
+
+  fn compile_ui_element<R: BufRead>(
+    &mut self,
+    parent: Node<Element>,
+    reader: &mut quick_xml::Reader<R>,
+    buf: &mut Vec<u8>,
+    url: &Url,
+    file_id: &FileId,
+  ) -> Result<(), ()> {
+    buf.clear();
+
+    loop {
+      match self.read_event(reader, buf, file_id)? {
+        Event::Start(e) => {
+          let name = e.name();
+          let name = reader
+            .decode(&name)
+            .map_err(handle_error_with_location!(self, file_id, reader))?;
+
+          match name {
+            "Unstyled" => {
+              let e = e.to_owned();
+              self.compile_unstyled(e, parent.clone(), reader, buf, url, file_id)?;
+            }
+
+            "Include" => {
+              let kind = DiagnosticKind::ExpectedSelfClosing { el: name.to_string() };
+              let tag = e.name().to_vec();
+              self.skip_element(kind, &tag, reader, buf, file_id)?;
+            }
+
+            // The children of a true `<If>` go into `parent`, as if they were written in its place.
+            "If" => {
+              if self.compile_if(&e, reader, file_id)? {
+                self.compile_ui_element(parent.clone(), reader, buf, url, file_id)?;
+              } else {
+                let tag = e.name().to_vec();
+                self.skip_to_end(&tag, reader, buf, file_id)?;
+              }
+            }
+
+            _ if self.components.contains_key(name) => {
+              let kind = DiagnosticKind::ExpectedSelfClosing { el: name.to_string() };
+              let tag = e.name().to_vec();
+              self.skip_element(kind, &tag, reader, buf, file_id)?;
+            }
+
+            _ => {
+              let context = match parent.inner().data {
+                ElementData::Root(..) => "Body",
+                ElementData::Unstyled(..) => "Unstyled",
+                ElementData::Text(..) => unreachable!("text is never compiled into"),
+              };
+              let kind = invalid_element(name, context);
+              let tag = e.name().to_vec();
+              self.skip_element(kind, &tag, reader, buf, file_id)?;
+            }
+          }
+        }
+
+        Event::Empty(e) => {
+          let name = e.name();
+          let name = reader
+            .decode(&name)
+            .map_err(handle_error_with_location!(self, file_id, reader))?;
+
+          if name == "Include" {
+            self.compile_include(e.to_owned(), parent.clone(), reader, url, file_id)?;
+          } else if self.components.contains_key(name) {
+            let name = name.to_string();
+            self.compile_component(&name, e.to_owned(), parent.clone(), reader, url, file_id)?;
+          } else {
+            self.handle_event(Event::Empty(e), file_id, reader)?;
+          }
+        }
+
+        Event::Text(text) => {
+          // The text node is located where its first non-whitespace character is.
+          let leading = text.iter().take_while(|x| x.is_ascii_whitespace()).count();
+          let location = self.source_location(self.event_start + leading, file_id);
+
+          let text = text
+            .unescaped()
+            .map_err(handle_error_with_location!(self, file_id, reader))?;
+          let text = reader
+            .decode(&text)
+            .map_err(handle_error_with_location!(self, file_id, reader))?;
+
+          if let Some(text) = self.options.whitespace().apply(text) {
+            let mut el = Element::new(
+              ElementData::Text(TextElement { text }),
+              dom::RawElementAttributes::default(),
+            );
+            el.source = location;
+            parent.append(el);
+          }
+        }
+
+        Event::End(..) => break,
+
+        event => self.handle_event(event, file_id, reader)?,
+      }
+
+      buf.clear();
+    }
+
+    Ok(())
+  }
+
+  /// Warns about the rules of the stylesheet that match no element.
+  ///
+  /// Scripts can change the tree and the classes of its elements, so documents with scripts or script
+  /// attributes aren't checked.
+  fn check_unused_rules(&mut self) {
+    let nodes: Vec<_> = self.root.descendants().filter(|x| !x.inner().is_text()).collect();
+    let dynamic = !self.scripts.is_empty()
+      || nodes.iter().any(|x| {
+        let el = x.inner();
+        let is_script = |x: &Option<dom::RawAttributeValue>| matches!(x, Some(dom::RawAttributeValue::Script { .. }));
+        is_script(&el.raw_attributes.class) || is_script(&el.raw_attributes.id)
+      });
+    if dynamic {
+      return;
+    }
+
+    // Classes and ids are only set at runtime, these are removed again so the document doesn't change.
+    for node in &nodes {
+      let mut el = node.inner_mut();
+      let classes = match &el.raw_attributes.class {
+        Some(dom::RawAttributeValue::Raw { value, .. }) => value.split_ascii_whitespace().map(str::to_string).collect(),
+        _ => Vec::new(),
+      };
+      let id = match &el.raw_attributes.id {
+        Some(dom::RawAttributeValue::Raw { value, .. }) => Some(value.clone()),
+        _ => None,
+      };
+      el.classes = classes;
+      el.id = id;
+    }
+
+    let unused: Vec<_> = self
+      .stylesheet
+      .rules
+      .iter()
+      .enumerate()
+      .filter(|(_, rule)| !nodes.iter().any(|node| rule.matches(node)))
+      .map(|(index, rule)| (index, rule.selectors.to_css_string()))
+      .collect();
+
+    for node in &nodes {
+      let mut el = node.inner_mut();
+      el.classes.clear();
+      el.id = None;
+    }
+
+    for (index, selectors) in unused {
+      if self.rule_sources.get(index).copied().flatten().is_none() {
+        continue;
+      }
+
+      let location = self.rule_location(index);
+      self.reporter.add_diagnostic(Diagnostic {
+        location,
+        min_level: Level::Warn,
+        kind: DiagnosticKind::UnusedRule { selectors },
+        fixes: Vec::new(),
+      });
+    }
+  }
+
+  // Only the syntax can be checked here, names are resolved against the scope at runtime.
+  fn check_expression(&mut self, script: &str, span: Range<usize>, file_id: &FileId) {
+    if let Err(e) = rhai::Engine::new().compile_expression(script) {
+      self.reporter.add_diagnostic(Diagnostic {
+        location: Some((file_id.clone(), span)),
+        min_level: Level::Error,
+        kind: DiagnosticKind::ScriptParseError(e.to_string()),
+        fixes: Vec::new(),
+      });
+    }
+  }
+
+  fn check_inline_style(&mut self, value: &str, span: Range<usize>, file_id: &FileId) {
+    let mut input = cssparser::ParserInput::new(value);
+    let mut parser = cssparser::Parser::new(&mut input);
+    for declaration in cssparser::DeclarationListParser::new(&mut parser, style::parser::DeclarationParser) {
+      if let Err(e) = declaration {
+        self.reporter.add_diagnostic(Diagnostic {
+          location: Some((file_id.clone(), span.clone())),
+          min_level: Level::Error,
+          kind: DiagnosticKind::CssParseError(e),
+          fixes: Vec::new(),
+        });
+      }
+    }
+  }
+
+  fn compile_unstyled<'a, R: BufRead>(
+    &mut self,
+    e: BytesStart<'a>,
+    parent: Node<Element>,
+    reader: &mut quick_xml::Reader<R>,
+    buf: &mut Vec<u8>,
+    url: &Url,
+    file_id: &FileId,
+  ) -> Result<(), ()> {
+    buf.clear();
+
+    let location = self.source_location(self.event_start, file_id);
+
+    let name = e.name();
+    let name = reader
+      .decode(&name)
+      .map_err(handle_error_with_location!(self, file_id, reader))?;
+
+    let mut raw_attributes = dom::RawElementAttributes::default();
+    for attr in e.attributes() {
+      let attr = attr.map_err(handle_error_with_location!(self, file_id, reader))?;
+      let key = reader
+        .decode(attr.key)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = attr
+        .unescaped_value()
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let value = reader
+        .decode(&value)
+        .map_err(handle_error_with_location!(self, file_id, reader))?;
+
+      match key {
+        "class" => {
+          raw_attributes.class = Some(dom::RawAttributeValue::Raw {
+            value: value.to_string(),
+            up_to_date: false,
+          });
+        }
+
+        ":class" => {
+          self.check_expression(value, self.attribute_span(&e, attr.key, reader), file_id);
+          raw_attributes.class = Some(dom::RawAttributeValue::Script {
+            script: value.to_string(),
+            up_to_date: false,
+            ast: None,
+          });
+        }
+
+        "id" => {
+          raw_attributes.id = Some(dom::RawAttributeValue::Raw {
+            value: value.to_string(),
+            up_to_date: false,
+          });
+        }
+
+        ":id" => {
+          self.check_expression(value, self.attribute_span(&e, attr.key, reader), file_id);
+          raw_attributes.id = Some(dom::RawAttributeValue::Script {
+            script: value.to_string(),
+            up_to_date: false,
+            ast: None,
+          });
+        }
+
+        "style" => {
+          self.check_inline_style(value, self.attribute_span(&e, attr.key, reader), file_id);
+          raw_attributes.style = Some(dom::RawAttributeValue::Raw {
+            value: value.to_string(),
+            up_to_date: false,
+          });
+        }
+
+        ":style" => {
+          self.check_expression(value, self.attribute_span(&e, attr.key, reader), file_id);
+          raw_attributes.style = Some(dom::RawAttributeValue::Script {
+            script: value.to_string(),
+            up_to_date: false,
+            ast: None,
+          });
+        }
+
+        _ => {
+          self.reporter.add_diagnostic(Diagnostic {
+            location: Some((file_id.clone(), self.attribute_span(&e, attr.key, reader))),
+            min_level: Level::Info,
+            kind: DiagnosticKind::InvalidAttribute {
+              attr: key.to_string(),
+              el: name.to_string(),
+            },
+            fixes: Vec::new(),
+          });
+        }
+      }
+    }
+
+    let mut el = Element::new(ElementData::Unstyled(UnstyledElement), raw_attributes);
+    el.source = location;
+    let node = parent.append(el);
+
+    self.compile_ui_element(node, reader, buf, url, file_id)
+  }
+}
+
+/// Returns `url` relative to the document at `document` if they have the same origin, so compiled documents
+/// don't depend on where they were compiled.
+fn relative_url(document: &Url, url: &Url) -> String {
+  let same_origin = document.scheme() == url.scheme()
+    && document.host_str() == url.host_str()
+    && document.port() == url.port()
+    && !url.cannot_be_a_base();
+  let (document_segments, segments) = match (document.path_segments(), url.path_segments()) {
+    (Some(document_segments), Some(segments)) if same_origin => {
+      (document_segments.collect::<Vec<_>>(), segments.collect::<Vec<_>>())
+    }
+    _ => return url.to_string(),
+  };
+
+  // The last segment is the document itself.
+  let directory = &document_segments[..document_segments.len() - 1];
+  let common = directory.iter().zip(&segments).take_while(|(a, b)| a == b).count();
+
+  let mut relative = vec![".."; directory.len() - common];
+  relative.extend(&segments[common..]);
+  let mut relative = relative.join("/");
+  if let Some(query) = url.query() {
+    relative.push('?');
+    relative.push_str(query);
+  }
+
+  relative
+}
+
+/// Elements the compiler knows about, other names are reported as invalid wherever they appear.
+const ELEMENTS: &[&str] = &[
+  "Frame", "Head", "Body", "Meta", "Style", "Script", "Unstyled", "Include", "Define", "Asset", "Base", "Title",
+  "Window", "If",
+];
+
+fn invalid_element(el: &str, parent: &str) -> DiagnosticKind<'static> {
+  if ELEMENTS.contains(&el) {
+    DiagnosticKind::InvalidContext {
+      el: el.to_string(),
+      parent: parent.to_string(),
+    }
+  } else {
+    DiagnosticKind::InvalidElement { el: el.to_string() }
+  }
+}
+
+// Reads the document at `url`, reporting failures.
+fn read_document<URL: IntoUrl, FileId: fmt::Debug + Clone>(
+  url: URL,
+  options: &CompileOptions,
+  reporter: &mut dyn DiagnosticReporter<FileId = FileId>,
+) -> Result<(Url, String), ()> {
+  let url = url.into_url().map_err(handle_error!(reporter))?;
+
+  let mut reader = Reader::get(&url, options.network_policy()).map_err(handle_error!(reporter))?;
+  let mut source = String::new();
+  reader.read_to_string(&mut source).map_err(handle_error!(reporter))?;
+
+  Ok((url, source))
 }
 
 pub fn compile<URL: IntoUrl, FileId: fmt::Debug + Clone>(
   url: URL,
+  options: &CompileOptions,
   reporter: &mut dyn DiagnosticReporter<FileId = FileId>,
 ) -> Result<CompiledDocument, ()> {
-  let url = url.into_url().map_err(handle_error!(reporter))?;
+  let (url, source) = read_document(url, options, reporter)?;
+  compile_from_str(&source, url, options, reporter)
+}
 
-  let mut out = String::new();
-  let mut reader = Reader::get(&url).map_err(handle_error!(reporter))?;
-  reader.read_to_string(&mut out).map_err(handle_error!(reporter))?;
+/// Like `compile`, without running the scripts of the document, e.g. to patch a live document with
+/// `CompiledDocument::patch`.
+pub fn compile_inert<URL: IntoUrl, FileId: fmt::Debug + Clone>(
+  url: URL,
+  options: &CompileOptions,
+  reporter: &mut dyn DiagnosticReporter<FileId = FileId>,
+) -> Result<CompiledDocument, ()> {
+  let (url, source) = read_document(url, options, reporter)?;
+  let reporter = &mut options::LevelFilter { reporter, options };
+  let ctx = compile_context(&source, url, options, reporter)?;
+  Ok(build_document(ctx, true))
+}
+
+/// Compiles every document of `inputs` in parallel, returning the result of each in input order along with the
+/// reporter its diagnostics went to.
+///
+/// `reporter` is called once per document with its index in `inputs`. The documents share `options` and the
+/// cache of remote resources, see `cache::set_cache_dir`. They're compiled on the current rayon pool, run this
+/// in `rayon::ThreadPool::install` to pick another one.
+pub fn compile_many<URL, R, F>(
+  inputs: Vec<URL>,
+  options: &CompileOptions,
+  reporter: F,
+) -> Vec<(Result<CompiledDocument, ()>, R)>
+where
+  URL: IntoUrl + Send,
+  R: DiagnosticReporter + Send,
+  F: Fn(usize) -> R + Sync,
+{
+  inputs
+    .into_par_iter()
+    .enumerate()
+    .map(|(index, url)| {
+      let mut reporter = reporter(index);
+      let result = compile(url, options, &mut reporter);
+      (result, reporter)
+    })
+    .collect()
+}
+
+/// Compiles a document read from `reader`, see `compile_from_str`.
+pub fn compile_from_reader<R: Read, URL: IntoUrl, FileId: fmt::Debug + Clone>(
+  mut reader: R,
+  base: URL,
+  options: &CompileOptions,
+  reporter: &mut dyn DiagnosticReporter<FileId = FileId>,
+) -> Result<CompiledDocument, ()> {
+  let mut source = String::new();
+  reader.read_to_string(&mut source).map_err(handle_error!(reporter))?;
+
+  compile_from_str(&source, base, options, reporter)
+}
+
+/// Compiles a document that isn't read from `base` itself, e.g. an unsaved editor buffer.
+///
+/// `base` is the URL the document is reported under, and relative `src` URLs are resolved against it. It
+/// doesn't have to exist, unless it's a path, which is canonicalized.
+pub fn compile_from_str<URL: IntoUrl, FileId: fmt::Debug + Clone>(
+  source: &str,
+  base: URL,
+  options: &CompileOptions,
+  reporter: &mut dyn DiagnosticReporter<FileId = FileId>,
+) -> Result<CompiledDocument, ()> {
+  let reporter = &mut options::LevelFilter { reporter, options };
+  let ctx = compile_context(source, base, options, reporter)?;
+  Ok(build_document(ctx, false))
+}
+
+/// Builds the document compiled by `ctx`, running its scripts unless it's `inert`.
+fn build_document<FileId: fmt::Debug + Clone>(ctx: Context<'_, FileId>, inert: bool) -> CompiledDocument {
+  let mut doc = if inert {
+    CompiledDocument::new_inert(ctx.root, ctx.stylesheet)
+  } else {
+    CompiledDocument::new(ctx.root, ctx.stylesheet)
+  };
+  doc.direction = ctx.direction;
+  doc.metadata = ctx.metadata;
+  doc.resources = ctx.resources;
+  doc.set_source_map(ctx.source_map);
+  for script in ctx.scripts {
+    if inert {
+      doc.scripts.get_mut().unwrap().push(script);
+    } else {
+      doc.add_script(script);
+    }
+  }
+  doc.init_yoga();
+
+  doc
+}
+
+/// Reports the diagnostics `compile` would without building the document, e.g. to validate documents in CI.
+///
+/// Styles are still compiled and remote resources fetched, but scripts don't run and there's no layout.
+pub fn check<URL: IntoUrl, FileId: fmt::Debug + Clone>(
+  url: URL,
+  options: &CompileOptions,
+  reporter: &mut dyn DiagnosticReporter<FileId = FileId>,
+) -> Result<(), ()> {
+  let (url, source) = read_document(url, options, reporter)?;
+  check_from_str(&source, url, options, reporter)
+}
+
+/// Checks a document that isn't read from `base` itself, see `check` and `compile_from_str`.
+pub fn check_from_str<URL: IntoUrl, FileId: fmt::Debug + Clone>(
+  source: &str,
+  base: URL,
+  options: &CompileOptions,
+  reporter: &mut dyn DiagnosticReporter<FileId = FileId>,
+) -> Result<(), ()> {
+  let reporter = &mut options::LevelFilter { reporter, options };
+  compile_context(source, base, options, reporter).map(drop)
+}
+
+// Compiles everything that goes into a document, up to the checkpoint after which it's built.
+fn compile_context<'r, URL: IntoUrl, FileId: fmt::Debug + Clone>(
+  source: &str,
+  base: URL,
+  options: &'r CompileOptions,
+  reporter: &'r mut dyn DiagnosticReporter<FileId = FileId>,
+) -> Result<Context<'r, FileId>, ()> {
+  let url = base.into_url().map_err(handle_error!(reporter))?;
 
-  let file_id = reporter.add_file(url.to_string(), out);
+  options.progress().report(Progress::Document(&url));
+  let prefetched = prefetch::prefetch(source, &url, options);
+  let file_id = reporter.add_file(url.to_string(), source.to_string());
 
-  let reader = Reader::get(&url).map_err(handle_error!(reporter))?;
-  let mut reader = quick_xml::Reader::from_reader(reader);
+  let mut reader = quick_xml::Reader::from_str(source);
   reader.check_comments(true);
 
   let mut buf = Vec::new();
@@ -612,17 +2267,114 @@ pub fn compile<URL: IntoUrl, FileId: fmt::Debug + Clone>(
   ));
 
   let mut ctx = Context {
-    root,
+    root: root.clone(),
+    body: root,
+    includes: vec![url.clone()],
+    prefetched,
     reporter,
+    options,
     stylesheet: StyleSheet::new(),
+    direction: style::Direction::LTR,
+    metadata: DocumentMetadata::default(),
+    scripts: Vec::new(),
+    resources: BTreeMap::new(),
+    event_start: 0,
+    source: source.to_string(),
+    components: HashMap::new(),
+    expanding: Vec::new(),
+    source_map: if options.source_map() {
+      let mut source_map = SourceMap::default();
+      source_map.add_file(&relative_url(&url, &url));
+      Some(source_map)
+    } else {
+      None
+    },
+    source_file: 0,
+    rule_sources: Vec::new(),
+    style_sources: Vec::new(),
   };
 
   ctx.compile_root(&mut reader, &mut buf, &url, &file_id)?;
+  ctx.check_unused_rules();
 
   ctx.reporter.checkpoint()?;
 
-  let doc = CompiledDocument::new(ctx.root, ctx.stylesheet);
-  doc.init_yoga();
+  Ok(ctx)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use codespan_reporting::files::{Files, SimpleFiles};
+
+  /// Keeps the files to resolve positions, any error fails the compilation.
+  struct Reporter {
+    files: SimpleFiles<String, String>,
+    errors: Vec<String>,
+  }
+
+  impl DiagnosticReporter for Reporter {
+    type FileId = usize;
+
+    fn add_file(&mut self, filename: String, source: String) -> usize {
+      self.files.add(filename, source)
+    }
+
+    fn add_diagnostic(&mut self, diagnostic: Diagnostic<usize>) {
+      if diagnostic.min_level == Level::Error || diagnostic.min_level == Level::Bug {
+        self.errors.push(diagnostic.to_string());
+      }
+    }
+
+    fn get_position(&mut self, file: &usize, line: usize, col: usize) -> usize {
+      self.files.line_range(*file, line).unwrap().start + col - 1
+    }
+
+    fn get_line(&mut self, file: &usize, pos: usize) -> usize {
+      self.files.line_index(*file, pos).unwrap()
+    }
 
-  Ok(doc)
+    fn checkpoint(&mut self) -> Result<(), ()> {
+      if self.errors.is_empty() {
+        Ok(())
+      } else {
+        Err(())
+      }
+    }
+  }
+
+  fn fixture(name: &str) -> std::path::PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures").join(name)
+  }
+
+  fn compile_document(name: &str, options: &CompileOptions) -> CompiledDocument {
+    let mut reporter = Reporter {
+      files: SimpleFiles::new(),
+      errors: Vec::new(),
+    };
+    let doc = compile(&fixture(name), options, &mut reporter);
+    assert!(reporter.errors.is_empty(), "{:?}", reporter.errors);
+    doc.unwrap()
+  }
+
+  fn compile_fixture(options: &CompileOptions) -> Vec<u8> {
+    compile_document("reproducible.frame", options).save()
+  }
+
+  #[test]
+  fn compilation_is_reproducible() {
+    let mut options = CompileOptions::new();
+    options.set_source_map(true);
+
+    assert_eq!(compile_fixture(&options), compile_fixture(&options));
+  }
+
+  #[test]
+  fn shared_styles_apply_without_a_head() {
+    let mut options = CompileOptions::new();
+    options.add_stylesheet(Url::from_file_path(fixture("reproducible.css")).unwrap());
+
+    let doc = compile_document("headless.frame", &options);
+    assert_eq!(doc.stylesheet.read().unwrap().rules.len(), 2);
+  }
 }
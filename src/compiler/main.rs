@@ -1,31 +1,92 @@
-use std::path::Path;
+use std::{
+  collections::BTreeMap,
+  fs,
+  io::{self, prelude::*},
+  path::{Path, PathBuf},
+  time::Duration,
+};
 
 use codespan_reporting::{
-  diagnostic::{Diagnostic, Label},
+  diagnostic::{Diagnostic, Label, LabelStyle},
   files::{Files, SimpleFiles},
   term,
-  term::termcolor::{ColorChoice, StandardStream},
+  term::termcolor::{ColorChoice, NoColor, StandardStream},
 };
 use cssparser::ToCss;
+use dom::Compression;
+use url::Url;
+
+use compiler::{
+  check_from_str, compile, compile_from_str, compile_many, manifest::Manifest, CompileOptions, DiagnosticKind, Fix,
+  Level, NetworkPolicy, Progress, SassOptions,
+};
 
-use compiler::{compile, DiagnosticKind, Level};
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum MessageFormat {
+  Human,
+  /// One JSON object per line on stdout.
+  Json,
+}
 
 struct DiagnosticPrinter {
+  format: MessageFormat,
   should_exit: bool,
   writer: StandardStream,
   config: codespan_reporting::term::Config,
   files: SimpleFiles<String, String>,
+  /// Fixes of the diagnostics printed so far, by file.
+  fixes: Vec<(usize, Fix)>,
 }
 
 impl DiagnosticPrinter {
-  fn new() -> Self {
+  fn new(format: MessageFormat) -> Self {
     Self {
+      format,
       should_exit: false,
       writer: StandardStream::stderr(ColorChoice::Auto),
       config: codespan_reporting::term::Config::default(),
       files: SimpleFiles::new(),
+      fixes: Vec::new(),
     }
   }
+
+  /// Applies the fixes of the diagnostics printed so far to the files they're for. Files that aren't on disk
+  /// or changed since they were read are left alone, e.g. documents read from stdin.
+  fn apply_fixes(&self) -> io::Result<()> {
+    let mut by_file: BTreeMap<usize, Vec<&Fix>> = BTreeMap::new();
+    for (file_id, fix) in &self.fixes {
+      by_file.entry(*file_id).or_default().push(fix);
+    }
+
+    for (file_id, mut fixes) in by_file {
+      let path = match Url::parse(&self.files.name(file_id).unwrap()).map(|x| x.to_file_path()) {
+        Ok(Ok(path)) => path,
+        _ => continue,
+      };
+      let mut source = self.files.source(file_id).unwrap().to_string();
+      match fs::read_to_string(&path) {
+        Ok(current) if current == source => {}
+        _ => continue,
+      }
+
+      // From the end, so the ranges of the other fixes stay valid. Overlapping fixes are dropped.
+      fixes.sort_by_key(|x| std::cmp::Reverse(x.range.start));
+      let mut applied = 0;
+      let mut end = source.len();
+      for fix in fixes {
+        if fix.range.end <= end {
+          source.replace_range(fix.range.clone(), &fix.replacement);
+          end = fix.range.start;
+          applied += 1;
+        }
+      }
+
+      fs::write(&path, source)?;
+      eprintln!("fixed {} problem(s) in `{}`", applied, path.display());
+    }
+
+    Ok(())
+  }
 }
 
 impl compiler::DiagnosticReporter for DiagnosticPrinter {
@@ -45,6 +106,32 @@ impl compiler::DiagnosticReporter for DiagnosticPrinter {
 
   fn add_diagnostic(&mut self, diagnostic: compiler::Diagnostic<Self::FileId>) {
     let location = diagnostic.location;
+    if let Some((file_id, _)) = &location {
+      self
+        .fixes
+        .extend(diagnostic.fixes.iter().map(|x| (*file_id, x.clone())));
+    }
+    let fixes: Vec<_> = diagnostic
+      .fixes
+      .iter()
+      .map(|fix| {
+        serde_json::json!({
+          "start": fix.range.start,
+          "end": fix.range.end,
+          "replacement": fix.replacement,
+          "message": fix.message,
+        })
+      })
+      .collect();
+    let kind_name = diagnostic.kind.name();
+    let code = diagnostic.kind.code();
+    let message = diagnostic.kind.to_string();
+    let level = match diagnostic.min_level {
+      Level::Bug => "bug",
+      Level::Error => "error",
+      Level::Warn => "warning",
+      Level::Info => "note",
+    };
 
     let codespan_diagnostic = match diagnostic.min_level {
       Level::Bug => {
@@ -59,66 +146,73 @@ impl compiler::DiagnosticReporter for DiagnosticPrinter {
 
       Level::Warn => Diagnostic::warning(),
       Level::Info => Diagnostic::note(),
-    };
+    }
+    .with_code(code);
 
     let diagnostic = match diagnostic.kind {
       DiagnosticKind::ExpectedSelfClosing { .. } => {
-        let (file_id, pos) = location.unwrap();
+        let (file_id, range) = location.clone().unwrap();
         codespan_diagnostic
           .with_message("childless elements should be self-closing")
-          .with_code("E0000")
           .with_labels(vec![
-            Label::primary(file_id, pos - 1..pos).with_message("expected self-closing tag"),
-            Label::secondary(file_id, pos - 1..pos).with_message("help: replace with `/>`"),
+            Label::primary(file_id, range.end - 1..range.end).with_message("expected self-closing tag"),
+            Label::secondary(file_id, range.end - 1..range.end).with_message("help: replace with `/>`"),
           ])
       }
 
       DiagnosticKind::ExpectedClosingTag { el } => {
-        let (file_id, pos) = location.unwrap();
+        let (file_id, range) = location.clone().unwrap();
         codespan_diagnostic
           .with_message("element should have explicit closing tag")
-          .with_code("E0000")
           .with_labels(vec![
-            Label::primary(file_id, pos..pos).with_message("expected explicit closing tag"),
-            Label::secondary(file_id, pos - 2..pos - 1).with_message("help: remove`/`"),
-            Label::secondary(file_id, pos..pos).with_message(format!("help: add `</{}>`", el)),
+            Label::primary(file_id, range.end..range.end).with_message("expected explicit closing tag"),
+            Label::secondary(file_id, range.end - 2..range.end - 1).with_message("help: remove`/`"),
+            Label::secondary(file_id, range.end..range.end).with_message(format!("help: add `</{}>`", el)),
           ])
       }
 
       DiagnosticKind::InvalidAttribute { el, attr } => {
-        let (file_id, pos) = location.unwrap();
-        codespan_diagnostic
-          .with_message("invalid attribute")
-          .with_code("E0000")
-          .with_labels(vec![
-            Label::primary(file_id, pos..pos).with_message(format!("invalid attribute `{}` for `{}`", attr, el))
-          ])
+        let (file_id, range) = location.clone().unwrap();
+        codespan_diagnostic.with_message("invalid attribute").with_labels(vec![
+          Label::primary(file_id, range).with_message(format!("invalid attribute `{}` for `{}`", attr, el))
+        ])
       }
 
       DiagnosticKind::InvalidElement { el } => {
-        let (file_id, pos) = location.unwrap();
-        codespan_diagnostic
-          .with_message("invalid element")
-          .with_code("E0000")
-          .with_labels(vec![
-            Label::primary(file_id, pos..pos).with_message(format!("invalid element `{}`", el))
-          ])
+        let (file_id, range) = location.clone().unwrap();
+        codespan_diagnostic.with_message("invalid element").with_labels(vec![
+          Label::primary(file_id, range).with_message(format!("invalid element `{}`", el))
+        ])
+      }
+
+      DiagnosticKind::DuplicateElement { el } => {
+        let (file_id, range) = location.clone().unwrap();
+        codespan_diagnostic.with_message("duplicate element").with_labels(vec![
+          Label::primary(file_id, range).with_message(format!("found more than one `{}`", el))
+        ])
+      }
+
+      DiagnosticKind::MissingElement { el, parent } => {
+        let (file_id, range) = location.clone().unwrap();
+        codespan_diagnostic.with_message("missing element").with_labels(vec![
+          Label::primary(file_id, range).with_message(format!("`{}` is missing a `{}`", parent, el))
+        ])
       }
 
       DiagnosticKind::InvalidContext { el, parent } => {
-        let (file_id, pos) = location.unwrap();
+        let (file_id, range) = location.clone().unwrap();
         codespan_diagnostic
           .with_message("element found in invalid context")
-          .with_code("E0000")
-          .with_labels(vec![Label::primary(file_id, pos..pos)
-            .with_message(format!("element `{}` is not allowed inside `{}`", el, parent))])
+          .with_labels(vec![
+            Label::primary(file_id, range).with_message(format!("element `{}` is not allowed inside `{}`", el, parent))
+          ])
       }
 
       DiagnosticKind::CssParseError(err) => {
-        let (file_id, pos) = location.unwrap();
+        let (file_id, range) = location.clone().unwrap();
+        let pos = range.start;
         codespan_diagnostic
           .with_message("CSS parsing error")
-          .with_code("E0000")
           .with_labels(vec![match err.0.kind {
             cssparser::ParseErrorKind::Basic(err) => match err {
               cssparser::BasicParseErrorKind::UnexpectedToken(token) => {
@@ -149,24 +243,68 @@ impl compiler::DiagnosticReporter for DiagnosticPrinter {
           }])
       }
 
-      DiagnosticKind::SassParseError(err) => {
-        let (file_id, pos) = location.unwrap();
-        codespan_diagnostic
-          .with_message("libsass error")
-          .with_code("E0000")
-          .with_labels(vec![Label::primary(file_id, pos..pos).with_message(err)])
-      }
+      DiagnosticKind::SassParseError(err) => match location.clone() {
+        Some((file_id, range)) => codespan_diagnostic
+          .with_message("Sass error")
+          .with_labels(vec![Label::primary(file_id, range).with_message(err)]),
+
+        None => codespan_diagnostic.with_message(err),
+      },
+
+      DiagnosticKind::SassWarning(warning) => match location.clone() {
+        Some((file_id, range)) => codespan_diagnostic
+          .with_message("Sass warning")
+          .with_labels(vec![Label::primary(file_id, range).with_message(warning)]),
+
+        None => codespan_diagnostic.with_message(warning),
+      },
 
       kind => {
-        if let Some((file_id, pos)) = location {
-          codespan_diagnostic.with_labels(vec![Label::primary(file_id, pos..pos).with_message(kind.to_string())])
+        if let Some((file_id, range)) = location.clone() {
+          codespan_diagnostic.with_labels(vec![Label::primary(file_id, range).with_message(kind.to_string())])
         } else {
           codespan_diagnostic.with_message(kind.to_string())
         }
       }
     };
 
-    term::emit(&mut self.writer.lock(), &self.config, &self.files, &diagnostic).unwrap();
+    match self.format {
+      MessageFormat::Human => term::emit(&mut self.writer.lock(), &self.config, &self.files, &diagnostic).unwrap(),
+
+      MessageFormat::Json => {
+        let mut rendered = NoColor::new(Vec::new());
+        term::emit(&mut rendered, &self.config, &self.files, &diagnostic).unwrap();
+
+        let labels: Vec<_> = diagnostic
+          .labels
+          .iter()
+          .map(|label| {
+            serde_json::json!({
+              "file": self.files.name(label.file_id),
+              "start": label.range.start,
+              "end": label.range.end,
+              "line": self.files.line_index(label.file_id, label.range.start).map(|x| x + 1),
+              "message": label.message,
+              "primary": label.style == LabelStyle::Primary,
+            })
+          })
+          .collect();
+
+        let json = serde_json::json!({
+          "kind": kind_name,
+          "code": code,
+          "level": level,
+          "file": location.as_ref().and_then(|(file_id, _)| self.files.name(*file_id)),
+          "start": location.as_ref().map(|(_, range)| range.start),
+          "end": location.as_ref().map(|(_, range)| range.end),
+          "message": message,
+          "labels": labels,
+          "fixes": fixes,
+          "rendered": String::from_utf8_lossy(&rendered.into_inner()),
+        });
+        println!("{}", json);
+      }
+    }
   }
 
   fn checkpoint(&mut self) -> Result<(), ()> {
@@ -178,17 +316,191 @@ impl compiler::DiagnosticReporter for DiagnosticPrinter {
   }
 }
 
-use clap::{App, Arg};
+use clap::{value_t, App, AppSettings, Arg, ArgMatches, SubCommand};
+
+/// Reports an error that isn't about a particular file.
+fn report_error(printer: &mut DiagnosticPrinter, e: io::Error) {
+  compiler::DiagnosticReporter::add_diagnostic(
+    printer,
+    compiler::Diagnostic {
+      kind: DiagnosticKind::IOError(e),
+      location: None,
+      min_level: Level::Error,
+      fixes: Vec::new(),
+    },
+  );
+}
+
+/// Compiles every document of the manifest at `path` in parallel, returning whether all of them compiled. The
+/// documents share `options` and the cache of remote resources.
+fn build(path: &Path, mut options: CompileOptions, format: MessageFormat, fix: bool) -> bool {
+  let manifest = match Manifest::load(path).and_then(|x| x.apply(&mut options).map(|_| x)) {
+    Ok(manifest) => manifest,
+    Err(e) => {
+      let e = io::Error::new(e.kind(), format!("`{}`: {}", path.display(), e));
+      report_error(&mut DiagnosticPrinter::new(format), e);
+      return false;
+    }
+  };
+
+  let sources: Vec<_> = manifest.documents.iter().map(|x| manifest.source(x)).collect();
+  // A printer per document, so a failed document doesn't stop the others.
+  let results = compile_many(sources.iter().collect(), &options, |_| DiagnosticPrinter::new(format));
+
+  let mut success = true;
+  for (document, (result, mut printer)) in manifest.documents.iter().zip(results) {
+    if fix {
+      if let Err(e) = printer.apply_fixes() {
+        report_error(&mut printer, e);
+        success = false;
+      }
+    }
+
+    let doc = match result {
+      Ok(doc) => doc,
+      Err(()) => {
+        success = false;
+        continue;
+      }
+    };
+
+    let output = manifest.output(document);
+    let file = output
+      .parent()
+      .map_or(Ok(()), fs::create_dir_all)
+      .and_then(|_| fs::File::create(&output));
+    match file {
+      Ok(f) => doc.save_into(f, manifest.compression),
+      Err(e) => {
+        report_error(
+          &mut printer,
+          io::Error::new(e.kind(), format!("`{}`: {}", output.display(), e)),
+        );
+        success = false;
+      }
+    }
+  }
+
+  success
+}
+
+/// Writes the compiled document at `input` as XML to `output`, `-` meaning stdin and stdout.
+fn decompile(input: &str, output: &str) -> io::Result<()> {
+  let doc = match input {
+    "-" => dom::CompiledDocument::try_load_inert(io::stdin().lock()),
+    input => dom::CompiledDocument::try_load_inert(io::BufReader::new(fs::File::open(input)?)),
+  };
+  let doc = doc.map_err(|e| match e {
+    dom::LoadError::Io(e) => e,
+    e => io::Error::new(io::ErrorKind::InvalidData, format!("`{}`: {}", input, e)),
+  })?;
+
+  let xml = compiler::decompile::decompile(&doc);
+  match output {
+    "-" => io::stdout().write_all(xml.as_bytes()),
+    output => fs::write(output, xml),
+  }
+}
+
+/// Prints what the compiler is doing to stderr, one line per step.
+fn print_progress(progress: Progress) {
+  let (step, what) = match progress {
+    Progress::Document(url) => ("Compiling", url.to_string()),
+    Progress::Fetching(url) => ("Fetching", url.to_string()),
+    Progress::Fetched(url) => ("Fetched", url.to_string()),
+    Progress::CompilingSass(url) => ("Sass", url.map_or_else(|| "inline style".to_string(), Url::to_string)),
+    Progress::ParsingCss(url) => ("CSS", url.map_or_else(|| "inline style".to_string(), Url::to_string)),
+  };
+
+  eprintln!("{:>12} {}", step, what);
+}
+
+fn network_policy(matches: &ArgMatches) -> NetworkPolicy {
+  let mut policy = NetworkPolicy::default();
+
+  if matches.is_present("timeout") {
+    let timeout = value_t!(matches, "timeout", u64).unwrap_or_else(|e| e.exit());
+    policy.timeout = if timeout == 0 {
+      None
+    } else {
+      Some(Duration::from_secs(timeout))
+    };
+  }
+
+  if matches.is_present("max-redirects") {
+    policy.max_redirects = value_t!(matches, "max-redirects", usize).unwrap_or_else(|e| e.exit());
+  }
+
+  policy.allowed_hosts = matches.values_of("allow-host").map(|x| x.map(str::to_string).collect());
+  policy.allowed_schemes = matches
+    .values_of("allow-scheme")
+    .map(|x| x.map(str::to_string).collect());
+
+  for header in matches.values_of("header").into_iter().flatten() {
+    match header.find(':') {
+      Some(i) => policy
+        .headers
+        .push((header[..i].trim().to_string(), header[i + 1..].trim().to_string())),
+      None => clap::Error::with_description(
+        &format!("`{}` isn't a header, expected `NAME: VALUE`", header),
+        clap::ErrorKind::InvalidValue,
+      )
+      .exit(),
+    }
+  }
+
+  policy
+}
+
+fn sass_options(matches: &ArgMatches) -> SassOptions {
+  let mut sass = SassOptions::default();
+
+  sass.include_paths = matches
+    .values_of("sass-include-path")
+    .into_iter()
+    .flatten()
+    .map(PathBuf::from)
+    .collect();
+
+  for variable in matches.values_of("sass-var").into_iter().flatten() {
+    match variable.find('=') {
+      Some(i) => sass
+        .variables
+        .push((variable[..i].trim().to_string(), variable[i + 1..].trim().to_string())),
+      None => clap::Error::with_description(
+        &format!("`{}` isn't a variable, expected `NAME=VALUE`", variable),
+        clap::ErrorKind::InvalidValue,
+      )
+      .exit(),
+    }
+  }
+
+  if matches.is_present("sass-precision") {
+    sass.precision = Some(value_t!(matches, "sass-precision", i32).unwrap_or_else(|e| e.exit()));
+  }
+
+  sass
+}
 
 fn main() {
   let matches = App::new(env!("CARGO_PKG_NAME"))
     .version(env!("CARGO_PKG_VERSION"))
     .author(env!("CARGO_PKG_AUTHORS"))
     .about(env!("CARGO_PKG_DESCRIPTION"))
+    .setting(AppSettings::SubcommandsNegateReqs)
+    .subcommand(
+      SubCommand::with_name("build")
+        .about("Compiles the documents listed in a project manifest, with the options given before `build`")
+        .arg(
+          Arg::with_name("MANIFEST")
+            .help("Sets the manifest to build [default: frame.toml]")
+            .index(1),
+        ),
+    )
     .arg(
       Arg::with_name("INPUT")
-        .help("Sets the input file to use")
-        .required(true)
+        .help("Sets the input file to use, `-` reads the document from stdin")
+        .required_unless("explain")
         .index(1),
     )
     .arg(
@@ -196,16 +508,324 @@ fn main() {
         .short("o")
         .long("output")
         .value_name("FILE")
-        .help("Sets the output file")
-        .required(true)
+        .help("Sets the output file, `-` writes the document to stdout")
+        .required_unless_one(&["check", "explain"])
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("explain")
+        .long("explain")
+        .value_name("CODE")
+        .help("Describes the diagnostic with the given code, e.g. `E0003`, and how to fix it")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("check")
+        .long("check")
+        .help("Only reports diagnostics, without writing the document")
+        .conflicts_with("output"),
+    )
+    .arg(
+      Arg::with_name("progress")
+        .long("progress")
+        .help("Prints the documents compiled and the resources fetched to stderr as the compilation goes"),
+    )
+    .arg(
+      Arg::with_name("fix")
+        .long("fix")
+        .help("Applies the fixes diagnostics suggest to the source files, e.g. making tags self-closing"),
+    )
+    .arg(
+      Arg::with_name("base-url")
+        .long("base-url")
+        .value_name("URL")
+        .help("Sets the URL relative `src` attributes are resolved against when reading from stdin, defaults to the current directory")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("cache-dir")
+        .long("cache-dir")
+        .value_name("DIR")
+        .help("Sets where remote resources are cached, defaults to a directory in the system's temporary directory")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("offline")
+        .long("offline")
+        .help("Only uses cached remote resources, failing if one isn't cached"),
+    )
+    .arg(
+      Arg::with_name("timeout")
+        .long("timeout")
+        .value_name("SECS")
+        .help("Sets how long a request for a remote resource may take, 0 waits forever [default: 30]")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("max-redirects")
+        .long("max-redirects")
+        .value_name("N")
+        .help("Sets how many redirects a request may follow [default: 10]")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("allow-host")
+        .long("allow-host")
+        .value_name("HOST")
+        .help("Only allows remote resources from the given hosts")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("allow-scheme")
+        .long("allow-scheme")
+        .value_name("SCHEME")
+        .help("Only allows resources with the given URL schemes, e.g. `file`")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("header")
+        .long("header")
+        .value_name("NAME: VALUE")
+        .help("Sends a header with every request for a remote resource, e.g. `Authorization: Bearer ...`")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("emit")
+        .long("emit")
+        .value_name("KIND")
+        .help("Sets what is written, `xml` reads a compiled document as INPUT and writes it back as XML with a single stylesheet")
+        .possible_values(&["cframe", "xml"])
+        .default_value("cframe")
+        .conflicts_with("check")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("compression")
+        .long("compression")
+        .value_name("ALGORITHM")
+        .help("Compresses the document, documents load the same either way")
+        .possible_values(&["none", "zstd", "lz4"])
+        .default_value("none")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("define")
+        .long("define")
+        .value_name("KEY=VALUE")
+        .help("Defines a key for `<If cfg=\"...\">` conditions, the value being optional")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("sass-include-path")
+        .long("sass-include-path")
+        .value_name("DIR")
+        .help("Adds a directory Sass imports are looked up in, e.g. of a shared library")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("sass-var")
+        .long("sass-var")
+        .value_name("NAME=VALUE")
+        .help("Defines a Sass variable before every Sass stylesheet, the value is a Sass expression")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("sass-precision")
+        .long("sass-precision")
+        .value_name("DIGITS")
+        .help("Sets the number of digits after the decimal point of numbers in Sass output")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("source-map")
+        .long("source-map")
+        .help("Embeds where elements and scripts come from, so runtime errors point at the source"),
+    )
+    .arg(
+      Arg::with_name("embed-images")
+        .long("embed-images")
+        .help("Embeds the images of stylesheets into the documents, so they don't need to be read at runtime"),
+    )
+    .arg(
+      Arg::with_name("deny")
+        .short("D")
+        .long("deny")
+        .value_name("KIND")
+        .help("Reports diagnostics of a kind as errors, e.g. `InvalidAttribute`")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("warn")
+        .short("W")
+        .long("warn")
+        .value_name("KIND")
+        .help("Reports diagnostics of a kind as warnings")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("allow")
+        .short("A")
+        .long("allow")
+        .value_name("KIND")
+        .help("Doesn't report diagnostics of a kind")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("message-format")
+        .long("message-format")
+        .value_name("FMT")
+        .help("Sets how diagnostics are printed, `json` prints one object per line on stdout")
+        .possible_values(&["human", "json"])
+        .default_value("human")
         .takes_value(true),
     )
     .get_matches();
 
-  let mut printer = DiagnosticPrinter::new();
-  let result = compile(&Path::new(matches.value_of("INPUT").unwrap()), &mut printer);
-  if let Ok(doc) = result {
-    let f = std::fs::File::create(matches.value_of("output").unwrap()).unwrap();
-    doc.save_into(f);
+  if let Some(code) = matches.value_of("explain") {
+    match compiler::codes::explain(code) {
+      Some(explanation) => println!("{}", explanation),
+      None => {
+        eprintln!("error: `{}` isn't a diagnostic code", code);
+        std::process::exit(1);
+      }
+    }
+    return;
+  }
+
+  let format = match matches.value_of("message-format") {
+    Some("json") => MessageFormat::Json,
+    _ => MessageFormat::Human,
+  };
+
+  let cache_dir = matches
+    .value_of("cache-dir")
+    .map_or_else(|| std::env::temp_dir().join("frameui-compiler-cache"), PathBuf::from);
+  compiler::cache::set_cache_dir(Some(cache_dir));
+  compiler::cache::set_offline(matches.is_present("offline"));
+
+  let mut options = CompileOptions::new();
+  options.set_source_map(matches.is_present("source-map"));
+  options.set_embed_images(matches.is_present("embed-images"));
+  options.set_network_policy(network_policy(&matches));
+  options.set_sass(sass_options(&matches));
+  if matches.is_present("progress") {
+    options.set_progress_observer(print_progress);
+  }
+  for define in matches.values_of("define").into_iter().flatten() {
+    match define.find('=') {
+      Some(i) => options.set_define(&define[..i], &define[i + 1..]),
+      None => options.set_define(define, ""),
+    };
+  }
+  for (arg, level) in &[
+    ("deny", Some(Level::Error)),
+    ("warn", Some(Level::Warn)),
+    ("allow", None),
+  ] {
+    for kind in matches.values_of(arg).into_iter().flatten() {
+      options.set_level(kind, *level);
+    }
+  }
+
+  if let Some(matches) = matches.subcommand_matches("build") {
+    let manifest = Path::new(matches.value_of("MANIFEST").unwrap_or("frame.toml"));
+    if !build(manifest, options, format, matches.is_present("fix")) {
+      std::process::exit(1);
+    }
+    return;
+  }
+
+  let output = matches.value_of("output");
+  if output == Some("-") && format == MessageFormat::Json {
+    clap::Error::with_description(
+      "`--message-format json` prints to stdout, so it can't be used with `--output -`",
+      clap::ErrorKind::ArgumentConflict,
+    )
+    .exit();
+  }
+
+  if matches.value_of("emit") == Some("xml") {
+    if let Err(e) = decompile(matches.value_of("INPUT").unwrap(), output.unwrap_or("-")) {
+      report_error(&mut DiagnosticPrinter::new(format), e);
+      std::process::exit(1);
+    }
+    return;
+  }
+
+  let compression = match matches.value_of("compression") {
+    Some("zstd") => Compression::Zstd,
+    Some("lz4") => Compression::Lz4,
+    _ => Compression::None,
+  };
+
+  let check = matches.is_present("check");
+  let mut printer = DiagnosticPrinter::new(format);
+  let result = match matches.value_of("INPUT").unwrap() {
+    "-" => {
+      let mut source = String::new();
+      if let Err(e) = io::stdin().read_to_string(&mut source) {
+        report_error(&mut printer, e);
+        std::process::exit(1);
+      }
+
+      let base = match matches.value_of("base-url") {
+        Some(base) => base.to_string(),
+        // A file that doesn't have to exist, so relative URLs resolve against the current directory.
+        None => Url::from_file_path(std::env::current_dir().unwrap().join("stdin"))
+          .unwrap()
+          .to_string(),
+      };
+
+      if check {
+        check_from_str(&source, base.as_str(), &options, &mut printer).map(|_| None)
+      } else {
+        compile_from_str(&source, base.as_str(), &options, &mut printer).map(Some)
+      }
+    }
+
+    input if check => compiler::check(&Path::new(input), &options, &mut printer).map(|_| None),
+    input => compile(&Path::new(input), &options, &mut printer).map(Some),
+  };
+
+  if matches.is_present("fix") {
+    if let Err(e) = printer.apply_fixes() {
+      report_error(&mut printer, e);
+      std::process::exit(1);
+    }
+  }
+
+  match (result, output) {
+    (Ok(Some(doc)), Some("-")) => {
+      let stdout = io::stdout();
+      doc.save_into(stdout.lock(), compression);
+    }
+
+    (Ok(Some(doc)), Some(output)) => {
+      let f = std::fs::File::create(output).unwrap();
+      doc.save_into(f, compression);
+    }
+
+    (Ok(_), _) => {}
+
+    // Failing matters to scripts and CI, e.g. for `--check`.
+    (Err(()), _) => std::process::exit(1),
   }
 }
@@ -0,0 +1,136 @@
+//! Project manifests, usually named `frame.toml`, listing the documents of an application and how to build
+//! them, see the `build` subcommand of the compiler.
+//!
+//! ```toml
+//! out-dir = "dist"
+//! compression = "zstd"
+//! styles = ["styles/common.scss"]
+//!
+//! [defines]
+//! platform = "linux"
+//!
+//! [sass]
+//! include-paths = ["node_modules"]
+//! variables = { theme = "dark" }
+//!
+//! [[documents]]
+//! src = "index.frame"
+//!
+//! [[documents]]
+//! src = "settings/index.frame"
+//! out = "settings.cframe"
+//! ```
+
+use std::{
+  collections::BTreeMap,
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use dom::Compression;
+use serde::Deserialize;
+use url::Url;
+
+use super::CompileOptions;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Manifest {
+  /// Where the compiled documents go, relative to the manifest.
+  #[serde(default = "default_out_dir")]
+  pub out_dir: PathBuf,
+  #[serde(default)]
+  pub compression: Compression,
+  #[serde(default)]
+  pub source_map: bool,
+  /// Stylesheets added to every document, see `CompileOptions::add_stylesheet`.
+  #[serde(default)]
+  pub styles: Vec<PathBuf>,
+  /// Keys for `<If cfg="...">` conditions, see `CompileOptions::set_define`.
+  #[serde(default)]
+  pub defines: BTreeMap<String, String>,
+  #[serde(default)]
+  pub sass: ManifestSass,
+  pub documents: Vec<ManifestDocument>,
+
+  /// Directory of the manifest, paths in it are relative to this.
+  #[serde(skip)]
+  pub root: PathBuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ManifestDocument {
+  pub src: PathBuf,
+  /// Path of the compiled document relative to `Manifest::out_dir`, defaults to the name of `src` with the
+  /// `cframe` extension.
+  pub out: Option<PathBuf>,
+}
+
+/// Added to the `SassOptions` the build was started with.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ManifestSass {
+  /// Relative to the manifest.
+  #[serde(default)]
+  pub include_paths: Vec<PathBuf>,
+  #[serde(default)]
+  pub variables: BTreeMap<String, String>,
+  pub precision: Option<i32>,
+}
+
+fn default_out_dir() -> PathBuf {
+  PathBuf::from("out")
+}
+
+impl Manifest {
+  pub fn load(path: &Path) -> io::Result<Self> {
+    let source = fs::read_to_string(path)?;
+    let mut manifest: Manifest = toml::from_str(&source).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    manifest.root = path.parent().map_or_else(PathBuf::new, Path::to_path_buf);
+    Ok(manifest)
+  }
+
+  /// Applies the settings of the manifest on top of `options`.
+  pub fn apply(&self, options: &mut CompileOptions) -> io::Result<()> {
+    options.set_source_map(options.source_map() || self.source_map);
+    for (key, value) in &self.defines {
+      options.set_define(key, value);
+    }
+
+    let mut sass = options.sass().clone();
+    sass
+      .include_paths
+      .extend(self.sass.include_paths.iter().map(|x| self.root.join(x)));
+    sass
+      .variables
+      .extend(self.sass.variables.iter().map(|(k, v)| (k.clone(), v.clone())));
+    sass.precision = self.sass.precision.or(sass.precision);
+    options.set_sass(sass);
+
+    for style in &self.styles {
+      let path = fs::canonicalize(self.root.join(style))
+        .map_err(|e| io::Error::new(e.kind(), format!("`{}`: {}", style.display(), e)))?;
+      options.add_stylesheet(Url::from_file_path(path).unwrap());
+    }
+
+    Ok(())
+  }
+
+  /// Path of the source of `document`.
+  #[must_use]
+  pub fn source(&self, document: &ManifestDocument) -> PathBuf {
+    self.root.join(&document.src)
+  }
+
+  /// Path the compiled `document` is written to.
+  #[must_use]
+  pub fn output(&self, document: &ManifestDocument) -> PathBuf {
+    let out = match &document.out {
+      Some(out) => out.clone(),
+      None => Path::new(document.src.file_name().unwrap_or_default()).with_extension("cframe"),
+    };
+
+    self.root.join(&self.out_dir).join(out)
+  }
+}
@@ -0,0 +1,93 @@
+use std::{io, time::Duration};
+
+use reqwest::{
+  blocking::Client,
+  header::{HeaderMap, HeaderName, HeaderValue},
+  redirect,
+};
+use url::Url;
+
+use super::DiagnosticKind;
+
+/// Limits and settings of the requests made for remote resources, and which URLs may be read at all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkPolicy {
+  /// Time a request may take, from connecting to reading the whole response.
+  pub timeout: Option<Duration>,
+  pub max_redirects: usize,
+  /// Schemes of the URLs that may be read, including `file`, `None` allowing any.
+  pub allowed_schemes: Option<Vec<String>>,
+  /// Hosts remote resources may come from, `None` allowing any.
+  pub allowed_hosts: Option<Vec<String>>,
+  /// Sent with every request, e.g. an `Authorization` header.
+  pub headers: Vec<(String, String)>,
+}
+
+impl Default for NetworkPolicy {
+  fn default() -> Self {
+    Self {
+      timeout: Some(Duration::from_secs(30)),
+      max_redirects: 10,
+      allowed_schemes: None,
+      allowed_hosts: None,
+      headers: Vec::new(),
+    }
+  }
+}
+
+impl NetworkPolicy {
+  #[must_use]
+  pub fn allows(&self, url: &Url) -> bool {
+    let scheme_allowed = self
+      .allowed_schemes
+      .as_ref()
+      .map_or(true, |x| x.iter().any(|scheme| scheme == url.scheme()));
+    // Local files have no host to restrict.
+    let host_allowed = url.scheme() == "file"
+      || self.allowed_hosts.as_ref().map_or(true, |x| {
+        url.host_str().map_or(false, |host| x.iter().any(|y| y == host))
+      });
+
+    scheme_allowed && host_allowed
+  }
+
+  pub(crate) fn check(&self, url: &Url) -> Result<(), DiagnosticKind<'static>> {
+    if self.allows(url) {
+      Ok(())
+    } else {
+      Err(DiagnosticKind::BlockedUrl { url: url.to_string() })
+    }
+  }
+
+  /// A client applying the policy, redirects to URLs it doesn't allow fail the request.
+  pub(crate) fn client(&self) -> Result<Client, DiagnosticKind<'static>> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &self.headers {
+      let invalid = |e: &dyn std::fmt::Display| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("invalid header `{}`: {}", name, e))
+      };
+      let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(&e))?;
+      let value = HeaderValue::from_str(value).map_err(|e| invalid(&e))?;
+      headers.append(name, value);
+    }
+
+    let policy = self.clone();
+    let redirect = redirect::Policy::custom(move |attempt| {
+      if attempt.previous().len() > policy.max_redirects {
+        attempt.error("too many redirects")
+      } else if !policy.allows(attempt.url()) {
+        let message = format!("redirect to `{}` isn't allowed", attempt.url());
+        attempt.error(message)
+      } else {
+        attempt.follow()
+      }
+    });
+
+    let client = Client::builder()
+      .default_headers(headers)
+      .redirect(redirect)
+      .timeout(self.timeout)
+      .build()?;
+    Ok(client)
+  }
+}
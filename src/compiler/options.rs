@@ -0,0 +1,227 @@
+use std::{collections::HashMap, fmt, path::PathBuf, sync::Arc};
+
+use url::Url;
+
+use super::{
+  progress::Observer, Diagnostic, DiagnosticKind, DiagnosticReporter, Level, NetworkPolicy, ProgressObserver,
+};
+
+/// How the whitespace of text inside UI elements is handled, text made only of whitespace is always dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Whitespace {
+  /// Trims the text and replaces runs of whitespace in it with a single space, like HTML does.
+  Collapse,
+  /// Keeps the text as written.
+  Preserve,
+}
+
+impl Default for Whitespace {
+  fn default() -> Self {
+    Whitespace::Collapse
+  }
+}
+
+impl Whitespace {
+  /// Returns the text of a text node with the content `text`, or `None` if there shouldn't be one.
+  #[must_use]
+  pub fn apply(self, text: &str) -> Option<String> {
+    if text.trim().is_empty() {
+      return None;
+    }
+
+    match self {
+      Whitespace::Collapse => Some(text.split_whitespace().collect::<Vec<_>>().join(" ")),
+      Whitespace::Preserve => Some(text.to_string()),
+    }
+  }
+}
+
+/// Settings of a compilation.
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+  /// Levels overriding the default ones by `DiagnosticKind::name`, `None` dropping the diagnostic.
+  levels: HashMap<String, Option<Level>>,
+  whitespace: Whitespace,
+  source_map: bool,
+  network_policy: NetworkPolicy,
+  /// Values `<If cfg="...">` conditions are evaluated against, by key.
+  defines: HashMap<String, String>,
+  stylesheets: Vec<Url>,
+  sass: SassOptions,
+  progress: Observer,
+  embed_images: bool,
+}
+
+/// Settings passed to the Sass compiler for `sass` and `scss` styles.
+#[derive(Debug, Clone, Default)]
+pub struct SassOptions {
+  /// Directories `@import` looks in after the directory of the stylesheet, for shared libraries.
+  pub include_paths: Vec<PathBuf>,
+  /// Variables defined before the stylesheet, by name without the `$`. Values are Sass expressions, so
+  /// strings need quotes.
+  pub variables: Vec<(String, String)>,
+  /// Number of digits after the decimal point of numbers, `None` keeping the default of the backend.
+  pub precision: Option<i32>,
+}
+
+impl SassOptions {
+  /// Source defining `variables`, put before the stylesheet.
+  pub(crate) fn prelude(&self, indented_syntax: bool) -> String {
+    let terminator = if indented_syntax { "\n" } else { ";\n" };
+    self
+      .variables
+      .iter()
+      .map(|(name, value)| format!("${}: {}{}", name.trim_start_matches('$'), value, terminator))
+      .collect()
+  }
+}
+
+impl CompileOptions {
+  #[must_use]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn set_whitespace(&mut self, whitespace: Whitespace) -> &mut Self {
+    self.whitespace = whitespace;
+    self
+  }
+
+  #[must_use]
+  pub fn whitespace(&self) -> Whitespace {
+    self.whitespace
+  }
+
+  /// Adds a `dom::source_map::SourceMap` to the document, so script errors and devtools can point at the
+  /// source the elements and scripts come from. Off by default, as it grows the document.
+  pub fn set_source_map(&mut self, source_map: bool) -> &mut Self {
+    self.source_map = source_map;
+    self
+  }
+
+  #[must_use]
+  pub fn source_map(&self) -> bool {
+    self.source_map
+  }
+
+  /// Sets how remote resources are requested and which URLs may be read, `NetworkPolicy::default()` is used
+  /// otherwise.
+  pub fn set_network_policy(&mut self, network_policy: NetworkPolicy) -> &mut Self {
+    self.network_policy = network_policy;
+    self
+  }
+
+  #[must_use]
+  pub fn network_policy(&self) -> &NetworkPolicy {
+    &self.network_policy
+  }
+
+  /// Defines `key` as `value` for `<If cfg="...">` conditions, `value` being empty for keys that are only
+  /// checked for being defined.
+  pub fn set_define(&mut self, key: &str, value: &str) -> &mut Self {
+    self.defines.insert(key.to_string(), value.to_string());
+    self
+  }
+
+  #[must_use]
+  pub fn define(&self, key: &str) -> Option<&str> {
+    self.defines.get(key).map(String::as_str)
+  }
+
+  /// Embeds the images of stylesheets into the document like `<Asset>` does, so it can be shown without
+  /// network or file access. Off by default, image URLs are only made absolute then.
+  pub fn set_embed_images(&mut self, embed_images: bool) -> &mut Self {
+    self.embed_images = embed_images;
+    self
+  }
+
+  #[must_use]
+  pub fn embed_images(&self) -> bool {
+    self.embed_images
+  }
+
+  pub fn set_sass(&mut self, sass: SassOptions) -> &mut Self {
+    self.sass = sass;
+    self
+  }
+
+  #[must_use]
+  pub fn sass(&self) -> &SassOptions {
+    &self.sass
+  }
+
+  /// Reports what the compiler is doing to `observer`, replacing the observer set before.
+  pub fn set_progress_observer(&mut self, observer: impl ProgressObserver + 'static) -> &mut Self {
+    self.progress = Observer(Some(Arc::new(observer)));
+    self
+  }
+
+  pub(crate) fn progress(&self) -> &Observer {
+    &self.progress
+  }
+
+  /// Adds the stylesheet at `url` to every compiled document, before the document's own styles. Lets
+  /// documents built together share styles without linking them each.
+  pub fn add_stylesheet(&mut self, url: Url) -> &mut Self {
+    self.stylesheets.push(url);
+    self
+  }
+
+  #[must_use]
+  pub fn stylesheets(&self) -> &[Url] {
+    &self.stylesheets
+  }
+
+  /// Reports diagnostics whose `DiagnosticKind::name` is `kind` at `level` instead of their default level,
+  /// or not at all if `level` is `None`.
+  ///
+  /// Raising a kind to `Level::Error` makes `DiagnosticReporter::checkpoint` fail on it like on any other
+  /// error. Lowering one only changes how it's reported though, a diagnostic that stops the compilation,
+  /// e.g. a syntax error, still stops it. Compiler bugs are always reported as such.
+  pub fn set_level(&mut self, kind: &str, level: Option<Level>) -> &mut Self {
+    self.levels.insert(kind.to_string(), level);
+    self
+  }
+
+  /// The level `kind` is reported at, `default` being the level the compiler reports it at.
+  #[must_use]
+  pub fn level(&self, kind: &DiagnosticKind, default: Level) -> Option<Level> {
+    match default {
+      Level::Bug => Some(Level::Bug),
+      _ => self.levels.get(kind.name()).cloned().unwrap_or(Some(default)),
+    }
+  }
+}
+
+/// Applies the levels of `CompileOptions` to the diagnostics before passing them on.
+pub(crate) struct LevelFilter<'r, FileId> {
+  pub reporter: &'r mut dyn DiagnosticReporter<FileId = FileId>,
+  pub options: &'r CompileOptions,
+}
+
+impl<FileId: fmt::Debug + Clone> DiagnosticReporter for LevelFilter<'_, FileId> {
+  type FileId = FileId;
+
+  fn add_file(&mut self, filename: String, source: String) -> FileId {
+    self.reporter.add_file(filename, source)
+  }
+
+  fn add_diagnostic(&mut self, mut diagnostic: Diagnostic<FileId>) {
+    if let Some(level) = self.options.level(&diagnostic.kind, diagnostic.min_level) {
+      diagnostic.min_level = level;
+      self.reporter.add_diagnostic(diagnostic);
+    }
+  }
+
+  fn get_position(&mut self, file: &FileId, line: usize, col: usize) -> usize {
+    self.reporter.get_position(file, line, col)
+  }
+
+  fn get_line(&mut self, file: &FileId, pos: usize) -> usize {
+    self.reporter.get_line(file, pos)
+  }
+
+  fn checkpoint(&mut self) -> Result<(), ()> {
+    self.reporter.checkpoint()
+  }
+}
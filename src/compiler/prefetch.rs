@@ -0,0 +1,102 @@
+use std::{
+  collections::{HashMap, HashSet},
+  io::prelude::*,
+};
+
+use quick_xml::events::{BytesStart, Event};
+use rayon::prelude::*;
+use url::Url;
+
+use super::{cfg, CompileOptions, Progress, Reader};
+
+/// Elements whose `src` is fetched while compiling.
+const ELEMENTS: &[&[u8]] = &[b"Style", b"Script", b"Include", b"Asset"];
+
+/// Returns whether the children of an `<If>` element are compiled, invalid conditions aren't.
+fn is_enabled(e: &BytesStart, options: &CompileOptions) -> bool {
+  let condition = e
+    .attributes()
+    .filter_map(Result::ok)
+    .find(|x| x.key == b"cfg")
+    .and_then(|x| x.unescaped_value().ok().map(|x| x.into_owned()));
+
+  condition
+    .as_deref()
+    .and_then(|x| std::str::from_utf8(x).ok())
+    .map_or(false, |x| cfg::evaluate(x, options).unwrap_or(false))
+}
+
+/// Downloads the remote resources referenced by the elements of `source` on the rayon pool, instead of one
+/// after another as the compiler reaches them. Elements inside `<If>` elements that aren't compiled are skipped.
+///
+/// Only successful downloads are returned, failed ones are fetched again by the compiler so the error is
+/// reported at the element. Local files are cheap to read and are left to the compiler as well.
+pub fn prefetch(source: &str, url: &Url, options: &CompileOptions) -> HashMap<Url, Vec<u8>> {
+  let mut reader = quick_xml::Reader::from_str(source);
+  let mut buf = Vec::new();
+  let mut urls = HashSet::new();
+  let mut base = url.clone();
+  // Depth inside an `<If>` that isn't compiled, 0 outside of one.
+  let mut skipped = 0;
+
+  loop {
+    match reader.read_event(&mut buf) {
+      Ok(Event::Start(..)) if skipped > 0 => skipped += 1,
+      Ok(Event::End(..)) if skipped > 0 => skipped -= 1,
+      Ok(Event::Empty(..)) if skipped > 0 => {}
+
+      Ok(Event::Start(e)) if e.name() == b"If" && !is_enabled(&e, options) => skipped = 1,
+
+      Ok(Event::Empty(e)) if e.name() == b"Base" => {
+        let href = e
+          .attributes()
+          .filter_map(Result::ok)
+          .find(|x| x.key == b"href")
+          .and_then(|x| x.unescaped_value().ok().map(|x| x.into_owned()));
+        if let Some(base_url) = href
+          .as_deref()
+          .and_then(|x| std::str::from_utf8(x).ok())
+          .and_then(|x| url.join(x).ok())
+        {
+          base = base_url;
+        }
+      }
+
+      Ok(Event::Start(e)) | Ok(Event::Empty(e)) if ELEMENTS.contains(&e.name()) => {
+        for attr in e.attributes().filter_map(Result::ok) {
+          if attr.key != b"src" {
+            continue;
+          }
+
+          let src = attr.unescaped_value().ok();
+          let url = src
+            .as_deref()
+            .and_then(|x| std::str::from_utf8(x).ok())
+            .and_then(|x| base.join(x).ok());
+          if let Some(url) = url.filter(|x| x.scheme() != "file") {
+            urls.insert(url);
+          }
+        }
+      }
+
+      // Syntax errors are left for the compiler to report.
+      Ok(Event::Eof) | Err(_) => break,
+      Ok(_) => {}
+    }
+
+    buf.clear();
+  }
+
+  let policy = options.network_policy();
+  let progress = options.progress();
+  urls
+    .into_par_iter()
+    .filter_map(|url| {
+      progress.report(Progress::Fetching(&url));
+      let mut data = Vec::new();
+      let result = Reader::get(&url, policy).and_then(|mut x| Ok(x.read_to_end(&mut data)?));
+      progress.report(Progress::Fetched(&url));
+      result.ok().map(|_| (url, data))
+    })
+    .collect()
+}
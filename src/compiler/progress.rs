@@ -0,0 +1,52 @@
+use std::{fmt, sync::Arc};
+
+use url::Url;
+
+/// What the compiler started or finished doing, reported to the `ProgressObserver` of `CompileOptions`.
+///
+/// Remote resources are mostly fetched before the document is compiled, all at once, so the number of
+/// `Fetching` events without a `Fetched` one is how many downloads are still running.
+#[derive(Debug, Clone, Copy)]
+pub enum Progress<'a> {
+  /// Started compiling a document, the one passed to the compiler or an included one.
+  Document(&'a Url),
+  /// Started downloading a remote resource.
+  Fetching(&'a Url),
+  /// Finished downloading a remote resource, successfully or not.
+  Fetched(&'a Url),
+  /// Started compiling a Sass or SCSS stylesheet, `None` for one written inside a `<Style>`.
+  CompilingSass(Option<&'a Url>),
+  /// Started parsing a CSS stylesheet or the output of the Sass compiler, `None` for one written inside a
+  /// `<Style>`.
+  ParsingCss(Option<&'a Url>),
+}
+
+/// Receives the `Progress` of compilations, e.g. to show a progress bar. Called from the threads downloading
+/// resources too, so it has to be thread-safe.
+pub trait ProgressObserver: Send + Sync {
+  fn progress(&self, progress: Progress<'_>);
+}
+
+impl<F: Fn(Progress<'_>) + Send + Sync> ProgressObserver for F {
+  fn progress(&self, progress: Progress<'_>) {
+    self(progress)
+  }
+}
+
+/// The observer of a compilation, if there is one.
+#[derive(Clone, Default)]
+pub(crate) struct Observer(pub Option<Arc<dyn ProgressObserver>>);
+
+impl Observer {
+  pub fn report(&self, progress: Progress<'_>) {
+    if let Some(observer) = &self.0 {
+      observer.progress(progress);
+    }
+  }
+}
+
+impl fmt::Debug for Observer {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("Observer").field(&self.0.is_some()).finish()
+  }
+}
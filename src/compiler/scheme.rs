@@ -0,0 +1,42 @@
+//! Handlers reading URLs of schemes the compiler doesn't know, so applications can serve documents and
+//! their resources from e.g. `res://` URLs of resources embedded in the binary, archives or databases.
+
+use std::{
+  io,
+  sync::{Arc, RwLock},
+};
+
+use url::Url;
+
+type Handler = Arc<dyn Fn(&Url) -> io::Result<Vec<u8>> + Send + Sync>;
+
+static HANDLERS: RwLock<Vec<(String, Handler)>> = RwLock::new(Vec::new());
+
+/// Reads URLs with the scheme `scheme` with `handler`, replacing the handler registered for it before.
+///
+/// Handlers take precedence over the built-in schemes, so `file` or `https` can be served by the application
+/// too. The `NetworkPolicy` of the compilation still decides whether a URL may be read at all.
+pub fn register_scheme<F>(scheme: &str, handler: F)
+where
+  F: Fn(&Url) -> io::Result<Vec<u8>> + Send + Sync + 'static,
+{
+  let scheme = scheme.to_ascii_lowercase();
+  let mut handlers = HANDLERS.write().unwrap();
+  handlers.retain(|(x, _)| *x != scheme);
+  handlers.push((scheme, Arc::new(handler)));
+}
+
+/// Removes the handler of `scheme`, if there is one.
+pub fn unregister_scheme(scheme: &str) {
+  let scheme = scheme.to_ascii_lowercase();
+  HANDLERS.write().unwrap().retain(|(x, _)| *x != scheme);
+}
+
+// Cloned out of the lock, so handlers can register schemes themselves.
+pub(crate) fn handler(scheme: &str) -> Option<Handler> {
+  let handlers = HANDLERS.read().unwrap();
+  handlers
+    .iter()
+    .find(|(x, _)| x == scheme)
+    .map(|(_, handler)| Arc::clone(handler))
+}
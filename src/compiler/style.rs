@@ -1,13 +1,14 @@
-use std::{fmt, io::prelude::*};
+use std::{fmt, io::prelude::*, ops::Range};
 
 use quick_xml::events::BytesStart;
 use serde::{Deserialize, Serialize};
 use source_map_mappings::{parse_mappings, Bias, Mappings};
 use url::Url;
 
-use style::StyleSheet;
+use dom::source_map::SourceLocation;
+use style::{Declaration, StyleSheet};
 
-use super::{handle_error_with_location, Context, Diagnostic, DiagnosticKind, Level, Reader};
+use super::{handle_error_with_location, Context, Diagnostic, DiagnosticKind, DiagnosticReporter, Level, Progress};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum StyleType {
@@ -22,7 +23,7 @@ enum StyleSource {
   Data(String),
 }
 
-enum SourceMapOrFileId<FileId> {
+pub(crate) enum SourceMapOrFileId<FileId> {
   SourceMap(SourceMap),
   FileId(FileId),
 }
@@ -39,7 +40,7 @@ struct RawSourceMap {
 }
 
 #[derive(Debug)]
-struct SourceMap {
+pub(crate) struct SourceMap {
   version: u64,
   file: String,
   sources: Vec<String>,
@@ -62,7 +63,50 @@ impl SourceMap {
   }
 }
 
+/// Guesses the type of the stylesheet at `url` from its extension, defaulting to SCSS.
+fn style_type(url: &Url) -> StyleType {
+  let filename = url.path_segments().unwrap().next_back().unwrap();
+  let mut split = filename.split('.');
+  let ext = split.next_back().unwrap();
+  if split.next_back().is_some() {
+    match ext {
+      "css" => StyleType::CSS,
+      "sass" => StyleType::Sass,
+      _ => StyleType::SCSS,
+    }
+  } else {
+    StyleType::SCSS
+  }
+}
+
 impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
+  /// Reports `@warn` messages, `input` is the source passed to libsass as `input_path`.
+  fn report_sass_warnings(&mut self, warnings: Vec<sass::Warning>, input_path: &str, input: &str) {
+    for warning in warnings {
+      let (line, column) = (warning.line, warning.column);
+      let location = warning.file.and_then(|file| {
+        let source = if file == input_path {
+          input.to_string()
+        } else {
+          std::fs::read_to_string(&file).ok()?
+        };
+
+        let file_id = self.reporter.add_file(file, source);
+        let pos = self
+          .reporter
+          .get_position(&file_id, line.saturating_sub(1) as usize, column as usize);
+        Some((file_id, pos..pos))
+      });
+
+      self.reporter.add_diagnostic(Diagnostic {
+        location,
+        min_level: Level::Warn,
+        kind: DiagnosticKind::SassWarning(warning.message),
+        fixes: Vec::new(),
+      });
+    }
+  }
+
   pub fn compile_style<'a, R: BufRead>(
     &mut self,
     e: BytesStart<'a>,
@@ -101,42 +145,47 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
               "css" => StyleType::CSS,
               "sass" => StyleType::Sass,
               "scss" => StyleType::SCSS,
-              _ => unimplemented!(),
+              _ => {
+                let kind = DiagnosticKind::InvalidAttribute {
+                  attr: "type".to_string(),
+                  el: "Style".to_string(),
+                };
+                self.report(kind, reader, file_id);
+                return Err(());
+              }
             })
           }
 
           _ => {
             self.reporter.add_diagnostic(Diagnostic {
-              location: Some((file_id.clone(), reader.buffer_position())),
+              location: Some((file_id.clone(), self.attribute_span(&e, attr.key, reader))),
               min_level: Level::Info,
               kind: DiagnosticKind::InvalidAttribute {
                 attr: key.to_string(),
                 el: "Style".to_string(),
               },
+              fixes: Vec::new(),
             });
           }
         }
       }
 
-      let src = src.unwrap();
+      let src = match src {
+        Some(src) => src,
+        None => {
+          let kind = DiagnosticKind::MissingAttribute {
+            attr: "src".to_string(),
+            el: "Style".to_string(),
+          };
+          self.report(kind, reader, file_id);
+          return Err(());
+        }
+      };
       let url = url
         .join(&src)
         .map_err(handle_error_with_location!(self, file_id, reader))?;
 
-      let ty = ty.unwrap_or_else(|| {
-        let filename = url.path_segments().unwrap().next_back().unwrap();
-        let mut split = filename.split('.');
-        let ext = split.next_back().unwrap();
-        if split.next_back().is_some() {
-          match ext {
-            "css" => StyleType::CSS,
-            "sass" => StyleType::Sass,
-            _ => StyleType::SCSS,
-          }
-        } else {
-          StyleType::SCSS
-        }
-      });
+      let ty = ty.unwrap_or_else(|| style_type(&url));
 
       (StyleSource::Url(url), ty)
     } else {
@@ -159,33 +208,91 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
               "css" => StyleType::CSS,
               "sass" => StyleType::Sass,
               "scss" => StyleType::SCSS,
-              _ => unimplemented!(),
+              _ => {
+                let kind = DiagnosticKind::InvalidAttribute {
+                  attr: "type".to_string(),
+                  el: "Style".to_string(),
+                };
+                self.report(kind, reader, file_id);
+                return Err(());
+              }
             })
           }
 
           _ => {
             self.reporter.add_diagnostic(Diagnostic {
-              location: Some((file_id.clone(), reader.buffer_position())),
+              location: Some((file_id.clone(), self.attribute_span(&e, attr.key, reader))),
               min_level: Level::Info,
               kind: DiagnosticKind::InvalidAttribute {
                 attr: key.to_string(),
                 el: "Style".to_string(),
               },
+              fixes: Vec::new(),
             });
           }
         }
       }
 
-      let text = reader
-        .read_text(e.name(), buf)
-        .map_err(handle_error_with_location!(self, file_id, reader))?;
+      let text = self.read_text("Style", reader, buf, file_id)?;
       (StyleSource::Data(text), ty.unwrap_or(StyleType::SCSS))
     };
 
+    self.compile_style_source(source, ty, offset, url, reader, file_id)
+  }
+
+  /// Adds the stylesheet at `url` as if the document linked it with `<Style src>`, problems reading it are
+  /// reported at the current position of `reader`.
+  pub fn compile_shared_style<R: BufRead>(
+    &mut self,
+    url: &Url,
+    reader: &mut quick_xml::Reader<R>,
+    file_id: &FileId,
+  ) -> Result<(), ()> {
+    let rules = self.stylesheet.rules.len();
+    self.compile_style_source(StyleSource::Url(url.clone()), style_type(url), 0, url, reader, file_id)?;
+
+    // Documents rarely use every rule of a shared stylesheet, so they aren't checked for unused ones.
+    for source in &mut self.rule_sources[rules..] {
+      *source = None;
+    }
+
+    Ok(())
+  }
+
+  /// Where the rule at `index` of the stylesheet starts in its original source.
+  pub(crate) fn rule_location(&mut self, index: usize) -> Option<(FileId, Range<usize>)> {
+    let (source, location) = self.rule_sources.get(index).copied().flatten()?;
+    css_location(
+      &mut *self.reporter,
+      &self.style_sources[source],
+      location.line,
+      location.column,
+    )
+  }
+
+  /// Compiles `source` into the stylesheet, `offset` being the line inline styles start at and `base` the URL
+  /// their images are relative to.
+  fn compile_style_source<R: BufRead>(
+    &mut self,
+    source: StyleSource,
+    ty: StyleType,
+    offset: usize,
+    base: &Url,
+    reader: &mut quick_xml::Reader<R>,
+    file_id: &FileId,
+  ) -> Result<(), ()> {
+    let style_url = match &source {
+      StyleSource::Url(url) => Some(url.clone()),
+      StyleSource::Data(..) => None,
+    };
+    let base = style_url.clone().unwrap_or_else(|| base.clone());
+
     let (css, offset, source) = match ty {
       StyleType::CSS => match source {
         StyleSource::Url(url) => {
-          let mut url_reader = Reader::get(&url).map_err(handle_error_with_location!(self, file_id, reader))?;
+          let mut url_reader = self
+            .open(&url)
+            .map_err(handle_error_with_location!(self, file_id, reader))?;
           let mut out = String::new();
           url_reader
             .read_to_string(&mut out)
@@ -200,7 +307,9 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
       ty => {
         let (text, url) = match source {
           StyleSource::Url(url) => {
-            let mut url_reader = Reader::get(&url).map_err(handle_error_with_location!(self, file_id, reader))?;
+            let mut url_reader = self
+              .open(&url)
+              .map_err(handle_error_with_location!(self, file_id, reader))?;
 
             let mut out = String::new();
             url_reader
@@ -213,72 +322,188 @@ impl<'r, FileId: fmt::Debug + Clone> Context<'r, FileId> {
           StyleSource::Data(text) => (text, Url::parse("file:///C/bar.txt").unwrap()),
         };
 
+        // Positions stay consistent, as errors and the source map include the prelude in the source.
+        let options = self.options;
+        let sass_options = options.sass();
+        let text = sass_options.prelude(ty == StyleType::Sass) + &text;
+
+        options.progress().report(Progress::CompilingSass(style_url.as_ref()));
         let ctx = sass::DataContext::new(&text).unwrap();
         let opt = ctx.options();
         opt.set_input_path(url.as_str()).unwrap();
         opt.set_source_map_file("stdin").unwrap();
         opt.set_source_map_contents(true);
         opt.set_is_indented_syntax_src(ty == StyleType::Sass);
+        for include_path in &sass_options.include_paths {
+          opt.push_include_path(&include_path.to_string_lossy()).unwrap();
+        }
+        if let Some(precision) = sass_options.precision {
+          opt.set_precision(precision);
+        }
+        // The output is only read by the style parser, so there is no point in formatting it.
+        opt.set_output_style(sass::OutputStyle::Compressed);
+
+        let compiled = ctx.compile_owned().map_err(|e| {
+          self.report_sass_warnings(e.warnings, url.as_str(), &text);
+
+          // Not every backend knows where an error happened.
+          let location = match (e.file, e.src) {
+            (Some(file), Some(src)) if e.line > 0 => {
+              let file_id = self.reporter.add_file(file, src);
+              let pos = self
+                .reporter
+                .get_position(&file_id, e.line as usize - 1, e.column as usize);
+              Some((file_id, pos..pos))
+            }
+
+            _ => None,
+          };
 
-        let compiled = ctx.compile().map_err(|e| {
-          let file_id = self.reporter.add_file(e.file().unwrap(), e.src().unwrap());
-          let pos = self
-            .reporter
-            .get_position(&file_id, e.line() as usize - 1, e.column() as usize);
           self.reporter.add_diagnostic(Diagnostic {
-            location: Some((file_id, pos)),
+            location,
             min_level: Level::Error,
-            kind: DiagnosticKind::SassParseError(e.text().unwrap()),
+            kind: DiagnosticKind::SassParseError(e.text),
+            fixes: Vec::new(),
           });
         })?;
 
-        let css = compiled.output().unwrap();
-        let source_map = unsafe { compiled.source_map().unwrap() };
-        let source_map = SourceMap::parse(&source_map);
+        self.report_sass_warnings(compiled.warnings, url.as_str(), &text);
+
+        // Without a source map, CSS errors can only point at the compiled output.
+        match compiled.source_map {
+          Some(source_map) => (
+            compiled.css,
+            0,
+            SourceMapOrFileId::SourceMap(SourceMap::parse(&source_map)),
+          ),
 
-        (css, 0, SourceMapOrFileId::SourceMap(source_map))
+          None => {
+            let file_id = self.reporter.add_file(url.to_string(), compiled.css.clone());
+            (compiled.css, 0, SourceMapOrFileId::FileId(file_id))
+          }
+        }
       }
     };
 
+    self.options.progress().report(Progress::ParsingCss(style_url.as_ref()));
+    let first_rule = self.stylesheet.rules.len();
     let mut input = StyleSheet::create_parser_input_with_line_offset(&css, offset as u32);
-    self.stylesheet.parse(&mut input).map_err(|e| {
-      let location = match source {
-        SourceMapOrFileId::FileId(file_id) => {
-          let pos = self
-            .reporter
-            .get_position(&file_id, e.0.location.line as usize, e.0.location.column as usize);
-          Some((file_id, pos))
-        }
-
-        SourceMapOrFileId::SourceMap(source_map) => {
-          let original_location = source_map
-            .mappings
-            .original_location_for(e.0.location.line, e.0.location.column, Bias::GreatestLowerBound)
-            .unwrap();
-          let original_location = original_location.original.as_ref().unwrap();
-
-          let file_id = self.reporter.add_file(
-            source_map.sources[original_location.source as usize].clone(),
-            source_map.sources_content[original_location.source as usize].clone(),
-          );
-
-          let pos = self.reporter.get_position(
-            &file_id,
-            original_location.original_line as usize,
-            original_location.original_column as usize,
-          );
-
-          Some((file_id, pos))
-        }
-      };
-
+    let locations = self.stylesheet.parse_with_locations(&mut input).map_err(|e| {
+      let location = css_location(&mut *self.reporter, &source, e.0.location.line, e.0.location.column);
       self.reporter.add_diagnostic(Diagnostic {
         location,
         min_level: Level::Error,
         kind: DiagnosticKind::CssParseError(e),
+        fixes: Vec::new(),
       });
     })?;
 
-    Ok(())
+    let file_name = style_url.as_ref().map(|url| self.relative(url));
+    if let Some(source_map) = &mut self.source_map {
+      let file = match file_name {
+        _ if ty != StyleType::CSS => None,
+        Some(name) => Some(source_map.add_file(&name)),
+        None => Some(self.source_file),
+      };
+
+      source_map.rules.extend(locations.iter().map(|location| {
+        file.map(|file| SourceLocation {
+          file,
+          line: location.line as usize + 1,
+          column: location.column as usize,
+        })
+      }));
+    }
+
+    let index = self.style_sources.len();
+    self
+      .rule_sources
+      .extend(locations.into_iter().map(|location| Some((index, location))));
+    self.style_sources.push(source);
+
+    self.resolve_images(first_rule, &base)
+  }
+
+  /// Makes the URLs of the images of the rules from `first_rule` on absolute, see
+  /// `CompileOptions::set_embed_images`.
+  fn resolve_images(&mut self, first_rule: usize, base: &Url) -> Result<(), ()> {
+    let mut failed = false;
+    for index in first_rule..self.stylesheet.rules.len() {
+      for declaration in 0..self.stylesheet.rules[index].properties.len() {
+        let url = match &self.stylesheet.rules[index].properties[declaration] {
+          Declaration::BackgroundImage(Some(url)) => url.clone(),
+          _ => continue,
+        };
+
+        match self.resolve_image(&url, base) {
+          Ok(url) => self.stylesheet.rules[index].properties[declaration] = Declaration::BackgroundImage(Some(url)),
+          Err(kind) => {
+            failed = true;
+            let location = self.rule_location(index);
+            self.reporter.add_diagnostic(Diagnostic {
+              location,
+              min_level: Level::Error,
+              kind,
+              fixes: Vec::new(),
+            });
+          }
+        }
+      }
+    }
+
+    if failed {
+      Err(())
+    } else {
+      Ok(())
+    }
+  }
+
+  /// How the document refers to the image at `url`, embedding the image first if the options ask for it.
+  fn resolve_image(&mut self, url: &str, base: &Url) -> Result<String, DiagnosticKind<'static>> {
+    let url = base.join(url)?;
+    if !self.options.embed_images() {
+      return Ok(url.to_string());
+    }
+
+    let mut data = Vec::new();
+    self.open(&url)?.read_to_end(&mut data)?;
+    let key = self.relative(&url);
+    self.resources.insert(key.clone(), data);
+    Ok(key)
+  }
+}
+
+/// Location in the original source of `line` and `column` of the CSS compiled from `source`.
+fn css_location<FileId: Clone>(
+  reporter: &mut dyn DiagnosticReporter<FileId = FileId>,
+  source: &SourceMapOrFileId<FileId>,
+  line: u32,
+  column: u32,
+) -> Option<(FileId, Range<usize>)> {
+  match source {
+    SourceMapOrFileId::FileId(file_id) => {
+      let pos = reporter.get_position(file_id, line as usize, column as usize);
+      Some((file_id.clone(), pos..pos))
+    }
+
+    SourceMapOrFileId::SourceMap(source_map) => {
+      let original_location = source_map
+        .mappings
+        .original_location_for(line, column, Bias::GreatestLowerBound)?;
+      let original_location = original_location.original.as_ref()?;
+
+      let file_id = reporter.add_file(
+        source_map.sources[original_location.source as usize].clone(),
+        source_map.sources_content[original_location.source as usize].clone(),
+      );
+
+      let pos = reporter.get_position(
+        &file_id,
+        original_location.original_line as usize,
+        original_location.original_column as usize,
+      );
+
+      Some((file_id, pos..pos))
+    }
   }
 }
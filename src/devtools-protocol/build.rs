@@ -1,7 +1,7 @@
 use proc_macro2::{Ident, TokenStream};
 use quote::{format_ident, quote};
 use serde::{Deserialize, Serialize};
-use std::{env, fs::File, io::prelude::*, path::Path};
+use std::{collections::BTreeSet, env, fs::File, io::prelude::*, path::Path};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Protocol {
@@ -290,25 +290,109 @@ fn uppercase_first(s: &str) -> String {
   }
 }
 
+fn property_refs<'a>(props: &'a [PropertyType], out: &mut Vec<&'a str>) {
+  for prop in props {
+    match &prop.data {
+      RefTypeOr::Ref(reference) => out.push(&reference.r#ref),
+      RefTypeOr::Other(ProtocolType::Array(arr)) => array_refs(&arr.items, out),
+      RefTypeOr::Other(ProtocolType::Object(obj)) => {
+        if let Some(props) = &obj.properties {
+          property_refs(props, out);
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+fn array_refs<'a>(items: &'a RefTypeOr<ArrayItemType>, out: &mut Vec<&'a str>) {
+  match items {
+    RefTypeOr::Ref(reference) => out.push(&reference.r#ref),
+    RefTypeOr::Other(ArrayItemType::Object(obj)) => {
+      if let Some(props) = &obj.properties {
+        property_refs(props, out);
+      }
+    }
+    _ => {}
+  }
+}
+
+/// Returns the names of every other domain whose types are referenced by `domain`.
+fn referenced_domains(domain: &ProtocolDomain) -> BTreeSet<String> {
+  let mut refs = Vec::new();
+
+  for ty in domain.types.iter().flatten() {
+    match &ty.data {
+      DomainTypeData::Object(obj) => property_refs(obj.properties.as_deref().unwrap_or_default(), &mut refs),
+      DomainTypeData::Array(arr) => array_refs(&arr.items, &mut refs),
+      _ => {}
+    }
+  }
+
+  for command in domain.commands.iter().flatten() {
+    property_refs(command.event.parameters.as_deref().unwrap_or_default(), &mut refs);
+    property_refs(command.returns.as_deref().unwrap_or_default(), &mut refs);
+  }
+
+  for event in domain.events.iter().flatten() {
+    property_refs(event.parameters.as_deref().unwrap_or_default(), &mut refs);
+  }
+
+  refs
+    .into_iter()
+    .filter_map(|reference| reference.split('.').next().filter(|_| reference.contains('.')))
+    .filter(|ns| *ns != domain.domain)
+    .map(ToString::to_string)
+    .collect()
+}
+
+fn feature_enabled(domain: &str) -> bool {
+  env::var_os("CARGO_FEATURE_ALL").is_some()
+    || env::var_os(format!("CARGO_FEATURE_{}", domain.to_uppercase())).is_some()
+}
+
 fn main() {
   println!("cargo:rerun-if-changed=devtools-protocol/json/browser_protocol.json");
   println!("cargo:rerun-if-changed=devtools-protocol/json/js_protocol.json");
+  println!("cargo:rerun-if-changed=frameui_protocol.json");
 
   let mut browser: Protocol =
     serde_json::from_reader(File::open("devtools-protocol/json/browser_protocol.json").unwrap()).unwrap();
   let js: Protocol = serde_json::from_reader(File::open("devtools-protocol/json/js_protocol.json").unwrap()).unwrap();
+  let frameui: Protocol = serde_json::from_reader(File::open("frameui_protocol.json").unwrap()).unwrap();
 
   browser.domains.extend(js.domains);
+  browser.domains.extend(frameui.domains);
+
+  // Only generate the domains selected through cargo features, plus every domain
+  // they (transitively) reference types from.
+  let mut enabled: BTreeSet<String> = browser
+    .domains
+    .iter()
+    .filter(|x| feature_enabled(&x.domain))
+    .map(|x| x.domain.clone())
+    .collect();
+
+  let mut pending: Vec<String> = enabled.iter().cloned().collect();
+  while let Some(name) = pending.pop() {
+    let domain = browser.domains.iter().find(|x| x.domain == name).unwrap();
+    for dependency in referenced_domains(domain) {
+      if enabled.insert(dependency.clone()) {
+        pending.push(dependency);
+      }
+    }
+  }
 
   let mut domains = Vec::new();
   let mut domain_names = Vec::new();
 
-  for domain in &browser.domains {
+  for domain in browser.domains.iter().filter(|x| enabled.contains(&x.domain)) {
     domain_names.push(domain.domain.clone());
 
     let mut types = Vec::new();
     let mut commands = Vec::new();
     let mut command_results = Vec::new();
+    let mut events = Vec::new();
 
     for ty in domain.types.clone().unwrap_or_default() {
       let ident = format_ident!("{}", ty.id);
@@ -426,6 +510,44 @@ fn main() {
       }
     }
 
+    for event in domain.events.as_ref().unwrap_or(&Vec::new()) {
+      let name = format!("{}.{}", domain.domain, event.name);
+      let ident = format_ident!("{}", uppercase_first(&event.name));
+      let description = event.description.clone().unwrap_or_default();
+
+      if let Some(parameters) = &event.parameters {
+        let struct_ident = format_ident!("{}Event", ident);
+        let props: Vec<_> = generate_properties(
+          &struct_ident,
+          &mut types,
+          parameters,
+          true,
+          &domain.domain,
+          &browser.domains,
+        );
+
+        types.push(quote!(
+          #[doc = #description]
+          #[derive(Debug, Clone, PartialEq, serde::Serialize)]
+          pub struct #struct_ident {
+            #(#props),*
+          }
+        ));
+
+        events.push(quote!(
+          #[serde(rename = #name)]
+          #[doc = #description]
+          #ident(#struct_ident)
+        ));
+      } else {
+        events.push(quote!(
+          #[serde(rename = #name)]
+          #[doc = #description]
+          #ident {}
+        ));
+      }
+    }
+
     let ident = format_ident!("{}", domain.domain.to_lowercase());
     let description = domain.description.clone().unwrap_or_default();
     let dependencies = format!(
@@ -449,6 +571,12 @@ fn main() {
         pub enum CommandResult {
           #(#command_results),*
         }
+
+        #[derive(Debug, Clone, PartialEq, serde::Serialize)]
+        #[serde(tag = "method", content = "params")]
+        pub enum Event {
+          #(#events),*
+        }
       }
     ));
   }
@@ -465,6 +593,24 @@ fn main() {
     quote!(#variant_ident(#mod_ident::CommandResult))
   });
 
+  let event_variants = domain_names.iter().map(|name| {
+    let variant_ident = format_ident!("{}", name);
+    let mod_ident = format_ident!("{}", name.to_lowercase());
+    quote!(#variant_ident(#mod_ident::Event))
+  });
+
+  let event_conversions = domain_names.iter().map(|name| {
+    let variant_ident = format_ident!("{}", name);
+    let mod_ident = format_ident!("{}", name.to_lowercase());
+    quote!(
+      impl From<#mod_ident::Event> for EventMessage {
+        fn from(event: #mod_ident::Event) -> Self {
+          EventMessage::#variant_ident(event)
+        }
+      }
+    )
+  });
+
   let version = format!(
     "DevTools Protocol Version {}.{}",
     browser.version.major, browser.version.minor
@@ -497,6 +643,26 @@ fn main() {
     pub enum CommandResultData {
       #(#command_result_variants),*
     }
+
+    #[derive(Debug, Clone, PartialEq, serde::Serialize)]
+    pub struct CommandError {
+      pub id: u64,
+      pub error: CommandErrorData,
+    }
+
+    #[derive(Debug, Clone, PartialEq, serde::Serialize)]
+    pub struct CommandErrorData {
+      pub code: i64,
+      pub message: String,
+    }
+
+    #[derive(Debug, Clone, PartialEq, serde::Serialize)]
+    #[serde(untagged)]
+    pub enum EventMessage {
+      #(#event_variants),*
+    }
+
+    #(#event_conversions)*
   );
 
   let path = Path::new(&env::var("OUT_DIR").unwrap()).join("bindings.rs");
@@ -0,0 +1,425 @@
+#![allow(non_snake_case)]
+
+use std::{
+  any::Any,
+  cell::RefCell,
+  ffi::{CStr, CString},
+  os::raw::{c_char, c_void},
+  panic::{self, AssertUnwindSafe},
+  ptr, slice,
+};
+
+use super::*;
+
+/// Result of C API functions that don't return a pointer, the message of an
+/// error is returned by `FrameUi_last_error_message`.
+#[repr(C)]
+#[doc = "module=dom"]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FrameUiStatus {
+  Ok = 0,
+  /// Invalid arguments or input, e.g. a document that failed to load.
+  Error = 1,
+  /// A bug in FrameUI, the objects passed to the call shouldn't be used anymore.
+  Panic = 2,
+}
+
+impl From<Result<(), FrameUiStatus>> for FrameUiStatus {
+  fn from(result: Result<(), FrameUiStatus>) -> Self {
+    result.err().unwrap_or(FrameUiStatus::Ok)
+  }
+}
+
+thread_local! {
+  static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+/// Stores the message returned by `FrameUi_last_error_message` on this thread.
+pub fn set_last_error(message: impl fmt::Display) {
+  // Interior nul bytes would truncate the message, so they're dropped.
+  let message = message.to_string().replace('\0', "");
+  LAST_ERROR.with(|x| *x.borrow_mut() = Some(CString::new(message).unwrap()));
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+  if let Some(message) = payload.downcast_ref::<&str>() {
+    (*message).to_string()
+  } else if let Some(message) = payload.downcast_ref::<String>() {
+    message.clone()
+  } else {
+    "unknown panic".to_string()
+  }
+}
+
+/// Runs the body of a C API function, so neither errors nor panics reach the caller.
+///
+/// Every `extern "C"` function goes through this, unwinding into C is undefined behaviour.
+pub fn catch<T, F: FnOnce() -> Result<T, String>>(f: F) -> Result<T, FrameUiStatus> {
+  match panic::catch_unwind(AssertUnwindSafe(f)) {
+    Ok(Ok(value)) => Ok(value),
+    Ok(Err(message)) => {
+      set_last_error(message);
+      Err(FrameUiStatus::Error)
+    }
+    Err(payload) => {
+      set_last_error(panic_message(payload));
+      Err(FrameUiStatus::Panic)
+    }
+  }
+}
+
+/// Like [`catch`], returning null on failure.
+pub fn catch_ptr<T, F: FnOnce() -> Result<*mut T, String>>(f: F) -> *mut T {
+  catch(f).unwrap_or(ptr::null_mut())
+}
+
+/// Like [`catch`], returning null on failure.
+pub fn catch_const_ptr<T, F: FnOnce() -> Result<*const T, String>>(f: F) -> *const T {
+  catch(f).unwrap_or(ptr::null())
+}
+
+/// Borrows a string argument, failing on null pointers and invalid UTF-8.
+///
+/// # Safety
+/// `ptr` has to be null or point to a nul terminated string that outlives `'a`.
+pub unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, String> {
+  if ptr.is_null() {
+    return Err(format!("`{}` is null", name));
+  }
+
+  CStr::from_ptr(ptr)
+    .to_str()
+    .map_err(|e| format!("`{}` is not valid UTF-8: {}", name, e))
+}
+
+// Ownership of returned heap data:
+// - `const` pointers are borrowed and must not be freed.
+// - strings returned as `char *` are owned by the caller and freed with `FrameUi_string_free`.
+// - buffers returned as `uint8_t *` with a length are owned by the caller and freed with `FrameUi_buffer_free`.
+// - objects are freed with their own `_drop` function.
+// Memory allocated by FrameUI must never be passed to `free`, the allocators may differ.
+
+/// Hands `string` over to C, to be freed with `FrameUi_string_free`.
+pub fn owned_string(string: String) -> *mut c_char {
+  CString::new(string.replace('\0', "")).unwrap().into_raw()
+}
+
+/// Hands `buffer` over to C, to be freed with `FrameUi_buffer_free`.
+pub fn owned_buffer(buffer: Vec<u8>, len: &mut usize) -> *mut u8 {
+  let buffer = buffer.into_boxed_slice();
+  *len = buffer.len();
+  Box::into_raw(buffer) as *mut u8
+}
+
+/// Version of the C ABI, bumped on every incompatible change to the exported functions and types.
+///
+/// Hosts loading FrameUI dynamically compare it with `FrameUi_abi_version` before calling anything else.
+#[doc = "module=dom"]
+pub const FRAMEUI_ABI_VERSION: u32 = 1;
+
+/// Functions that aren't tied to an object.
+#[doc = "module=dom"]
+pub struct FrameUi;
+
+impl FrameUi {
+  #[no_mangle]
+  /// Returns the version of FrameUI, e.g. `0.1.0`. The string is static and must not be freed.
+  #[doc = "module=dom,index=0"]
+  pub extern "C" fn FrameUi_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+  }
+
+  #[no_mangle]
+  /// Returns the `FRAMEUI_ABI_VERSION` the library was built with.
+  #[doc = "module=dom,index=1"]
+  pub extern "C" fn FrameUi_abi_version() -> u32 {
+    FRAMEUI_ABI_VERSION
+  }
+
+  #[no_mangle]
+  /// Returns the message of the last failed call on this thread, or null if no call failed.
+  ///
+  /// The message is owned by FrameUI and stays valid until the next failed call on the same thread.
+  #[doc = "module=dom,index=2"]
+  pub extern "C" fn FrameUi_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|x| x.borrow().as_ref().map_or(ptr::null(), |x| x.as_ptr()))
+  }
+
+  #[no_mangle]
+  /// Frees a string returned by FrameUI, null is ignored.
+  #[doc = "module=dom,index=3"]
+  pub unsafe extern "C" fn FrameUi_string_free(string: *mut c_char) {
+    let _ = catch(|| {
+      if !string.is_null() {
+        drop(CString::from_raw(string));
+      }
+      Ok(())
+    });
+  }
+
+  #[no_mangle]
+  /// Frees a buffer of `len` bytes returned by FrameUI, null is ignored.
+  #[doc = "module=dom,index=4"]
+  pub unsafe extern "C" fn FrameUi_buffer_free(data: *mut u8, len: usize) {
+    let _ = catch(|| {
+      if !data.is_null() {
+        drop(Box::from_raw(slice::from_raw_parts_mut(data, len)));
+      }
+      Ok(())
+    });
+  }
+}
+
+/// A reference to an element of a document, keeping the element alive.
+#[doc = "module=dom"]
+pub struct ElementHandle(Node<Element>);
+
+impl ElementHandle {
+  #[no_mangle]
+  #[doc = "module=dom,index=0"]
+  pub unsafe extern "C" fn ElementHandle_drop(&mut self) {
+    let _ = catch(|| {
+      drop(Box::from_raw(self as *mut Self));
+      Ok(())
+    });
+  }
+
+  #[no_mangle]
+  /// Returns the id of the element, or null if it has none. The string is freed with `FrameUi_string_free`.
+  #[doc = "module=dom,index=1"]
+  pub unsafe extern "C" fn ElementHandle_get_id(&self) -> *mut c_char {
+    catch_ptr(|| Ok(self.0.inner().id.clone().map_or(ptr::null_mut(), owned_string)))
+  }
+}
+
+/// Called for every dispatched event, `target` and `event_type` are only valid during the call.
+#[doc = "module=dom"]
+pub type EventCallback = extern "C" fn(target: *const ElementHandle, event_type: *const c_char, user: *mut c_void);
+
+struct UserData(*mut c_void);
+
+// The host is responsible for `user` being usable from the thread events are dispatched on.
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+fn element_handle(node: Option<Node<Element>>) -> *mut ElementHandle {
+  match node {
+    Some(node) => Box::into_raw(Box::new(ElementHandle(node))),
+    None => ptr::null_mut(),
+  }
+}
+
+// Documents are reference counted, every pointer returned by `CompiledDocument_load` or
+// `CompiledDocument_clone` has to be passed to `CompiledDocument_drop` or a function taking
+// ownership of it, e.g. `EventHandler_new`.
+#[allow(non_snake_case)]
+impl CompiledDocument {
+  #[no_mangle]
+  /// Returns null if `data` isn't a valid document, `FrameUi_last_error_message` tells why.
+  #[doc = "module=dom,index=0"]
+  pub unsafe extern "C" fn CompiledDocument_load(data: *const u8, len: usize) -> *const Self {
+    catch_const_ptr(|| {
+      if data.is_null() {
+        return Err("`data` is null".to_string());
+      }
+
+      let doc = CompiledDocument::try_load(slice::from_raw_parts(data, len)).map_err(|e| e.to_string())?;
+      Ok(Arc::into_raw(Arc::new(doc)))
+    })
+  }
+
+  #[no_mangle]
+  /// Returns null if the file can't be read or isn't a valid document, `FrameUi_last_error_message` tells why.
+  #[doc = "module=dom,index=1"]
+  pub unsafe extern "C" fn CompiledDocument_load_from_file(path: *const c_char) -> *const Self {
+    catch_const_ptr(|| {
+      let path = str_arg(path, "path")?;
+      let file = std::fs::File::open(path).map_err(|e| format!("could not open `{}`: {}", path, e))?;
+      let doc = CompiledDocument::try_load_from(file).map_err(|e| e.to_string())?;
+      Ok(Arc::into_raw(Arc::new(doc)))
+    })
+  }
+
+  #[no_mangle]
+  #[doc = "module=dom,index=2"]
+  pub unsafe extern "C" fn CompiledDocument_clone(&self) -> *const Self {
+    catch_const_ptr(|| {
+      let doc = Arc::from_raw(self as *const Self);
+      let clone = Arc::clone(&doc);
+      Arc::into_raw(doc);
+      Ok(Arc::into_raw(clone))
+    })
+  }
+
+  #[no_mangle]
+  #[doc = "module=dom,index=3"]
+  pub unsafe extern "C" fn CompiledDocument_drop(&self) {
+    let _ = catch(|| {
+      drop(Arc::from_raw(self as *const Self));
+      Ok(())
+    });
+  }
+
+  #[no_mangle]
+  /// Serializes the document, the returned buffer of `len` bytes is freed with
+  /// `FrameUi_buffer_free`.
+  #[doc = "module=dom,index=4"]
+  pub unsafe extern "C" fn CompiledDocument_save(&self, len: *mut usize) -> *mut u8 {
+    catch_ptr(|| {
+      if len.is_null() {
+        return Err("`len` is null".to_string());
+      }
+
+      Ok(owned_buffer(self.save(), &mut *len))
+    })
+  }
+
+  #[no_mangle]
+  /// Returns the first element matching `selector`, or null if there is none.
+  #[doc = "module=dom,index=5"]
+  pub unsafe extern "C" fn CompiledDocument_query_selector(&self, selector: *const c_char) -> *mut ElementHandle {
+    catch_ptr(|| Ok(element_handle(self.query_selector(str_arg(selector, "selector")?))))
+  }
+
+  #[no_mangle]
+  /// Returns the element with the given `id`, or null if there is none.
+  #[doc = "module=dom,index=6"]
+  pub unsafe extern "C" fn CompiledDocument_get_element_by_id(&self, id: *const c_char) -> *mut ElementHandle {
+    catch_ptr(|| Ok(element_handle(self.get_element_by_id(str_arg(id, "id")?))))
+  }
+
+  #[no_mangle]
+  #[doc = "module=dom,index=7"]
+  pub unsafe extern "C" fn CompiledDocument_set_scope_bool(&self, name: *const c_char, value: bool) -> FrameUiStatus {
+    catch(|| {
+      self
+        .set_scope_value(str_arg(name, "name")?, value.into())
+        .map_err(|e| e.to_string())
+    })
+    .into()
+  }
+
+  #[no_mangle]
+  #[doc = "module=dom,index=8"]
+  pub unsafe extern "C" fn CompiledDocument_set_scope_int(&self, name: *const c_char, value: i64) -> FrameUiStatus {
+    catch(|| {
+      self
+        .set_scope_value(str_arg(name, "name")?, value.into())
+        .map_err(|e| e.to_string())
+    })
+    .into()
+  }
+
+  #[no_mangle]
+  #[doc = "module=dom,index=9"]
+  pub unsafe extern "C" fn CompiledDocument_set_scope_float(&self, name: *const c_char, value: f64) -> FrameUiStatus {
+    catch(|| {
+      self
+        .set_scope_value(str_arg(name, "name")?, value.into())
+        .map_err(|e| e.to_string())
+    })
+    .into()
+  }
+
+  #[no_mangle]
+  #[doc = "module=dom,index=10"]
+  pub unsafe extern "C" fn CompiledDocument_set_scope_string(
+    &self,
+    name: *const c_char,
+    value: *const c_char,
+  ) -> FrameUiStatus {
+    catch(|| {
+      let value = str_arg(value, "value")?.to_string();
+      self
+        .set_scope_value(str_arg(name, "name")?, value.into())
+        .map_err(|e| e.to_string())
+    })
+    .into()
+  }
+
+  #[no_mangle]
+  /// Calls `callback` for every `event_type` event dispatched to an element matching `selector` or one of
+  /// its descendants.
+  #[doc = "module=dom,index=11"]
+  pub unsafe extern "C" fn CompiledDocument_add_event_listener(
+    &self,
+    selector: *const c_char,
+    event_type: *const c_char,
+    callback: EventCallback,
+    user: *mut c_void,
+  ) -> FrameUiStatus {
+    catch(|| {
+      let selector = str_arg(selector, "selector")?;
+      let user = UserData(user);
+      let added = self.add_event_listener(
+        selector,
+        str_arg(event_type, "event_type")?,
+        move |target, event_type| {
+          let handle = ElementHandle(target.clone());
+          let event_type = CString::new(event_type).unwrap();
+          callback(&handle, event_type.as_ptr(), user.0);
+        },
+      );
+
+      if added {
+        Ok(())
+      } else {
+        Err(format!("invalid selector `{}`", selector))
+      }
+    })
+    .into()
+  }
+
+  #[no_mangle]
+  /// Runs the listeners of `event_type` registered for `target` and its ancestors.
+  #[doc = "module=dom,index=12"]
+  pub unsafe extern "C" fn CompiledDocument_dispatch_event(
+    &self,
+    target: *const ElementHandle,
+    event_type: *const c_char,
+  ) -> FrameUiStatus {
+    catch(|| {
+      if target.is_null() {
+        return Err("`target` is null".to_string());
+      }
+
+      self.dispatch_event(&(*target).0, str_arg(event_type, "event_type")?);
+      Ok(())
+    })
+    .into()
+  }
+
+  #[no_mangle]
+  /// Gives scripts a `storage` variable persisted at `path`, usually a file in the application's data directory.
+  #[doc = "module=dom,index=13"]
+  pub unsafe extern "C" fn CompiledDocument_set_storage_path(&self, path: *const c_char) -> FrameUiStatus {
+    catch(|| {
+      let path = str_arg(path, "path")?;
+      self
+        .set_storage_path(path)
+        .map_err(|e| format!("could not open storage `{}`: {}", path, e))
+    })
+    .into()
+  }
+
+  #[no_mangle]
+  /// Declares the scope variable `name` as the value of `expression`, computed again whenever a variable it
+  /// mentions is set.
+  #[doc = "module=dom,index=14"]
+  pub unsafe extern "C" fn CompiledDocument_derive_scope_value(
+    &self,
+    name: *const c_char,
+    expression: *const c_char,
+  ) -> FrameUiStatus {
+    catch(|| {
+      let expression = str_arg(expression, "expression")?;
+      self
+        .derive_expression(str_arg(name, "name")?, expression)
+        .map_err(|e| match e {
+          ScopeError::Parse(e) => format!("could not parse `{}`: {}", expression, e),
+          e => e.to_string(),
+        })
+    })
+    .into()
+  }
+}
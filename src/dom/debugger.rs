@@ -0,0 +1,294 @@
+//! Breakpoints and stepping in the scripts of a document, driven by devtools.
+//!
+//! Rhai has no debugging hooks that know where the evaluation is, so while the debugger is enabled the functions
+//! of the document are replaced by versions calling [`BREAKPOINT_FN`] at the start of every line that can take
+//! a statement. The call blocks the thread running the script for as long as the debugger is paused there.
+//! Statements outside of functions already ran when the document was loaded, attribute expressions and derived
+//! values are never paused in.
+
+use std::{
+  collections::BTreeMap,
+  fmt,
+  sync::{Arc, Condvar, Mutex},
+  time::Instant,
+};
+
+use rhai::{Dynamic, Engine, RegisterFn, INT};
+
+use crate::{script, source_map::SourceLocation};
+
+/// Name of the function the instrumented scripts call with their index and the line, starting at 0.
+pub const BREAKPOINT_FN: &str = "__breakpoint";
+
+/// A script of the document as the debugger sees it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+  pub source: String,
+  /// Where the script starts in the document, if it has a source map.
+  pub location: Option<SourceLocation>,
+  /// Lines of the script the debugger can pause at, starting at 0.
+  pub breakable_lines: Vec<usize>,
+}
+
+impl Script {
+  /// Returns the first line the debugger can pause at from `line` on.
+  #[must_use]
+  pub fn breakable_line(&self, line: usize) -> Option<usize> {
+    self.breakable_lines.iter().copied().find(|x| *x >= line)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breakpoint {
+  pub script: usize,
+  pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+  /// The thread running a script stopped before `line` of `script`, because of `breakpoints` or a step or pause
+  /// request if it's empty.
+  Paused {
+    script: usize,
+    line: usize,
+    breakpoints: Vec<u64>,
+  },
+  Resumed,
+  /// The document was reloaded, these are its scripts from now on. Breakpoints stay at their script and line.
+  ScriptsChanged(Vec<Script>),
+}
+
+/// Receives the events of the debugger, from the thread running the scripts.
+pub struct Listener(Box<dyn Fn(Event) + Send + Sync>);
+
+#[derive(Default)]
+struct State {
+  listener: Option<Listener>,
+  /// The functions of the document before they were instrumented, put back once the debugger is disabled.
+  original: Option<Arc<rhai::AST>>,
+  breakpoints: BTreeMap<u64, Breakpoint>,
+  next_breakpoint: u64,
+  inactive: bool,
+  /// Whether to pause at the next line, after a step or a pause request.
+  pause_requested: bool,
+  paused: bool,
+  /// Variables of the document scope when the running callback started.
+  globals: Vec<(String, Dynamic)>,
+}
+
+/// Debugger state of a document, shared with the breakpoint function of its engine.
+#[derive(Default)]
+pub struct Debugger {
+  state: Mutex<State>,
+  resumed: Condvar,
+}
+
+impl fmt::Debug for Debugger {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let state = self.state.lock().unwrap();
+    f.debug_struct("Debugger")
+      .field("enabled", &state.listener.is_some())
+      .field("breakpoints", &state.breakpoints)
+      .field("paused", &state.paused)
+      .finish()
+  }
+}
+
+impl Debugger {
+  #[must_use]
+  pub fn is_enabled(&self) -> bool {
+    self.state.lock().unwrap().listener.is_some()
+  }
+
+  /// Starts sending events to `listener`, `original` are the functions of the document the instrumented ones
+  /// replace. Enabling the debugger again only replaces the listener, the functions from before the first time
+  /// are kept.
+  pub(crate) fn enable(&self, listener: impl Fn(Event) + Send + Sync + 'static, original: Arc<rhai::AST>) {
+    let mut state = self.state.lock().unwrap();
+    state.listener = Some(Listener(Box::new(listener)));
+    state.original.get_or_insert(original);
+  }
+
+  /// Stops debugging, resuming the script if it's paused, and returns the functions the instrumented ones
+  /// replaced.
+  pub(crate) fn disable(&self) -> Option<Arc<rhai::AST>> {
+    let mut state = self.state.lock().unwrap();
+    state.listener = None;
+    state.breakpoints.clear();
+    state.pause_requested = false;
+    state.paused = false;
+    self.resumed.notify_all();
+    state.original.take()
+  }
+
+  /// Applies a reload while debugging, `functions` replace the ones with the same name in the functions put
+  /// back once the debugger is disabled.
+  pub(crate) fn reload(&self, functions: &rhai::AST, scripts: Vec<Script>) {
+    let mut state = self.state.lock().unwrap();
+    if let Some(original) = &mut state.original {
+      *original = Arc::new(original.merge(functions));
+    }
+
+    self.emit(&state, Event::ScriptsChanged(scripts));
+  }
+
+  /// Adds a breakpoint and returns its id.
+  pub fn add_breakpoint(&self, breakpoint: Breakpoint) -> u64 {
+    let mut state = self.state.lock().unwrap();
+    let id = state.next_breakpoint;
+    state.next_breakpoint += 1;
+    state.breakpoints.insert(id, breakpoint);
+    id
+  }
+
+  /// Removes a breakpoint, returning whether it existed.
+  pub fn remove_breakpoint(&self, id: u64) -> bool {
+    self.state.lock().unwrap().breakpoints.remove(&id).is_some()
+  }
+
+  /// Sets whether breakpoints pause the scripts, stepping and pause requests still do.
+  pub fn set_breakpoints_active(&self, active: bool) {
+    self.state.lock().unwrap().inactive = !active;
+  }
+
+  /// Pauses at the next line a script runs.
+  pub fn pause(&self) {
+    self.state.lock().unwrap().pause_requested = true;
+  }
+
+  /// Resumes the paused script, `step` pauses it again at the next line that runs, wherever it is.
+  pub fn resume(&self, step: bool) {
+    let mut state = self.state.lock().unwrap();
+    state.paused = false;
+    state.pause_requested = step;
+    self.resumed.notify_all();
+  }
+
+  /// Returns the variables of the document scope as they were when the paused callback started, the scope
+  /// itself stays locked by the paused script.
+  #[must_use]
+  pub fn paused_globals(&self) -> Option<Vec<(String, Dynamic)>> {
+    let state = self.state.lock().unwrap();
+    if state.paused {
+      Some(state.globals.clone())
+    } else {
+      None
+    }
+  }
+
+  pub(crate) fn set_globals(&self, globals: Vec<(String, Dynamic)>) {
+    self.state.lock().unwrap().globals = globals;
+  }
+
+  fn emit(&self, state: &State, event: Event) {
+    if let Some(Listener(listener)) = &state.listener {
+      listener(event);
+    }
+  }
+
+  // Called by the instrumented scripts before every line.
+  fn hit(&self, script: usize, line: usize) {
+    let mut state = self.state.lock().unwrap();
+    if state.listener.is_none() {
+      return;
+    }
+
+    let breakpoints: Vec<_> = if state.inactive {
+      Vec::new()
+    } else {
+      let at = Breakpoint { script, line };
+      state
+        .breakpoints
+        .iter()
+        .filter(|(_, breakpoint)| **breakpoint == at)
+        .map(|(id, _)| *id)
+        .collect()
+    };
+    if breakpoints.is_empty() && !state.pause_requested {
+      return;
+    }
+
+    state.pause_requested = false;
+    state.paused = true;
+    self.emit(
+      &state,
+      Event::Paused {
+        script,
+        line,
+        breakpoints,
+      },
+    );
+
+    let start = Instant::now();
+    while state.paused {
+      state = self.resumed.wait(state).unwrap();
+    }
+    script::extend_timeout(start.elapsed());
+
+    self.emit(&state, Event::Resumed);
+  }
+}
+
+/// Registers the breakpoint function instrumented scripts call.
+pub(crate) fn register(engine: &mut Engine, debugger: &Arc<Debugger>) {
+  let debugger = Arc::clone(debugger);
+  engine.register_fn(BREAKPOINT_FN, move |script: INT, line: INT| {
+    debugger.hit(script as usize, line as usize);
+  });
+}
+
+/// Inserts a call to the breakpoint function at the start of every line of `source` that begins a statement,
+/// returning the instrumented source and the lines that got one.
+///
+/// A line only begins a statement if the code before it ends with `;`, `{` or `}`, and if the script still
+/// compiles with the call, so lines continuing an expression, like `else` or the arguments of a call, are
+/// skipped. Each line keeps its number.
+pub(crate) fn instrument(engine: &Engine, index: usize, source: &str) -> (String, Vec<usize>) {
+  let mut lines: Vec<String> = source.lines().map(ToString::to_string).collect();
+  if engine.compile(source).is_err() {
+    return (source.to_string(), Vec::new());
+  }
+
+  let originals = lines.clone();
+  let mut breakable = Vec::new();
+  let mut statement_start = true;
+
+  for (line, original) in originals.iter().enumerate() {
+    let code = original.trim();
+    if code.is_empty() || code.starts_with("//") {
+      continue;
+    }
+
+    // Function definitions and closing braces don't run anything themselves.
+    if statement_start && !code.starts_with("fn ") && !code.starts_with('}') {
+      let indent = original.len() - original.trim_start().len();
+      lines[line] = format!(
+        "{}{}({}, {}); {}",
+        &original[..indent],
+        BREAKPOINT_FN,
+        index,
+        line,
+        &original[indent..]
+      );
+      breakable.push(line);
+    }
+
+    let code = code.split("//").next().unwrap_or_default().trim_end();
+    statement_start = code.ends_with(';') || code.ends_with('{') || code.ends_with('}');
+  }
+
+  // The script compiles without the calls, so every error is caused by one of them. Those are rare, the call
+  // closest before the error is removed until it compiles, instead of compiling once per line.
+  while let Err(e) = engine.compile(&lines.join("\n")) {
+    let error_line = e.1.line().map_or(0, |x| x.saturating_sub(1));
+    let position = breakable.iter().rposition(|x| *x <= error_line).unwrap_or(0);
+    let line = breakable.remove(position);
+    lines[line] = originals[line].clone();
+
+    if breakable.is_empty() {
+      break;
+    }
+  }
+
+  (lines.join("\n"), breakable)
+}
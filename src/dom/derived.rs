@@ -0,0 +1,102 @@
+//! Scope values computed from other scope values, kept up to date by `CompiledDocument::set_scope_value`.
+
+use std::{collections::HashSet, fmt, sync::Arc};
+
+use super::script::ScriptErrors;
+
+pub type DeriveFn = Arc<dyn Fn(&rhai::Scope) -> rhai::Dynamic + Send + Sync>;
+
+enum Derivation {
+  Native(DeriveFn),
+  Expression { source: String, ast: rhai::AST },
+}
+
+pub struct Derived {
+  name: String,
+  dependencies: Vec<String>,
+  derivation: Derivation,
+}
+
+impl fmt::Debug for Derived {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Derived")
+      .field("name", &self.name)
+      .field("dependencies", &self.dependencies)
+      .finish()
+  }
+}
+
+impl Derived {
+  pub fn native(name: &str, dependencies: &[&str], derive: DeriveFn) -> Self {
+    Self {
+      name: name.to_string(),
+      dependencies: dependencies.iter().map(|x| x.to_string()).collect(),
+      derivation: Derivation::Native(derive),
+    }
+  }
+
+  /// Compiles `expression`, its dependencies are the identifiers in it that name a variable of `scope`.
+  ///
+  /// Identifiers are found by a plain scan of the source, so e.g. a matching word in a string literal
+  /// adds a dependency as well. This only costs an unneeded re-evaluation.
+  pub fn expression(
+    engine: &rhai::Engine,
+    scope: &rhai::Scope,
+    name: &str,
+    expression: &str,
+  ) -> Result<Self, Box<rhai::ParseError>> {
+    let ast = engine.compile_expression_with_scope(scope, expression)?;
+
+    let mut dependencies = Vec::new();
+    for word in expression.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+      if scope.contains(word) && !dependencies.iter().any(|x| x == word) {
+        dependencies.push(word.to_string());
+      }
+    }
+
+    Ok(Self {
+      name: name.to_string(),
+      dependencies,
+      derivation: Derivation::Expression {
+        source: expression.to_string(),
+        ast,
+      },
+    })
+  }
+
+  #[must_use]
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  #[must_use]
+  pub fn dependencies(&self) -> &[String] {
+    &self.dependencies
+  }
+
+  /// Whether the value has to be computed again after the variables in `changed` were set, `None` meaning
+  /// that any variable may have changed.
+  #[must_use]
+  pub fn is_stale(&self, changed: Option<&HashSet<String>>) -> bool {
+    match changed {
+      Some(changed) => self.dependencies.iter().any(|x| changed.contains(x)),
+      None => true,
+    }
+  }
+
+  /// Computes the value, errors of expressions are reported to `errors` and yield `None`.
+  pub fn evaluate(
+    &self,
+    engine: &rhai::Engine,
+    scope: &mut rhai::Scope,
+    errors: &ScriptErrors,
+  ) -> Option<rhai::Dynamic> {
+    match &self.derivation {
+      Derivation::Native(derive) => Some(derive(scope)),
+      Derivation::Expression { source, ast } => engine
+        .eval_ast_with_scope(scope, ast)
+        .map_err(|e| errors.report(source, e))
+        .ok(),
+    }
+  }
+}
@@ -0,0 +1,214 @@
+//! `fetch(url, callback)` for scripts, requests run on a small pool of threads and `callback` is called with
+//! the response from `CompiledDocument::run_callbacks`.
+
+use std::{
+  collections::VecDeque,
+  sync::{Arc, Mutex},
+  thread,
+  time::Duration,
+};
+
+use reqwest::{blocking::Client, Url};
+use rhai::{Dynamic, Engine, FnPtr, ImmutableString, Map, RegisterFn, INT};
+
+use super::Invalidation;
+
+/// Limits of the requests scripts make with `fetch`, so a document can't flood the network or reach hosts it
+/// shouldn't.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchPolicy {
+  /// Requests running at the same time, the others wait for one of them to finish. Zero blocks every request.
+  pub max_concurrent: usize,
+  /// Time a request may take, body included.
+  pub timeout: Option<Duration>,
+  /// Hosts requests may go to, any host if it's `None`. Only `http` and `https` URLs are ever fetched.
+  pub allowed_hosts: Option<Vec<String>>,
+}
+
+impl Default for FetchPolicy {
+  fn default() -> Self {
+    Self {
+      max_concurrent: 4,
+      timeout: Some(Duration::from_secs(30)),
+      allowed_hosts: None,
+    }
+  }
+}
+
+impl FetchPolicy {
+  /// Scripts can't fetch anything.
+  #[must_use]
+  pub fn blocked() -> Self {
+    Self {
+      max_concurrent: 0,
+      timeout: None,
+      allowed_hosts: Some(Vec::new()),
+    }
+  }
+
+  /// Returns why `url` can't be fetched, if it can't.
+  fn check(&self, url: &str) -> Result<Url, String> {
+    let url = Url::parse(url).map_err(|e| format!("invalid URL `{}`: {}", url, e))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+      return Err(format!("`{}` isn't an http URL", url));
+    }
+
+    let allowed = match (&self.allowed_hosts, url.host_str()) {
+      (None, _) => true,
+      (Some(hosts), Some(host)) => hosts.iter().any(|x| x == host),
+      (Some(..), None) => false,
+    };
+    if !allowed || self.max_concurrent == 0 {
+      return Err(format!("fetching `{}` isn't allowed", url));
+    }
+
+    Ok(url)
+  }
+
+  fn client(&self) -> Client {
+    let mut builder = Client::builder();
+    if let Some(timeout) = self.timeout {
+      builder = builder.timeout(timeout);
+    }
+
+    builder.build().unwrap_or_default()
+  }
+}
+
+struct Request {
+  url: Url,
+  callback: FnPtr,
+  invalidation: Arc<Invalidation>,
+}
+
+#[derive(Default)]
+struct Pool {
+  policy: FetchPolicy,
+  client: Option<Client>,
+  queue: VecDeque<Request>,
+  workers: usize,
+}
+
+#[derive(Default)]
+pub struct Requests {
+  pool: Mutex<Pool>,
+  completed: Mutex<Vec<(FnPtr, Dynamic)>>,
+}
+
+impl std::fmt::Debug for Requests {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let pool = self.pool.lock().unwrap();
+    f.debug_struct("Requests")
+      .field("policy", &pool.policy)
+      .field("queued", &pool.queue.len())
+      .field("workers", &pool.workers)
+      .finish()
+  }
+}
+
+impl Requests {
+  /// Replaces the policy, requests already running keep the timeout they started with.
+  pub fn set_policy(&self, policy: FetchPolicy) {
+    let mut pool = self.pool.lock().unwrap();
+    pool.client = None;
+    pool.policy = policy;
+  }
+
+  /// Fetches `url` in the background, the document is invalidated once the response arrived. URLs the policy
+  /// doesn't allow get an error response without a request.
+  pub fn start(self: &Arc<Self>, url: String, callback: FnPtr, invalidation: Arc<Invalidation>) {
+    let mut pool = self.pool.lock().unwrap();
+    let url = match pool.policy.check(&url) {
+      Ok(url) => url,
+      Err(e) => {
+        drop(pool);
+        self.complete(callback, error_response(e), &invalidation);
+        return;
+      }
+    };
+
+    pool.queue.push_back(Request {
+      url,
+      callback,
+      invalidation,
+    });
+
+    if pool.workers < pool.policy.max_concurrent {
+      pool.workers += 1;
+      let requests = Arc::clone(self);
+      thread::spawn(move || requests.work());
+    }
+  }
+
+  // Runs queued requests until there are none left.
+  fn work(&self) {
+    loop {
+      let (request, client) = {
+        let mut guard = self.pool.lock().unwrap();
+        let pool = &mut *guard;
+        let request = match pool.queue.pop_front() {
+          Some(request) => request,
+          None => {
+            pool.workers -= 1;
+            return;
+          }
+        };
+
+        let policy = &pool.policy;
+        let client = pool.client.get_or_insert_with(|| policy.client()).clone();
+        (request, client)
+      };
+
+      let response = get(&client, request.url);
+      self.complete(request.callback, response, &request.invalidation);
+    }
+  }
+
+  fn complete(&self, callback: FnPtr, response: Dynamic, invalidation: &Invalidation) {
+    self.completed.lock().unwrap().push((callback, response));
+    invalidation.invalidate();
+  }
+
+  /// Returns the callbacks of the finished requests along with their response.
+  pub(crate) fn take_completed(&self) -> Vec<(FnPtr, Dynamic)> {
+    std::mem::take(&mut *self.completed.lock().unwrap())
+  }
+}
+
+// Scripts get `#{ ok, status, body, error }`, `status` is 0 and `error` set if there was no response.
+fn get(client: &Client, url: Url) -> Dynamic {
+  let result = client.get(url).send().and_then(|x| {
+    let status = x.status();
+    x.text().map(|body| (status, body))
+  });
+
+  match result {
+    Ok((status, body)) => {
+      let mut response = Map::new();
+      response.insert("ok".into(), status.is_success().into());
+      response.insert("status".into(), (status.as_u16() as INT).into());
+      response.insert("body".into(), body.into());
+      response.insert("error".into(), ().into());
+      response.into()
+    }
+
+    Err(e) => error_response(e.to_string()),
+  }
+}
+
+fn error_response(error: String) -> Dynamic {
+  let mut response = Map::new();
+  response.insert("ok".into(), false.into());
+  response.insert("status".into(), (0 as INT).into());
+  response.insert("body".into(), "".into());
+  response.insert("error".into(), error.into());
+  response.into()
+}
+
+pub fn register(engine: &mut Engine, requests: &Arc<Requests>, invalidation: &Arc<Invalidation>) {
+  let requests = Arc::clone(requests);
+  let invalidation = Arc::clone(invalidation);
+  engine.register_fn("fetch", move |url: ImmutableString, callback: FnPtr| {
+    requests.start(url.to_string(), callback, Arc::clone(&invalidation))
+  });
+}
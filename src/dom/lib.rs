@@ -1,4 +1,13 @@
-use std::sync::RwLock;
+use std::{
+  collections::{BTreeMap, HashMap, HashSet},
+  fmt, io,
+  path::PathBuf,
+  sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, RwLock,
+  },
+  time::Instant,
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -7,15 +16,110 @@ use serde::{Deserialize, Serialize};
 //                                           [i]
 //                                                 [S]tandard
 //                                                       Version
-pub const MAGIC_BYTES: &[u8] = &[0x46, 0x55, 0x69, 0x53, 0];
+pub const MAGIC_BYTES: &[u8] = &[0x46, 0x55, 0x69, 0x53, 1];
+
+/// How the document is compressed after the magic bytes, the byte following them is the discriminant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum Compression {
+  None = 0,
+  Zstd = 1,
+  Lz4 = 2,
+}
 
-pub mod tree;
-use tree::Node;
+impl Default for Compression {
+  fn default() -> Self {
+    Compression::None
+  }
+}
+
+impl Compression {
+  #[must_use]
+  pub fn from_byte(byte: u8) -> Option<Self> {
+    match byte {
+      0 => Some(Compression::None),
+      1 => Some(Compression::Zstd),
+      2 => Some(Compression::Lz4),
+      _ => None,
+    }
+  }
+}
+
+/// Why a saved document couldn't be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+  Io(io::Error),
+  /// The data doesn't start with `MAGIC_BYTES`, it isn't a document or was saved by another version.
+  MagicBytes(Vec<u8>),
+  UnknownCompression(u8),
+  Deserialize(bincode::Error),
+}
 
-fn safe_yoga_node_new() -> yoga::Node {
-  unsafe { yoga::Node::new() }
+impl fmt::Display for LoadError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Io(e) => write!(f, "could not read the document: {}", e),
+      Self::MagicBytes(bytes) => write!(f, "magic bytes don't match {:?} == {:?}", bytes, MAGIC_BYTES),
+      Self::UnknownCompression(byte) => write!(f, "unknown compression {}", byte),
+      Self::Deserialize(e) => write!(f, "invalid document: {}", e),
+    }
+  }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+  fn from(e: io::Error) -> Self {
+    Self::Io(e)
+  }
 }
 
+impl From<bincode::Error> for LoadError {
+  fn from(e: bincode::Error) -> Self {
+    Self::Deserialize(e)
+  }
+}
+
+/// An error setting or deriving a variable of the document scope.
+#[derive(Debug)]
+pub enum ScopeError {
+  /// The variable is a constant or a module, e.g. `document` or a `const` declared by a script.
+  Constant(String),
+  Parse(Box<rhai::ParseError>),
+}
+
+impl fmt::Display for ScopeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Constant(name) => write!(f, "`{}` is a constant and can't be replaced", name),
+      Self::Parse(e) => write!(f, "could not parse the expression: {}", e),
+    }
+  }
+}
+
+impl std::error::Error for ScopeError {}
+
+impl From<Box<rhai::ParseError>> for ScopeError {
+  fn from(e: Box<rhai::ParseError>) -> Self {
+    Self::Parse(e)
+  }
+}
+
+pub mod debugger;
+pub mod derived;
+#[cfg(feature = "fetch")]
+pub mod fetch;
+pub mod script;
+pub mod source_map;
+pub mod storage;
+pub mod timers;
+pub mod tree;
+use tree::{Node, WeakNode};
+
+#[cfg(feature = "c-dom")]
+pub mod c_api;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Element {
   pub data: ElementData,
@@ -24,13 +128,26 @@ pub struct Element {
 
   pub classes: Vec<String>,
   pub id: Option<String>,
-  pub style: Vec<style::StyleRule>,
+  /// Declarations of the `style` attribute, applied after the stylesheet.
+  pub style: Vec<style::Declaration>,
+  /// Name of the component the element was expanded from, if it's the root of one.
+  pub component: Option<String>,
+  /// Where the element starts in the source, if the document has a source map.
+  pub source: Option<source_map::SourceLocation>,
+
+  /// Variables of the element's own scope, they shadow the document scope and the scopes of its ancestors
+  /// in the expressions of the element and its descendants.
+  #[serde(skip)]
+  pub scope: Vec<(String, rhai::Dynamic)>,
 
-  #[serde(skip, default = "safe_yoga_node_new")]
+  #[serde(skip)]
   pub yg: yoga::Node,
 
   #[serde(skip)]
   pub computed: style::ComputedStyle,
+  /// How far the content is scrolled right and down, only used when `overflow` isn't `visible`.
+  #[serde(skip)]
+  pub scroll_offset: (f32, f32),
 }
 
 impl PartialEq for Element {
@@ -78,89 +195,132 @@ impl Element {
       classes: Vec::new(),
       id: None,
       style: Vec::new(),
+      component: None,
+      source: None,
+      scope: Vec::new(),
 
-      yg: unsafe { yoga::Node::new() },
+      yg: yoga::Node::new(),
       computed: style::ComputedStyle::default(),
+      scroll_offset: (0.0, 0.0),
     }
   }
 
+  /// Returns the tree node owning a yoga node, once its document initialized yoga.
+  #[must_use]
+  pub fn from_yoga(yg: &yoga::Node) -> Option<Node<Element>> {
+    yg.get_context::<WeakNode<Element>>()?.upgrade()
+  }
+
   pub fn prepare_yoga(&mut self) {
-    unsafe {
-      self.yg.set_width(self.computed.width);
-      self.yg.set_height(self.computed.height);
-      self.yg.set_margin(yoga::Edge::Top, self.computed.margin_top);
-      self.yg.set_margin(yoga::Edge::Bottom, self.computed.margin_bottom);
-      self.yg.set_margin(yoga::Edge::Left, self.computed.margin_left);
-      self.yg.set_margin(yoga::Edge::Right, self.computed.margin_right);
-    }
-  }
-
-  pub fn compute_attributes(&mut self, engine: &rhai::Engine, scope: &mut rhai::Scope) {
-    if let Some(class) = &mut self.raw_attributes.class {
-      match class {
-        RawAttributeValue::Raw { value, up_to_date } => {
-          if !*up_to_date {
-            self.classes = value.split_ascii_whitespace().map(|s| s.to_string()).collect()
-          }
-        }
+    self.yg.set_width(self.computed.width);
+    self.yg.set_height(self.computed.height);
+    self.yg.set_margin(yoga::Edge::Top, self.computed.margin_top);
+    self.yg.set_margin(yoga::Edge::Bottom, self.computed.margin_bottom);
+    self.yg.set_margin(yoga::Edge::Left, self.computed.margin_left);
+    self.yg.set_margin(yoga::Edge::Right, self.computed.margin_right);
+    self.yg.set_margin(yoga::Edge::Start, self.computed.margin_start);
+    self.yg.set_margin(yoga::Edge::End, self.computed.margin_end);
+    self.yg.set_padding(yoga::Edge::Top, self.computed.padding_top);
+    self.yg.set_padding(yoga::Edge::Bottom, self.computed.padding_bottom);
+    self.yg.set_padding(yoga::Edge::Left, self.computed.padding_left);
+    self.yg.set_padding(yoga::Edge::Right, self.computed.padding_right);
+    self.yg.set_padding(yoga::Edge::Start, self.computed.padding_start);
+    self.yg.set_padding(yoga::Edge::End, self.computed.padding_end);
+    self.yg.set_border(yoga::Edge::All, self.computed.used_border_width());
+
+    // Static elements ignore their offsets.
+    let offsets = match self.computed.position {
+      style::Position::Static => [yoga::Value::Undefined; 4],
+      _ => [
+        self.computed.top,
+        self.computed.right,
+        self.computed.bottom,
+        self.computed.left,
+      ],
+    };
+    self.yg.set_position_type(self.computed.position.into());
+    self.yg.set_position(yoga::Edge::Top, offsets[0]);
+    self.yg.set_position(yoga::Edge::Right, offsets[1]);
+    self.yg.set_position(yoga::Edge::Bottom, offsets[2]);
+    self.yg.set_position(yoga::Edge::Left, offsets[3]);
+    self.yg.set_display(self.computed.display.into());
+    self.yg.set_overflow(self.computed.overflow.into());
+    self.yg.set_direction(self.computed.direction.into());
+  }
 
-        RawAttributeValue::Script {
-          script,
-          up_to_date,
-          ast,
-        } => {
-          if ast.is_none() || !*up_to_date {
-            *ast = Some(engine.compile_expression_with_scope(scope, script).unwrap());
-            *up_to_date = true;
-          }
+  /// Size of the area inside the border in the last layout, where the content is visible.
+  #[must_use]
+  pub fn padding_box_size(&self) -> (f32, f32) {
+    let width =
+      self.yg.get_width() - self.yg.get_layout_border(yoga::Edge::Left) - self.yg.get_layout_border(yoga::Edge::Right);
+    let height =
+      self.yg.get_height() - self.yg.get_layout_border(yoga::Edge::Top) - self.yg.get_layout_border(yoga::Edge::Bottom);
+    (width, height)
+  }
 
-          let dynamic_classes: Vec<rhai::Dynamic> = engine.eval_ast_with_scope(scope, ast.as_ref().unwrap()).unwrap();
-          self.classes.clear();
-          for class in dynamic_classes {
-            self.classes.push(class.take_string().unwrap());
-          }
+  /// Sets a variable of the element's scope, adding it if it doesn't exist yet.
+  pub fn set_scope_value(&mut self, name: &str, value: rhai::Dynamic) {
+    match self.scope.iter_mut().find(|(key, _)| key == name) {
+      Some((_, old)) => *old = value,
+      None => self.scope.push((name.to_string(), value)),
+    }
+  }
+
+  /// Applies the raw attributes and clears the missing ones, script attributes are left to
+  /// [`compute_attributes`] which runs them without locking the element.
+  fn apply_raw_attributes(&mut self, errors: &script::ScriptErrors) {
+    let source = self.source;
+    match &mut self.raw_attributes.class {
+      Some(RawAttributeValue::Raw { value, up_to_date }) => {
+        if !*up_to_date {
+          self.classes = value.split_ascii_whitespace().map(|s| s.to_string()).collect()
         }
       }
-    } else {
-      self.classes.clear();
+      Some(RawAttributeValue::Script { .. }) => {}
+      None => self.classes.clear(),
     }
 
-    if let Some(id) = &mut self.raw_attributes.id {
-      match id {
-        RawAttributeValue::Raw { value, up_to_date } => {
-          if !*up_to_date {
-            self.id = Some(value.clone());
-          }
+    match &mut self.raw_attributes.id {
+      Some(RawAttributeValue::Raw { value, up_to_date }) => {
+        if !*up_to_date {
+          self.id = Some(value.clone());
         }
+      }
+      Some(RawAttributeValue::Script { .. }) => {}
+      None => self.id = None,
+    }
 
-        RawAttributeValue::Script {
-          script,
-          up_to_date,
-          ast,
-        } => {
-          if ast.is_none() || !*up_to_date {
-            *ast = Some(engine.compile_expression_with_scope(scope, script).unwrap());
-            *up_to_date = true;
-          }
-
-          self.id = Some(engine.eval_ast_with_scope(scope, ast.as_ref().unwrap()).unwrap());
+    match &mut self.raw_attributes.style {
+      Some(RawAttributeValue::Raw { value, up_to_date }) => {
+        if !*up_to_date {
+          self.style = parse_inline_style(value, errors, source);
+          *up_to_date = true;
         }
       }
-    } else {
-      self.id = None;
+      Some(RawAttributeValue::Script { .. }) => {}
+      None => self.style.clear(),
     }
   }
 
   #[must_use]
   pub fn get_render(&self) -> style::RenderStyle {
-    unsafe {
-      style::RenderStyle {
-        width: self.yg.get_width(),
-        height: self.yg.get_height(),
-        top: self.yg.get_top(),
-        left: self.yg.get_left(),
-        background_color: self.computed.background_color,
-      }
+    style::RenderStyle {
+      width: self.yg.get_width(),
+      height: self.yg.get_height(),
+      top: self.yg.get_top(),
+      left: self.yg.get_left(),
+      background: self.computed.background.clone(),
+      border_width: self.computed.used_border_width(),
+      border_color: self.computed.border_color,
+      border_style: self.computed.border_style,
+      border_radius: self.computed.border_radius,
+      display: self.computed.display,
+      opacity: self.computed.opacity,
+      box_shadow: self.computed.box_shadow,
+      background_image: self.computed.background_image.clone(),
+      background_size: self.computed.background_size,
+      background_repeat: self.computed.background_repeat,
+      overflow: self.computed.overflow,
     }
   }
 
@@ -169,19 +329,147 @@ impl Element {
     match self.data {
       ElementData::Root(..) => "#root",
       ElementData::Unstyled(..) => "Unstyled",
+      ElementData::Text(..) => "#text",
     }
   }
 
+  #[must_use]
+  pub fn is_text(&self) -> bool {
+    matches!(self.data, ElementData::Text(..))
+  }
+
   #[must_use]
   pub fn get_namespace(&self) -> Option<&str> {
     None
   }
 }
 
+// Invalid declarations are reported like script errors, the ones before them still apply.
+fn parse_inline_style(
+  value: &str,
+  errors: &script::ScriptErrors,
+  location: Option<source_map::SourceLocation>,
+) -> Vec<style::Declaration> {
+  let mut input = cssparser::ParserInput::new(value);
+  let mut parser = cssparser::Parser::new(&mut input);
+
+  let mut declarations = Vec::new();
+  for declaration in cssparser::DeclarationListParser::new(&mut parser, style::parser::DeclarationParser) {
+    match declaration {
+      Ok(declaration) => declarations.extend(declaration),
+      Err((e, source)) => errors.report_at(source, location, format!("invalid declaration: {:?}", e.kind)),
+    }
+  }
+
+  declarations
+}
+
+/// A script attribute copied out of its element, to be evaluated while the element isn't locked.
+struct AttributeScript {
+  script: String,
+  ast: Option<rhai::AST>,
+}
+
+impl AttributeScript {
+  // Expressions are compiled once, a failed compilation is reported once and leaves `ast` empty.
+  fn take(
+    value: Option<&mut RawAttributeValue>,
+    engine: &rhai::Engine,
+    scope: &rhai::Scope,
+    errors: &script::ScriptErrors,
+    location: Option<source_map::SourceLocation>,
+  ) -> Option<Self> {
+    match value? {
+      RawAttributeValue::Raw { .. } => None,
+      RawAttributeValue::Script {
+        script,
+        up_to_date,
+        ast,
+      } => {
+        if !*up_to_date {
+          *ast = engine
+            .compile_expression_with_scope(scope, script)
+            .map_err(|e| errors.report_at(script, location, e))
+            .ok();
+          *up_to_date = true;
+        }
+
+        Some(Self {
+          script: script.clone(),
+          ast: ast.clone(),
+        })
+      }
+    }
+  }
+
+  fn eval<T: rhai::Variant + Clone>(
+    &self,
+    engine: &rhai::Engine,
+    scope: &mut rhai::Scope,
+    errors: &script::ScriptErrors,
+    location: Option<source_map::SourceLocation>,
+  ) -> Option<T> {
+    engine
+      .eval_ast_with_scope(scope, self.ast.as_ref()?)
+      .map_err(|e| errors.report_at(&self.script, location, e))
+      .ok()
+  }
+}
+
+/// Evaluates the attributes of `node`, errors are reported to `errors` and a script attribute falls back to the
+/// source of its expression as if it was a raw value.
+///
+/// The element is only locked to copy its scripts out and to store their results, so expressions can use the
+/// document API on it and on any other element.
+fn compute_attributes(
+  node: &Node<Element>,
+  engine: &rhai::Engine,
+  scope: &mut rhai::Scope,
+  errors: &script::ScriptErrors,
+) {
+  let (source, class, id, style) = {
+    let mut el = node.inner_mut();
+    el.apply_raw_attributes(errors);
+
+    let source = el.source;
+    let raw = &mut el.raw_attributes;
+    (
+      source,
+      AttributeScript::take(raw.class.as_mut(), engine, scope, errors, source),
+      AttributeScript::take(raw.id.as_mut(), engine, scope, errors, source),
+      AttributeScript::take(raw.style.as_mut(), engine, scope, errors, source),
+    )
+  };
+
+  let classes = class.map(|class| match class.eval::<rhai::Array>(engine, scope, errors, source) {
+    Some(classes) => classes.into_iter().map(|x| x.to_string()).collect(),
+    None => class.script.split_ascii_whitespace().map(|s| s.to_string()).collect(),
+  });
+  let id = id.map(|id| {
+    id.eval::<String>(engine, scope, errors, source)
+      .unwrap_or_else(|| id.script.clone())
+  });
+  let style = style
+    .and_then(|style| style.eval::<String>(engine, scope, errors, source))
+    .map(|value| parse_inline_style(&value, errors, source));
+
+  let mut el = node.inner_mut();
+  if let Some(classes) = classes {
+    el.classes = classes;
+  }
+  if let Some(id) = id {
+    el.id = Some(id);
+  }
+  if let Some(style) = style {
+    el.style = style;
+  }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ElementData {
   Root(RootElement),
   Unstyled(UnstyledElement),
+  Text(TextElement),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -190,39 +478,404 @@ pub struct RootElement;
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct UnstyledElement;
 
+/// Text content of an element, it isn't matched by selectors and has no children.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextElement {
+  pub text: String,
+}
+
+/// Callback run whenever a document is invalidated from outside of the event loop.
+pub struct InvalidationListener(Box<dyn Fn() + Send + Sync>);
+
+impl fmt::Debug for InvalidationListener {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("InvalidationListener")
+  }
+}
+
+/// Invalidation state of a document, shared with the script API so scripts can request a redraw.
+#[derive(Debug, Default)]
+pub struct Invalidation {
+  invalidated: AtomicBool,
+  listener: RwLock<Option<InvalidationListener>>,
+}
+
+impl Invalidation {
+  pub fn invalidate(&self) {
+    self.invalidated.store(true, Ordering::SeqCst);
+
+    if let Some(listener) = &*self.listener.read().unwrap() {
+      (listener.0)();
+    }
+  }
+}
+
+/// Callback run for events dispatched to elements matching `selector`, see `CompiledDocument::add_event_listener`.
+pub struct EventListener {
+  selector: selectors::SelectorList<style::selectors::SelectorImpl>,
+  event_type: String,
+  callback: Arc<dyn Fn(&Node<Element>, &str) + Send + Sync>,
+}
+
+impl fmt::Debug for EventListener {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("EventListener")
+      .field("event_type", &self.event_type)
+      .finish()
+  }
+}
+
+/// How the window showing a document should look, from the `<Title>` and `<Window>` elements of its head.
+/// Hosts pick their own defaults for what isn't set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentMetadata {
+  pub title: Option<String>,
+  /// Inner size of the window in logical pixels.
+  pub size: Option<(f64, f64)>,
+  pub resizable: Option<bool>,
+  /// Whether the window has a title bar and borders.
+  pub decorations: Option<bool>,
+}
+
+#[doc = "module=dom"]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CompiledDocument {
   pub root: Node<Element>,
-  pub stylesheet: style::StyleSheet,
+  pub stylesheet: RwLock<style::StyleSheet>,
+  /// Base direction of the document, elements inherit it unless they set `direction` themselves.
+  pub direction: style::Direction,
+  pub metadata: DocumentMetadata,
+  /// Sources of the `<Script>` elements, run in order when the document is loaded. Reloads replace them.
+  pub scripts: RwLock<Vec<String>>,
+  /// Files embedded with `<Asset>` elements by URL, so the document doesn't need network or file access to
+  /// get them. URLs are relative to the document, unless the file comes from another host.
+  pub resources: BTreeMap<String, Vec<u8>>,
+  /// Debug information, only there if the compiler was asked for it.
+  source_map: RwLock<Option<source_map::SourceMap>>,
 
   #[serde(skip)]
   pub engine: rhai::Engine,
   #[serde(skip)]
   pub scope: RwLock<rhai::Scope<'static>>,
+  #[serde(skip)]
+  modules: HashMap<String, rhai::Module>,
+  /// Declaration order, so a derived value only depends on values declared before it.
+  #[serde(skip)]
+  derived: RwLock<Vec<derived::Derived>>,
+  /// Functions defined by the scripts of the document, timers call into it. Callbacks hold on to the functions
+  /// they started with, the debugger replaces them.
+  #[serde(skip)]
+  script: RwLock<Arc<rhai::AST>>,
+  #[serde(skip)]
+  timers: Arc<timers::Timers>,
+  #[serde(skip)]
+  script_errors: script::ScriptErrors,
+  #[cfg(feature = "fetch")]
+  #[serde(skip)]
+  requests: Arc<fetch::Requests>,
+
+  #[serde(skip)]
+  config: RwLock<Arc<yoga::Config>>,
+
+  #[serde(skip)]
+  invalidation: Arc<Invalidation>,
+  #[serde(skip)]
+  event_listeners: RwLock<Vec<EventListener>>,
+  /// Whether an element was scrolled since the last `compute_style`.
+  #[serde(skip)]
+  scrolled: AtomicBool,
+  #[serde(skip)]
+  debugger: Arc<debugger::Debugger>,
+  #[serde(skip)]
+  focused: RwLock<Option<WeakNode<Element>>>,
+  #[serde(skip)]
+  reloads: AtomicU64,
 }
 
 use std::io::prelude::*;
 
 impl CompiledDocument {
   pub fn new(root: Node<Element>, stylesheet: style::StyleSheet) -> Self {
+    let mut doc = Self::new_inert(root, stylesheet);
+    doc.init_scripting();
+    doc
+  }
+
+  /// Creates a document without setting up its scripting, so the scripts added to it don't run, like
+  /// `load_inert`.
+  #[must_use]
+  pub fn new_inert(root: Node<Element>, stylesheet: style::StyleSheet) -> Self {
     Self {
       root,
-      stylesheet,
+      stylesheet: RwLock::new(stylesheet),
+      direction: style::Direction::LTR,
+      metadata: DocumentMetadata::default(),
+      scripts: RwLock::new(Vec::new()),
+      resources: BTreeMap::new(),
+      source_map: RwLock::new(None),
       engine: rhai::Engine::default(),
       scope: RwLock::new(rhai::Scope::default()),
+      modules: HashMap::new(),
+      derived: RwLock::new(Vec::new()),
+      script: RwLock::new(Arc::new(rhai::AST::default())),
+      timers: Arc::new(timers::Timers::default()),
+      script_errors: script::ScriptErrors::default(),
+      #[cfg(feature = "fetch")]
+      requests: Arc::new(fetch::Requests::default()),
+      config: RwLock::new(Arc::new(yoga::Config::new())),
+      invalidation: Arc::new(Invalidation::default()),
+      event_listeners: RwLock::new(Vec::new()),
+      scrolled: AtomicBool::new(false),
+      debugger: Arc::new(debugger::Debugger::default()),
+      focused: RwLock::new(None),
+      reloads: AtomicU64::new(0),
     }
   }
 
+  /// Registers the script API in the engine and adds the `document` variable to the scope.
+  fn init_scripting(&mut self) {
+    script::ScriptPolicy::default().apply(&mut self.engine);
+    script::register(&mut self.engine, &self.timers);
+    debugger::register(&mut self.engine, &self.debugger);
+    storage::register(&mut self.engine);
+    #[cfg(feature = "fetch")]
+    fetch::register(&mut self.engine, &self.requests, &self.invalidation);
+    self.scope.write().unwrap().push_constant(
+      "document",
+      script::Document::new(self.root.clone(), Arc::clone(&self.invalidation)),
+    );
+
+    self
+      .script_errors
+      .set_source_map(self.source_map.get_mut().unwrap().clone());
+
+    for (index, source) in self.scripts.get_mut().unwrap().clone().iter().enumerate() {
+      self.run_script(source, self.script_location(index));
+    }
+  }
+
+  /// Adds a script to the document and runs it, its variables go into the scope and its functions can be
+  /// used as callbacks.
+  pub fn add_script(&mut self, source: String) {
+    let index = self.scripts.get_mut().unwrap().len();
+    self.run_script(&source, self.script_location(index));
+    self.scripts.get_mut().unwrap().push(source);
+  }
+
+  fn script_location(&self, index: usize) -> Option<source_map::SourceLocation> {
+    self
+      .source_map
+      .read()
+      .unwrap()
+      .as_ref()?
+      .scripts
+      .get(index)
+      .copied()
+      .flatten()
+  }
+
+  fn run_script(&mut self, source: &str, location: Option<source_map::SourceLocation>) {
+    let ast = match self.engine.compile(source) {
+      Ok(ast) => ast,
+      Err(e) => {
+        let location = location.map(|x| x.offset(e.1));
+        return self.script_errors.report_at(source, location, e);
+      }
+    };
+
+    if let Err(e) = self.engine.consume_ast_with_scope(self.scope.get_mut().unwrap(), &ast) {
+      let location = location.map(|x| x.offset(e.position()));
+      self.script_errors.report_at(source, location, e);
+    }
+
+    let script = self.script.get_mut().unwrap();
+    *script = Arc::new(script.merge(&ast));
+  }
+
+  /// Returns a copy of the source map, reloads replace it.
+  #[must_use]
+  pub fn source_map(&self) -> Option<source_map::SourceMap> {
+    self.source_map.read().unwrap().clone()
+  }
+
+  /// Sets where the document comes from, scripts added afterwards and errors of the elements are located
+  /// with it.
+  pub fn set_source_map(&mut self, source_map: Option<source_map::SourceMap>) {
+    self.script_errors.set_source_map(source_map.clone());
+    *self.source_map.get_mut().unwrap() = source_map;
+  }
+
+  /// Replaces the limits applied to scripts, `ScriptPolicy::default()` is used until this is called.
+  pub fn set_script_policy(&mut self, policy: script::ScriptPolicy) {
+    policy.apply(&mut self.engine);
+  }
+
+  /// Replaces the limits of the requests scripts make, `FetchPolicy::default()` is used until this is called.
+  #[cfg(feature = "fetch")]
+  pub fn set_fetch_policy(&mut self, policy: fetch::FetchPolicy) {
+    self.requests.set_policy(policy);
+  }
+
+  /// Makes the functions of `module` available to scripts as `name::function(...)`, so applications can expose
+  /// their own APIs to documents.
+  pub fn register_module(&mut self, name: &str, module: rhai::Module) {
+    self
+      .scope
+      .get_mut()
+      .unwrap()
+      .push_module(name.to_string(), module.clone());
+    self.modules.insert(name.to_string(), module);
+  }
+
+  /// Gives scripts a `storage` variable persisted at `path`, loading the values saved by previous runs.
+  pub fn set_storage_path(&self, path: impl Into<PathBuf>) -> io::Result<()> {
+    let storage = storage::Storage::open(path.into())?;
+    self.scope.write().unwrap().push_constant("storage", storage);
+    Ok(())
+  }
+
+  /// Returns an embedded file, `url` is the URL it was embedded from relative to the document, or the absolute
+  /// URL of a file from another host.
+  #[must_use]
+  pub fn resource(&self, url: &str) -> Option<&[u8]> {
+    self.resources.get(url).map(Vec::as_slice)
+  }
+
+  /// Errors raised by the scripts of the document, for hosts and devtools to show.
+  #[must_use]
+  pub fn script_errors(&self) -> &script::ScriptErrors {
+    &self.script_errors
+  }
+
+  /// Runs the script timers that are due and the callbacks of finished requests, returning when the next
+  /// timer is due.
+  pub fn run_callbacks(&self) -> Option<Instant> {
+    for callback in self.timers.take_due(Instant::now()) {
+      self.call(&callback, []);
+    }
+
+    #[cfg(feature = "fetch")]
+    for (callback, response) in self.requests.take_completed() {
+      self.call(&callback, [response]);
+    }
+
+    self.timers.next_due()
+  }
+
+  fn call(&self, callback: &rhai::FnPtr, args: impl AsMut<[rhai::Dynamic]>) {
+    {
+      let script = Arc::clone(&self.script.read().unwrap());
+      let mut scope = self.scope.write().unwrap();
+      if self.debugger.is_enabled() {
+        let globals = scope.iter().map(|(name, value)| (name.to_string(), value)).collect();
+        self.debugger.set_globals(globals);
+      }
+
+      if let Err(e) = self
+        .engine
+        .call_fn_dynamic(&mut scope, &*script, callback.fn_name(), None, args)
+      {
+        self.script_errors.report(callback.fn_name(), e);
+      }
+    }
+
+    // There's no telling which variables the callback assigned to.
+    self.update_derived(None);
+  }
+
+  #[must_use]
+  pub fn debugger(&self) -> &debugger::Debugger {
+    &self.debugger
+  }
+
+  /// Starts debugging the scripts of the document, sending the events of the debugger to `listener`. Returns
+  /// the scripts, their index is the one breakpoints and events use.
+  ///
+  /// The functions of the scripts are replaced by instrumented versions, see [`debugger`], until
+  /// `disable_debugger` is called.
+  pub fn enable_debugger(&self, listener: impl Fn(debugger::Event) + Send + Sync + 'static) -> Vec<debugger::Script> {
+    // Holding the functions keeps a reload from slipping in between instrumenting and enabling.
+    let mut script = self.script.write().unwrap();
+    let (functions, scripts) = self.instrument_scripts(&self.scripts.read().unwrap());
+    let original = std::mem::replace(&mut *script, Arc::new(functions));
+    self.debugger.enable(listener, original);
+
+    scripts
+  }
+
+  /// Returns the instrumented functions of `scripts` and how the debugger sees them.
+  fn instrument_scripts(&self, scripts: &[String]) -> (rhai::AST, Vec<debugger::Script>) {
+    let mut functions = rhai::AST::default();
+    let mut debug_scripts = Vec::with_capacity(scripts.len());
+    for (index, source) in scripts.iter().enumerate() {
+      let (instrumented, breakable_lines) = debugger::instrument(&self.engine, index, source);
+      // Scripts that don't compile never defined any function.
+      if let Ok(ast) = self.engine.compile(&instrumented) {
+        functions = functions.merge(&ast.clone_functions_only());
+      }
+
+      debug_scripts.push(debugger::Script {
+        source: source.clone(),
+        location: self.script_location(index),
+        breakable_lines,
+      });
+    }
+
+    (functions, debug_scripts)
+  }
+
+  /// Stops debugging and puts back the functions of the scripts, resuming the script if it's paused.
+  pub fn disable_debugger(&self) {
+    let mut script = self.script.write().unwrap();
+    if let Some(original) = self.debugger.disable() {
+      *script = original;
+    }
+  }
+
+  /// Marks the document as needing a new layout and redraw, e.g. after the
+  /// stylesheet was edited from devtools.
+  pub fn invalidate(&self) {
+    self.invalidation.invalidate();
+  }
+
+  /// Returns whether the document was invalidated since the last call, clearing the flag.
+  pub fn take_invalidated(&self) -> bool {
+    self.invalidation.invalidated.swap(false, Ordering::SeqCst)
+  }
+
+  /// Sets a callback that is run every time the document is invalidated, used
+  /// by windowing integrations to wake up their event loop.
+  pub fn set_invalidation_listener<F: Fn() + Send + Sync + 'static>(&self, listener: F) {
+    *self.invalidation.listener.write().unwrap() = Some(InvalidationListener(Box::new(listener)));
+  }
+
+  /// Serializes the document without compression.
   #[must_use]
   pub fn save(&self) -> Vec<u8> {
-    let mut buf = Vec::with_capacity(bincode::serialized_size(self).unwrap() as usize + MAGIC_BYTES.len());
-    self.save_into(&mut buf);
+    let mut buf = Vec::with_capacity(bincode::serialized_size(self).unwrap() as usize + MAGIC_BYTES.len() + 1);
+    self.save_into(&mut buf, Compression::None);
     buf
   }
 
-  pub fn save_into<W: Write>(&self, mut writer: W) {
+  pub fn save_into<W: Write>(&self, mut writer: W, compression: Compression) {
     writer.write_all(MAGIC_BYTES).unwrap();
-    bincode::serialize_into(writer, self).unwrap();
+    writer.write_all(&[compression as u8]).unwrap();
+
+    match compression {
+      Compression::None => bincode::serialize_into(writer, self).unwrap(),
+
+      Compression::Zstd => {
+        let mut encoder = zstd::stream::write::Encoder::new(writer, 0).unwrap();
+        bincode::serialize_into(&mut encoder, self).unwrap();
+        encoder.finish().unwrap();
+      }
+
+      Compression::Lz4 => {
+        let mut encoder = lz4::EncoderBuilder::new().build(writer).unwrap();
+        bincode::serialize_into(&mut encoder, self).unwrap();
+        encoder.finish().1.unwrap();
+      }
+    }
   }
 
   #[must_use]
@@ -231,82 +884,571 @@ impl CompiledDocument {
   }
 
   #[must_use]
-  pub fn load_from<R: Read>(mut reader: R) -> Self {
+  pub fn load_from<R: Read>(reader: R) -> Self {
+    Self::try_load_from(reader).unwrap_or_else(|e| panic!("{}", e))
+  }
+
+  /// Like `load`, failing instead of panicking on data that isn't a valid document.
+  pub fn try_load(data: &[u8]) -> Result<Self, LoadError> {
+    Self::try_load_from(data)
+  }
+
+  /// Like `load_from`, failing instead of panicking on data that isn't a valid document.
+  pub fn try_load_from<R: Read>(reader: R) -> Result<Self, LoadError> {
+    let mut doc = Self::try_load_inert(reader)?;
+    doc.init_yoga();
+    doc.init_scripting();
+    Ok(doc)
+  }
+
+  /// Loads a document without running its scripts or setting up its layout, for tools that only look at what
+  /// was compiled.
+  #[must_use]
+  pub fn load_inert<R: Read>(reader: R) -> Self {
+    Self::try_load_inert(reader).unwrap_or_else(|e| panic!("{}", e))
+  }
+
+  /// Like `load_inert`, failing instead of panicking on data that isn't a valid document.
+  pub fn try_load_inert<R: Read>(mut reader: R) -> Result<Self, LoadError> {
     let mut magic_bytes = [0; MAGIC_BYTES.len()];
-    reader.read_exact(&mut magic_bytes).unwrap();
+    reader.read_exact(&mut magic_bytes)?;
 
     if magic_bytes != MAGIC_BYTES {
-      panic!("magic bytes don't match {:?} == {:?}", magic_bytes, MAGIC_BYTES);
+      return Err(LoadError::MagicBytes(magic_bytes.to_vec()));
     }
 
-    let doc: CompiledDocument = bincode::deserialize_from(reader).unwrap();
-    doc.init_yoga();
-    doc
+    let mut compression = [0];
+    reader.read_exact(&mut compression)?;
+
+    Ok(match Compression::from_byte(compression[0]) {
+      Some(Compression::None) => bincode::deserialize_from(reader)?,
+      Some(Compression::Zstd) => bincode::deserialize_from(zstd::stream::read::Decoder::new(reader)?)?,
+      Some(Compression::Lz4) => bincode::deserialize_from(lz4::Decoder::new(reader)?)?,
+      None => return Err(LoadError::UnknownCompression(compression[0])),
+    })
+  }
+
+  /// Returns the yoga config shared by every node of the document.
+  pub fn yoga_config(&self) -> Arc<yoga::Config> {
+    Arc::clone(&self.config.read().unwrap())
+  }
+
+  /// Updates the point scale factor of the document config, so layout results
+  /// are rounded to physical pixels at the given device pixel ratio.
+  pub fn set_point_scale_factor(&self, scale: f32) {
+    let mut config = self.config.write().unwrap();
+    if (config.point_scale_factor() - scale).abs() < f32::EPSILON {
+      return;
+    }
+
+    let mut new_config = yoga::Config::clone(&config);
+    new_config.set_point_scale_factor(scale);
+    *config = Arc::new(new_config);
+
+    for node in self.root.descendants() {
+      node.inner_mut().yg.set_config(&config);
+    }
   }
 
   pub fn init_yoga(&self) {
-    // Even though we don't need mutable access from the rust side,
-    // we still want to make sure we are the only one with access to the
-    // yoga nodes.
+    let config = self.yoga_config();
+    {
+      let mut root = self.root.inner_mut();
+      root.yg.set_config(&config);
+      root.yg.set_context(self.root.downgrade());
+    }
+
     for node in self.root.descendants().skip(1) {
-      let inner = node.inner();
-      let parent = inner.parent().unwrap().inner();
-      unsafe {
-        parent.yg.insert_child(*node.inner().yg, parent.yg.child_count());
+      {
+        let mut el = node.inner_mut();
+        el.yg.set_config(&config);
+        el.yg.set_context(node.downgrade());
       }
+
+      let parent = node.inner().parent().cloned().unwrap();
+      let mut parent = parent.inner_mut();
+      let index = parent.yg.child_count();
+      parent.yg.insert_child(&mut node.inner_mut().yg, index);
     }
   }
 
-  pub fn compute_style(&self, width: f32, height: f32, direction: yoga::Direction) {
+  /// Applies a recompiled version of the document in place, e.g. to hot reload it during development. `new`
+  /// should be inert, see `new_inert`.
+  ///
+  /// Elements are matched by their position, matching elements keep their node, layout, scroll offset and
+  /// everything scripts attached to them, and only get the attributes and text of the new version. The focus
+  /// moves to the element taking the place of the focused one if it's replaced.
+  ///
+  /// The script scope is kept as is, the scripts of the new version don't run again but their functions
+  /// replace the ones with the same name. The scripts and the source map are the ones of the new version
+  /// afterwards, while debugging the new functions are instrumented and the debugger gets the new scripts.
+  pub fn patch(&self, mut new: CompiledDocument) {
+    let focus_path = self.focused().map(|node| node_path(&node));
+
+    {
+      let mut stylesheet = self.stylesheet.write().unwrap();
+      *stylesheet = std::mem::replace(&mut *new.stylesheet.write().unwrap(), style::StyleSheet::new());
+      self.reloads.fetch_add(1, Ordering::SeqCst);
+    }
+
+    let source_map = std::mem::take(new.source_map.get_mut().unwrap());
+    self.script_errors.set_source_map(source_map.clone());
+    *self.source_map.write().unwrap() = source_map;
+
+    let sources = std::mem::take(new.scripts.get_mut().unwrap());
+    let mut functions = rhai::AST::default();
+    for source in &sources {
+      // Errors were reported when compiling the new version.
+      if let Ok(ast) = self.engine.compile(source) {
+        functions = functions.merge(&ast.clone_functions_only());
+      }
+    }
+    {
+      // The debugger can't be enabled or disabled while the functions are held.
+      let mut script = self.script.write().unwrap();
+      if self.debugger.is_enabled() {
+        let (instrumented, debug_scripts) = self.instrument_scripts(&sources);
+        self.debugger.reload(&functions, debug_scripts);
+        *script = Arc::new(script.merge(&instrumented));
+      } else {
+        *script = Arc::new(script.merge(&functions));
+      }
+    }
+    *self.scripts.write().unwrap() = sources;
+
+    self.patch_node(&self.root, &new.root);
+
+    if let Some(path) = focus_path {
+      let focused = path
+        .iter()
+        .try_fold(self.root.clone(), |node, index| node.children().nth(*index));
+      self.focus(focused.as_ref());
+    }
+
+    self.invalidate();
+  }
+
+  /// Returns how many times the document was patched, tools holding on to rules or scripts of an earlier
+  /// version compare it to tell they're stale.
+  #[must_use]
+  pub fn reloads(&self) -> u64 {
+    self.reloads.load(Ordering::SeqCst)
+  }
+
+  /// Returns the focused element, if any.
+  #[must_use]
+  pub fn focused(&self) -> Option<Node<Element>> {
+    self.focused.read().unwrap().as_ref()?.upgrade()
+  }
+
+  /// Focuses `node`, or nothing if it's `None`. The document doesn't handle input itself, hosts decide what
+  /// gets focused.
+  pub fn focus(&self, node: Option<&Node<Element>>) {
+    *self.focused.write().unwrap() = node.map(Node::downgrade);
+  }
+
+  fn patch_node(&self, old: &Node<Element>, new: &Node<Element>) {
+    {
+      let mut old = old.inner_mut();
+      let mut new = new.inner_mut();
+      old.data = new.data.clone();
+      old.raw_attributes = std::mem::take(&mut new.raw_attributes);
+      old.style = std::mem::take(&mut new.style);
+      old.component = new.component.take();
+      old.source = new.source;
+    }
+
+    let old_children: Vec<_> = old.children().collect();
+    let new_children: Vec<_> = new.children().collect();
+
+    // Children are patched until the first one of another kind, everything after it is replaced. Text that
+    // only changed its content is patched.
+    let matching = old_children
+      .iter()
+      .zip(&new_children)
+      .take_while(|(old, new)| std::mem::discriminant(&old.inner().data) == std::mem::discriminant(&new.inner().data))
+      .count();
+
+    for (old, new) in old_children.iter().zip(&new_children).take(matching) {
+      self.patch_node(old, new);
+    }
+
+    for child in &old_children[matching..] {
+      old.inner_mut().yg.remove_child(&mut child.inner_mut().yg);
+      child.detach();
+
+      // Detached subtrees still reference themselves, see `Drop for CompiledDocument`.
+      let nodes: Vec<_> = child.descendants().collect();
+      for node in nodes {
+        node.unlink();
+      }
+    }
+
+    let config = self.yoga_config();
+    for child in &new_children[matching..] {
+      new.inner_mut().yg.remove_child(&mut child.inner_mut().yg);
+      child.detach();
+
+      old.append_node(child.clone());
+      let mut parent = old.inner_mut();
+      let index = parent.yg.child_count();
+      parent.yg.insert_child(&mut child.inner_mut().yg, index);
+
+      for node in child.descendants() {
+        node.inner_mut().yg.set_config(&config);
+      }
+    }
+  }
+
+  /// Scrolls the content of `node` so its point at `(x, y)` is in the top left corner of the padding box,
+  /// stopping at the edges of the content, and invalidates the document. Does nothing if the overflow of
+  /// `node` is `visible`.
+  pub fn scroll_to(&self, node: &Node<Element>, x: f32, y: f32) {
+    let (content_width, content_height) = content_size(node);
+    let mut el = node.inner_mut();
+    if el.computed.overflow == style::Overflow::Visible {
+      return;
+    }
+
+    let (width, height) = el.padding_box_size();
+    el.scroll_offset = (
+      x.min(content_width - width).max(0.0),
+      y.min(content_height - height).max(0.0),
+    );
+    drop(el);
+
+    self.scrolled.store(true, Ordering::SeqCst);
+    self.invalidate();
+  }
+
+  /// Computes the style and layout of every node.
+  ///
+  /// Returns whether any node changed its computed style, layout or scroll offset since the last call.
+  pub fn compute_style(&self, width: f32, height: f32, direction: yoga::Direction) -> bool {
+    let mut changed = self.scrolled.swap(false, Ordering::SeqCst);
+
+    let stylesheet = self.stylesheet.read().unwrap();
     for node in self.root.descendants() {
-      node
-        .inner_mut()
-        .compute_attributes(&self.engine, &mut self.scope.write().unwrap());
+      let chain = scope_chain(&node);
+      let mut scope = self.scope.write().unwrap();
+      if chain.is_empty() {
+        compute_attributes(&node, &self.engine, &mut scope, &self.script_errors);
+      } else {
+        // Variables pushed later shadow earlier ones with the same name.
+        let mut scope = scope.clone();
+        for (name, value) in chain {
+          scope.push_dynamic(name, value);
+        }
 
-      let mut computed = node.inner().computed;
+        compute_attributes(&node, &self.engine, &mut scope, &self.script_errors);
+      }
+      drop(scope);
 
-      self.stylesheet.apply(&node, &mut computed);
+      // Every pass starts over, so removing a class or a declaration reverts what it set. The only inherited
+      // property is `direction`, which yoga inherits itself.
+      let mut computed = style::ComputedStyle::default();
+
+      if !node.inner().is_text() {
+        stylesheet.apply(&node, &mut computed);
+      }
+      for declaration in &node.inner().style {
+        declaration.apply(&mut computed);
+      }
 
       let mut el = node.inner_mut();
+      changed |= el.computed != computed;
       el.computed = computed;
       el.prepare_yoga();
     }
 
-    let mut root = self.root.inner_mut();
-    unsafe {
-      root.yg.calculate_layout(width, height, direction);
+    self.root.inner_mut().yg.calculate_layout(width, height, direction);
+
+    for node in self.root.descendants() {
+      let mut el = node.inner_mut();
+      if el.yg.has_new_layout() {
+        changed = true;
+        el.yg.set_has_new_layout(false);
+      }
     }
+
+    changed
   }
 
   pub fn query_selector(&self, selector: &str) -> Option<Node<Element>> {
-    let mut input = cssparser::ParserInput::new(selector);
-    let list = selectors::SelectorList::parse(
-      &style::selectors::SelectorParser,
-      &mut cssparser::Parser::new(&mut input),
-    )
-    .ok()?;
+    query_selector(&self.root, selector)
+  }
 
-    let mut context = selectors::matching::MatchingContext::new(
-      selectors::matching::MatchingMode::Normal,
-      None,
-      None,
-      selectors::matching::QuirksMode::NoQuirks,
-    );
+  pub fn get_element_by_id(&self, id: &str) -> Option<Node<Element>> {
+    get_element_by_id(&self.root, id)
+  }
 
-    for node in self.root.descendants() {
-      if selectors::matching::matches_selector_list(&list, &node, &mut context) {
-        return Some(node);
+  /// Calls `callback` with the target and type of every `event_type` event dispatched to an element
+  /// matching `selector` or to one of its descendants.
+  ///
+  /// Returns `false` without adding the listener if `selector` is invalid.
+  pub fn add_event_listener<F: Fn(&Node<Element>, &str) + Send + Sync + 'static>(
+    &self,
+    selector: &str,
+    event_type: &str,
+    callback: F,
+  ) -> bool {
+    let selector = match parse_selector(selector) {
+      Some(selector) => selector,
+      None => return false,
+    };
+
+    self.event_listeners.write().unwrap().push(EventListener {
+      selector,
+      event_type: event_type.to_string(),
+      callback: Arc::new(callback),
+    });
+
+    true
+  }
+
+  /// Runs the listeners of `event_type` registered for `target` and its ancestors, innermost first.
+  ///
+  /// Nothing dispatches events on its own yet, embedders call this from their input handling.
+  pub fn dispatch_event(&self, target: &Node<Element>, event_type: &str) {
+    let mut callbacks = Vec::new();
+    {
+      let listeners = self.event_listeners.read().unwrap();
+      let mut context = matching_context();
+
+      let mut current = Some(target.clone());
+      while let Some(node) = current {
+        for listener in listeners.iter().filter(|x| x.event_type == event_type) {
+          if selectors::matching::matches_selector_list(&listener.selector, &node, &mut context) {
+            callbacks.push(Arc::clone(&listener.callback));
+          }
+        }
+
+        current = node.inner().parent().cloned();
       }
     }
 
-    None
+    // The lock is released first, so listeners can add listeners of their own.
+    for callback in callbacks {
+      callback(target, event_type);
+    }
+  }
+
+  /// Sets a variable of the scope of `node`, visible to the expressions of `node` and its descendants, and
+  /// invalidates the document.
+  pub fn set_element_scope_value(&self, node: &Node<Element>, name: &str, value: rhai::Dynamic) {
+    node.inner_mut().set_scope_value(name, value);
+    self.invalidate();
+  }
+
+  /// Sets a scope variable, adding it if it doesn't exist yet, and invalidates the document.
+  ///
+  /// Derived values depending on the variable are computed again. Constants and modules can't be set.
+  pub fn set_scope_value(&self, name: &str, value: rhai::Dynamic) -> Result<(), ScopeError> {
+    replace_scope_value(&mut self.scope.write().unwrap(), name, value)?;
+
+    let mut changed = HashSet::new();
+    changed.insert(name.to_string());
+    self.update_derived(Some(changed));
+
+    self.invalidate();
+    Ok(())
+  }
+
+  /// Declares the scope variable `name` as computed by `derive` from the variables in `dependencies`, it's
+  /// computed again whenever one of them is set with `set_scope_value`.
+  pub fn derive<F: Fn(&rhai::Scope) -> rhai::Dynamic + Send + Sync + 'static>(
+    &self,
+    name: &str,
+    dependencies: &[&str],
+    derive: F,
+  ) -> Result<(), ScopeError> {
+    self.add_derived(derived::Derived::native(name, dependencies, Arc::new(derive)))
+  }
+
+  /// Declares the scope variable `name` as the value of the script `expression`, e.g. `price * quantity`.
+  ///
+  /// The expression depends on the scope variables it mentions, including other derived values, so those
+  /// have to be declared first.
+  pub fn derive_expression(&self, name: &str, expression: &str) -> Result<(), ScopeError> {
+    let derived = derived::Derived::expression(&self.engine, &self.scope.read().unwrap(), name, expression)?;
+    self.add_derived(derived)
+  }
+
+  fn add_derived(&self, derived: derived::Derived) -> Result<(), ScopeError> {
+    {
+      let mut scope = self.scope.write().unwrap();
+      if is_constant(&scope, derived.name()) {
+        return Err(ScopeError::Constant(derived.name().to_string()));
+      }
+
+      let value = derived
+        .evaluate(&self.engine, &mut scope, &self.script_errors)
+        .unwrap_or_else(|| rhai::Dynamic::from(()));
+      replace_scope_value(&mut scope, derived.name(), value)?;
+    }
+
+    let mut all = self.derived.write().unwrap();
+    all.retain(|x| x.name() != derived.name());
+    all.push(derived);
+    drop(all);
+
+    self.invalidate();
+    Ok(())
+  }
+
+  /// Computes the derived values depending on `changed` again, or every derived value if it's `None`.
+  ///
+  /// A recomputed value counts as changed for the values declared after it.
+  fn update_derived(&self, mut changed: Option<HashSet<String>>) {
+    let derived = self.derived.read().unwrap();
+    let mut scope = self.scope.write().unwrap();
+
+    for derived in derived.iter() {
+      if !derived.is_stale(changed.as_ref()) {
+        continue;
+      }
+
+      // Failed expressions keep their previous value.
+      if let Some(value) = derived.evaluate(&self.engine, &mut scope, &self.script_errors) {
+        // A script may have declared a constant with the same name since the value was derived.
+        if let Err(e) = replace_scope_value(&mut scope, derived.name(), value) {
+          self.script_errors.report(derived.name(), e);
+          continue;
+        }
+
+        if let Some(changed) = &mut changed {
+          changed.insert(derived.name().to_string());
+        }
+      }
+    }
+  }
+}
+
+fn is_constant(scope: &rhai::Scope, name: &str) -> bool {
+  !matches!(scope.get_index(name), None | Some((_, rhai::EntryType::Normal)))
+}
+
+/// Sets the scope variable `name` in place, or adds it if there's none. Constants and modules, e.g. `document`,
+/// `storage` or a `const` of a script, are left alone.
+fn replace_scope_value(scope: &mut rhai::Scope<'static>, name: &str, value: rhai::Dynamic) -> Result<(), ScopeError> {
+  match scope.get_index(name) {
+    None => {
+      scope.push_dynamic(name.to_string(), value);
+      Ok(())
+    }
+    Some((index, rhai::EntryType::Normal)) => {
+      *scope.get_mut(index).0 = value;
+      Ok(())
+    }
+    Some(..) => Err(ScopeError::Constant(name.to_string())),
+  }
+}
+
+/// Size of the content of `node` in the last layout, measured from the top left corner of its padding box. It's
+/// at least the size of the padding box, children that go past it grow it, plus the padding on that side.
+#[must_use]
+pub fn content_size(node: &Node<Element>) -> (f32, f32) {
+  let el = node.inner();
+  let (mut width, mut height) = el.padding_box_size();
+  let border_left = el.yg.get_layout_border(yoga::Edge::Left);
+  let border_top = el.yg.get_layout_border(yoga::Edge::Top);
+  let padding_right = el.yg.get_layout_padding(yoga::Edge::Right);
+  let padding_bottom = el.yg.get_layout_padding(yoga::Edge::Bottom);
+  drop(el);
+
+  for child in node.children() {
+    let child = child.inner();
+    if child.computed.display == style::Display::None {
+      continue;
+    }
+
+    let right = child.yg.get_left() - border_left
+      + child.yg.get_width()
+      + child.yg.get_layout_margin(yoga::Edge::Right)
+      + padding_right;
+    let bottom = child.yg.get_top() - border_top
+      + child.yg.get_height()
+      + child.yg.get_layout_margin(yoga::Edge::Bottom)
+      + padding_bottom;
+    width = width.max(right);
+    height = height.max(bottom);
   }
+
+  (width, height)
+}
+
+/// Positions of `node` and its ancestors among their siblings, from the root down.
+fn node_path(node: &Node<Element>) -> Vec<usize> {
+  let mut path = Vec::new();
+  let mut node = node.clone();
+  loop {
+    let parent = match node.inner().parent() {
+      Some(parent) => parent.clone(),
+      None => break,
+    };
+
+    path.push(parent.children().position(|x| x == node).unwrap());
+    node = parent;
+  }
+
+  path.reverse();
+  path
+}
+
+/// The variables of the scopes of `node` and its ancestors, outermost first.
+fn scope_chain(node: &Node<Element>) -> Vec<(String, rhai::Dynamic)> {
+  let mut scopes = Vec::new();
+  let mut current = Some(node.clone());
+  while let Some(node) = current {
+    let el = node.inner();
+    if !el.scope.is_empty() {
+      scopes.push(el.scope.clone());
+    }
+    current = el.parent().cloned();
+  }
+
+  scopes.into_iter().rev().flatten().collect()
+}
+
+fn parse_selector(selector: &str) -> Option<selectors::SelectorList<style::selectors::SelectorImpl>> {
+  let mut input = cssparser::ParserInput::new(selector);
+  selectors::SelectorList::parse(
+    &style::selectors::SelectorParser,
+    &mut cssparser::Parser::new(&mut input),
+  )
+  .ok()
+}
+
+fn matching_context() -> selectors::matching::MatchingContext<'static, style::selectors::SelectorImpl> {
+  selectors::matching::MatchingContext::new(
+    selectors::matching::MatchingMode::Normal,
+    None,
+    None,
+    selectors::matching::QuirksMode::NoQuirks,
+  )
+}
+
+/// Returns the first element of the subtree of `root` matching `selector`.
+pub(crate) fn query_selector(root: &Node<Element>, selector: &str) -> Option<Node<Element>> {
+  let list = parse_selector(selector)?;
+  let mut context = matching_context();
+
+  root
+    .descendants()
+    .find(|node| selectors::matching::matches_selector_list(&list, node, &mut context))
+}
+
+pub(crate) fn get_element_by_id(root: &Node<Element>, id: &str) -> Option<Node<Element>> {
+  root.descendants().find(|node| node.inner().id.as_deref() == Some(id))
 }
 
 impl Drop for CompiledDocument {
   fn drop(&mut self) {
-    unsafe {
-      self.root.inner_mut().yg.free_recursive();
+    // Nodes hold strong references to their relatives, so the tree has to be
+    // unlinked before the elements (and their yoga nodes) can be freed.
+    let nodes: Vec<_> = self.root.descendants().collect();
+    for node in nodes {
+      node.unlink();
     }
   }
 }
@@ -335,11 +1477,19 @@ impl selectors::Element for Node<Element> {
   }
 
   fn prev_sibling_element(&self) -> Option<Self> {
-    self.inner().previous_sibling().cloned()
+    let mut sibling = self.inner().previous_sibling().cloned();
+    while sibling.as_ref().map_or(false, |x| x.inner().is_text()) {
+      sibling = sibling.unwrap().inner().previous_sibling().cloned();
+    }
+    sibling
   }
 
   fn next_sibling_element(&self) -> Option<Self> {
-    self.inner().next_sibling().cloned()
+    let mut sibling = self.inner().next_sibling().cloned();
+    while sibling.as_ref().map_or(false, |x| x.inner().is_text()) {
+      sibling = sibling.unwrap().inner().next_sibling().cloned();
+    }
+    sibling
   }
 
   fn is_empty(&self) -> bool {
@@ -441,3 +1591,89 @@ macro_rules! include_document {
     ::std::sync::Arc::new(::project_a::dom::CompiledDocument::load(include_bytes!($file)))
   };
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn removed_class_reverts_its_properties() {
+    let mut stylesheet = style::StyleSheet::new();
+    stylesheet
+      .parse(&mut style::StyleSheet::create_parser_input(
+        ".hidden { display: none; }",
+      ))
+      .unwrap();
+
+    let root = Node::new(Element::new(
+      ElementData::Root(RootElement),
+      RawElementAttributes::default(),
+    ));
+    let child = Node::new(Element::new(
+      ElementData::Unstyled(UnstyledElement),
+      RawElementAttributes {
+        class: Some(RawAttributeValue::Raw {
+          value: "hidden".to_string(),
+          up_to_date: false,
+        }),
+        ..RawElementAttributes::default()
+      },
+    ));
+    root.append_node(child.clone());
+
+    let doc = CompiledDocument::new(root, stylesheet);
+    doc.init_yoga();
+
+    doc.compute_style(100.0, 100.0, yoga::Direction::LTR);
+    assert_eq!(child.inner().computed.display, style::Display::None);
+
+    child.inner_mut().raw_attributes.class = None;
+    assert!(doc.compute_style(100.0, 100.0, yoga::Direction::LTR));
+    assert_eq!(child.inner().computed.display, style::Display::Flex);
+  }
+
+  #[test]
+  fn replacing_a_variable_keeps_the_constants() {
+    let root = Node::new(Element::new(
+      ElementData::Root(RootElement),
+      RawElementAttributes::default(),
+    ));
+    let doc = CompiledDocument::new(root, style::StyleSheet::new());
+
+    doc
+      .set_scope_value("count", rhai::Dynamic::from(1 as rhai::INT))
+      .unwrap();
+    doc
+      .set_scope_value("count", rhai::Dynamic::from(2 as rhai::INT))
+      .unwrap();
+
+    let mut scope = doc.scope.write().unwrap();
+    assert_eq!(scope.get_value::<rhai::INT>("count"), Some(2));
+    assert!(doc.engine.eval_with_scope::<()>(&mut scope, "document = 1;").is_err());
+  }
+
+  #[test]
+  fn setting_a_constant_fails() {
+    let root = Node::new(Element::new(
+      ElementData::Root(RootElement),
+      RawElementAttributes::default(),
+    ));
+    let doc = CompiledDocument::new(root, style::StyleSheet::new());
+    doc
+      .engine
+      .consume_with_scope(&mut doc.scope.write().unwrap(), "const limit = 10;")
+      .unwrap();
+
+    assert!(matches!(
+      doc.set_scope_value("limit", rhai::Dynamic::from(20 as rhai::INT)),
+      Err(ScopeError::Constant(..))
+    ));
+    assert!(matches!(
+      doc.derive("document", &[], |_| rhai::Dynamic::from(())),
+      Err(ScopeError::Constant(..))
+    ));
+
+    // The scope is still usable.
+    assert_eq!(doc.scope.read().unwrap().get_value::<rhai::INT>("limit"), Some(10));
+  }
+}
@@ -0,0 +1,481 @@
+//! The API available to document scripts, a `document` variable and the `Element` type it hands out.
+//!
+//! Attribute expressions run without their element locked, so they can use the element and the rest of the
+//! document like any other script.
+
+use std::{
+  cell::Cell,
+  collections::VecDeque,
+  fmt,
+  sync::{Arc, Mutex, RwLock},
+  time::{Duration, Instant},
+};
+
+use rhai::{Array, Dynamic, Engine, EvalAltResult, FnPtr, ImmutableString, RegisterFn, RegisterResultFn, INT};
+
+use super::{
+  source_map::{SourceLocation, SourceMap},
+  timers::Timers,
+  tree::Node,
+  Element, ElementData, Invalidation, RawAttributeValue, RawElementAttributes, UnstyledElement,
+};
+
+type ScriptResult = Result<Dynamic, Box<EvalAltResult>>;
+
+/// An error raised by an attribute expression or a script callback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptError {
+  /// Source of the failing expression, or the name of the failing callback.
+  pub script: String,
+  pub message: String,
+  /// Where the error is in the original source as `file:line:column`, if the document has a source map.
+  pub location: Option<String>,
+}
+
+impl fmt::Display for ScriptError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let Some(location) = &self.location {
+      write!(f, "{}: ", location)?;
+    }
+    write!(f, "`{}`: {}", self.script, self.message)
+  }
+}
+
+type ScriptErrorListener = Box<dyn Fn(&ScriptError) + Send + Sync>;
+
+/// Keeps the latest script errors of a document, see `CompiledDocument::script_errors`.
+#[derive(Default)]
+pub struct ScriptErrors {
+  errors: Mutex<VecDeque<ScriptError>>,
+  listener: RwLock<Option<ScriptErrorListener>>,
+  /// The document's source map, locations are resolved against it.
+  source_map: RwLock<Option<SourceMap>>,
+}
+
+impl fmt::Debug for ScriptErrors {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ScriptErrors").field("errors", &self.errors).finish()
+  }
+}
+
+impl ScriptErrors {
+  const CAPACITY: usize = 100;
+
+  pub(crate) fn set_source_map(&self, source_map: Option<SourceMap>) {
+    *self.source_map.write().unwrap() = source_map;
+  }
+
+  /// Records an error, unless it repeats the last one, e.g. a broken expression evaluated on every layout.
+  pub fn report(&self, script: &str, message: impl fmt::Display) {
+    self.report_at(script, None, message);
+  }
+
+  /// Like `report`, `location` being where the error is in the source of the document.
+  pub fn report_at(&self, script: &str, location: Option<SourceLocation>, message: impl fmt::Display) {
+    let location = match (&*self.source_map.read().unwrap(), location) {
+      (Some(source_map), Some(location)) => Some(source_map.describe(location)),
+      _ => None,
+    };
+
+    let error = ScriptError {
+      script: script.to_string(),
+      message: message.to_string(),
+      location,
+    };
+
+    {
+      let mut errors = self.errors.lock().unwrap();
+      if errors.back() == Some(&error) {
+        return;
+      }
+
+      if errors.len() == Self::CAPACITY {
+        errors.pop_front();
+      }
+      errors.push_back(error.clone());
+    }
+
+    log::error!("script error in {}", error);
+    if let Some(listener) = &*self.listener.read().unwrap() {
+      listener(&error);
+    }
+  }
+
+  /// Returns the recorded errors, oldest first.
+  #[must_use]
+  pub fn errors(&self) -> Vec<ScriptError> {
+    self.errors.lock().unwrap().iter().cloned().collect()
+  }
+
+  pub fn set_listener<F: Fn(&ScriptError) + Send + Sync + 'static>(&self, listener: F) {
+    *self.listener.write().unwrap() = Some(Box::new(listener));
+  }
+}
+
+/// Limits applied to the scripts of a document, so a bad expression can't hang the UI thread.
+///
+/// Zero means unlimited for every limit. Rhai itself reads a call depth of zero as forbidding function calls,
+/// so `apply` passes it on as `usize::MAX`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptPolicy {
+  pub max_operations: u64,
+  pub max_call_depth: usize,
+  pub max_string_size: usize,
+  pub max_array_size: usize,
+  pub max_map_size: usize,
+  /// Wall clock time a single evaluation may take.
+  pub timeout: Option<Duration>,
+}
+
+impl Default for ScriptPolicy {
+  fn default() -> Self {
+    Self {
+      max_operations: 1_000_000,
+      max_call_depth: 64,
+      max_string_size: 1 << 20,
+      max_array_size: 1 << 16,
+      max_map_size: 1 << 16,
+      timeout: Some(Duration::from_millis(100)),
+    }
+  }
+}
+
+thread_local! {
+  static EVALUATION_START: Cell<Option<Instant>> = Cell::new(None);
+}
+
+impl ScriptPolicy {
+  /// No limits at all, for trusted documents.
+  #[must_use]
+  pub fn unlimited() -> Self {
+    Self {
+      max_operations: 0,
+      max_call_depth: 0,
+      max_string_size: 0,
+      max_array_size: 0,
+      max_map_size: 0,
+      timeout: None,
+    }
+  }
+
+  pub fn apply(&self, engine: &mut Engine) {
+    engine.set_max_operations(self.max_operations);
+    engine.set_max_call_levels(if self.max_call_depth == 0 {
+      usize::MAX
+    } else {
+      self.max_call_depth
+    });
+    engine.set_max_string_size(self.max_string_size);
+    engine.set_max_array_size(self.max_array_size);
+    engine.set_max_map_size(self.max_map_size);
+
+    // The operation count restarts with every evaluation, so its first operation marks the start.
+    let timeout = self.timeout;
+    engine.on_progress(move |&operations| {
+      let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return true,
+      };
+
+      EVALUATION_START.with(|start| {
+        if operations <= 1 {
+          start.set(Some(Instant::now()));
+        }
+
+        start.get().map_or(true, |start| start.elapsed() < timeout)
+      })
+    });
+  }
+}
+
+/// Moves the start of the running evaluation forward by `duration`, so the time it spent paused in the debugger
+/// doesn't count towards its timeout.
+pub(crate) fn extend_timeout(duration: Duration) {
+  EVALUATION_START.with(|start| {
+    if let Some(instant) = start.get() {
+      start.set(Some(instant + duration));
+    }
+  });
+}
+
+/// The `document` variable of every document scope.
+#[derive(Clone)]
+pub struct Document {
+  root: Node<Element>,
+  invalidation: Arc<Invalidation>,
+}
+
+/// An element as seen by scripts, changes made through it invalidate the document.
+#[derive(Clone)]
+pub struct ScriptElement {
+  node: Node<Element>,
+  invalidation: Arc<Invalidation>,
+}
+
+impl Document {
+  #[must_use]
+  pub fn new(root: Node<Element>, invalidation: Arc<Invalidation>) -> Self {
+    Self { root, invalidation }
+  }
+
+  fn element(&self, node: Option<Node<Element>>) -> Dynamic {
+    element(node, &self.invalidation)
+  }
+
+  fn create_element(&mut self) -> ScriptElement {
+    let node = Node::new(Element::new(
+      ElementData::Unstyled(UnstyledElement),
+      RawElementAttributes::default(),
+    ));
+
+    {
+      let mut el = node.inner_mut();
+      if let Some(config) = self.root.inner().yg.config() {
+        el.yg.set_config(config);
+      }
+      el.yg.set_context(node.downgrade());
+    }
+
+    ScriptElement {
+      node,
+      invalidation: Arc::clone(&self.invalidation),
+    }
+  }
+}
+
+fn element(node: Option<Node<Element>>, invalidation: &Arc<Invalidation>) -> Dynamic {
+  match node {
+    Some(node) => Dynamic::from(ScriptElement {
+      node,
+      invalidation: Arc::clone(invalidation),
+    }),
+    None => ().into(),
+  }
+}
+
+fn attribute<'a>(
+  attributes: &'a mut RawElementAttributes,
+  name: &str,
+) -> Result<&'a mut Option<RawAttributeValue>, Box<EvalAltResult>> {
+  match name {
+    "class" => Ok(&mut attributes.class),
+    "id" => Ok(&mut attributes.id),
+    "style" => Ok(&mut attributes.style),
+    _ => Err(format!("unknown attribute `{}`", name).into()),
+  }
+}
+
+fn raw(value: String) -> Option<RawAttributeValue> {
+  Some(RawAttributeValue::Raw {
+    value,
+    up_to_date: false,
+  })
+}
+
+impl ScriptElement {
+  fn get_attribute(&mut self, name: &str) -> ScriptResult {
+    let mut el = self.node.inner_mut();
+    attribute(&mut el.raw_attributes, name)?;
+
+    // `id` and `class` return their current value, even if they're set by an expression.
+    Ok(match name {
+      "id" => el.id.clone().map_or_else(|| ().into(), Dynamic::from),
+      "class" if el.raw_attributes.class.is_some() => el.classes.join(" ").into(),
+      _ => match attribute(&mut el.raw_attributes, name)? {
+        Some(RawAttributeValue::Raw { value, .. }) => value.clone().into(),
+        Some(RawAttributeValue::Script { script, .. }) => script.clone().into(),
+        None => ().into(),
+      },
+    })
+  }
+
+  fn set_attribute(&mut self, name: &str, value: String) -> ScriptResult {
+    {
+      let mut el = self.node.inner_mut();
+      *attribute(&mut el.raw_attributes, name)? = raw(value.clone());
+
+      match name {
+        "id" => el.id = Some(value),
+        "class" => el.classes = value.split_ascii_whitespace().map(str::to_string).collect(),
+        _ => {}
+      }
+    }
+
+    self.invalidation.invalidate();
+    Ok(().into())
+  }
+
+  fn remove_attribute(&mut self, name: &str) -> ScriptResult {
+    {
+      let mut el = self.node.inner_mut();
+      *attribute(&mut el.raw_attributes, name)? = None;
+
+      match name {
+        "id" => el.id = None,
+        "class" => el.classes.clear(),
+        _ => {}
+      }
+    }
+
+    self.invalidation.invalidate();
+    Ok(().into())
+  }
+
+  fn set_classes(&mut self, classes: Vec<String>) {
+    {
+      let mut el = self.node.inner_mut();
+      el.raw_attributes.class = raw(classes.join(" "));
+      el.classes = classes;
+    }
+
+    self.invalidation.invalidate();
+  }
+
+  fn add_class(&mut self, class: &str) {
+    let mut classes = self.node.inner().classes.clone();
+    if !classes.iter().any(|x| x == class) {
+      classes.push(class.to_string());
+      self.set_classes(classes);
+    }
+  }
+
+  fn remove_class(&mut self, class: &str) {
+    let mut classes = self.node.inner().classes.clone();
+    let len = classes.len();
+    classes.retain(|x| x != class);
+    if classes.len() != len {
+      self.set_classes(classes);
+    }
+  }
+
+  fn append(&mut self, child: ScriptElement) -> ScriptResult {
+    if let ElementData::Root(_) = child.node.inner().data {
+      return Err("the root element can't be moved".into());
+    }
+
+    if self.node.inner().is_text() {
+      return Err("text can't have children".into());
+    }
+
+    let mut current = Some(self.node.clone());
+    while let Some(node) = current {
+      if node == child.node {
+        return Err("an element can't be appended to itself or its descendants".into());
+      }
+      current = node.inner().parent().cloned();
+    }
+
+    child.detach();
+    self.node.append_node(child.node.clone());
+
+    {
+      let mut parent = self.node.inner_mut();
+      let index = parent.yg.child_count();
+      parent.yg.insert_child(&mut child.node.inner_mut().yg, index);
+    }
+
+    self.invalidation.invalidate();
+    Ok(().into())
+  }
+
+  fn detach(&self) {
+    let parent = self.node.inner().parent().cloned();
+    if let Some(parent) = parent {
+      parent.inner_mut().yg.remove_child(&mut self.node.inner_mut().yg);
+      self.node.detach();
+    }
+  }
+
+  /// Detaches the element and takes its subtree apart, since its nodes reference each other they would never
+  /// be freed otherwise. Elements of the subtree that scripts still hold are left without children.
+  fn remove(&mut self) {
+    self.detach();
+
+    let nodes: Vec<_> = self.node.descendants().collect();
+    for node in nodes {
+      node.inner_mut().yg.remove_all_children();
+      node.unlink();
+    }
+
+    self.invalidation.invalidate();
+  }
+}
+
+/// Registers the `Document` and `Element` types and their functions, and the timer functions backed by `timers`.
+pub fn register(engine: &mut Engine, timers: &Arc<Timers>) {
+  engine.register_type_with_name::<Document>("Document");
+  engine.register_get("root", |doc: &mut Document| doc.element(Some(doc.root.clone())));
+  engine.register_fn("get_element_by_id", |doc: &mut Document, id: ImmutableString| {
+    doc.element(super::get_element_by_id(&doc.root, &id))
+  });
+  engine.register_fn("query_selector", |doc: &mut Document, selector: ImmutableString| {
+    doc.element(super::query_selector(&doc.root, &selector))
+  });
+  engine.register_fn("create_element", Document::create_element);
+
+  engine.register_type_with_name::<ScriptElement>("Element");
+  engine.register_get("id", |el: &mut ScriptElement| {
+    el.node.inner().id.clone().map_or_else(|| ().into(), Dynamic::from)
+  });
+  engine.register_set("id", |el: &mut ScriptElement, id: ImmutableString| {
+    let _ = el.set_attribute("id", id.to_string());
+  });
+  engine.register_get("classes", |el: &mut ScriptElement| -> Array {
+    el.node.inner().classes.iter().cloned().map(Dynamic::from).collect()
+  });
+  engine.register_get("parent", |el: &mut ScriptElement| {
+    element(el.node.inner().parent().cloned(), &el.invalidation)
+  });
+  engine.register_get("children", |el: &mut ScriptElement| -> Array {
+    el.node
+      .children()
+      .map(|node| element(Some(node), &el.invalidation))
+      .collect()
+  });
+
+  engine.register_result_fn("get_attribute", |el: &mut ScriptElement, name: ImmutableString| {
+    el.get_attribute(&name)
+  });
+  engine.register_result_fn(
+    "set_attribute",
+    |el: &mut ScriptElement, name: ImmutableString, value: ImmutableString| el.set_attribute(&name, value.to_string()),
+  );
+  engine.register_result_fn("remove_attribute", |el: &mut ScriptElement, name: ImmutableString| {
+    el.remove_attribute(&name)
+  });
+
+  engine.register_fn("has_class", |el: &mut ScriptElement, class: ImmutableString| {
+    el.node.inner().classes.iter().any(|x| *x == *class)
+  });
+  engine.register_fn("add_class", |el: &mut ScriptElement, class: ImmutableString| {
+    el.add_class(&class)
+  });
+  engine.register_fn("remove_class", |el: &mut ScriptElement, class: ImmutableString| {
+    el.remove_class(&class)
+  });
+
+  engine.register_fn(
+    "set_scope_value",
+    |el: &mut ScriptElement, name: ImmutableString, value: Dynamic| {
+      el.node.inner_mut().set_scope_value(&name, value);
+      el.invalidation.invalidate();
+    },
+  );
+
+  engine.register_result_fn("append", ScriptElement::append);
+  engine.register_fn("remove", ScriptElement::remove);
+
+  let delay = |ms: INT| Duration::from_millis(ms.max(0) as u64);
+  let t = Arc::clone(timers);
+  engine.register_fn("set_timeout", move |callback: FnPtr, ms: INT| {
+    t.start(callback, delay(ms), false)
+  });
+  let t = Arc::clone(timers);
+  engine.register_fn("set_interval", move |callback: FnPtr, ms: INT| {
+    t.start(callback, delay(ms), true)
+  });
+  let t = Arc::clone(timers);
+  engine.register_fn("clear_timeout", move |id: INT| t.clear(id));
+  let t = Arc::clone(timers);
+  engine.register_fn("clear_interval", move |id: INT| t.clear(id));
+}
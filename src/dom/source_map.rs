@@ -0,0 +1,65 @@
+//! Where the elements and scripts of a compiled document come from, so errors and devtools can point at
+//! the original source. Documents only have a source map if they were compiled with one.
+
+use serde::{Deserialize, Serialize};
+
+/// A position in one of the files of a `SourceMap`, lines and columns start at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+  /// Index into `SourceMap::files`.
+  pub file: usize,
+  pub line: usize,
+  pub column: usize,
+}
+
+impl SourceLocation {
+  /// The location of `position` in a script starting at this location.
+  #[must_use]
+  pub fn offset(self, position: rhai::Position) -> Self {
+    match (position.line(), position.position()) {
+      (Some(1), Some(column)) => Self {
+        column: self.column + column - 1,
+        ..self
+      },
+
+      (Some(line), Some(column)) => Self {
+        line: self.line + line - 1,
+        column,
+        ..self
+      },
+
+      _ => self,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SourceMap {
+  /// Names of the source files, usually their URL.
+  pub files: Vec<String>,
+  /// Where each of `CompiledDocument::scripts` starts, by index.
+  pub scripts: Vec<Option<SourceLocation>>,
+  /// Where each rule of `CompiledDocument::stylesheet` starts, by index. `None` for rules compiled from Sass,
+  /// they aren't where they're written.
+  pub rules: Vec<Option<SourceLocation>>,
+}
+
+impl SourceMap {
+  /// Returns the index of the file `name`, adding it if it isn't in the map yet.
+  pub fn add_file(&mut self, name: &str) -> usize {
+    match self.files.iter().position(|x| x == name) {
+      Some(index) => index,
+      None => {
+        self.files.push(name.to_string());
+        self.files.len() - 1
+      }
+    }
+  }
+
+  /// Formats `location` as `file:line:column`.
+  #[must_use]
+  pub fn describe(&self, location: SourceLocation) -> String {
+    let file = self.files.get(location.file).map_or("<unknown>", String::as_str);
+    format!("{}:{}:{}", file, location.line, location.column)
+  }
+}
@@ -0,0 +1,78 @@
+//! The `storage` variable of scripts, string values persisted to a file chosen by the application.
+
+use std::{
+  collections::BTreeMap,
+  fs, io,
+  path::PathBuf,
+  sync::{Arc, Mutex},
+};
+
+use rhai::{Dynamic, Engine, ImmutableString, RegisterFn};
+
+#[derive(Debug)]
+struct Inner {
+  path: PathBuf,
+  values: Mutex<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Storage(Arc<Inner>);
+
+impl Storage {
+  /// Opens the storage persisted at `path`, which doesn't have to exist yet.
+  pub fn open(path: PathBuf) -> io::Result<Self> {
+    let values = match fs::read(&path) {
+      Ok(data) => bincode::deserialize(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+      Err(e) => return Err(e),
+    };
+
+    Ok(Self(Arc::new(Inner {
+      path,
+      values: Mutex::new(values),
+    })))
+  }
+
+  #[must_use]
+  pub fn get(&self, key: &str) -> Option<String> {
+    self.0.values.lock().unwrap().get(key).cloned()
+  }
+
+  pub fn set(&self, key: &str, value: String) {
+    let mut values = self.0.values.lock().unwrap();
+    values.insert(key.to_string(), value);
+    self.save(&values);
+  }
+
+  pub fn remove(&self, key: &str) {
+    let mut values = self.0.values.lock().unwrap();
+    if values.remove(key).is_some() {
+      self.save(&values);
+    }
+  }
+
+  // Scripts can't handle the error, so it's only logged and the values are kept in memory.
+  fn save(&self, values: &BTreeMap<String, String>) {
+    let result = bincode::serialize(values)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+      .and_then(|data| fs::write(&self.0.path, data));
+
+    if let Err(e) = result {
+      log::error!("could not save storage to {}: {}", self.0.path.display(), e);
+    }
+  }
+}
+
+pub fn register(engine: &mut Engine) {
+  engine.register_type_with_name::<Storage>("Storage");
+  engine.register_fn("get", |storage: &mut Storage, key: ImmutableString| {
+    storage.get(&key).map_or_else(|| ().into(), Dynamic::from)
+  });
+  engine.register_fn(
+    "set",
+    |storage: &mut Storage, key: ImmutableString, value: ImmutableString| storage.set(&key, value.to_string()),
+  );
+  engine.register_fn("remove", |storage: &mut Storage, key: ImmutableString| {
+    storage.remove(&key)
+  });
+}
@@ -0,0 +1,72 @@
+//! Timers started by scripts with `set_timeout` and `set_interval`, run by `CompiledDocument::run_callbacks`.
+
+use std::{
+  sync::{
+    atomic::{AtomicI64, Ordering},
+    Mutex,
+  },
+  time::{Duration, Instant},
+};
+
+/// Shortest interval of repeating timers, like browsers clamp them, so a zero interval doesn't keep the event
+/// loop busy.
+pub const MIN_INTERVAL: Duration = Duration::from_millis(4);
+
+#[derive(Debug)]
+struct Timer {
+  id: i64,
+  due: Instant,
+  interval: Option<Duration>,
+  callback: rhai::FnPtr,
+}
+
+#[derive(Debug, Default)]
+pub struct Timers {
+  next_id: AtomicI64,
+  pending: Mutex<Vec<Timer>>,
+}
+
+impl Timers {
+  /// Schedules `callback` after `delay`, repeating every `delay` if `repeat` is set. Returns the id of the timer.
+  ///
+  /// Repeating timers wait at least `MIN_INTERVAL`.
+  pub fn start(&self, callback: rhai::FnPtr, delay: Duration, repeat: bool) -> i64 {
+    let delay = if repeat { delay.max(MIN_INTERVAL) } else { delay };
+    let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+    self.pending.lock().unwrap().push(Timer {
+      id,
+      due: Instant::now() + delay,
+      interval: if repeat { Some(delay) } else { None },
+      callback,
+    });
+
+    id
+  }
+
+  /// Cancels a timer, unknown ids are ignored.
+  pub fn clear(&self, id: i64) {
+    self.pending.lock().unwrap().retain(|x| x.id != id);
+  }
+
+  /// Returns when the next timer is due, if any.
+  #[must_use]
+  pub fn next_due(&self) -> Option<Instant> {
+    self.pending.lock().unwrap().iter().map(|x| x.due).min()
+  }
+
+  /// Removes the timers due at `now` and returns their callbacks, intervals are scheduled again.
+  pub(crate) fn take_due(&self, now: Instant) -> Vec<rhai::FnPtr> {
+    let mut pending = self.pending.lock().unwrap();
+    let mut callbacks = Vec::new();
+
+    for timer in pending.iter_mut().filter(|x| x.due <= now) {
+      callbacks.push(timer.callback.clone());
+      if let Some(interval) = timer.interval {
+        timer.due = now + interval;
+      }
+    }
+
+    pending.retain(|x| x.due > now || x.interval.is_some());
+    callbacks
+  }
+}
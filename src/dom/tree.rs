@@ -1,4 +1,4 @@
-use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
 
 #[derive(Debug)]
 pub struct Node<T>(Arc<RwLock<NodeInner<T>>>);
@@ -16,6 +16,22 @@ impl<T> PartialEq for Node<T> {
 }
 impl<T> Eq for Node<T> {}
 
+/// A non-owning reference to a [`Node`].
+#[derive(Debug)]
+pub struct WeakNode<T>(Weak<RwLock<NodeInner<T>>>);
+
+impl<T> Clone for WeakNode<T> {
+  fn clone(&self) -> Self {
+    WeakNode(Weak::clone(&self.0))
+  }
+}
+
+impl<T> WeakNode<T> {
+  pub fn upgrade(&self) -> Option<Node<T>> {
+    self.0.upgrade().map(Node)
+  }
+}
+
 #[derive(Debug)]
 pub struct NodeInner<T> {
   parent: Option<Node<T>>,
@@ -75,6 +91,10 @@ impl<T> Node<T> {
     })))
   }
 
+  pub fn downgrade(&self) -> WeakNode<T> {
+    WeakNode(Arc::downgrade(&self.0))
+  }
+
   pub fn inner(&self) -> RwLockReadGuard<'_, NodeInner<T>> {
     self.0.read().unwrap()
   }
@@ -84,42 +104,70 @@ impl<T> Node<T> {
   }
 
   pub fn append(&self, data: T) -> Node<T> {
-    let mut inner = self.0.write().unwrap();
-    match inner.last_child.as_ref() {
-      Some(last) => {
-        let node = Node(Arc::new(RwLock::new(NodeInner {
-          parent: Some(self.clone()),
-          previous_sibling: Some(last.clone()),
-          next_sibling: None,
-          first_child: None,
-          last_child: None,
-          data,
-        })));
-
-        last.0.write().unwrap().next_sibling = Some(node.clone());
-        inner.last_child = Some(node.clone());
-
-        node
-      }
+    let node = Node::new(data);
+    self.append_node(node.clone());
+    node
+  }
 
-      None => {
-        let node = Node(Arc::new(RwLock::new(NodeInner {
-          parent: Some(self.clone()),
-          previous_sibling: None,
-          next_sibling: None,
-          first_child: None,
-          last_child: None,
-          data,
-        })));
+  /// Appends a node without a parent as the last child, see [`Node::detach`].
+  pub fn append_node(&self, node: Node<T>) {
+    let mut inner = self.inner_mut();
+    {
+      let mut child = node.inner_mut();
+      debug_assert!(child.parent.is_none(), "appended node already has a parent");
+      child.parent = Some(self.clone());
+      child.previous_sibling = inner.last_child.clone();
+    }
+
+    match &inner.last_child {
+      Some(last) => last.inner_mut().next_sibling = Some(node.clone()),
+      None => inner.first_child = Some(node.clone()),
+    }
 
-        inner.first_child = Some(node.clone());
-        inner.last_child = Some(node.clone());
+    inner.last_child = Some(node);
+  }
+
+  /// Removes the node, along with its descendants, from its parent.
+  pub fn detach(&self) {
+    let (parent, previous, next) = {
+      let mut inner = self.inner_mut();
+      (
+        inner.parent.take(),
+        inner.previous_sibling.take(),
+        inner.next_sibling.take(),
+      )
+    };
+
+    if let Some(previous) = &previous {
+      previous.inner_mut().next_sibling = next.clone();
+    }
 
-        node
+    if let Some(next) = &next {
+      next.inner_mut().previous_sibling = previous.clone();
+    }
+
+    if let Some(parent) = parent {
+      let mut parent = parent.inner_mut();
+      if previous.is_none() {
+        parent.first_child = next.clone();
+      }
+
+      if next.is_none() {
+        parent.last_child = previous;
       }
     }
   }
 
+  /// Drops every reference this node holds to its relatives.
+  pub(crate) fn unlink(&self) {
+    let mut inner = self.inner_mut();
+    inner.parent = None;
+    inner.previous_sibling = None;
+    inner.next_sibling = None;
+    inner.first_child = None;
+    inner.last_child = None;
+  }
+
   pub fn children(&self) -> Children<T> {
     Children {
       current: self.inner().first_child().cloned(),
@@ -1,6 +1,8 @@
 #![allow(non_snake_case)]
 
-use std::os::raw::c_void;
+use std::{os::raw::c_void, time::Instant};
+
+use dom::c_api::{catch, catch_ptr, FrameUiStatus};
 
 use super::*;
 
@@ -33,6 +35,7 @@ impl Windowing for CWindowing {
 #[allow(non_snake_case)]
 impl EventHandler {
   #[no_mangle]
+  /// Takes ownership of `renderer` and `doc`, returns null if either is null.
   #[doc = "module=event,index=0"]
   pub unsafe extern "C" fn EventHandler_new(
     renderer: *mut render::Renderer,
@@ -42,16 +45,21 @@ impl EventHandler {
     make_not_current: EmptyCallback,
     user: *mut c_void,
   ) -> *mut Self {
-    let windowing = CWindowing {
-      user,
-      swap_buffers,
-      make_current,
-      make_not_current,
-    };
-
-    let event_handler = EventHandler::new(windowing, *Box::from_raw(renderer), Arc::from_raw(doc));
-
-    Box::into_raw(Box::new(event_handler))
+    catch_ptr(|| {
+      if renderer.is_null() || doc.is_null() {
+        return Err("`renderer` and `doc` must not be null".to_string());
+      }
+
+      let windowing = CWindowing {
+        user,
+        swap_buffers,
+        make_current,
+        make_not_current,
+      };
+
+      let event_handler = EventHandler::new(windowing, *Box::from_raw(renderer), Arc::from_raw(doc));
+      Ok(Box::into_raw(Box::new(event_handler)))
+    })
   }
 
   #[no_mangle]
@@ -60,43 +68,80 @@ impl EventHandler {
   /// This is the longer description
   #[doc = "module=event,index=1"]
   pub unsafe extern "C" fn EventHandler_drop(&mut self) {
-    let event_handler = Box::from_raw(self as *mut Self);
-    event_handler.deinit();
+    let _ = catch(|| {
+      let event_handler = Box::from_raw(self as *mut Self);
+      event_handler.deinit();
+      Ok(())
+    });
   }
 
   #[no_mangle]
   #[doc = "module=event,index=2"]
-  pub unsafe extern "C" fn EventHandler_handle_resize(&mut self, size: DeviceSize) {
-    self.handle_event(Event::Resized(size))
+  pub unsafe extern "C" fn EventHandler_handle_resize(&mut self, size: DeviceSize) -> FrameUiStatus {
+    catch(|| {
+      self.handle_event(Event::Resized(size));
+      Ok(())
+    })
+    .into()
   }
 
   #[no_mangle]
   #[doc = "module=event,index=3"]
-  pub unsafe extern "C" fn EventHandler_handle_scale_factor_change(&mut self, scale: f32) {
-    self.handle_event(Event::ScaleFactorChanged(scale))
+  pub unsafe extern "C" fn EventHandler_handle_scale_factor_change(&mut self, scale: f32) -> FrameUiStatus {
+    catch(|| {
+      self.handle_event(Event::ScaleFactorChanged(scale));
+      Ok(())
+    })
+    .into()
   }
 
   #[no_mangle]
   #[doc = "module=event,index=4"]
-  pub unsafe extern "C" fn EventHandler_handle_redraw(&mut self) {
-    self.handle_event(Event::Redraw)
+  pub unsafe extern "C" fn EventHandler_handle_redraw(&mut self) -> FrameUiStatus {
+    catch(|| {
+      self.handle_event(Event::Redraw);
+      Ok(())
+    })
+    .into()
   }
 
   #[no_mangle]
   #[doc = "module=event,index=5"]
-  pub unsafe extern "C" fn EventHandler_handle_empty(&mut self) {
-    self.handle_event(Event::Empty)
+  pub unsafe extern "C" fn EventHandler_handle_empty(&mut self) -> FrameUiStatus {
+    catch(|| {
+      self.handle_event(Event::Empty);
+      Ok(())
+    })
+    .into()
   }
 
   #[no_mangle]
   #[doc = "module=event,index=6"]
   pub unsafe extern "C" fn EventHandler_get_user(&mut self) -> *mut c_void {
-    self.windowing.user
+    catch_ptr(|| Ok(self.windowing.user))
   }
 
   #[no_mangle]
   #[doc = "module=event,index=7"]
   pub unsafe extern "C" fn EventHandler_set_user(&mut self, user: *mut c_void) {
-    self.windowing.user = user;
+    let _ = catch(|| {
+      self.windowing.user = user;
+      Ok(())
+    });
+  }
+
+  #[no_mangle]
+  /// Returns the number of milliseconds until the next script timer is due, or -1 if there is none or the call
+  /// failed.
+  ///
+  /// The host should call `EventHandler_handle_empty` once it elapsed.
+  #[doc = "module=event,index=8"]
+  pub unsafe extern "C" fn EventHandler_next_timer_ms(&self) -> i64 {
+    catch(|| {
+      Ok(self.next_timer().map_or(-1, |due| {
+        due.saturating_duration_since(Instant::now()).as_millis() as i64
+      }))
+    })
+    .unwrap_or(-1)
   }
 }
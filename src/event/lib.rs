@@ -6,7 +6,7 @@
 pub mod c_api;
 
 use dom::CompiledDocument;
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
 pub use render::DeviceSize;
 
@@ -29,6 +29,7 @@ pub struct EventHandler<W: Windowing> {
   pub windowing: W,
   pub doc: Arc<CompiledDocument>,
   render_inner: bool,
+  next_timer: Option<Instant>,
 }
 
 impl<W: Windowing> EventHandler<W> {
@@ -39,6 +40,7 @@ impl<W: Windowing> EventHandler<W> {
       renderer,
       doc,
       render_inner: true,
+      next_timer: None,
     }
   }
 
@@ -48,7 +50,19 @@ impl<W: Windowing> EventHandler<W> {
     self.windowing.make_not_current();
   }
 
+  /// Returns when the next script timer is due, the host should deliver an event by then.
+  #[must_use]
+  pub fn next_timer(&self) -> Option<Instant> {
+    self.next_timer
+  }
+
   pub fn handle_event(&mut self, event: Event) {
+    self.next_timer = self.doc.run_callbacks();
+
+    if self.doc.take_invalidated() {
+      self.render_inner = true;
+    }
+
     match event {
       Event::Resized(size) => {
         self.renderer.set_device_size(size);
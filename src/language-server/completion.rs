@@ -0,0 +1,157 @@
+//! Completion of element and attribute names.
+
+use lsp_types::{CompletionItem, CompletionItemKind};
+
+struct ElementSpec {
+  name: &'static str,
+  description: &'static str,
+  attributes: &'static [(&'static str, &'static str)],
+}
+
+const UI_ATTRIBUTES: &[(&str, &str)] = &[
+  ("class", "Classes, separated by whitespace"),
+  (":class", "Expression for the classes, a string or an array of strings"),
+  ("id", "Identifier of the element"),
+  (":id", "Expression for the identifier"),
+  ("style", "Declarations applied to the element"),
+  (":style", "Expression for the declarations"),
+];
+
+/// The elements the compiler knows, keep in sync with `compile_root` and the functions it calls.
+const ELEMENTS: &[ElementSpec] = &[
+  ElementSpec {
+    name: "Frame",
+    description: "Root of the document",
+    attributes: &[],
+  },
+  ElementSpec {
+    name: "Head",
+    description: "Styles, scripts and settings of the document",
+    attributes: &[],
+  },
+  ElementSpec {
+    name: "Body",
+    description: "Elements of the document",
+    attributes: &[],
+  },
+  ElementSpec {
+    name: "Title",
+    description: "Title of the window",
+    attributes: &[],
+  },
+  ElementSpec {
+    name: "Window",
+    description: "Size and decorations of the window",
+    attributes: &[
+      ("width", "Inner width in logical pixels"),
+      ("height", "Inner height in logical pixels"),
+      ("resizable", "`true` or `false`"),
+      (
+        "decorations",
+        "Whether the window has a title bar and borders, `true` or `false`",
+      ),
+    ],
+  },
+  ElementSpec {
+    name: "Meta",
+    description: "Setting of the document, e.g. its direction",
+    attributes: &[("name", "Name of the setting"), ("content", "Value of the setting")],
+  },
+  ElementSpec {
+    name: "Base",
+    description: "URL relative URLs are resolved against",
+    attributes: &[("href", "The base URL")],
+  },
+  ElementSpec {
+    name: "Style",
+    description: "Stylesheet, inline or linked",
+    attributes: &[("src", "URL of the stylesheet"), ("type", "`css`, `sass` or `scss`")],
+  },
+  ElementSpec {
+    name: "Script",
+    description: "Script run when the document is loaded",
+    attributes: &[("src", "URL of the script")],
+  },
+  ElementSpec {
+    name: "Asset",
+    description: "File embedded into the document",
+    attributes: &[("src", "URL of the file")],
+  },
+  ElementSpec {
+    name: "Include",
+    description: "Document compiled in place of the element",
+    attributes: &[("src", "URL of the document")],
+  },
+  ElementSpec {
+    name: "Define",
+    description: "Component, used like an element",
+    attributes: &[("name", "Name of the component")],
+  },
+  ElementSpec {
+    name: "If",
+    description: "Children compiled only if the condition holds",
+    attributes: &[("cfg", "Condition, e.g. `all(debug, not(platform='windows'))`")],
+  },
+  ElementSpec {
+    name: "Unstyled",
+    description: "Element without default styles",
+    attributes: UI_ATTRIBUTES,
+  },
+];
+
+/// Completions at `offset` of `text`: element names after `<` and attribute names inside a tag.
+pub fn complete(text: &str, offset: usize) -> Vec<CompletionItem> {
+  let before = &text[..offset];
+  let open = match before.rfind('<') {
+    Some(open) if before.rfind('>').map_or(true, |close| close < open) => open,
+    _ => return Vec::new(),
+  };
+
+  let tag = &before[open + 1..];
+  // Closing tags and attribute values.
+  if tag.starts_with('/') || tag.matches('"').count() % 2 == 1 {
+    return Vec::new();
+  }
+
+  match tag.find(char::is_whitespace) {
+    None => {
+      let mut items: Vec<_> = ELEMENTS
+        .iter()
+        .map(|x| item(x.name, x.description, CompletionItemKind::Class))
+        .collect();
+      items.extend(components(text).map(|x| item(x, "Component", CompletionItemKind::Class)));
+      items
+    }
+
+    Some(end) => {
+      let name = &tag[..end];
+      let attributes = match ELEMENTS.iter().find(|x| x.name == name) {
+        Some(element) => element.attributes,
+        // Components take any attribute, they're substituted into the template.
+        None => return Vec::new(),
+      };
+
+      attributes
+        .iter()
+        .filter(|(attribute, _)| !tag.contains(&format!(" {}=", attribute)))
+        .map(|(attribute, description)| item(attribute, description, CompletionItemKind::Property))
+        .collect()
+    }
+  }
+}
+
+fn item(label: &str, detail: &str, kind: CompletionItemKind) -> CompletionItem {
+  CompletionItem {
+    kind: Some(kind),
+    ..CompletionItem::new_simple(label.to_string(), detail.to_string())
+  }
+}
+
+/// Names of the components defined in `text`.
+fn components(text: &str) -> impl Iterator<Item = &str> {
+  text.match_indices("<Define").filter_map(move |(i, _)| {
+    let tag = &text[i..i + text[i..].find('>')?];
+    let value = &tag[tag.find("name=\"")? + 6..];
+    Some(&value[..value.find('"')?])
+  })
+}
@@ -0,0 +1,102 @@
+//! Go to definition from the classes and ids of elements to the stylesheet rules using them.
+
+use std::fs;
+
+use lsp_types::{Location, Url};
+
+use crate::position::LineIndex;
+
+/// The selector of the class or id at `offset` of `text`, e.g. `.card` in `class="card"`.
+fn selector_at(text: &str, offset: usize) -> Option<String> {
+  let before = &text[..offset];
+  let open = before.rfind('<')?;
+  if before.rfind('>').map_or(false, |close| close > open) {
+    return None;
+  }
+
+  // Inside a value there's an odd number of quotes before the cursor.
+  let tag = &before[open..];
+  if tag.matches('"').count() % 2 == 0 {
+    return None;
+  }
+  let quote = open + tag.rfind('"')?;
+  let attribute = text[..quote].trim_end_matches('=').rsplit(char::is_whitespace).next()?;
+  let end = quote + 1 + text[quote + 1..].find('"')?;
+
+  let is_name = |c: char| !c.is_whitespace();
+  let start = text[quote + 1..offset]
+    .rfind(|c: char| !is_name(c))
+    .map_or(quote + 1, |i| quote + 2 + i);
+  let stop = text[offset..end]
+    .find(|c: char| !is_name(c))
+    .map_or(end, |i| offset + i);
+  let name = &text[start..stop];
+
+  match attribute {
+    _ if name.is_empty() => None,
+    "class" => Some(format!(".{}", name)),
+    "id" => Some(format!("#{}", name)),
+    _ => None,
+  }
+}
+
+/// Offsets of the uses of `selector` in `css`, not counting longer names starting with it.
+fn find_selector(css: &str, selector: &str) -> Vec<usize> {
+  css
+    .match_indices(selector)
+    .filter(|(i, _)| {
+      css[i + selector.len()..]
+        .chars()
+        .next()
+        .map_or(true, |c| !(c.is_alphanumeric() || c == '-' || c == '_'))
+    })
+    .map(|(i, _)| i)
+    .collect()
+}
+
+/// Rules of the stylesheets of the document at `uri` using the class or id at `offset`. Inline styles are
+/// searched in `text`, linked ones are read from disk.
+pub fn definition(uri: &Url, text: &str, offset: usize) -> Vec<Location> {
+  let selector = match selector_at(text, offset) {
+    Some(selector) => selector,
+    None => return Vec::new(),
+  };
+
+  let mut locations = Vec::new();
+  let index = LineIndex::new(text);
+  for (i, _) in text.match_indices("<Style") {
+    let tag_end = match text[i..].find('>') {
+      Some(end) => i + end,
+      None => continue,
+    };
+    let tag = &text[i..tag_end];
+
+    if let Some(src) = tag.find("src=\"").map(|x| &tag[x + 5..]) {
+      let src = &src[..src.find('"').unwrap_or(src.len())];
+      let url = match uri.join(src) {
+        Ok(url) => url,
+        Err(..) => continue,
+      };
+      let css = match url.to_file_path().ok().and_then(|path| fs::read_to_string(path).ok()) {
+        Some(css) => css,
+        None => continue,
+      };
+
+      let css_index = LineIndex::new(&css);
+      for start in find_selector(&css, &selector) {
+        locations.push(Location::new(
+          url.clone(),
+          css_index.range(start..start + selector.len()),
+        ));
+      }
+    } else if !tag.ends_with('/') {
+      let content_end = text[tag_end..].find("</Style>").map_or(text.len(), |x| tag_end + x);
+      for start in find_selector(&text[tag_end..content_end], &selector) {
+        let start = tag_end + start;
+        locations.push(Location::new(uri.clone(), index.range(start..start + selector.len())));
+      }
+    }
+  }
+
+  locations
+}
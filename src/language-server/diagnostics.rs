@@ -0,0 +1,175 @@
+//! Checks documents with the compiler and turns its diagnostics into the ones of the protocol.
+
+use std::{collections::HashMap, ops::Range};
+
+use compiler::{CompileOptions, DiagnosticReporter, Fix, Level};
+use lsp_types::{
+  CodeAction, CodeActionKind, Diagnostic, DiagnosticSeverity, NumberOrString, TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::position::LineIndex;
+
+/// A diagnostic of the compiler, owned so it outlives the compilation.
+struct Reported {
+  file: Option<usize>,
+  range: Range<usize>,
+  level: Level,
+  code: &'static str,
+  message: String,
+  fixes: Vec<Fix>,
+}
+
+/// Collects the files and diagnostics of a compilation.
+#[derive(Default)]
+struct Collector {
+  files: Vec<(String, String)>,
+  diagnostics: Vec<Reported>,
+}
+
+impl DiagnosticReporter for Collector {
+  type FileId = usize;
+
+  fn add_file(&mut self, filename: String, source: String) -> usize {
+    self.files.push((filename, source));
+    self.files.len() - 1
+  }
+
+  fn add_diagnostic(&mut self, diagnostic: compiler::Diagnostic<usize>) {
+    let (file, range) = match diagnostic.location {
+      Some((file, range)) => (Some(file), range),
+      None => (None, 0..0),
+    };
+
+    self.diagnostics.push(Reported {
+      file,
+      range,
+      level: diagnostic.min_level,
+      code: diagnostic.kind.code(),
+      message: diagnostic.kind.to_string(),
+      fixes: diagnostic.fixes,
+    });
+  }
+
+  fn get_position(&mut self, file: &usize, line: usize, col: usize) -> usize {
+    LineIndex::new(&self.files[*file].1).line_start(line) + col - 1
+  }
+
+  fn get_line(&mut self, file: &usize, pos: usize) -> usize {
+    LineIndex::new(&self.files[*file].1).line(pos)
+  }
+
+  fn checkpoint(&mut self) -> Result<(), ()> {
+    let failed = self
+      .diagnostics
+      .iter()
+      .any(|x| x.level == Level::Error || x.level == Level::Bug);
+    if failed {
+      Err(())
+    } else {
+      Ok(())
+    }
+  }
+}
+
+/// Returns the URL of the file named `name` by the compiler, and whether it's a component defined in it. The
+/// compiler names components `url (component `name`)`, their source is a copy of the `<Define>` element.
+fn file_url(name: &str) -> Option<(Url, bool)> {
+  let (url, component) = match name.find(" (component `") {
+    Some(index) => (&name[..index], true),
+    None => (name, false),
+  };
+
+  Some((Url::parse(url).ok()?, component))
+}
+
+/// A diagnostic with the quick fixes offered for it.
+pub struct FileDiagnostic {
+  pub diagnostic: Diagnostic,
+  pub fixes: Vec<(String, TextEdit)>,
+}
+
+/// Checks the document at `uri` with the content `text`, returning the diagnostics of every file it uses by
+/// URL. The document is always in the result, so fixed diagnostics are cleared.
+pub fn check(uri: &Url, text: &str, options: &CompileOptions) -> HashMap<Url, Vec<FileDiagnostic>> {
+  let mut collector = Collector::default();
+  // The result only says whether there were errors, which are in the diagnostics.
+  let _ = compiler::check_from_str(text, uri.clone(), options, &mut collector);
+
+  let mut result = HashMap::new();
+  result.insert(uri.clone(), Vec::new());
+
+  for reported in collector.diagnostics {
+    // Positions in components don't match the file they're defined in, their diagnostics are shown at the
+    // start of that file. Diagnostics of no file at all are shown at the start of the document.
+    let file = reported.file.and_then(|file| {
+      let (name, source) = &collector.files[file];
+      Some((file_url(name)?, source))
+    });
+    let (file_uri, range, fixes) = match file {
+      Some(((file_uri, true), _)) => (file_uri, lsp_types::Range::default(), Vec::new()),
+      Some(((file_uri, false), source)) => {
+        let index = LineIndex::new(source);
+        let fixes = reported
+          .fixes
+          .iter()
+          .map(|fix| {
+            (
+              fix.message.clone(),
+              TextEdit::new(index.range(fix.range.clone()), fix.replacement.clone()),
+            )
+          })
+          .collect();
+        (file_uri, index.range(reported.range.clone()), fixes)
+      }
+      None => (uri.clone(), LineIndex::new(text).range(0..0), Vec::new()),
+    };
+
+    let severity = match reported.level {
+      Level::Bug | Level::Error => DiagnosticSeverity::Error,
+      Level::Warn => DiagnosticSeverity::Warning,
+      Level::Info => DiagnosticSeverity::Information,
+    };
+
+    let diagnostic = Diagnostic::new(
+      range,
+      Some(severity),
+      Some(NumberOrString::String(reported.code.to_string())),
+      Some("frame".to_string()),
+      reported.message,
+      None,
+      None,
+    );
+    result
+      .entry(file_uri)
+      .or_insert_with(Vec::new)
+      .push(FileDiagnostic { diagnostic, fixes });
+  }
+
+  result
+}
+
+/// Quick fixes of the diagnostics of `uri` overlapping `range`.
+pub fn code_actions(uri: &Url, diagnostics: &[FileDiagnostic], range: lsp_types::Range) -> Vec<CodeAction> {
+  diagnostics
+    .iter()
+    .filter(|x| x.diagnostic.range.start <= range.end && range.start <= x.diagnostic.range.end)
+    .flat_map(|x| {
+      x.fixes.iter().map(move |(title, edit)| {
+        let mut changes = HashMap::new();
+        changes.insert(uri.clone(), vec![edit.clone()]);
+
+        CodeAction {
+          title: title.clone(),
+          kind: Some(CodeActionKind::QUICKFIX),
+          diagnostics: Some(vec![x.diagnostic.clone()]),
+          edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..WorkspaceEdit::default()
+          }),
+          is_preferred: Some(true),
+          ..CodeAction::default()
+        }
+      })
+    })
+    .collect()
+}
@@ -0,0 +1,222 @@
+//! Language server for `.frame` documents, speaking the Language Server Protocol over stdio.
+//!
+//! Documents are checked with the compiler whenever they change, remote resources coming from the cache after
+//! the first check. Completion and go to definition work on the text of the document alone.
+
+mod completion;
+mod definition;
+mod diagnostics;
+mod position;
+
+use std::{
+  collections::{HashMap, HashSet},
+  error::Error,
+};
+
+use compiler::CompileOptions;
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+  notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _, PublishDiagnostics,
+  },
+  request::{CodeActionRequest, Completion, GotoDefinition, Request as _},
+  CodeActionOrCommand, CodeActionParams, CodeActionProviderCapability, CompletionOptions, CompletionParams,
+  CompletionResponse, DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+  GotoDefinitionParams, GotoDefinitionResponse, PublishDiagnosticsParams, ServerCapabilities,
+  TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+
+use diagnostics::FileDiagnostic;
+use position::LineIndex;
+
+struct Server {
+  connection: Connection,
+  options: CompileOptions,
+  /// Text of the open documents.
+  documents: HashMap<Url, String>,
+  /// Diagnostics of the last check by file, for quick fixes.
+  diagnostics: HashMap<Url, Vec<FileDiagnostic>>,
+  /// Files diagnostics were published for by the last check of each open document, so the ones that don't
+  /// have any anymore are cleared.
+  published: HashMap<Url, HashSet<Url>>,
+}
+
+impl Server {
+  fn send(&self, message: impl Into<Message>) {
+    // Fails only if the client went away, which the main loop notices.
+    let _ = self.connection.sender.send(message.into());
+  }
+
+  fn check(&mut self, uri: &Url) {
+    let text = match self.documents.get(uri) {
+      Some(text) => text,
+      None => return,
+    };
+
+    let result = diagnostics::check(uri, text, &self.options);
+    let files: HashSet<_> = result.keys().cloned().collect();
+    let stale: Vec<_> = self
+      .published
+      .insert(uri.clone(), files)
+      .unwrap_or_default()
+      .into_iter()
+      .filter(|file| !result.contains_key(file))
+      .collect();
+
+    for (file, diagnostics) in result {
+      self.publish(file.clone(), diagnostics.iter().map(|x| x.diagnostic.clone()).collect());
+      self.diagnostics.insert(file, diagnostics);
+    }
+    for file in stale {
+      self.diagnostics.remove(&file);
+      self.publish(file, Vec::new());
+    }
+  }
+
+  fn publish(&self, file: Url, diagnostics: Vec<lsp_types::Diagnostic>) {
+    let params = PublishDiagnosticsParams::new(file, diagnostics, None);
+    self.send(Notification::new(PublishDiagnostics::METHOD.to_string(), params));
+  }
+
+  fn handle_notification(&mut self, notification: Notification) {
+    let notification = match notification.extract::<DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD) {
+      Ok(params) => {
+        let uri = params.text_document.uri;
+        self.documents.insert(uri.clone(), params.text_document.text);
+        self.check(&uri);
+        return;
+      }
+      Err(notification) => notification,
+    };
+
+    let notification = match notification.extract::<DidChangeTextDocumentParams>(DidChangeTextDocument::METHOD) {
+      Ok(params) => {
+        // The server asks for full syncs, so the last change has the whole text.
+        if let Some(change) = params.content_changes.into_iter().last() {
+          let uri = params.text_document.uri;
+          self.documents.insert(uri.clone(), change.text);
+          self.check(&uri);
+        }
+        return;
+      }
+      Err(notification) => notification,
+    };
+
+    if let Ok(params) = notification.extract::<DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD) {
+      let uri = params.text_document.uri;
+      self.documents.remove(&uri);
+      let mut files = self.published.remove(&uri).unwrap_or_default();
+      files.insert(uri);
+      for file in files {
+        self.diagnostics.remove(&file);
+        self.publish(file, Vec::new());
+      }
+    }
+  }
+
+  /// Returns the result of `request`, `None` for requests the server doesn't handle.
+  fn handle_request(&self, request: Request) -> Option<(RequestId, serde_json::Value)> {
+    let request = match request.extract::<CompletionParams>(Completion::METHOD) {
+      Ok((id, params)) => {
+        let position = params.text_document_position;
+        let items = self
+          .documents
+          .get(&position.text_document.uri)
+          .map_or_else(Vec::new, |text| {
+            completion::complete(text, LineIndex::new(text).offset(position.position))
+          });
+        return Some((id, serde_json::to_value(CompletionResponse::Array(items)).unwrap()));
+      }
+      Err(request) => request,
+    };
+
+    let request = match request.extract::<GotoDefinitionParams>(GotoDefinition::METHOD) {
+      Ok((id, params)) => {
+        let position = params.text_document_position_params;
+        let uri = position.text_document.uri;
+        let locations = self.documents.get(&uri).map_or_else(Vec::new, |text| {
+          definition::definition(&uri, text, LineIndex::new(text).offset(position.position))
+        });
+        return Some((
+          id,
+          serde_json::to_value(GotoDefinitionResponse::Array(locations)).unwrap(),
+        ));
+      }
+      Err(request) => request,
+    };
+
+    match request.extract::<CodeActionParams>(CodeActionRequest::METHOD) {
+      Ok((id, params)) => {
+        let uri = params.text_document.uri;
+        let actions: Vec<_> = self
+          .diagnostics
+          .get(&uri)
+          .map_or_else(Vec::new, |x| diagnostics::code_actions(&uri, x, params.range))
+          .into_iter()
+          .map(CodeActionOrCommand::CodeAction)
+          .collect();
+        Some((id, serde_json::to_value(actions).unwrap()))
+      }
+      Err(..) => None,
+    }
+  }
+
+  fn run(mut self) -> Result<(), Box<dyn Error + Sync + Send>> {
+    while let Ok(message) = self.connection.receiver.recv() {
+      match message {
+        Message::Request(request) => {
+          if self.connection.handle_shutdown(&request)? {
+            break;
+          }
+
+          let id = request.id.clone();
+          let response = match self.handle_request(request) {
+            Some((id, result)) => Response::new_ok(id, result),
+            None => Response::new_err(
+              id,
+              lsp_server::ErrorCode::MethodNotFound as i32,
+              "unsupported request".to_string(),
+            ),
+          };
+          self.send(response);
+        }
+
+        Message::Notification(notification) => self.handle_notification(notification),
+        Message::Response(..) => {}
+      }
+    }
+
+    Ok(())
+  }
+}
+
+fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
+  let (connection, io_threads) = Connection::stdio();
+
+  let capabilities = ServerCapabilities {
+    text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::Full)),
+    completion_provider: Some(CompletionOptions {
+      trigger_characters: Some(vec!["<".to_string(), " ".to_string()]),
+      ..CompletionOptions::default()
+    }),
+    definition_provider: Some(true),
+    code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+    ..ServerCapabilities::default()
+  };
+  connection.initialize(serde_json::to_value(capabilities)?)?;
+
+  // Checks run on every change, so remote resources are cached like the command line compiler does.
+  compiler::cache::set_cache_dir(Some(std::env::temp_dir().join("frameui-compiler-cache")));
+
+  let server = Server {
+    connection,
+    options: CompileOptions::new(),
+    documents: HashMap::new(),
+    diagnostics: HashMap::new(),
+    published: HashMap::new(),
+  };
+  server.run()?;
+
+  io_threads.join()?;
+  Ok(())
+}
@@ -0,0 +1,61 @@
+//! Conversions between byte offsets, which the compiler reports, and the line and UTF-16 column positions of
+//! the protocol.
+
+use lsp_types::{Position, Range};
+
+/// Start offsets of the lines of a source.
+pub struct LineIndex<'a> {
+  source: &'a str,
+  starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+  pub fn new(source: &'a str) -> Self {
+    let starts = std::iter::once(0)
+      .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+      .collect();
+    Self { source, starts }
+  }
+
+  /// Index of the line `offset` is on, starting at 0.
+  pub fn line(&self, offset: usize) -> usize {
+    match self.starts.binary_search(&offset) {
+      Ok(line) => line,
+      Err(line) => line - 1,
+    }
+  }
+
+  /// Offset of the start of `line`, or of the end of the source past the last line.
+  pub fn line_start(&self, line: usize) -> usize {
+    self.starts.get(line).copied().unwrap_or(self.source.len())
+  }
+
+  pub fn position(&self, offset: usize) -> Position {
+    let offset = offset.min(self.source.len());
+    let line = self.line(offset);
+    let start = self.line_start(line);
+    let column = self.source.get(start..offset).map_or(0, |x| x.encode_utf16().count());
+    Position::new(line as u64, column as u64)
+  }
+
+  pub fn range(&self, range: std::ops::Range<usize>) -> Range {
+    Range::new(self.position(range.start), self.position(range.end))
+  }
+
+  /// Offset of `position`, clamped to its line.
+  pub fn offset(&self, position: Position) -> usize {
+    let start = self.line_start(position.line as usize);
+    let end = self.line_start(position.line as usize + 1);
+    let line = &self.source[start..end];
+
+    let mut column = 0;
+    for (i, c) in line.char_indices() {
+      if column >= position.character as usize || c == '\n' {
+        return start + i;
+      }
+      column += c.len_utf16();
+    }
+
+    end
+  }
+}
@@ -25,9 +25,7 @@ fn main() {
 
   // let notifier = Box::new(window::Notifier::new());
   let window = window::Window::new::<()>(
-    glutin::window::WindowBuilder::new()
-      .with_title("Foo")
-      .with_inner_size(glutin::dpi::LogicalSize::new(1920, 1080)),
+    window::window_builder(&doc.metadata),
     &event_loop,
     event_loop.create_proxy(),
     Arc::clone(&doc),
@@ -61,7 +59,13 @@ fn main() {
     }
 
     match &mut window {
-      Some(window) => window.handle_event(&event),
+      Some(window) => {
+        window.handle_event(&event);
+
+        if let Some(deadline) = window.next_timer() {
+          *control_flow = glutin::event_loop::ControlFlow::WaitUntil(deadline);
+        }
+      }
       None => {}
     }
   });
@@ -2,9 +2,11 @@
 
 use std::{
   ffi::CString,
+  mem::ManuallyDrop,
   os::raw::{c_char, c_void},
 };
 
+use dom::c_api::{catch, catch_ptr, FrameUiStatus};
 use gleam::gl;
 
 use super::*;
@@ -32,23 +34,27 @@ impl Gl {
   #[no_mangle]
   #[doc = "module=render,index=0"]
   pub unsafe extern "C" fn Gl_load_gl(func: GlLoadFunc) -> *mut Gl {
-    let gl = gl::GlFns::load_with(|symbol| {
-      let string = CString::new(symbol).unwrap();
-      func(string.as_ptr())
-    });
-
-    Box::into_raw(Box::new(gl)) as *mut _
+    catch_ptr(|| {
+      let gl = gl::GlFns::load_with(|symbol| {
+        let string = CString::new(symbol).unwrap();
+        func(string.as_ptr())
+      });
+
+      Ok(Box::into_raw(Box::new(gl)) as *mut _)
+    })
   }
 
   #[no_mangle]
   #[doc = "module=render,index=1"]
   pub unsafe extern "C" fn Gl_load_gles(func: GlLoadFunc) -> *mut Gl {
-    let gl = gl::GlesFns::load_with(|symbol| {
-      let string = CString::new(symbol).unwrap();
-      func(string.as_ptr())
-    });
-
-    Box::into_raw(Box::new(gl)) as *mut _
+    catch_ptr(|| {
+      let gl = gl::GlesFns::load_with(|symbol| {
+        let string = CString::new(symbol).unwrap();
+        func(string.as_ptr())
+      });
+
+      Ok(Box::into_raw(Box::new(gl)) as *mut _)
+    })
   }
 }
 
@@ -78,39 +84,63 @@ impl RenderNotifier for Notifier {
 #[allow(non_snake_case)]
 impl Renderer {
   #[no_mangle]
+  /// Takes ownership of `gl`, returns null if the renderer couldn't be created.
   #[doc = "module=render,index=0"]
   pub unsafe extern "C" fn Renderer_new(gl: *mut Gl, device_pixel_ratio: f32, device_size: DeviceSize) -> *mut Self {
-    let gl = *Box::from_raw(gl as *mut _);
-
-    let renderer = Renderer::new(gl, device_pixel_ratio, device_size.into(), Box::new(Notifier));
-
-    Box::into_raw(Box::new(renderer))
+    catch_ptr(|| {
+      if gl.is_null() {
+        return Err("`gl` is null".to_string());
+      }
+
+      let gl = *Box::from_raw(gl as *mut _);
+      let renderer = Renderer::new(gl, device_pixel_ratio, device_size.into(), Box::new(Notifier));
+      Ok(Box::into_raw(Box::new(renderer)))
+    })
   }
 
   #[no_mangle]
   #[doc = "module=render,index=1"]
   pub unsafe extern "C" fn Renderer_drop(&mut self) {
-    let renderer = Box::from_raw(self as *mut Self);
-    renderer.deinit();
+    let _ = catch(|| {
+      let renderer = Box::from_raw(self as *mut Self);
+      renderer.deinit();
+      Ok(())
+    });
   }
 
   #[no_mangle]
   #[doc = "module=render,index=2"]
-  pub unsafe extern "C" fn Renderer_set_device_size(&mut self, size: DeviceSize) {
-    self.set_device_size(size.into());
+  pub unsafe extern "C" fn Renderer_set_device_size(&mut self, size: DeviceSize) -> FrameUiStatus {
+    catch(|| {
+      self.set_device_size(size.into());
+      Ok(())
+    })
+    .into()
   }
 
   #[no_mangle]
   #[doc = "module=render,index=3"]
-  pub unsafe extern "C" fn Renderer_set_scale_factor(&mut self, scale: f32) {
-    self.set_scale_factor(scale);
+  pub unsafe extern "C" fn Renderer_set_scale_factor(&mut self, scale: f32) -> FrameUiStatus {
+    catch(|| {
+      self.set_scale_factor(scale);
+      Ok(())
+    })
+    .into()
   }
 
   #[no_mangle]
   #[doc = "module=render,index=4"]
-  pub unsafe extern "C" fn Renderer_render(&mut self, inner: bool, doc: *const dom::CompiledDocument) {
-    let doc = Arc::from_raw(doc);
-    self.render(inner, &doc);
-    Arc::into_raw(doc);
+  pub unsafe extern "C" fn Renderer_render(&mut self, inner: bool, doc: *const dom::CompiledDocument) -> FrameUiStatus {
+    catch(|| {
+      if doc.is_null() {
+        return Err("`doc` is null".to_string());
+      }
+
+      // The caller keeps its reference, even if rendering panics.
+      let doc = ManuallyDrop::new(Arc::from_raw(doc));
+      self.render(inner, &doc);
+      Ok(())
+    })
+    .into()
   }
 }
@@ -4,18 +4,238 @@
 
 use euclid::Size2D;
 use gleam::gl::Gl;
-use std::rc::Rc;
+use std::{collections::HashMap, rc::Rc};
 use webrender::{
   api::{units::*, *},
   DebugFlags, ShaderPrecacheFlags,
 };
 
-use dom::CompiledDocument;
+use dom::{
+  content_size,
+  tree::{Node, NodeEdge},
+  CompiledDocument, Element,
+};
 use std::sync::Arc;
 
 #[cfg(feature = "c-render")]
 pub mod c_api;
 
+/// Position of `node` in the document, yoga lays elements out relative to their parent.
+fn document_origin(node: &Node<Element>) -> LayoutPoint {
+  let mut origin = LayoutPoint::zero();
+  let mut current = Some(node.clone());
+  while let Some(node) = current {
+    let inner = node.inner();
+    origin.x += inner.yg.get_left();
+    origin.y += inner.yg.get_top();
+    current = inner.parent().cloned();
+  }
+
+  origin
+}
+
+fn push_background(
+  builder: &mut DisplayListBuilder,
+  background: &style::Background,
+  rect: LayoutRect,
+  space_and_clip: SpaceAndClipInfo,
+) {
+  let common = CommonItemProperties::new(rect, space_and_clip);
+  // Gradients are drawn once over the whole element, their points are relative to its origin.
+  let (width, height) = (rect.size.width, rect.size.height);
+  let center = LayoutPoint::new(width / 2.0, height / 2.0);
+
+  match background {
+    style::Background::Color(r, g, b, a) => builder.push_rect(&common, rect, to_color((*r, *g, *b, *a))),
+
+    style::Background::LinearGradient { direction, stops } => {
+      // Unit vector of the gradient line, y going down.
+      let direction = match *direction {
+        style::GradientDirection::Angle(angle) => {
+          let angle = angle.to_radians();
+          LayoutVector2D::new(angle.sin(), -angle.cos())
+        }
+        style::GradientDirection::Corner { top, right } => {
+          let x = if right { height } else { -height };
+          let y = if top { -width } else { width };
+          LayoutVector2D::new(x, y).normalize()
+        }
+      };
+
+      // The line is long enough for the corners to get the colors of its ends.
+      let length = (width * direction.x).abs() + (height * direction.y).abs();
+      let gradient = builder.create_gradient(
+        center - direction * (length / 2.0),
+        center + direction * (length / 2.0),
+        gradient_stops(stops),
+        ExtendMode::Clamp,
+      );
+      builder.push_gradient(&common, rect, gradient, rect.size, LayoutSize::zero());
+    }
+
+    style::Background::RadialGradient { shape, stops } => {
+      let radius = match shape {
+        style::GradientShape::Circle => {
+          let radius = center.to_vector().length();
+          LayoutSize::new(radius, radius)
+        }
+        // Keeps the aspect ratio of the element.
+        style::GradientShape::Ellipse => LayoutSize::new(center.x, center.y) * std::f32::consts::SQRT_2,
+      };
+
+      let gradient = builder.create_radial_gradient(center, radius, gradient_stops(stops), ExtendMode::Clamp);
+      builder.push_radial_gradient(&common, rect, gradient, rect.size, LayoutSize::zero());
+    }
+  }
+}
+
+/// Gives every stop a position, which can't be before the one of the previous stop.
+fn gradient_stops(stops: &[style::ColorStop]) -> Vec<GradientStop> {
+  let mut offsets: Vec<_> = stops.iter().map(|x| x.position).collect();
+  if let Some(first) = offsets.first_mut() {
+    first.get_or_insert(0.0);
+  }
+  if let Some(last) = offsets.last_mut() {
+    last.get_or_insert(1.0);
+  }
+
+  let mut previous = f32::MIN;
+  for offset in offsets.iter_mut().flatten() {
+    previous = offset.max(previous);
+    *offset = previous;
+  }
+
+  // Stops without a position are spread evenly between the ones around them.
+  let positioned: Vec<_> = offsets
+    .iter()
+    .enumerate()
+    .filter_map(|(i, offset)| offset.map(|offset| (i, offset)))
+    .collect();
+  for pair in positioned.windows(2) {
+    let ((start, from), (end, to)) = (pair[0], pair[1]);
+    for (i, offset) in offsets.iter_mut().enumerate().take(end).skip(start + 1) {
+      *offset = Some(from + (to - from) * (i - start) as f32 / (end - start) as f32);
+    }
+  }
+
+  stops
+    .iter()
+    .zip(offsets)
+    .map(|(stop, offset)| GradientStop {
+      offset: offset.unwrap_or(0.0),
+      color: to_color(stop.color),
+    })
+    .collect()
+}
+
+/// Decodes the image at `url`, embedded in `doc` or, for `file` URLs, on disk.
+fn decode_image(doc: &CompiledDocument, url: &str) -> Option<image::RgbaImage> {
+  let read;
+  let data = match doc.resource(url) {
+    Some(data) => data,
+    None => {
+      let path = match url::Url::parse(url) {
+        Ok(url) if url.scheme() == "file" => url.to_file_path().ok()?,
+        _ => {
+          log::warn!(
+            "image {} isn't embedded in the document, compile it with embedded images",
+            url
+          );
+          return None;
+        }
+      };
+
+      read = std::fs::read(path)
+        .map_err(|e| log::warn!("can't read image {}: {}", url, e))
+        .ok()?;
+      &read
+    }
+  };
+
+  match image::load_from_memory(data) {
+    Ok(image) => Some(image.into_rgba()),
+    Err(e) => {
+      log::warn!("can't decode image {}: {}", url, e);
+      None
+    }
+  }
+}
+
+/// Size of the tiles of a background image of size `image` drawn over `area`.
+fn background_tile(size: style::BackgroundSize, image: LayoutSize, area: LayoutSize) -> LayoutSize {
+  match size {
+    style::BackgroundSize::Contain => image * (area.width / image.width).min(area.height / image.height),
+    style::BackgroundSize::Cover => image * (area.width / image.width).max(area.height / image.height),
+    style::BackgroundSize::Size(width, height) => {
+      let resolve = |value, reference: f32| match value {
+        yoga::Value::Px(value) => Some(value),
+        yoga::Value::Percent(percent) => Some(reference * percent / 100.0),
+        yoga::Value::Auto | yoga::Value::Undefined => None,
+      };
+
+      match (resolve(width, area.width), resolve(height, area.height)) {
+        (Some(width), Some(height)) => LayoutSize::new(width, height),
+        (Some(width), None) => LayoutSize::new(width, image.height * width / image.width),
+        (None, Some(height)) => LayoutSize::new(image.width * height / image.height, height),
+        (None, None) => image,
+      }
+    }
+  }
+}
+
+fn push_box_shadow(
+  builder: &mut DisplayListBuilder,
+  shadow: &style::BoxShadow,
+  rect: LayoutRect,
+  radius: f32,
+  space_and_clip: SpaceAndClipInfo,
+) {
+  let offset = LayoutVector2D::new(shadow.offset_x, shadow.offset_y);
+  let (clip_rect, clip_mode) = if shadow.inset {
+    (rect, BoxShadowClipMode::Inset)
+  } else {
+    // The blur and the spread extend the shadow past the box it's cast by.
+    let extent = shadow.spread.max(0.0) + shadow.blur;
+    let shadow_rect = LayoutRect::new(rect.origin + offset, rect.size).inflate(extent, extent);
+    (shadow_rect, BoxShadowClipMode::Outset)
+  };
+
+  builder.push_box_shadow(
+    &CommonItemProperties::new(clip_rect, space_and_clip),
+    rect,
+    offset,
+    to_color(shadow.color),
+    shadow.blur,
+    shadow.spread,
+    BorderRadius::uniform(radius),
+    clip_mode,
+  );
+}
+
+fn to_color((r, g, b, a): (u8, u8, u8, u8)) -> ColorF {
+  ColorF::new(
+    f32::from(r) / 255.0,
+    f32::from(g) / 255.0,
+    f32::from(b) / 255.0,
+    f32::from(a) / 255.0,
+  )
+}
+
+fn to_border_style(value: style::BorderStyle) -> BorderStyle {
+  match value {
+    style::BorderStyle::None => BorderStyle::None,
+    style::BorderStyle::Hidden => BorderStyle::Hidden,
+    style::BorderStyle::Solid => BorderStyle::Solid,
+    style::BorderStyle::Dashed => BorderStyle::Dashed,
+    style::BorderStyle::Dotted => BorderStyle::Dotted,
+    style::BorderStyle::Double => BorderStyle::Double,
+    style::BorderStyle::Groove => BorderStyle::Groove,
+    style::BorderStyle::Ridge => BorderStyle::Ridge,
+    style::BorderStyle::Inset => BorderStyle::Inset,
+    style::BorderStyle::Outset => BorderStyle::Outset,
+  }
+}
+
 // pub trait HandyDandyRectBuilder {
 //   fn to(&self, x2: i32, y2: i32) -> LayoutRect;
 //   fn by(&self, w: i32, h: i32) -> LayoutRect;
@@ -89,7 +309,17 @@ pub struct Renderer {
   pipeline_id: PipelineId,
   document_id: DocumentId,
   layout_size: Size2D<f32, LayoutPixel>,
+  viewport_changed: bool,
   epoch: Epoch,
+  /// Images uploaded to webrender by URL, `None` for the ones that couldn't be loaded so they aren't tried
+  /// again every frame.
+  images: HashMap<String, Option<Image>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Image {
+  key: ImageKey,
+  size: LayoutSize,
 }
 
 impl Renderer {
@@ -145,7 +375,9 @@ impl Renderer {
       pipeline_id,
       document_id,
       layout_size,
+      viewport_changed: true,
       epoch,
+      images: HashMap::new(),
     }
   }
 
@@ -156,6 +388,7 @@ impl Renderer {
   pub fn set_device_size(&mut self, size: DeviceSize) {
     self.device_size = DeviceIntSize::new(size.width, size.height);
     self.layout_size = self.device_size.to_f32() / euclid::Scale::new(self.device_pixel_ratio);
+    self.viewport_changed = true;
 
     let mut txn = Transaction::new();
     txn.set_document_view(self.device_size.into(), self.device_pixel_ratio);
@@ -165,6 +398,7 @@ impl Renderer {
   pub fn set_scale_factor(&mut self, scale: f32) {
     self.device_pixel_ratio = scale;
     self.layout_size = self.device_size.to_f32() / euclid::Scale::new(self.device_pixel_ratio);
+    self.viewport_changed = true;
 
     let mut txn = Transaction::new();
     txn.set_document_view(self.device_size.into(), self.device_pixel_ratio);
@@ -179,15 +413,18 @@ impl Renderer {
 
       let mut builder = DisplayListBuilder::new(self.pipeline_id, self.layout_size);
 
-      self.render_inner(&mut builder, &mut txn, doc);
-      txn.set_display_list(
-        self.epoch,
-        Some(ColorF::new(0.3, 0.0, 0.0, 1.0)),
-        self.layout_size,
-        builder.finalize(),
-        true,
-      );
-      txn.generate_frame();
+      // The previous display list stays valid if neither the viewport nor any element changed.
+      if self.render_inner(&mut builder, &mut txn, doc) {
+        self.viewport_changed = false;
+        txn.set_display_list(
+          self.epoch,
+          Some(ColorF::new(0.3, 0.0, 0.0, 1.0)),
+          self.layout_size,
+          builder.finalize(),
+          true,
+        );
+        txn.generate_frame();
+      }
     }
 
     self.api.send_transaction(self.document_id, txn);
@@ -197,30 +434,234 @@ impl Renderer {
     let _ = self.renderer.flush_pipeline_info();
   }
 
-  fn render_inner(&mut self, builder: &mut DisplayListBuilder, txn: &mut Transaction, doc: &Arc<CompiledDocument>) {
+  /// Returns the image at `url`, uploading it in `txn` the first time it's drawn.
+  fn image(&mut self, txn: &mut Transaction, doc: &CompiledDocument, url: &str) -> Option<Image> {
+    if let Some(image) = self.images.get(url) {
+      return *image;
+    }
+
+    let image = decode_image(doc, url).map(|decoded| {
+      let (width, height) = decoded.dimensions();
+      let mut pixels = decoded.into_raw();
+
+      // Webrender takes BGRA pixels with premultiplied alpha.
+      let mut opaque = true;
+      for pixel in pixels.chunks_exact_mut(4) {
+        let alpha = u32::from(pixel[3]);
+        opaque &= alpha == 255;
+        let premultiply = |value: u8| ((u32::from(value) * alpha + 127) / 255) as u8;
+        let (r, g, b) = (premultiply(pixel[0]), premultiply(pixel[1]), premultiply(pixel[2]));
+        pixel[0] = b;
+        pixel[1] = g;
+        pixel[2] = r;
+      }
+
+      let flags = if opaque {
+        ImageDescriptorFlags::IS_OPAQUE
+      } else {
+        ImageDescriptorFlags::empty()
+      };
+      let key = self.api.generate_image_key();
+      txn.add_image(
+        key,
+        ImageDescriptor::new(width as i32, height as i32, ImageFormat::BGRA8, flags),
+        ImageData::new(pixels),
+        None,
+      );
+
+      Image {
+        key,
+        size: LayoutSize::new(width as f32, height as f32),
+      }
+    });
+
+    self.images.insert(url.to_string(), image);
+    image
+  }
+
+  /// Builds the display list of `doc`, returns `false` without building anything if neither the document nor
+  /// the viewport changed.
+  fn render_inner(
+    &mut self,
+    builder: &mut DisplayListBuilder,
+    txn: &mut Transaction,
+    doc: &Arc<CompiledDocument>,
+  ) -> bool {
     let content_bounds = LayoutRect::new(LayoutPoint::zero(), builder.content_size());
     let root_space_and_clip = SpaceAndClipInfo::root_scroll(self.pipeline_id);
-    let spatial_id = root_space_and_clip.spatial_id;
 
-    doc.compute_style(self.layout_size.width, self.layout_size.height, yoga::Direction::LTR);
-    for node in doc.root.descendants() {
-      let computed = node.inner().get_render();
+    doc.set_point_scale_factor(self.device_pixel_ratio);
+    let changed = doc.compute_style(self.layout_size.width, self.layout_size.height, doc.direction.into());
+    if !changed && !self.viewport_changed {
+      return false;
+    }
 
-      let rect = LayoutRect::new(
-        LayoutPoint::new(computed.left, computed.top),
-        LayoutSize::new(computed.width, computed.height),
-      );
+    // Start of the subtree being skipped because it isn't displayed.
+    let mut hidden = None;
+    // Elements whose subtree is drawn in a stacking context, to be faded as a whole.
+    let mut groups = Vec::new();
+    // Elements clipping their content, with the clip and scroll frame their descendants are drawn in.
+    let mut clips: Vec<(Node<Element>, SpaceAndClipInfo)> = Vec::new();
+    // The root scroll frame uses the id 0.
+    let mut next_scroll_id = 1;
+    for edge in doc.root.traverse() {
+      let node = match edge {
+        NodeEdge::Start(node) => node,
+        NodeEdge::End(node) => {
+          if hidden.as_ref() == Some(&node) {
+            hidden = None;
+            continue;
+          }
+
+          if clips.last().map(|(clipping, _)| clipping) == Some(&node) {
+            clips.pop();
+          }
+          if groups.last() == Some(&node) {
+            groups.pop();
+            builder.pop_stacking_context();
+          }
+          continue;
+        }
+      };
+
+      if hidden.is_some() {
+        continue;
+      }
 
-      builder.push_rect(
-        &CommonItemProperties::new(rect, root_space_and_clip),
-        rect,
-        ColorF::new(
-          computed.background_color.0 as f32 / 255.0,
-          computed.background_color.1 as f32 / 255.0,
-          computed.background_color.2 as f32 / 255.0,
-          computed.background_color.3 as f32 / 255.0,
-        ),
-      );
+      let computed = node.inner().get_render();
+      if computed.display == style::Display::None {
+        hidden = Some(node);
+        continue;
+      }
+
+      let parent = clips.last().map_or(root_space_and_clip, |(_, clip)| *clip);
+
+      if computed.opacity < 1.0 {
+        builder.push_simple_stacking_context_with_filters(
+          LayoutPoint::zero(),
+          parent.spatial_id,
+          PrimitiveFlags::IS_BACKFACE_VISIBLE,
+          &[FilterOp::Opacity(
+            PropertyBinding::Value(computed.opacity),
+            computed.opacity,
+          )],
+          &[],
+          &[],
+        );
+        groups.push(node.clone());
+      }
+
+      let rect = LayoutRect::new(document_origin(&node), LayoutSize::new(computed.width, computed.height));
+
+      // Radii larger than half of a side would make the corners overlap.
+      let radius = computed
+        .border_radius
+        .min(computed.width / 2.0)
+        .min(computed.height / 2.0);
+      let space_and_clip = if radius > 0.0 {
+        let clip_id = builder.define_clip_rounded_rect(
+          &parent,
+          ComplexClipRegion::new(rect, BorderRadius::uniform(radius), ClipMode::Clip),
+        );
+        SpaceAndClipInfo {
+          spatial_id: parent.spatial_id,
+          clip_id,
+        }
+      } else {
+        parent
+      };
+
+      // Outer shadows are drawn below the background, inner ones above it.
+      let (outer_shadow, inner_shadow) = match computed.box_shadow {
+        Some(shadow) if shadow.inset => (None, Some(shadow)),
+        shadow => (shadow, None),
+      };
+
+      if let Some(shadow) = outer_shadow {
+        push_box_shadow(builder, &shadow, rect, radius, parent);
+      }
+
+      push_background(builder, &computed.background, rect, space_and_clip);
+
+      if let Some(url) = &computed.background_image {
+        if let Some(image) = self.image(txn, doc, url) {
+          let tile = background_tile(computed.background_size, image.size, rect.size);
+          let bounds = match computed.background_repeat {
+            style::BackgroundRepeat::Repeat => rect,
+            style::BackgroundRepeat::RepeatX => {
+              LayoutRect::new(rect.origin, LayoutSize::new(rect.size.width, tile.height))
+            }
+            style::BackgroundRepeat::RepeatY => {
+              LayoutRect::new(rect.origin, LayoutSize::new(tile.width, rect.size.height))
+            }
+            style::BackgroundRepeat::NoRepeat => LayoutRect::new(rect.origin, tile),
+          };
+
+          builder.push_repeating_image(
+            &CommonItemProperties::new(rect, space_and_clip),
+            bounds,
+            tile,
+            LayoutSize::zero(),
+            ImageRendering::Auto,
+            AlphaType::PremultipliedAlpha,
+            image.key,
+            ColorF::WHITE,
+          );
+        }
+      }
+
+      if let Some(shadow) = inner_shadow {
+        push_box_shadow(builder, &shadow, rect, radius, space_and_clip);
+      }
+
+      if computed.border_width > 0.0 {
+        let side = BorderSide {
+          color: to_color(computed.border_color),
+          style: to_border_style(computed.border_style),
+        };
+        let details = BorderDetails::Normal(NormalBorder {
+          top: side,
+          right: side,
+          bottom: side,
+          left: side,
+          radius: BorderRadius::uniform(radius),
+          do_aa: true,
+        });
+
+        builder.push_border(
+          &CommonItemProperties::new(rect, space_and_clip),
+          rect,
+          LayoutSideOffsets::new_all_same(computed.border_width),
+          details,
+        );
+      }
+
+      // The content is clipped to the padding box, every such element gets a scroll frame so the application
+      // can scroll it, `hidden` ones just don't take input events.
+      if computed.overflow != style::Overflow::Visible {
+        let padding_box = rect.inflate(-computed.border_width, -computed.border_width);
+        let (content_width, content_height) = content_size(&node);
+        let content_rect = LayoutRect::new(padding_box.origin, LayoutSize::new(content_width, content_height));
+        let sensitivity = match computed.overflow {
+          style::Overflow::Hidden => ScrollSensitivity::Script,
+          _ => ScrollSensitivity::ScriptAndInputEvents,
+        };
+
+        let scroll_id = ExternalScrollId(next_scroll_id, self.pipeline_id);
+        next_scroll_id += 1;
+        let scroll_frame = builder.define_scroll_frame(
+          &space_and_clip,
+          Some(scroll_id),
+          content_rect,
+          padding_box,
+          sensitivity,
+          LayoutVector2D::zero(),
+        );
+
+        let (x, y) = node.inner().scroll_offset;
+        txn.scroll_node_with_id(LayoutPoint::new(x, y), scroll_id, ScrollClamping::ToContentBounds);
+        clips.push((node.clone(), scroll_frame));
+      }
     }
 
     // let mask_clip_id = builder.define_clip_image_mask(
@@ -377,5 +818,7 @@ impl Renderer {
     // }
 
     // builder.pop_stacking_context();
+
+    true
   }
 }
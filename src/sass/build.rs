@@ -64,6 +64,11 @@ fn compile() {
 }
 
 fn main() {
+  // The grass backend doesn't link libsass at all.
+  if env::var_os("CARGO_FEATURE_GRASS").is_some() {
+    return;
+  }
+
   compile();
 
   let bindings = bindgen::Builder::default()
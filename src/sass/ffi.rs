@@ -0,0 +1,363 @@
+pub mod sys {
+  #![allow(non_upper_case_globals)]
+  #![allow(non_camel_case_types)]
+  #![allow(non_snake_case)]
+  #![allow(clippy::all)]
+  #![allow(clippy::pedantic)]
+  #![allow(clippy::cargo)]
+
+  include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+}
+
+use std::{
+  ffi::{CStr, CString, NulError},
+  marker::PhantomData,
+  os::raw::c_char,
+  str::Utf8Error,
+};
+
+use crate::{functions::Functions, CompileError, CompileResult, OutputStyle, SassValue, Warning};
+
+#[derive(Debug)]
+pub struct FileContext {
+  file_ctx: *mut sys::Sass_File_Context,
+  ctx: *mut sys::Sass_Context,
+  functions: Functions,
+}
+
+impl FileContext {
+  pub fn new(input_path: &str) -> Result<Self, NulError> {
+    let input_path = CString::new(input_path)?;
+    unsafe {
+      let file_ctx = sys::sass_make_file_context(input_path.as_ptr());
+      let ctx = sys::sass_file_context_get_context(file_ctx);
+      Ok(Self {
+        file_ctx,
+        ctx,
+        functions: Functions::new(sys::sass_context_get_options(ctx)),
+      })
+    }
+  }
+
+  #[must_use]
+  pub fn options(&self) -> Options<'_> {
+    unsafe {
+      Options {
+        opts: sys::sass_context_get_options(self.ctx),
+        _phantom: PhantomData,
+      }
+    }
+  }
+
+  /// Registers a function implemented in Rust, e.g. `asset-url($path)`.
+  pub fn add_function<F>(&mut self, signature: &str, function: F) -> Result<(), NulError>
+  where
+    F: Fn(&[SassValue]) -> Result<SassValue, String> + Send + 'static,
+  {
+    unsafe {
+      self
+        .functions
+        .add(sys::sass_context_get_options(self.ctx), signature, function)
+    }
+  }
+
+  /// Compiles the input, copying the results out of the context.
+  pub fn compile_owned(&self) -> Result<CompileResult, CompileError> {
+    let status = unsafe { sys::sass_compile_file_context(self.file_ctx) };
+    unsafe { owned_result(self.ctx, status, self.functions.take_warnings()) }
+  }
+
+  pub fn compile(&self) -> Result<Compiled<'_>, Error<'_>> {
+    unsafe {
+      let status = sys::sass_compile_file_context(self.file_ctx);
+
+      if status == 0 {
+        Ok(Compiled {
+          ctx: self.ctx,
+          _phantom: PhantomData,
+        })
+      } else {
+        Err(Error {
+          ctx: self.ctx,
+          _phantom: PhantomData,
+        })
+      }
+    }
+  }
+}
+
+impl Drop for FileContext {
+  fn drop(&mut self) {
+    unsafe {
+      sys::sass_delete_file_context(self.file_ctx);
+    }
+  }
+}
+
+// libsass keeps no global state between contexts, so a context can be used from
+// any thread, one at a time. The warnings are collected into a `RefCell` during
+// compilation, which is why contexts aren't `Sync`.
+unsafe impl Send for FileContext {}
+
+#[derive(Debug)]
+pub struct DataContext {
+  data_ctx: *mut sys::Sass_Data_Context,
+  ctx: *mut sys::Sass_Context,
+  functions: Functions,
+}
+
+impl DataContext {
+  pub fn new(source_string: &str) -> Result<Self, NulError> {
+    let source_string = CString::new(source_string)?;
+    unsafe {
+      let data_ctx = sys::sass_make_data_context(sys::sass_copy_c_string(source_string.as_ptr()));
+      let ctx = sys::sass_data_context_get_context(data_ctx);
+      Ok(Self {
+        data_ctx,
+        ctx,
+        functions: Functions::new(sys::sass_context_get_options(ctx)),
+      })
+    }
+  }
+
+  #[must_use]
+  pub fn options(&self) -> Options<'_> {
+    unsafe {
+      Options {
+        opts: sys::sass_context_get_options(self.ctx),
+        _phantom: PhantomData,
+      }
+    }
+  }
+
+  /// Registers a function implemented in Rust, e.g. `asset-url($path)`.
+  pub fn add_function<F>(&mut self, signature: &str, function: F) -> Result<(), NulError>
+  where
+    F: Fn(&[SassValue]) -> Result<SassValue, String> + Send + 'static,
+  {
+    unsafe {
+      self
+        .functions
+        .add(sys::sass_context_get_options(self.ctx), signature, function)
+    }
+  }
+
+  /// Compiles the input, copying the results out of the context.
+  pub fn compile_owned(&self) -> Result<CompileResult, CompileError> {
+    let status = unsafe { sys::sass_compile_data_context(self.data_ctx) };
+    unsafe { owned_result(self.ctx, status, self.functions.take_warnings()) }
+  }
+
+  pub fn compile(&self) -> Result<Compiled<'_>, Error<'_>> {
+    unsafe {
+      let status = sys::sass_compile_data_context(self.data_ctx);
+
+      if status == 0 {
+        Ok(Compiled {
+          ctx: self.ctx,
+          _phantom: PhantomData,
+        })
+      } else {
+        Err(Error {
+          ctx: self.ctx,
+          _phantom: PhantomData,
+        })
+      }
+    }
+  }
+}
+
+impl Drop for DataContext {
+  fn drop(&mut self) {
+    unsafe {
+      sys::sass_delete_data_context(self.data_ctx);
+    }
+  }
+}
+
+// See `FileContext`.
+unsafe impl Send for DataContext {}
+
+impl From<OutputStyle> for sys::Sass_Output_Style {
+  fn from(style: OutputStyle) -> Self {
+    match style {
+      OutputStyle::Nested => sys::Sass_Output_Style::SASS_STYLE_NESTED,
+      OutputStyle::Expanded => sys::Sass_Output_Style::SASS_STYLE_EXPANDED,
+      OutputStyle::Compact => sys::Sass_Output_Style::SASS_STYLE_COMPACT,
+      OutputStyle::Compressed => sys::Sass_Output_Style::SASS_STYLE_COMPRESSED,
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct Options<'ctx> {
+  opts: *mut sys::Sass_Options,
+  _phantom: PhantomData<&'ctx ()>,
+}
+
+impl Options<'_> {
+  pub fn set_source_map_file(&self, value: &str) -> Result<(), NulError> {
+    unsafe {
+      let value = CString::new(value)?;
+      sys::sass_option_set_source_map_file(self.opts, value.as_ptr());
+      Ok(())
+    }
+  }
+
+  pub fn set_source_map_contents(&self, value: bool) {
+    unsafe {
+      sys::sass_option_set_source_map_contents(self.opts, value);
+    }
+  }
+
+  pub fn set_is_indented_syntax_src(&self, value: bool) {
+    unsafe {
+      sys::sass_option_set_is_indented_syntax_src(self.opts, value);
+    }
+  }
+
+  pub fn set_output_style(&self, value: OutputStyle) {
+    unsafe {
+      sys::sass_option_set_output_style(self.opts, value.into());
+    }
+  }
+
+  /// Sets the number of digits after the decimal point of numbers in the output.
+  pub fn set_precision(&self, value: i32) {
+    unsafe {
+      sys::sass_option_set_precision(self.opts, value);
+    }
+  }
+
+  pub fn set_input_path(&self, value: &str) -> Result<(), NulError> {
+    unsafe {
+      let value = CString::new(value)?;
+      sys::sass_option_set_input_path(self.opts, value.as_ptr());
+      Ok(())
+    }
+  }
+
+  /// Adds a directory `@import` looks in after the directory of the input.
+  pub fn push_include_path(&self, value: &str) -> Result<(), NulError> {
+    unsafe {
+      let value = CString::new(value)?;
+      sys::sass_option_push_include_path(self.opts, value.as_ptr());
+      Ok(())
+    }
+  }
+}
+
+unsafe fn owned_string(value: *const c_char) -> Option<String> {
+  if value.is_null() {
+    None
+  } else {
+    Some(CStr::from_ptr(value).to_string_lossy().into_owned())
+  }
+}
+
+unsafe fn owned_result(
+  ctx: *mut sys::Sass_Context,
+  status: i32,
+  warnings: Vec<Warning>,
+) -> Result<CompileResult, CompileError> {
+  if status == 0 {
+    Ok(CompileResult {
+      css: owned_string(sys::sass_context_get_output_string(ctx)).unwrap_or_default(),
+      source_map: owned_string(sys::sass_context_get_source_map_string(ctx)),
+      warnings,
+    })
+  } else {
+    Err(CompileError {
+      message: owned_string(sys::sass_context_get_error_message(ctx)).unwrap_or_default(),
+      text: owned_string(sys::sass_context_get_error_text(ctx)).unwrap_or_default(),
+      file: owned_string(sys::sass_context_get_error_file(ctx)),
+      src: owned_string(sys::sass_context_get_error_src(ctx)),
+      line: sys::sass_context_get_error_line(ctx),
+      column: sys::sass_context_get_error_column(ctx),
+      warnings,
+    })
+  }
+}
+
+#[derive(Debug)]
+pub struct Compiled<'ctx> {
+  ctx: *mut sys::Sass_Context,
+  _phantom: PhantomData<&'ctx ()>,
+}
+
+impl Compiled<'_> {
+  #[must_use]
+  pub fn status(&self) -> i32 {
+    unsafe { sys::sass_context_get_error_status(self.ctx) }
+  }
+
+  pub fn output(&self) -> Result<String, Utf8Error> {
+    unsafe {
+      let c_str = CStr::from_ptr(sys::sass_context_get_output_string(self.ctx));
+      Ok(c_str.to_str()?.to_string())
+    }
+  }
+
+  pub unsafe fn source_map(&self) -> Result<String, Utf8Error> {
+    let c_str = CStr::from_ptr(sys::sass_context_get_source_map_string(self.ctx));
+    Ok(c_str.to_str()?.to_string())
+  }
+}
+
+#[derive(Debug)]
+pub struct Error<'ctx> {
+  ctx: *mut sys::Sass_Context,
+  _phantom: PhantomData<&'ctx ()>,
+}
+
+impl Error<'_> {
+  #[must_use]
+  pub fn status(&self) -> i32 {
+    unsafe { sys::sass_context_get_error_status(self.ctx) }
+  }
+
+  pub fn json(&self) -> Result<String, Utf8Error> {
+    unsafe {
+      let c_str = CStr::from_ptr(sys::sass_context_get_error_json(self.ctx));
+      Ok(c_str.to_str()?.to_string())
+    }
+  }
+
+  pub fn text(&self) -> Result<String, Utf8Error> {
+    unsafe {
+      let c_str = CStr::from_ptr(sys::sass_context_get_error_text(self.ctx));
+      Ok(c_str.to_str()?.to_string())
+    }
+  }
+
+  pub fn message(&self) -> Result<String, Utf8Error> {
+    unsafe {
+      let c_str = CStr::from_ptr(sys::sass_context_get_error_message(self.ctx));
+      Ok(c_str.to_str()?.to_string())
+    }
+  }
+
+  pub fn file(&self) -> Result<String, Utf8Error> {
+    unsafe {
+      let c_str = CStr::from_ptr(sys::sass_context_get_error_file(self.ctx));
+      Ok(c_str.to_str()?.to_string())
+    }
+  }
+
+  pub fn src(&self) -> Result<String, Utf8Error> {
+    unsafe {
+      let c_str = CStr::from_ptr(sys::sass_context_get_error_src(self.ctx));
+      Ok(c_str.to_str()?.to_string())
+    }
+  }
+
+  #[must_use]
+  pub fn line(&self) -> u64 {
+    unsafe { sys::sass_context_get_error_line(self.ctx) }
+  }
+
+  #[must_use]
+  pub fn column(&self) -> u64 {
+    unsafe { sys::sass_context_get_error_column(self.ctx) }
+  }
+}
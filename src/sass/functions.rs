@@ -0,0 +1,261 @@
+use std::{
+  cell::RefCell,
+  ffi::{CStr, CString, NulError},
+  fmt,
+  os::raw::{c_char, c_void},
+  panic::{self, AssertUnwindSafe},
+};
+
+use crate::{ffi::sys, ListSeparator, SassFunction, SassValue, Warning};
+
+impl SassValue {
+  /// Copies a libsass value, returns `None` for errors and warnings.
+  ///
+  /// # Safety
+  ///
+  /// `value` must point to a valid libsass value.
+  unsafe fn from_raw(value: *const sys::Sass_Value) -> Option<Self> {
+    Some(match sys::sass_value_get_tag(value) {
+      sys::Sass_Tag::SASS_NULL => SassValue::Null,
+      sys::Sass_Tag::SASS_BOOLEAN => SassValue::Boolean(sys::sass_boolean_get_value(value)),
+      sys::Sass_Tag::SASS_NUMBER => SassValue::Number {
+        value: sys::sass_number_get_value(value),
+        unit: string_from_raw(sys::sass_number_get_unit(value)),
+      },
+      sys::Sass_Tag::SASS_STRING => SassValue::String {
+        value: string_from_raw(sys::sass_string_get_value(value)),
+        quoted: sys::sass_string_is_quoted(value),
+      },
+      sys::Sass_Tag::SASS_COLOR => SassValue::Color {
+        r: sys::sass_color_get_r(value),
+        g: sys::sass_color_get_g(value),
+        b: sys::sass_color_get_b(value),
+        a: sys::sass_color_get_a(value),
+      },
+      sys::Sass_Tag::SASS_LIST => SassValue::List {
+        values: (0..sys::sass_list_get_length(value))
+          .filter_map(|i| Self::from_raw(sys::sass_list_get_value(value, i)))
+          .collect(),
+        separator: match sys::sass_list_get_separator(value) {
+          sys::Sass_Separator::SASS_COMMA => ListSeparator::Comma,
+          _ => ListSeparator::Space,
+        },
+        bracketed: sys::sass_list_get_is_bracketed(value),
+      },
+      sys::Sass_Tag::SASS_MAP => SassValue::Map(
+        (0..sys::sass_map_get_length(value))
+          .filter_map(|i| {
+            Some((
+              Self::from_raw(sys::sass_map_get_key(value, i))?,
+              Self::from_raw(sys::sass_map_get_value(value, i))?,
+            ))
+          })
+          .collect(),
+      ),
+      sys::Sass_Tag::SASS_ERROR | sys::Sass_Tag::SASS_WARNING => return None,
+    })
+  }
+
+  /// Creates a libsass value, ownership is passed to the caller.
+  fn into_raw(self) -> *mut sys::Sass_Value {
+    unsafe {
+      match self {
+        SassValue::Null => sys::sass_make_null(),
+        SassValue::Boolean(value) => sys::sass_make_boolean(value),
+        SassValue::Number { value, unit } => {
+          let unit = c_string_lossy(&unit);
+          sys::sass_make_number(value, unit.as_ptr())
+        }
+        SassValue::String { value, quoted } => {
+          let value = c_string_lossy(&value);
+          if quoted {
+            sys::sass_make_qstring(value.as_ptr())
+          } else {
+            sys::sass_make_string(value.as_ptr())
+          }
+        }
+        SassValue::Color { r, g, b, a } => sys::sass_make_color(r, g, b, a),
+        SassValue::List {
+          values,
+          separator,
+          bracketed,
+        } => {
+          let separator = match separator {
+            ListSeparator::Comma => sys::Sass_Separator::SASS_COMMA,
+            ListSeparator::Space => sys::Sass_Separator::SASS_SPACE,
+          };
+
+          let list = sys::sass_make_list(values.len() as _, separator, bracketed);
+          for (i, value) in values.into_iter().enumerate() {
+            sys::sass_list_set_value(list, i as _, value.into_raw());
+          }
+          list
+        }
+        SassValue::Map(entries) => {
+          let map = sys::sass_make_map(entries.len() as _);
+          for (i, (key, value)) in entries.into_iter().enumerate() {
+            sys::sass_map_set_key(map, i as _, key.into_raw());
+            sys::sass_map_set_value(map, i as _, value.into_raw());
+          }
+          map
+        }
+      }
+    }
+  }
+}
+
+unsafe fn string_from_raw(value: *const c_char) -> String {
+  if value.is_null() {
+    String::new()
+  } else {
+    CStr::from_ptr(value).to_string_lossy().into_owned()
+  }
+}
+
+/// Sass strings can't contain nul bytes, anything after one is dropped.
+fn c_string_lossy(value: &str) -> CString {
+  CString::new(value.split('\0').next().unwrap_or_default()).unwrap()
+}
+
+fn make_error(message: &str) -> *mut sys::Sass_Value {
+  let message = c_string_lossy(message);
+  unsafe { sys::sass_make_error(message.as_ptr()) }
+}
+
+unsafe extern "C" fn call_function(
+  args: *const sys::Sass_Value,
+  cb: sys::Sass_Function_Entry,
+  _compiler: *mut sys::Sass_Compiler,
+) -> *mut sys::Sass_Value {
+  let function = &*(sys::sass_function_get_cookie(cb) as *const Box<SassFunction>);
+
+  // libsass always passes the arguments as a list.
+  let args = match SassValue::from_raw(args) {
+    Some(SassValue::List { values, .. }) => values,
+    Some(value) => vec![value],
+    None => Vec::new(),
+  };
+
+  // Unwinding into libsass is undefined behavior.
+  match panic::catch_unwind(AssertUnwindSafe(|| function(&args))) {
+    Ok(Ok(value)) => value.into_raw(),
+    Ok(Err(message)) => make_error(&message),
+    Err(_) => make_error("custom function panicked"),
+  }
+}
+
+unsafe extern "C" fn collect_warning(
+  args: *const sys::Sass_Value,
+  cb: sys::Sass_Function_Entry,
+  compiler: *mut sys::Sass_Compiler,
+) -> *mut sys::Sass_Value {
+  let warnings = &*(sys::sass_function_get_cookie(cb) as *const RefCell<Vec<Warning>>);
+
+  let message = match SassValue::from_raw(args) {
+    Some(SassValue::List { values, .. }) => values.first().map(ToString::to_string).unwrap_or_default(),
+    Some(value) => value.to_string(),
+    None => String::new(),
+  };
+
+  // libsass pushes the `@warn` rule itself as the last callee.
+  let callee = sys::sass_compiler_get_last_callee(compiler);
+  let (file, line, column) = if callee.is_null() {
+    (None, 0, 0)
+  } else {
+    (
+      Some(string_from_raw(sys::sass_callee_get_path(callee))),
+      sys::sass_callee_get_line(callee) as u64,
+      sys::sass_callee_get_column(callee) as u64,
+    )
+  };
+
+  if let Ok(mut warnings) = warnings.try_borrow_mut() {
+    warnings.push(Warning {
+      message,
+      file,
+      line,
+      column,
+    });
+  }
+
+  sys::sass_make_null()
+}
+
+const WARN_SIGNATURE: &[u8] = b"@warn\0";
+
+/// Custom functions of a context, they have to outlive the compilation.
+pub(crate) struct Functions {
+  entries: Vec<(CString, Box<Box<SassFunction>>)>,
+  warnings: Box<RefCell<Vec<Warning>>>,
+}
+
+impl Functions {
+  /// Creates the function list of a context, overriding `@warn` to collect warnings
+  /// instead of printing them.
+  ///
+  /// # Safety
+  ///
+  /// `opts` must belong to the context owning the returned list.
+  pub(crate) unsafe fn new(opts: *mut sys::Sass_Options) -> Self {
+    let functions = Self {
+      entries: Vec::new(),
+      warnings: Box::new(RefCell::new(Vec::new())),
+    };
+    functions.install(opts);
+    functions
+  }
+
+  /// Returns the warnings emitted since the last call.
+  pub(crate) fn take_warnings(&self) -> Vec<Warning> {
+    self.warnings.borrow_mut().drain(..).collect()
+  }
+
+  /// Adds a function and passes the updated function list to libsass.
+  ///
+  /// # Safety
+  ///
+  /// `opts` must belong to the context owning `self`.
+  pub(crate) unsafe fn add<F>(
+    &mut self,
+    opts: *mut sys::Sass_Options,
+    signature: &str,
+    function: F,
+  ) -> Result<(), NulError>
+  where
+    F: Fn(&[SassValue]) -> Result<SassValue, String> + Send + 'static,
+  {
+    let signature = CString::new(signature)?;
+    self.entries.push((signature, Box::new(Box::new(function))));
+    self.install(opts);
+    Ok(())
+  }
+
+  /// Replaces the function list of `opts`, libsass takes ownership of the new list.
+  unsafe fn install(&self, opts: *mut sys::Sass_Options) {
+    let list = sys::sass_make_function_list((self.entries.len() + 1) as _);
+
+    let cookie = &*self.warnings as *const RefCell<Vec<Warning>> as *mut c_void;
+    let entry = sys::sass_make_function(WARN_SIGNATURE.as_ptr() as *const c_char, Some(collect_warning), cookie);
+    sys::sass_function_set_list_entry(list, 0, entry);
+
+    for (i, (signature, function)) in self.entries.iter().enumerate() {
+      let cookie = &**function as *const Box<SassFunction> as *mut c_void;
+      let entry = sys::sass_make_function(signature.as_ptr(), Some(call_function), cookie);
+      sys::sass_function_set_list_entry(list, (i + 1) as _, entry);
+    }
+
+    let previous = sys::sass_option_get_c_functions(opts);
+    sys::sass_option_set_c_functions(opts, list);
+    if !previous.is_null() {
+      sys::sass_delete_function_list(previous);
+    }
+  }
+}
+
+impl fmt::Debug for Functions {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_list()
+      .entries(self.0.iter().map(|(signature, _)| signature))
+      .finish()
+  }
+}
@@ -0,0 +1,206 @@
+use std::{
+  cell::RefCell,
+  ffi::NulError,
+  path::{Path, PathBuf},
+};
+
+use crate::{CompileError, CompileResult, OutputStyle, SassValue};
+
+#[derive(Debug)]
+struct Settings {
+  input_path: Option<String>,
+  indented_syntax: bool,
+  output_style: OutputStyle,
+  include_paths: Vec<PathBuf>,
+  has_functions: bool,
+}
+
+#[derive(Debug)]
+enum Input {
+  File(String),
+  Data(String),
+}
+
+/// A grass compilation, mirroring the state of a libsass context.
+#[derive(Debug)]
+struct Context {
+  input: Input,
+  settings: RefCell<Settings>,
+}
+
+impl Context {
+  fn new(input: Input) -> Self {
+    let input_path = match &input {
+      Input::File(path) => Some(path.clone()),
+      Input::Data(..) => None,
+    };
+
+    Self {
+      input,
+      settings: RefCell::new(Settings {
+        input_path,
+        indented_syntax: false,
+        output_style: OutputStyle::Nested,
+        include_paths: Vec::new(),
+        has_functions: false,
+      }),
+    }
+  }
+
+  fn compile_owned(&self) -> Result<CompileResult, CompileError> {
+    let settings = self.settings.borrow();
+    let error = |message: String| CompileError {
+      text: message.clone(),
+      message,
+      file: settings.input_path.clone(),
+      src: None,
+      line: 0,
+      column: 0,
+      warnings: Vec::new(),
+    };
+
+    if settings.indented_syntax {
+      return Err(error(
+        "the grass backend doesn't support the indented syntax".to_string(),
+      ));
+    }
+
+    if settings.has_functions {
+      return Err(error("the grass backend doesn't support custom functions".to_string()));
+    }
+
+    let style = match settings.output_style {
+      OutputStyle::Compressed => grass_backend::OutputStyle::Compressed,
+      _ => grass_backend::OutputStyle::Expanded,
+    };
+
+    let mut options = grass_backend::Options::default().style(style);
+
+    // Imports are resolved relative to the input, like libsass does.
+    let load_path = settings
+      .input_path
+      .as_deref()
+      .and_then(|x| Path::new(x).parent())
+      .filter(|x| x.is_dir());
+    if let Some(load_path) = load_path {
+      options = options.load_path(load_path);
+    }
+    for include_path in &settings.include_paths {
+      options = options.load_path(include_path);
+    }
+
+    let css = match &self.input {
+      Input::File(path) => grass_backend::from_path(path, &options),
+      Input::Data(source) => grass_backend::from_string(source.clone(), &options),
+    };
+
+    css
+      .map(|css| CompileResult {
+        css,
+        source_map: None,
+        warnings: Vec::new(),
+      })
+      .map_err(|e| error(e.to_string()))
+  }
+}
+
+/// Compiles a Sass file with grass.
+///
+/// Only owned compilation is supported, grass has no source maps, custom
+/// functions or indented syntax, and prints warnings itself.
+#[derive(Debug)]
+pub struct FileContext(Context);
+
+impl FileContext {
+  pub fn new(input_path: &str) -> Result<Self, NulError> {
+    Ok(Self(Context::new(Input::File(input_path.to_string()))))
+  }
+
+  #[must_use]
+  pub fn options(&self) -> Options<'_> {
+    Options {
+      settings: &self.0.settings,
+    }
+  }
+
+  /// Custom functions aren't supported by grass, compiling fails once one was added.
+  pub fn add_function<F>(&mut self, _signature: &str, _function: F) -> Result<(), NulError>
+  where
+    F: Fn(&[SassValue]) -> Result<SassValue, String> + Send + 'static,
+  {
+    self.0.settings.borrow_mut().has_functions = true;
+    Ok(())
+  }
+
+  pub fn compile_owned(&self) -> Result<CompileResult, CompileError> {
+    self.0.compile_owned()
+  }
+}
+
+/// Compiles a Sass string with grass, see [`FileContext`] for its limitations.
+#[derive(Debug)]
+pub struct DataContext(Context);
+
+impl DataContext {
+  pub fn new(source_string: &str) -> Result<Self, NulError> {
+    Ok(Self(Context::new(Input::Data(source_string.to_string()))))
+  }
+
+  #[must_use]
+  pub fn options(&self) -> Options<'_> {
+    Options {
+      settings: &self.0.settings,
+    }
+  }
+
+  /// Custom functions aren't supported by grass, compiling fails once one was added.
+  pub fn add_function<F>(&mut self, _signature: &str, _function: F) -> Result<(), NulError>
+  where
+    F: Fn(&[SassValue]) -> Result<SassValue, String> + Send + 'static,
+  {
+    self.0.settings.borrow_mut().has_functions = true;
+    Ok(())
+  }
+
+  pub fn compile_owned(&self) -> Result<CompileResult, CompileError> {
+    self.0.compile_owned()
+  }
+}
+
+#[derive(Debug)]
+pub struct Options<'ctx> {
+  settings: &'ctx RefCell<Settings>,
+}
+
+impl Options<'_> {
+  /// Source maps aren't supported by grass, the option is ignored.
+  pub fn set_source_map_file(&self, _value: &str) -> Result<(), NulError> {
+    Ok(())
+  }
+
+  /// Source maps aren't supported by grass, the option is ignored.
+  pub fn set_source_map_contents(&self, _value: bool) {}
+
+  pub fn set_is_indented_syntax_src(&self, value: bool) {
+    self.settings.borrow_mut().indented_syntax = value;
+  }
+
+  pub fn set_input_path(&self, value: &str) -> Result<(), NulError> {
+    self.settings.borrow_mut().input_path = Some(value.to_string());
+    Ok(())
+  }
+
+  /// grass only has expanded and compressed output, other styles are expanded.
+  pub fn set_output_style(&self, value: OutputStyle) {
+    self.settings.borrow_mut().output_style = value;
+  }
+
+  /// Adds a directory `@import` looks in after the directory of the input.
+  pub fn push_include_path(&self, value: &str) -> Result<(), NulError> {
+    self.settings.borrow_mut().include_paths.push(PathBuf::from(value));
+    Ok(())
+  }
+
+  /// grass always uses a precision of 10 digits, the option is ignored.
+  pub fn set_precision(&self, _value: i32) {}
+}
@@ -1,245 +1,154 @@
-pub mod sys {
-  #![allow(non_upper_case_globals)]
-  #![allow(non_camel_case_types)]
-  #![allow(non_snake_case)]
-  #![allow(clippy::all)]
-  #![allow(clippy::pedantic)]
-  #![allow(clippy::cargo)]
-
-  include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
-}
+#[cfg(not(feature = "grass"))]
+mod ffi;
+#[cfg(not(feature = "grass"))]
+mod functions;
+#[cfg(not(feature = "grass"))]
+pub use ffi::*;
 
-use std::{
-  ffi::{CStr, CString, NulError},
-  marker::PhantomData,
-  str::Utf8Error,
-};
+#[cfg(feature = "grass")]
+mod grass;
+#[cfg(feature = "grass")]
+pub use self::grass::*;
 
-#[derive(Debug)]
-pub struct FileContext {
-  file_ctx: *mut sys::Sass_File_Context,
-  ctx: *mut sys::Sass_Context,
-}
-
-impl FileContext {
-  pub fn new(input_path: &str) -> Result<Self, NulError> {
-    let input_path = CString::new(input_path)?;
-    unsafe {
-      let file_ctx = sys::sass_make_file_context(input_path.as_ptr());
-      Ok(Self {
-        file_ctx,
-        ctx: sys::sass_file_context_get_context(file_ctx),
-      })
-    }
-  }
+mod value;
+pub use value::{ListSeparator, SassFunction, SassValue};
 
-  #[must_use]
-  pub fn options(&self) -> Options<'_> {
-    unsafe {
-      Options {
-        opts: sys::sass_context_get_options(self.ctx),
-        _phantom: PhantomData,
-      }
-    }
-  }
+use std::{
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    mpsc, Arc,
+  },
+  thread,
+};
 
-  pub fn compile(&self) -> Result<Compiled<'_>, Error<'_>> {
-    unsafe {
-      let status = sys::sass_compile_file_context(self.file_ctx);
-
-      if status == 0 {
-        Ok(Compiled {
-          ctx: self.ctx,
-          _phantom: PhantomData,
-        })
-      } else {
-        Err(Error {
-          ctx: self.ctx,
-          _phantom: PhantomData,
-        })
-      }
-    }
-  }
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OutputStyle {
+  Nested,
+  Expanded,
+  Compact,
+  Compressed,
 }
 
-impl Drop for FileContext {
-  fn drop(&mut self) {
-    unsafe {
-      sys::sass_delete_file_context(self.file_ctx);
-    }
-  }
+/// A warning emitted with `@warn`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+  pub message: String,
+  /// Path of the file containing the `@warn` rule, as passed to the compiler.
+  pub file: Option<String>,
+  /// 1-based line of the `@warn` rule.
+  pub line: u64,
+  /// 1-based column of the `@warn` rule.
+  pub column: u64,
 }
 
-#[derive(Debug)]
-pub struct DataContext {
-  data_ctx: *mut sys::Sass_Data_Context,
-  ctx: *mut sys::Sass_Context,
+#[derive(Debug, Clone)]
+pub struct CompileResult {
+  pub css: String,
+  pub source_map: Option<String>,
+  pub warnings: Vec<Warning>,
 }
 
-impl DataContext {
-  pub fn new(source_string: &str) -> Result<Self, NulError> {
-    let source_string = CString::new(source_string)?;
-    unsafe {
-      let data_ctx = sys::sass_make_data_context(sys::sass_copy_c_string(source_string.as_ptr()));
-      Ok(Self {
-        data_ctx,
-        ctx: sys::sass_data_context_get_context(data_ctx),
-      })
-    }
-  }
-
-  #[must_use]
-  pub fn options(&self) -> Options<'_> {
-    unsafe {
-      Options {
-        opts: sys::sass_context_get_options(self.ctx),
-        _phantom: PhantomData,
-      }
-    }
-  }
-
-  pub fn compile(&self) -> Result<Compiled<'_>, Error<'_>> {
-    unsafe {
-      let status = sys::sass_compile_data_context(self.data_ctx);
-
-      if status == 0 {
-        Ok(Compiled {
-          ctx: self.ctx,
-          _phantom: PhantomData,
-        })
-      } else {
-        Err(Error {
-          ctx: self.ctx,
-          _phantom: PhantomData,
-        })
-      }
-    }
-  }
+#[derive(Debug, Clone)]
+pub struct CompileError {
+  pub message: String,
+  /// The message formatted with its location, as the backend prints it.
+  pub text: String,
+  pub file: Option<String>,
+  pub src: Option<String>,
+  /// 1-based line of the error, 0 if the backend doesn't know it.
+  pub line: u64,
+  pub column: u64,
+  pub warnings: Vec<Warning>,
 }
 
-impl Drop for DataContext {
-  fn drop(&mut self) {
-    unsafe {
-      sys::sass_delete_data_context(self.data_ctx);
+impl CompileError {
+  fn new(message: String) -> Self {
+    Self {
+      text: message.clone(),
+      message,
+      file: None,
+      src: None,
+      line: 0,
+      column: 0,
+      warnings: Vec::new(),
     }
   }
 }
 
-#[derive(Debug)]
-pub struct Options<'ctx> {
-  opts: *mut sys::Sass_Options,
-  _phantom: PhantomData<&'ctx ()>,
+/// A Sass source compiled by [`compile_many`].
+#[derive(Debug, Clone)]
+pub struct Input {
+  pub source: String,
+  pub input_path: Option<String>,
+  pub indented_syntax: bool,
+  pub output_style: OutputStyle,
+  pub source_map: bool,
 }
 
-impl Options<'_> {
-  pub fn set_source_map_file(&self, value: &str) -> Result<(), NulError> {
-    unsafe {
-      let value = CString::new(value)?;
-      sys::sass_option_set_source_map_file(self.opts, value.as_ptr());
-      Ok(())
-    }
-  }
-
-  pub fn set_source_map_contents(&self, value: bool) {
-    unsafe {
-      sys::sass_option_set_source_map_contents(self.opts, value);
-    }
-  }
+impl Input {
+  pub fn compile(&self) -> Result<CompileResult, CompileError> {
+    let ctx = DataContext::new(&self.source).map_err(|e| CompileError::new(e.to_string()))?;
 
-  pub fn set_is_indented_syntax_src(&self, value: bool) {
-    unsafe {
-      sys::sass_option_set_is_indented_syntax_src(self.opts, value);
+    let opt = ctx.options();
+    if let Some(input_path) = &self.input_path {
+      opt
+        .set_input_path(input_path)
+        .map_err(|e| CompileError::new(e.to_string()))?;
     }
-  }
 
-  pub fn set_input_path(&self, value: &str) -> Result<(), NulError> {
-    unsafe {
-      let value = CString::new(value)?;
-      sys::sass_option_set_input_path(self.opts, value.as_ptr());
-      Ok(())
+    if self.source_map {
+      opt
+        .set_source_map_file("stdin")
+        .map_err(|e| CompileError::new(e.to_string()))?;
+      opt.set_source_map_contents(true);
     }
-  }
-}
-
-#[derive(Debug)]
-pub struct Compiled<'ctx> {
-  ctx: *mut sys::Sass_Context,
-  _phantom: PhantomData<&'ctx ()>,
-}
 
-impl Compiled<'_> {
-  #[must_use]
-  pub fn status(&self) -> i32 {
-    unsafe { sys::sass_context_get_error_status(self.ctx) }
-  }
-
-  pub fn output(&self) -> Result<String, Utf8Error> {
-    unsafe {
-      let c_str = CStr::from_ptr(sys::sass_context_get_output_string(self.ctx));
-      Ok(c_str.to_str()?.to_string())
-    }
-  }
+    opt.set_is_indented_syntax_src(self.indented_syntax);
+    opt.set_output_style(self.output_style);
 
-  pub unsafe fn source_map(&self) -> Result<String, Utf8Error> {
-    let c_str = CStr::from_ptr(sys::sass_context_get_source_map_string(self.ctx));
-    Ok(c_str.to_str()?.to_string())
+    ctx.compile_owned()
   }
 }
 
-#[derive(Debug)]
-pub struct Error<'ctx> {
-  ctx: *mut sys::Sass_Context,
-  _phantom: PhantomData<&'ctx ()>,
-}
-
-impl Error<'_> {
-  #[must_use]
-  pub fn status(&self) -> i32 {
-    unsafe { sys::sass_context_get_error_status(self.ctx) }
-  }
-
-  pub fn json(&self) -> Result<String, Utf8Error> {
-    unsafe {
-      let c_str = CStr::from_ptr(sys::sass_context_get_error_json(self.ctx));
-      Ok(c_str.to_str()?.to_string())
-    }
-  }
-
-  pub fn text(&self) -> Result<String, Utf8Error> {
-    unsafe {
-      let c_str = CStr::from_ptr(sys::sass_context_get_error_text(self.ctx));
-      Ok(c_str.to_str()?.to_string())
-    }
-  }
-
-  pub fn message(&self) -> Result<String, Utf8Error> {
-    unsafe {
-      let c_str = CStr::from_ptr(sys::sass_context_get_error_message(self.ctx));
-      Ok(c_str.to_str()?.to_string())
-    }
-  }
+/// Compiles every input on a pool of `threads` threads, returning the results in input order.
+pub fn compile_many(inputs: Vec<Input>, threads: usize) -> Vec<Result<CompileResult, CompileError>> {
+  let count = inputs.len();
+  let inputs = Arc::new(inputs);
+  let next = Arc::new(AtomicUsize::new(0));
+  let (sender, receiver) = mpsc::channel();
+
+  let workers: Vec<_> = (0..threads.max(1).min(count))
+    .map(|_| {
+      let inputs = Arc::clone(&inputs);
+      let next = Arc::clone(&next);
+      let sender = sender.clone();
+
+      thread::spawn(move || loop {
+        let index = next.fetch_add(1, Ordering::SeqCst);
+        let input = match inputs.get(index) {
+          Some(input) => input,
+          None => break,
+        };
+
+        if sender.send((index, input.compile())).is_err() {
+          break;
+        }
+      })
+    })
+    .collect();
+  drop(sender);
 
-  pub fn file(&self) -> Result<String, Utf8Error> {
-    unsafe {
-      let c_str = CStr::from_ptr(sys::sass_context_get_error_file(self.ctx));
-      Ok(c_str.to_str()?.to_string())
-    }
+  let mut results: Vec<_> = (0..count).map(|_| None).collect();
+  for (index, result) in receiver {
+    results[index] = Some(result);
   }
 
-  pub fn src(&self) -> Result<String, Utf8Error> {
-    unsafe {
-      let c_str = CStr::from_ptr(sys::sass_context_get_error_src(self.ctx));
-      Ok(c_str.to_str()?.to_string())
+  for worker in workers {
+    // Forwards a panic of a worker, its input would have no result.
+    if let Err(e) = worker.join() {
+      std::panic::resume_unwind(e);
     }
   }
 
-  #[must_use]
-  pub fn line(&self) -> u64 {
-    unsafe { sys::sass_context_get_error_line(self.ctx) }
-  }
-
-  #[must_use]
-  pub fn column(&self) -> u64 {
-    unsafe { sys::sass_context_get_error_column(self.ctx) }
-  }
+  results.into_iter().map(Option::unwrap).collect()
 }
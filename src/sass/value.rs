@@ -0,0 +1,76 @@
+use std::fmt;
+
+/// A custom Sass function, called with the arguments of the Sass call.
+///
+/// Errors are reported like any other Sass error. Functions have to be `Send`
+/// so their context can be moved to another thread.
+pub type SassFunction = dyn Fn(&[SassValue]) -> Result<SassValue, String> + Send;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ListSeparator {
+  Comma,
+  Space,
+}
+
+/// An owned Sass value, as passed to and returned from custom functions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SassValue {
+  Null,
+  Boolean(bool),
+  Number {
+    value: f64,
+    unit: String,
+  },
+  String {
+    value: String,
+    quoted: bool,
+  },
+  Color {
+    r: f64,
+    g: f64,
+    b: f64,
+    a: f64,
+  },
+  List {
+    values: Vec<SassValue>,
+    separator: ListSeparator,
+    bracketed: bool,
+  },
+  Map(Vec<(SassValue, SassValue)>),
+}
+
+impl fmt::Display for SassValue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SassValue::Null => write!(f, "null"),
+      SassValue::Boolean(value) => write!(f, "{}", value),
+      SassValue::Number { value, unit } => write!(f, "{}{}", value, unit),
+      SassValue::String { value, .. } => write!(f, "{}", value),
+      SassValue::Color { r, g, b, a } => write!(f, "rgba({}, {}, {}, {})", r, g, b, a),
+      SassValue::List { values, separator, .. } => {
+        let separator = match separator {
+          ListSeparator::Comma => ", ",
+          ListSeparator::Space => " ",
+        };
+
+        for (i, value) in values.iter().enumerate() {
+          if i > 0 {
+            f.write_str(separator)?;
+          }
+          write!(f, "{}", value)?;
+        }
+        Ok(())
+      }
+      SassValue::Map(entries) => {
+        f.write_str("(")?;
+        for (i, (key, value)) in entries.iter().enumerate() {
+          if i > 0 {
+            f.write_str(", ")?;
+          }
+          write!(f, "{}: {}", key, value)?;
+        }
+        f.write_str(")")
+      }
+    }
+  }
+}
@@ -1,15 +1,32 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
 pub mod parser;
 pub mod selectors;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RenderStyle {
   pub width: f32,
   pub height: f32,
   pub top: f32,
   pub left: f32,
-  pub background_color: (u8, u8, u8, u8),
+  pub background: Background,
+  /// Used width of the border, 0 if it isn't drawn.
+  pub border_width: f32,
+  pub border_color: (u8, u8, u8, u8),
+  pub border_style: BorderStyle,
+  /// Radius of every corner, the background and the border are clipped to it.
+  pub border_radius: f32,
+  pub display: Display,
+  /// Opacity of the element and its descendants, as a group.
+  pub opacity: f32,
+  pub box_shadow: Option<BoxShadow>,
+  /// URL of the image drawn over the background, see `Declaration::BackgroundImage`.
+  pub background_image: Option<String>,
+  pub background_size: BackgroundSize,
+  pub background_repeat: BackgroundRepeat,
+  pub overflow: Overflow,
 }
 
 impl Default for RenderStyle {
@@ -19,20 +36,454 @@ impl Default for RenderStyle {
       height: f32::NAN,
       top: f32::NAN,
       left: f32::NAN,
-      background_color: (0, 0, 0, 0),
+      background: Background::default(),
+      border_width: 0.0,
+      border_color: (0, 0, 0, 0),
+      border_style: BorderStyle::None,
+      border_radius: 0.0,
+      display: Display::Flex,
+      opacity: 1.0,
+      box_shadow: None,
+      background_image: None,
+      background_size: BackgroundSize::default(),
+      background_repeat: BackgroundRepeat::default(),
+      overflow: Overflow::Visible,
+    }
+  }
+}
+
+/// Inline base direction of an element or document.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+  Inherit,
+  LTR,
+  RTL,
+}
+
+impl Default for Direction {
+  fn default() -> Self {
+    Self::Inherit
+  }
+}
+
+impl From<Direction> for yoga::Direction {
+  fn from(direction: Direction) -> Self {
+    match direction {
+      Direction::Inherit => yoga::Direction::Inherit,
+      Direction::LTR => yoga::Direction::LTR,
+      Direction::RTL => yoga::Direction::RTL,
+    }
+  }
+}
+
+/// Whether an element is laid out, as a flex container, or hidden along with its descendants.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Display {
+  Flex,
+  None,
+}
+
+impl Display {
+  /// Keyword of the display, as written in a stylesheet.
+  #[must_use]
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Flex => "flex",
+      Self::None => "none",
+    }
+  }
+}
+
+impl Default for Display {
+  fn default() -> Self {
+    Self::Flex
+  }
+}
+
+impl From<Display> for yoga::Display {
+  fn from(display: Display) -> Self {
+    match display {
+      Display::Flex => yoga::Display::Flex,
+      Display::None => yoga::Display::None,
+    }
+  }
+}
+
+/// What happens to the content of an element that doesn't fit in its padding box.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Overflow {
+  Visible,
+  /// Clipped, it can still be scrolled by the application.
+  Hidden,
+  /// Clipped and scrollable.
+  Scroll,
+  /// Like `Scroll`, there are no scroll bars to hide when the content fits.
+  Auto,
+}
+
+impl Overflow {
+  /// Keyword of the overflow, as written in a stylesheet.
+  #[must_use]
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Visible => "visible",
+      Self::Hidden => "hidden",
+      Self::Scroll => "scroll",
+      Self::Auto => "auto",
+    }
+  }
+}
+
+impl Default for Overflow {
+  fn default() -> Self {
+    Self::Visible
+  }
+}
+
+impl From<Overflow> for yoga::Overflow {
+  fn from(overflow: Overflow) -> Self {
+    match overflow {
+      Overflow::Visible => yoga::Overflow::Visible,
+      Overflow::Hidden => yoga::Overflow::Hidden,
+      Overflow::Scroll | Overflow::Auto => yoga::Overflow::Scroll,
+    }
+  }
+}
+
+/// How an element is placed, `top`, `right`, `bottom` and `left` move relative and absolute elements.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Position {
+  Static,
+  /// Offset from where the element would be, without moving its siblings.
+  Relative,
+  /// Out of the flow, offset from the padding box of the parent.
+  Absolute,
+}
+
+impl Position {
+  /// Keyword of the position, as written in a stylesheet.
+  #[must_use]
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Static => "static",
+      Self::Relative => "relative",
+      Self::Absolute => "absolute",
+    }
+  }
+}
+
+impl Default for Position {
+  fn default() -> Self {
+    Self::Static
+  }
+}
+
+// Yoga has no static position, a relative one without offsets is the same.
+impl From<Position> for yoga::PositionType {
+  fn from(position: Position) -> Self {
+    match position {
+      Position::Static | Position::Relative => yoga::PositionType::Relative,
+      Position::Absolute => yoga::PositionType::Absolute,
+    }
+  }
+}
+
+/// What the padding box of an element is filled with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Background {
+  Color(u8, u8, u8, u8),
+  LinearGradient {
+    direction: GradientDirection,
+    stops: Vec<ColorStop>,
+  },
+  /// A gradient from the center of the element to its farthest corner.
+  RadialGradient {
+    shape: GradientShape,
+    stops: Vec<ColorStop>,
+  },
+}
+
+impl Default for Background {
+  fn default() -> Self {
+    Self::Color(0, 0, 0, 0)
+  }
+}
+
+impl fmt::Display for Background {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let stops = match self {
+      Self::Color(r, g, b, a) => return write!(f, "rgba({}, {}, {}, {})", r, g, b, f32::from(*a) / 255.0),
+      Self::LinearGradient { direction, stops } => {
+        match direction {
+          GradientDirection::Angle(angle) => write!(f, "linear-gradient({}deg", angle)?,
+          GradientDirection::Corner { top, right } => write!(
+            f,
+            "linear-gradient(to {} {}",
+            if *top { "top" } else { "bottom" },
+            if *right { "right" } else { "left" }
+          )?,
+        }
+        stops
+      }
+      Self::RadialGradient { shape, stops } => {
+        match shape {
+          GradientShape::Circle => write!(f, "radial-gradient(circle")?,
+          GradientShape::Ellipse => write!(f, "radial-gradient(ellipse")?,
+        }
+        stops
+      }
+    };
+
+    for stop in stops {
+      let (r, g, b, a) = stop.color;
+      write!(f, ", rgba({}, {}, {}, {})", r, g, b, f32::from(a) / 255.0)?;
+      if let Some(position) = stop.position {
+        write!(f, " {}%", position * 100.0)?;
+      }
+    }
+
+    write!(f, ")")
+  }
+}
+
+/// Where a linear gradient goes to.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GradientDirection {
+  /// Clockwise from the top, in degrees.
+  Angle(f32),
+  /// Towards a corner, the angle depends on the size of the element so the middle of the gradient joins the
+  /// two other corners.
+  Corner { top: bool, right: bool },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GradientShape {
+  Circle,
+  Ellipse,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColorStop {
+  pub color: (u8, u8, u8, u8),
+  /// Position along the gradient between 0 and 1, `None` to spread the stop evenly between the ones around it.
+  pub position: Option<f32>,
+}
+
+/// Size of the tiles of a background image, they start at the top left corner of the element.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BackgroundSize {
+  /// As large as possible while the image fits the element.
+  Contain,
+  /// As small as possible while the image covers the element.
+  Cover,
+  /// Width and height, percentages being of the size of the element. `Auto` keeps the aspect ratio of the
+  /// image, or its size if both are `Auto`.
+  Size(yoga::Value, yoga::Value),
+}
+
+impl Default for BackgroundSize {
+  fn default() -> Self {
+    Self::Size(yoga::Value::Auto, yoga::Value::Auto)
+  }
+}
+
+impl fmt::Display for BackgroundSize {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match *self {
+      Self::Contain => write!(f, "contain"),
+      Self::Cover => write!(f, "cover"),
+      Self::Size(width, height) => write!(f, "{} {}", value_to_css(width), value_to_css(height)),
+    }
+  }
+}
+
+/// Axes a background image is repeated along.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackgroundRepeat {
+  Repeat,
+  RepeatX,
+  RepeatY,
+  NoRepeat,
+}
+
+impl BackgroundRepeat {
+  /// Keyword of the repetition, as written in a stylesheet.
+  #[must_use]
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Repeat => "repeat",
+      Self::RepeatX => "repeat-x",
+      Self::RepeatY => "repeat-y",
+      Self::NoRepeat => "no-repeat",
+    }
+  }
+}
+
+impl Default for BackgroundRepeat {
+  fn default() -> Self {
+    Self::Repeat
+  }
+}
+
+/// A shadow cast by the border box of an element, or inside of it if `inset`. Lengths are in pixels.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoxShadow {
+  pub offset_x: f32,
+  pub offset_y: f32,
+  pub blur: f32,
+  pub spread: f32,
+  pub color: (u8, u8, u8, u8),
+  pub inset: bool,
+}
+
+impl fmt::Display for BoxShadow {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.inset {
+      write!(f, "inset ")?;
     }
+
+    let (r, g, b, a) = self.color;
+    write!(
+      f,
+      "{}px {}px {}px {}px rgba({}, {}, {}, {})",
+      self.offset_x,
+      self.offset_y,
+      self.blur,
+      self.spread,
+      r,
+      g,
+      b,
+      f32::from(a) / 255.0
+    )
+  }
+}
+
+/// How the border of an element is drawn, the same on every side.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BorderStyle {
+  None,
+  Hidden,
+  Solid,
+  Dashed,
+  Dotted,
+  Double,
+  Groove,
+  Ridge,
+  Inset,
+  Outset,
+}
+
+impl BorderStyle {
+  /// Keyword of the style, as written in a stylesheet.
+  #[must_use]
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::None => "none",
+      Self::Hidden => "hidden",
+      Self::Solid => "solid",
+      Self::Dashed => "dashed",
+      Self::Dotted => "dotted",
+      Self::Double => "double",
+      Self::Groove => "groove",
+      Self::Ridge => "ridge",
+      Self::Inset => "inset",
+      Self::Outset => "outset",
+    }
+  }
+}
+
+impl Default for BorderStyle {
+  fn default() -> Self {
+    Self::None
   }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ComputedStyle {
   pub width: yoga::Value,
   pub height: yoga::Value,
-  pub background_color: (u8, u8, u8, u8),
+  pub background: Background,
+  pub direction: Direction,
   pub margin_top: yoga::Value,
   pub margin_bottom: yoga::Value,
   pub margin_left: yoga::Value,
   pub margin_right: yoga::Value,
+  pub margin_start: yoga::Value,
+  pub margin_end: yoga::Value,
+  pub padding_top: yoga::Value,
+  pub padding_bottom: yoga::Value,
+  pub padding_left: yoga::Value,
+  pub padding_right: yoga::Value,
+  pub padding_start: yoga::Value,
+  pub padding_end: yoga::Value,
+  pub border_width: f32,
+  pub border_color: (u8, u8, u8, u8),
+  pub border_style: BorderStyle,
+  pub border_radius: f32,
+  pub position: Position,
+  pub top: yoga::Value,
+  pub right: yoga::Value,
+  pub bottom: yoga::Value,
+  pub left: yoga::Value,
+  pub display: Display,
+  pub opacity: f32,
+  pub box_shadow: Option<BoxShadow>,
+  pub background_image: Option<String>,
+  pub background_size: BackgroundSize,
+  pub background_repeat: BackgroundRepeat,
+  pub overflow: Overflow,
+}
+
+impl ComputedStyle {
+  /// Width the border takes in the layout, like in CSS there's no border without a style.
+  #[must_use]
+  pub fn used_border_width(&self) -> f32 {
+    match self.border_style {
+      BorderStyle::None | BorderStyle::Hidden => 0.0,
+      _ => self.border_width,
+    }
+  }
+
+  /// Returns a declaration for every property, setting it to its computed value. Their `Declaration::value`
+  /// is how the computed value is written in a stylesheet.
+  #[must_use]
+  pub fn declarations(&self) -> Vec<Declaration> {
+    let (border_r, border_g, border_b, border_a) = self.border_color;
+    vec![
+      Declaration::Width(self.width),
+      Declaration::Height(self.height),
+      Declaration::Background(self.background.clone()),
+      Declaration::BackgroundImage(self.background_image.clone()),
+      Declaration::BackgroundSize(self.background_size),
+      Declaration::BackgroundRepeat(self.background_repeat),
+      Declaration::MarginTop(self.margin_top),
+      Declaration::MarginBottom(self.margin_bottom),
+      Declaration::MarginLeft(self.margin_left),
+      Declaration::MarginRight(self.margin_right),
+      Declaration::MarginStart(self.margin_start),
+      Declaration::MarginEnd(self.margin_end),
+      Declaration::PaddingTop(self.padding_top),
+      Declaration::PaddingBottom(self.padding_bottom),
+      Declaration::PaddingLeft(self.padding_left),
+      Declaration::PaddingRight(self.padding_right),
+      Declaration::PaddingStart(self.padding_start),
+      Declaration::PaddingEnd(self.padding_end),
+      Declaration::Direction(self.direction),
+      Declaration::BorderWidth(self.border_width),
+      Declaration::BorderColor(border_r, border_g, border_b, border_a),
+      Declaration::BorderStyle(self.border_style),
+      Declaration::BorderRadius(self.border_radius),
+      Declaration::Display(self.display),
+      Declaration::Overflow(self.overflow),
+      Declaration::Opacity(self.opacity),
+      Declaration::BoxShadow(self.box_shadow),
+      Declaration::Position(self.position),
+      Declaration::Top(self.top),
+      Declaration::Right(self.right),
+      Declaration::Bottom(self.bottom),
+      Declaration::Left(self.left),
+    ]
+  }
 }
 
 impl Default for ComputedStyle {
@@ -40,11 +491,38 @@ impl Default for ComputedStyle {
     Self {
       width: yoga::Value::Auto,
       height: yoga::Value::Auto,
-      background_color: (0, 0, 0, 0),
+      background: Background::default(),
+      direction: Direction::Inherit,
       margin_top: yoga::Value::Px(0.0),
       margin_bottom: yoga::Value::Px(0.0),
       margin_left: yoga::Value::Px(0.0),
       margin_right: yoga::Value::Px(0.0),
+      // Start and end edges take precedence over left and right, so they stay unset by default.
+      margin_start: yoga::Value::Undefined,
+      margin_end: yoga::Value::Undefined,
+      padding_top: yoga::Value::Px(0.0),
+      padding_bottom: yoga::Value::Px(0.0),
+      padding_left: yoga::Value::Px(0.0),
+      padding_right: yoga::Value::Px(0.0),
+      padding_start: yoga::Value::Undefined,
+      padding_end: yoga::Value::Undefined,
+      // `medium`, there's no `color` property for `currentcolor` so the border is black.
+      border_width: 3.0,
+      border_color: (0, 0, 0, 255),
+      border_style: BorderStyle::None,
+      border_radius: 0.0,
+      position: Position::Static,
+      top: yoga::Value::Auto,
+      right: yoga::Value::Auto,
+      bottom: yoga::Value::Auto,
+      left: yoga::Value::Auto,
+      display: Display::Flex,
+      opacity: 1.0,
+      box_shadow: None,
+      background_image: None,
+      background_size: BackgroundSize::default(),
+      background_repeat: BackgroundRepeat::default(),
+      overflow: Overflow::Visible,
     }
   }
 }
@@ -77,14 +555,31 @@ impl StyleSheet {
   }
 
   pub fn parse<'i>(&mut self, input: &mut cssparser::ParserInput<'i>) -> Result<(), Error<'i>> {
+    self.parse_with_locations(input).map(|_| ())
+  }
+
+  /// Like `parse`, also returning where each of the added rules starts.
+  pub fn parse_with_locations<'i>(
+    &mut self,
+    input: &mut cssparser::ParserInput<'i>,
+  ) -> Result<Vec<cssparser::SourceLocation>, Error<'i>> {
     let mut parser = cssparser::Parser::new(input);
 
-    let rule_list_parser = cssparser::RuleListParser::new_for_stylesheet(&mut parser, parser::QualifiedRuleParser);
-    for rule in rule_list_parser {
-      self.rules.push(rule?);
+    let mut locations = Vec::new();
+    let mut rule_list_parser = cssparser::RuleListParser::new_for_stylesheet(&mut parser, parser::QualifiedRuleParser);
+    loop {
+      rule_list_parser.input.skip_whitespace();
+      let location = rule_list_parser.input.current_source_location();
+      match rule_list_parser.next() {
+        Some(rule) => {
+          self.rules.push(rule?);
+          locations.push(location);
+        }
+        None => break,
+      }
     }
 
-    Ok(())
+    Ok(locations)
   }
 
   pub fn apply<E: ::selectors::Element<Impl = selectors::SelectorImpl>>(
@@ -94,6 +589,12 @@ impl StyleSheet {
   ) {
     self.rules.iter().for_each(|x| x.apply(element, computed));
   }
+
+  /// Formats the stylesheet as CSS, one rule per line.
+  #[must_use]
+  pub fn to_css(&self) -> String {
+    self.rules.iter().map(|rule| format!("{}\n", rule)).collect()
+  }
 }
 
 impl Default for StyleSheet {
@@ -115,6 +616,12 @@ impl StyleRule {
     element: &E,
     computed: &mut ComputedStyle,
   ) {
+    if self.matches(element) {
+      self.properties.iter().for_each(|x| x.apply(computed));
+    }
+  }
+
+  pub fn matches<E: ::selectors::Element<Impl = selectors::SelectorImpl>>(&self, element: &E) -> bool {
     let mut context = ::selectors::matching::MatchingContext::new(
       ::selectors::matching::MatchingMode::Normal,
       None,
@@ -122,14 +629,28 @@ impl StyleRule {
       ::selectors::matching::QuirksMode::NoQuirks,
     );
 
-    if ::selectors::matching::matches_selector_list(&self.selectors, element, &mut context) {
-      self.properties.iter().for_each(|x| x.apply(computed));
-    }
+    ::selectors::matching::matches_selector_list(&self.selectors, element, &mut context)
+  }
+
+  /// The selectors of the rule, as written in a stylesheet.
+  #[must_use]
+  pub fn selector_text(&self) -> String {
+    self.selectors.to_css_string()
   }
 }
 
 use cssparser::ToCss;
 
+impl fmt::Display for StyleRule {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} {{", self.selectors.to_css_string())?;
+    for declaration in &self.properties {
+      write!(f, " {};", declaration)?;
+    }
+    write!(f, " }}")
+  }
+}
+
 #[derive(Serialize, Deserialize)]
 struct SerdeStyleRule {
   selectors: String,
@@ -157,7 +678,7 @@ impl From<SerdeStyleRule> for StyleRule {
   }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Declaration {
   Width(yoga::Value),
   Height(yoga::Value),
@@ -166,6 +687,35 @@ pub enum Declaration {
   MarginBottom(yoga::Value),
   MarginLeft(yoga::Value),
   MarginRight(yoga::Value),
+  MarginStart(yoga::Value),
+  MarginEnd(yoga::Value),
+  PaddingTop(yoga::Value),
+  PaddingBottom(yoga::Value),
+  PaddingLeft(yoga::Value),
+  PaddingRight(yoga::Value),
+  PaddingStart(yoga::Value),
+  PaddingEnd(yoga::Value),
+  Direction(Direction),
+  BorderWidth(f32),
+  BorderColor(u8, u8, u8, u8),
+  BorderStyle(BorderStyle),
+  BorderRadius(f32),
+  Position(Position),
+  Top(yoga::Value),
+  Right(yoga::Value),
+  Bottom(yoga::Value),
+  Left(yoga::Value),
+  Display(Display),
+  Opacity(f32),
+  BoxShadow(Option<BoxShadow>),
+  Background(Background),
+  /// URL of the image, `None` for no image. The compiler makes the URLs of stylesheets absolute, or relative
+  /// to the document if the image is embedded in it. Other URLs are looked up in the resources of the
+  /// document as written.
+  BackgroundImage(Option<String>),
+  BackgroundSize(BackgroundSize),
+  BackgroundRepeat(BackgroundRepeat),
+  Overflow(Overflow),
 }
 
 impl Declaration {
@@ -173,11 +723,160 @@ impl Declaration {
     match self {
       Self::Width(value) => computed.width = *value,
       Self::Height(value) => computed.height = *value,
-      Self::BackgroundColor(r, g, b, a) => computed.background_color = (*r, *g, *b, *a),
+      Self::BackgroundColor(r, g, b, a) => computed.background = Background::Color(*r, *g, *b, *a),
       Self::MarginTop(value) => computed.margin_top = *value,
       Self::MarginBottom(value) => computed.margin_bottom = *value,
       Self::MarginLeft(value) => computed.margin_left = *value,
       Self::MarginRight(value) => computed.margin_right = *value,
+      Self::MarginStart(value) => computed.margin_start = *value,
+      Self::MarginEnd(value) => computed.margin_end = *value,
+      Self::PaddingTop(value) => computed.padding_top = *value,
+      Self::PaddingBottom(value) => computed.padding_bottom = *value,
+      Self::PaddingLeft(value) => computed.padding_left = *value,
+      Self::PaddingRight(value) => computed.padding_right = *value,
+      Self::PaddingStart(value) => computed.padding_start = *value,
+      Self::PaddingEnd(value) => computed.padding_end = *value,
+      Self::Direction(value) => computed.direction = *value,
+      Self::BorderWidth(value) => computed.border_width = *value,
+      Self::BorderColor(r, g, b, a) => computed.border_color = (*r, *g, *b, *a),
+      Self::BorderStyle(value) => computed.border_style = *value,
+      Self::BorderRadius(value) => computed.border_radius = *value,
+      Self::Position(value) => computed.position = *value,
+      Self::Top(value) => computed.top = *value,
+      Self::Right(value) => computed.right = *value,
+      Self::Bottom(value) => computed.bottom = *value,
+      Self::Left(value) => computed.left = *value,
+      Self::Display(value) => computed.display = *value,
+      Self::Opacity(value) => computed.opacity = *value,
+      Self::BoxShadow(value) => computed.box_shadow = *value,
+      Self::Background(value) => computed.background = value.clone(),
+      Self::BackgroundImage(value) => computed.background_image = value.clone(),
+      Self::BackgroundSize(value) => computed.background_size = *value,
+      Self::BackgroundRepeat(value) => computed.background_repeat = *value,
+      Self::Overflow(value) => computed.overflow = *value,
     }
   }
+
+  /// Name of the property, as written in a stylesheet.
+  #[must_use]
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Width(..) => "width",
+      Self::Height(..) => "height",
+      Self::BackgroundColor(..) => "background-color",
+      Self::MarginTop(..) => "margin-top",
+      Self::MarginBottom(..) => "margin-bottom",
+      Self::MarginLeft(..) => "margin-left",
+      Self::MarginRight(..) => "margin-right",
+      Self::MarginStart(..) => "margin-inline-start",
+      Self::MarginEnd(..) => "margin-inline-end",
+      Self::PaddingTop(..) => "padding-top",
+      Self::PaddingBottom(..) => "padding-bottom",
+      Self::PaddingLeft(..) => "padding-left",
+      Self::PaddingRight(..) => "padding-right",
+      Self::PaddingStart(..) => "padding-inline-start",
+      Self::PaddingEnd(..) => "padding-inline-end",
+      Self::Direction(..) => "direction",
+      Self::BorderWidth(..) => "border-width",
+      Self::BorderColor(..) => "border-color",
+      Self::BorderStyle(..) => "border-style",
+      Self::BorderRadius(..) => "border-radius",
+      Self::Position(..) => "position",
+      Self::Top(..) => "top",
+      Self::Right(..) => "right",
+      Self::Bottom(..) => "bottom",
+      Self::Left(..) => "left",
+      Self::Display(..) => "display",
+      Self::Opacity(..) => "opacity",
+      Self::BoxShadow(..) => "box-shadow",
+      Self::Background(..) => "background",
+      Self::BackgroundImage(..) => "background-image",
+      Self::BackgroundSize(..) => "background-size",
+      Self::BackgroundRepeat(..) => "background-repeat",
+      Self::Overflow(..) => "overflow",
+    }
+  }
+}
+
+fn value_to_css(value: yoga::Value) -> String {
+  match value {
+    yoga::Value::Px(v) => format!("{}px", v),
+    yoga::Value::Percent(v) => format!("{}%", v),
+    yoga::Value::Auto => "auto".to_string(),
+    yoga::Value::Undefined => "none".to_string(),
+  }
+}
+
+/// Formats the URL of an image as `url("...")`, `none` if there's no image.
+#[must_use]
+pub fn url_to_css(url: Option<&str>) -> String {
+  let url = match url {
+    Some(url) => url,
+    None => return "none".to_string(),
+  };
+
+  let mut css = "url(".to_string();
+  // Writing to a string doesn't fail.
+  let _ = cssparser::serialize_string(url, &mut css);
+  css.push(')');
+  css
+}
+
+/// Formats a colour as `rgba(r, g, b, a)`, the alpha between 0 and 1.
+#[must_use]
+pub fn color_to_css((r, g, b, a): (u8, u8, u8, u8)) -> String {
+  format!("rgba({}, {}, {}, {})", r, g, b, f32::from(a) / 255.0)
+}
+
+impl Declaration {
+  /// Value of the property, as written in a stylesheet.
+  #[must_use]
+  pub fn value(&self) -> String {
+    match *self {
+      Self::BackgroundColor(r, g, b, a) | Self::BorderColor(r, g, b, a) => color_to_css((r, g, b, a)),
+      Self::Direction(direction) => match direction {
+        Direction::Inherit => "inherit",
+        Direction::LTR => "ltr",
+        Direction::RTL => "rtl",
+      }
+      .to_string(),
+      Self::BorderWidth(length) | Self::BorderRadius(length) => format!("{}px", length),
+      Self::BorderStyle(style) => style.name().to_string(),
+      Self::Position(position) => position.name().to_string(),
+      Self::Display(display) => display.name().to_string(),
+      Self::Opacity(opacity) => opacity.to_string(),
+      Self::BoxShadow(shadow) => shadow.map_or_else(|| "none".to_string(), |x| x.to_string()),
+      Self::Background(ref background) => background.to_string(),
+      Self::BackgroundImage(ref url) => url_to_css(url.as_deref()),
+      Self::BackgroundSize(size) => size.to_string(),
+      Self::BackgroundRepeat(repeat) => repeat.name().to_string(),
+      Self::Overflow(overflow) => overflow.name().to_string(),
+
+      Self::Width(value)
+      | Self::Height(value)
+      | Self::MarginTop(value)
+      | Self::MarginBottom(value)
+      | Self::MarginLeft(value)
+      | Self::MarginRight(value)
+      | Self::MarginStart(value)
+      | Self::MarginEnd(value)
+      | Self::PaddingTop(value)
+      | Self::PaddingBottom(value)
+      | Self::PaddingLeft(value)
+      | Self::PaddingRight(value)
+      | Self::PaddingStart(value)
+      | Self::PaddingEnd(value)
+      | Self::Top(value)
+      | Self::Right(value)
+      | Self::Bottom(value)
+      | Self::Left(value) => value_to_css(value),
+    }
+  }
+}
+
+/// Formats the declaration as `name: value`, in a form `Declaration::parse` reads back.
+impl fmt::Display for Declaration {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {}", self.name(), self.value())
+  }
 }
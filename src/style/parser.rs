@@ -22,30 +22,509 @@ fn parse_yoga_value<'i, 't>(
   }
 }
 
+/// Parses a padding value, padding can't be `auto`.
+fn parse_padding_value<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<yoga::Value, cssparser::BasicParseError<'i>> {
+  let start_location = input.current_source_location();
+  match parse_yoga_value(input)? {
+    yoga::Value::Auto => Err(start_location.new_basic_unexpected_token_error(cssparser::Token::Ident("auto".into()))),
+    value => Ok(value),
+  }
+}
+
+/// Parses an opacity, a number or a percentage clamped between 0 and 1.
+fn parse_opacity<'i, 't>(input: &mut cssparser::Parser<'i, 't>) -> Result<f32, cssparser::BasicParseError<'i>> {
+  let opacity = match input.try_parse(cssparser::Parser::expect_percentage) {
+    Ok(percent) => percent,
+    Err(..) => input.expect_number()?,
+  };
+
+  Ok(opacity.max(0.0).min(1.0))
+}
+
+/// Parses an angle in degrees, from `deg`, `grad`, `rad` or `turn`. The unit can be left out for 0.
+fn parse_angle<'i, 't>(input: &mut cssparser::Parser<'i, 't>) -> Result<f32, cssparser::BasicParseError<'i>> {
+  let start_location = input.current_source_location();
+  let token = input.next()?.clone();
+  match &token {
+    cssparser::Token::Dimension { value, unit, .. } => match &*unit.to_ascii_lowercase() {
+      "deg" => Ok(*value),
+      "grad" => Ok(*value * 0.9),
+      "rad" => Ok(value.to_degrees()),
+      "turn" => Ok(*value * 360.0),
+      _ => Err(start_location.new_basic_unexpected_token_error(token.clone())),
+    },
+    cssparser::Token::Number { int_value: Some(0), .. } => Ok(0.0),
+
+    _ => Err(start_location.new_basic_unexpected_token_error(token.clone())),
+  }
+}
+
+/// Parses the direction of a linear gradient, an angle or `to` with a side or a corner.
+fn parse_gradient_direction<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<crate::GradientDirection, cssparser::BasicParseError<'i>> {
+  if let Ok(angle) = input.try_parse(parse_angle) {
+    return Ok(crate::GradientDirection::Angle(angle));
+  }
+
+  input.expect_ident_matching("to")?;
+  let mut vertical = None;
+  let mut horizontal = None;
+  for _ in 0..2 {
+    if vertical.is_none() {
+      if input.try_parse(|input| input.expect_ident_matching("top")).is_ok() {
+        vertical = Some(true);
+        continue;
+      } else if input.try_parse(|input| input.expect_ident_matching("bottom")).is_ok() {
+        vertical = Some(false);
+        continue;
+      }
+    }
+
+    if horizontal.is_none() {
+      if input.try_parse(|input| input.expect_ident_matching("right")).is_ok() {
+        horizontal = Some(true);
+        continue;
+      } else if input.try_parse(|input| input.expect_ident_matching("left")).is_ok() {
+        horizontal = Some(false);
+        continue;
+      }
+    }
+
+    break;
+  }
+
+  match (vertical, horizontal) {
+    (Some(top), Some(right)) => Ok(crate::GradientDirection::Corner { top, right }),
+    (Some(top), None) => Ok(crate::GradientDirection::Angle(if top { 0.0 } else { 180.0 })),
+    (None, Some(right)) => Ok(crate::GradientDirection::Angle(if right { 90.0 } else { 270.0 })),
+    (None, None) => {
+      let location = input.current_source_location();
+      let token = input.next()?.clone();
+      Err(location.new_basic_unexpected_token_error(token))
+    }
+  }
+}
+
+/// Parses a color with an optional position, a percentage.
+fn parse_color_stop<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<crate::ColorStop, cssparser::BasicParseError<'i>> {
+  let color = parse_color(input)?;
+  let position = input.try_parse(cssparser::Parser::expect_percentage).ok();
+  Ok(crate::ColorStop { color, position })
+}
+
+/// Parses the comma separated color stops of a gradient, there have to be at least 2 of them.
+fn parse_color_stops<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<Vec<crate::ColorStop>, cssparser::BasicParseError<'i>> {
+  let mut stops = vec![parse_color_stop(input)?];
+  loop {
+    input.expect_comma()?;
+    stops.push(parse_color_stop(input)?);
+    if input.is_exhausted() {
+      return Ok(stops);
+    }
+  }
+}
+
+fn parse_linear_gradient<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<crate::Background, cssparser::BasicParseError<'i>> {
+  let direction = input
+    .try_parse(|input| -> Result<_, cssparser::BasicParseError<'i>> {
+      let direction = parse_gradient_direction(input)?;
+      input.expect_comma()?;
+      Ok(direction)
+    })
+    .unwrap_or(crate::GradientDirection::Angle(180.0));
+
+  Ok(crate::Background::LinearGradient {
+    direction,
+    stops: parse_color_stops(input)?,
+  })
+}
+
+fn parse_radial_gradient<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<crate::Background, cssparser::BasicParseError<'i>> {
+  let shape = input
+    .try_parse(|input| -> Result<_, cssparser::BasicParseError<'i>> {
+      let shape = if input.try_parse(|input| input.expect_ident_matching("circle")).is_ok() {
+        crate::GradientShape::Circle
+      } else {
+        input.expect_ident_matching("ellipse")?;
+        crate::GradientShape::Ellipse
+      };
+      input.expect_comma()?;
+      Ok(shape)
+    })
+    .unwrap_or(crate::GradientShape::Ellipse);
+
+  Ok(crate::Background::RadialGradient {
+    shape,
+    stops: parse_color_stops(input)?,
+  })
+}
+
+/// Parses the value of `background`, a color or a gradient.
+fn parse_background<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<crate::Background, cssparser::BasicParseError<'i>> {
+  if let Ok((r, g, b, a)) = input.try_parse(parse_color) {
+    return Ok(crate::Background::Color(r, g, b, a));
+  }
+
+  let start_location = input.current_source_location();
+  let name = input.expect_function()?.clone();
+  let parse = match &*name.to_ascii_lowercase() {
+    "linear-gradient" => parse_linear_gradient,
+    "radial-gradient" => parse_radial_gradient,
+    _ => return Err(start_location.new_basic_unexpected_token_error(cssparser::Token::Function(name))),
+  };
+
+  input
+    .parse_nested_block(|input| parse(input).map_err(cssparser::ParseError::<()>::from))
+    .map_err(cssparser::ParseError::basic)
+}
+
+fn parse_background_image<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<Option<String>, cssparser::BasicParseError<'i>> {
+  if input.try_parse(|input| input.expect_ident_matching("none")).is_ok() {
+    Ok(None)
+  } else {
+    Ok(Some(input.expect_url()?.to_string()))
+  }
+}
+
+/// Parses a dimension of a background size, which can't be `none`.
+fn parse_background_dimension<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<yoga::Value, cssparser::BasicParseError<'i>> {
+  let start_location = input.current_source_location();
+  match parse_yoga_value(input)? {
+    yoga::Value::Undefined => {
+      Err(start_location.new_basic_unexpected_token_error(cssparser::Token::Ident("none".into())))
+    }
+    value => Ok(value),
+  }
+}
+
+fn parse_background_size<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<crate::BackgroundSize, cssparser::BasicParseError<'i>> {
+  if input.try_parse(|input| input.expect_ident_matching("contain")).is_ok() {
+    Ok(crate::BackgroundSize::Contain)
+  } else if input.try_parse(|input| input.expect_ident_matching("cover")).is_ok() {
+    Ok(crate::BackgroundSize::Cover)
+  } else {
+    let width = parse_background_dimension(input)?;
+    let height = input.try_parse(parse_background_dimension).unwrap_or(yoga::Value::Auto);
+    Ok(crate::BackgroundSize::Size(width, height))
+  }
+}
+
+fn parse_background_repeat<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<crate::BackgroundRepeat, cssparser::BasicParseError<'i>> {
+  use crate::BackgroundRepeat;
+
+  let start_location = input.current_source_location();
+  let ident = input.expect_ident()?.clone();
+  let repeat = match &*ident.to_ascii_lowercase() {
+    "repeat" => BackgroundRepeat::Repeat,
+    "repeat-x" => BackgroundRepeat::RepeatX,
+    "repeat-y" => BackgroundRepeat::RepeatY,
+    "no-repeat" => BackgroundRepeat::NoRepeat,
+    _ => return Err(start_location.new_basic_unexpected_token_error(cssparser::Token::Ident(ident))),
+  };
+
+  Ok(repeat)
+}
+
+/// Parses a single shadow: `none`, or the offsets, optional blur and spread, color and `inset` in any order
+/// as long as the lengths are together. The color defaults to black.
+fn parse_box_shadow<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<Option<crate::BoxShadow>, cssparser::BasicParseError<'i>> {
+  if input.try_parse(|input| input.expect_ident_matching("none")).is_ok() {
+    return Ok(None);
+  }
+
+  let mut inset = false;
+  let mut color = None;
+  let mut lengths = None;
+  loop {
+    if !inset && input.try_parse(|input| input.expect_ident_matching("inset")).is_ok() {
+      inset = true;
+      continue;
+    }
+
+    if color.is_none() {
+      if let Ok(value) = input.try_parse(parse_color) {
+        color = Some(value);
+        continue;
+      }
+    }
+
+    if lengths.is_none() {
+      if let Ok(offset_x) = input.try_parse(parse_signed_length) {
+        let offset_y = parse_signed_length(input)?;
+        let blur = input.try_parse(parse_length).unwrap_or(0.0);
+        let spread = input.try_parse(parse_signed_length).unwrap_or(0.0);
+        lengths = Some((offset_x, offset_y, blur, spread));
+        continue;
+      }
+    }
+
+    break;
+  }
+
+  let (offset_x, offset_y, blur, spread) = match lengths {
+    Some(lengths) => lengths,
+    None => {
+      let location = input.current_source_location();
+      let token = input.next()?.clone();
+      return Err(location.new_basic_unexpected_token_error(token));
+    }
+  };
+
+  Ok(Some(crate::BoxShadow {
+    offset_x,
+    offset_y,
+    blur,
+    spread,
+    color: color.unwrap_or((0, 0, 0, 255)),
+    inset,
+  }))
+}
+
+fn parse_overflow<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<crate::Overflow, cssparser::BasicParseError<'i>> {
+  use crate::Overflow;
+
+  let start_location = input.current_source_location();
+  let ident = input.expect_ident()?.clone();
+  let overflow = match &*ident.to_ascii_lowercase() {
+    "visible" => Overflow::Visible,
+    "hidden" => Overflow::Hidden,
+    "scroll" => Overflow::Scroll,
+    "auto" => Overflow::Auto,
+    _ => return Err(start_location.new_basic_unexpected_token_error(cssparser::Token::Ident(ident))),
+  };
+
+  Ok(overflow)
+}
+
+fn parse_display<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<crate::Display, cssparser::BasicParseError<'i>> {
+  if input.try_parse(|input| input.expect_ident_matching("none")).is_ok() {
+    Ok(crate::Display::None)
+  } else {
+    input.expect_ident_matching("flex")?;
+    Ok(crate::Display::Flex)
+  }
+}
+
+fn parse_position<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<crate::Position, cssparser::BasicParseError<'i>> {
+  if input.try_parse(|input| input.expect_ident_matching("relative")).is_ok() {
+    Ok(crate::Position::Relative)
+  } else if input.try_parse(|input| input.expect_ident_matching("absolute")).is_ok() {
+    Ok(crate::Position::Absolute)
+  } else {
+    input.expect_ident_matching("static")?;
+    Ok(crate::Position::Static)
+  }
+}
+
+fn parse_direction<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<crate::Direction, cssparser::BasicParseError<'i>> {
+  if input.try_parse(|input| input.expect_ident_matching("ltr")).is_ok() {
+    Ok(crate::Direction::LTR)
+  } else if input.try_parse(|input| input.expect_ident_matching("rtl")).is_ok() {
+    Ok(crate::Direction::RTL)
+  } else {
+    input.expect_ident_matching("inherit")?;
+    Ok(crate::Direction::Inherit)
+  }
+}
+
+/// Parses a color, `currentcolor` isn't supported.
+fn parse_color<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<(u8, u8, u8, u8), cssparser::BasicParseError<'i>> {
+  let start_location = input.current_source_location();
+  match cssparser::Color::parse(input)? {
+    cssparser::Color::CurrentColor => {
+      Err(start_location.new_basic_unexpected_token_error(cssparser::Token::Ident("currentcolor".into())))
+    }
+
+    cssparser::Color::RGBA(rgba) => Ok((rgba.red, rgba.green, rgba.blue, rgba.alpha)),
+  }
+}
+
+/// Parses a non-negative length in pixels, the unit can be left out for 0.
+fn parse_length<'i, 't>(input: &mut cssparser::Parser<'i, 't>) -> Result<f32, cssparser::BasicParseError<'i>> {
+  let start_location = input.current_source_location();
+  match input.next()? {
+    cssparser::Token::Dimension { value, unit, .. } if unit.eq_ignore_ascii_case("px") && *value >= 0.0 => Ok(*value),
+    cssparser::Token::Number { int_value: Some(0), .. } => Ok(0.0),
+
+    token => Err(start_location.new_basic_unexpected_token_error(token.clone())),
+  }
+}
+
+/// Parses a length in pixels that can be negative, the unit can be left out for 0.
+fn parse_signed_length<'i, 't>(input: &mut cssparser::Parser<'i, 't>) -> Result<f32, cssparser::BasicParseError<'i>> {
+  let start_location = input.current_source_location();
+  match input.next()? {
+    cssparser::Token::Dimension { value, unit, .. } if unit.eq_ignore_ascii_case("px") => Ok(*value),
+    cssparser::Token::Number { int_value: Some(0), .. } => Ok(0.0),
+
+    token => Err(start_location.new_basic_unexpected_token_error(token.clone())),
+  }
+}
+
+/// Parses a border width in pixels, or one of the `thin`, `medium` and `thick` keywords.
+fn parse_border_width<'i, 't>(input: &mut cssparser::Parser<'i, 't>) -> Result<f32, cssparser::BasicParseError<'i>> {
+  if input.try_parse(|input| input.expect_ident_matching("thin")).is_ok() {
+    Ok(1.0)
+  } else if input.try_parse(|input| input.expect_ident_matching("medium")).is_ok() {
+    Ok(3.0)
+  } else if input.try_parse(|input| input.expect_ident_matching("thick")).is_ok() {
+    Ok(5.0)
+  } else {
+    parse_length(input)
+  }
+}
+
+fn parse_border_style<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+) -> Result<crate::BorderStyle, cssparser::BasicParseError<'i>> {
+  use crate::BorderStyle;
+
+  let start_location = input.current_source_location();
+  let ident = input.expect_ident()?.clone();
+  let style = match &*ident.to_ascii_lowercase() {
+    "none" => BorderStyle::None,
+    "hidden" => BorderStyle::Hidden,
+    "solid" => BorderStyle::Solid,
+    "dashed" => BorderStyle::Dashed,
+    "dotted" => BorderStyle::Dotted,
+    "double" => BorderStyle::Double,
+    "groove" => BorderStyle::Groove,
+    "ridge" => BorderStyle::Ridge,
+    "inset" => BorderStyle::Inset,
+    "outset" => BorderStyle::Outset,
+    _ => return Err(start_location.new_basic_unexpected_token_error(cssparser::Token::Ident(ident))),
+  };
+
+  Ok(style)
+}
+
+/// Parses the 1 to 4 values of a shorthand like `margin`, returning them for the top, right, bottom and left
+/// sides. Like in CSS, a missing value is the one of the opposite side, or of the top for the left side.
+fn parse_sides<'i, 't>(
+  input: &mut cssparser::Parser<'i, 't>,
+  parse_value: fn(&mut cssparser::Parser<'i, 't>) -> Result<yoga::Value, cssparser::BasicParseError<'i>>,
+) -> Result<[yoga::Value; 4], cssparser::BasicParseError<'i>> {
+  let top = parse_value(input)?;
+  let right = input.try_parse(parse_value).unwrap_or(top);
+  let bottom = input.try_parse(parse_value).unwrap_or(top);
+  let left = input.try_parse(parse_value).unwrap_or(right);
+  Ok([top, right, bottom, left])
+}
+
 impl Declaration {
+  /// Parses the value of the property `name`, shorthands expand to a declaration for each of their longhands.
   pub fn parse<'i, 't>(
     name: &cssparser::CowRcStr<'i>,
     input: &mut cssparser::Parser<'i, 't>,
+  ) -> Result<Vec<Self>, cssparser::BasicParseError<'i>> {
+    match &**name {
+      "margin" => {
+        let [top, right, bottom, left] = parse_sides(input, parse_yoga_value)?;
+        Ok(vec![
+          Self::MarginTop(top),
+          Self::MarginRight(right),
+          Self::MarginBottom(bottom),
+          Self::MarginLeft(left),
+        ])
+      }
+
+      "padding" => {
+        let [top, right, bottom, left] = parse_sides(input, parse_padding_value)?;
+        Ok(vec![
+          Self::PaddingTop(top),
+          Self::PaddingRight(right),
+          Self::PaddingBottom(bottom),
+          Self::PaddingLeft(left),
+        ])
+      }
+
+      _ => Ok(vec![Self::parse_longhand(name, input)?]),
+    }
+  }
+
+  fn parse_longhand<'i, 't>(
+    name: &cssparser::CowRcStr<'i>,
+    input: &mut cssparser::Parser<'i, 't>,
   ) -> Result<Self, cssparser::BasicParseError<'i>> {
     match &**name {
       "width" => Ok(Self::Width(parse_yoga_value(input)?)),
       "height" => Ok(Self::Height(parse_yoga_value(input)?)),
+      "background" => Ok(Self::Background(parse_background(input)?)),
+      "background-image" => Ok(Self::BackgroundImage(parse_background_image(input)?)),
+      "background-size" => Ok(Self::BackgroundSize(parse_background_size(input)?)),
+      "background-repeat" => Ok(Self::BackgroundRepeat(parse_background_repeat(input)?)),
       "background-color" => {
-        let start_location = input.current_source_location();
-        let color = cssparser::Color::parse(input)?;
-        match color {
-          cssparser::Color::CurrentColor => {
-            Err(start_location.new_basic_unexpected_token_error(cssparser::Token::Ident("currentcolor".into())))
-          }
-
-          cssparser::Color::RGBA(rgba) => Ok(Self::BackgroundColor(rgba.red, rgba.green, rgba.blue, rgba.alpha)),
-        }
+        let (r, g, b, a) = parse_color(input)?;
+        Ok(Self::BackgroundColor(r, g, b, a))
       }
 
       "margin-top" => Ok(Self::MarginTop(parse_yoga_value(input)?)),
       "margin-bottom" => Ok(Self::MarginBottom(parse_yoga_value(input)?)),
       "margin-left" => Ok(Self::MarginLeft(parse_yoga_value(input)?)),
       "margin-right" => Ok(Self::MarginRight(parse_yoga_value(input)?)),
+      "margin-inline-start" => Ok(Self::MarginStart(parse_yoga_value(input)?)),
+      "margin-inline-end" => Ok(Self::MarginEnd(parse_yoga_value(input)?)),
+
+      "padding-top" => Ok(Self::PaddingTop(parse_padding_value(input)?)),
+      "padding-bottom" => Ok(Self::PaddingBottom(parse_padding_value(input)?)),
+      "padding-left" => Ok(Self::PaddingLeft(parse_padding_value(input)?)),
+      "padding-right" => Ok(Self::PaddingRight(parse_padding_value(input)?)),
+      "padding-inline-start" => Ok(Self::PaddingStart(parse_padding_value(input)?)),
+      "padding-inline-end" => Ok(Self::PaddingEnd(parse_padding_value(input)?)),
+
+      "direction" => Ok(Self::Direction(parse_direction(input)?)),
+
+      "border-width" => Ok(Self::BorderWidth(parse_border_width(input)?)),
+      "border-color" => {
+        let (r, g, b, a) = parse_color(input)?;
+        Ok(Self::BorderColor(r, g, b, a))
+      }
+      "border-style" => Ok(Self::BorderStyle(parse_border_style(input)?)),
+      "border-radius" => Ok(Self::BorderRadius(parse_length(input)?)),
+
+      "display" => Ok(Self::Display(parse_display(input)?)),
+      "overflow" => Ok(Self::Overflow(parse_overflow(input)?)),
+      "opacity" => Ok(Self::Opacity(parse_opacity(input)?)),
+      "box-shadow" => Ok(Self::BoxShadow(parse_box_shadow(input)?)),
+
+      "position" => Ok(Self::Position(parse_position(input)?)),
+      "top" => Ok(Self::Top(parse_yoga_value(input)?)),
+      "right" => Ok(Self::Right(parse_yoga_value(input)?)),
+      "bottom" => Ok(Self::Bottom(parse_yoga_value(input)?)),
+      "left" => Ok(Self::Left(parse_yoga_value(input)?)),
 
       _ => Err(cssparser::BasicParseError {
         kind: cssparser::BasicParseErrorKind::QualifiedRuleInvalid,
@@ -55,10 +534,23 @@ impl Declaration {
   }
 }
 
-struct DeclarationParser;
+/// Parses a bare declaration list, such as the body of a rule without its braces.
+pub fn parse_declarations<'i>(input: &mut cssparser::ParserInput<'i>) -> Result<Vec<Declaration>, crate::Error<'i>> {
+  let mut parser = cssparser::Parser::new(input);
+  let mut declarations = Vec::new();
+  for declaration in cssparser::DeclarationListParser::new(&mut parser, DeclarationParser) {
+    declarations.extend(declaration?);
+  }
+
+  Ok(declarations)
+}
+
+/// Parses the declarations of a list one by one, so callers can skip the invalid ones. Yields the expanded
+/// longhands of each declaration, see `Declaration::parse`.
+pub struct DeclarationParser;
 
 impl<'i> cssparser::DeclarationParser<'i> for DeclarationParser {
-  type Declaration = Declaration;
+  type Declaration = Vec<Declaration>;
   type Error = selectors::parser::SelectorParseErrorKind<'i>;
 
   fn parse_value<'t>(
@@ -73,7 +565,7 @@ impl<'i> cssparser::DeclarationParser<'i> for DeclarationParser {
 impl<'i> cssparser::AtRuleParser<'i> for DeclarationParser {
   type PreludeNoBlock = ();
   type PreludeBlock = ();
-  type AtRule = Declaration;
+  type AtRule = Vec<Declaration>;
   type Error = selectors::parser::SelectorParseErrorKind<'i>;
 }
 
@@ -101,7 +593,7 @@ impl<'i> cssparser::QualifiedRuleParser<'i> for QualifiedRuleParser {
     let mut declarations = Vec::new();
     for decl in decl_parser {
       let decl = decl.map_err(|(x, _)| x)?;
-      declarations.push(decl);
+      declarations.extend(decl);
     }
 
     Ok(StyleRule {
@@ -1,7 +1,8 @@
-use dom::CompiledDocument;
-use std::sync::Arc;
+use dom::{CompiledDocument, DocumentMetadata};
+use std::sync::{Arc, Mutex};
 
 use glutin::{
+  dpi::LogicalSize,
   event_loop::{EventLoopProxy, EventLoopWindowTarget},
   window::{WindowBuilder, WindowId},
   ContextBuilder, ContextWrapper, GlRequest, NotCurrent, PossiblyCurrent,
@@ -46,6 +47,25 @@ impl<T: Send + Sync + 'static> RenderNotifier for Notifier<T> {
   }
 }
 
+/// A `WindowBuilder` set up as the document asks for, what it doesn't set keeps the defaults of winit.
+#[must_use]
+pub fn window_builder(metadata: &DocumentMetadata) -> WindowBuilder {
+  let mut wb = WindowBuilder::new();
+  if let Some(title) = &metadata.title {
+    wb = wb.with_title(title);
+  }
+  if let Some((width, height)) = metadata.size {
+    wb = wb.with_inner_size(LogicalSize::new(width, height));
+  }
+  if let Some(resizable) = metadata.resizable {
+    wb = wb.with_resizable(resizable);
+  }
+  if let Some(decorations) = metadata.decorations {
+    wb = wb.with_decorations(decorations);
+  }
+  wb
+}
+
 pub struct Window {
   window_id: WindowId,
   event_handler: event::EventHandler<InternalWindow>,
@@ -93,6 +113,14 @@ impl Window {
     };
     windowing_impl.make_current();
 
+    let invalidation_proxy = Mutex::new(ep.clone());
+    doc.set_invalidation_listener(move || {
+      let _ = invalidation_proxy
+        .lock()
+        .unwrap()
+        .send_event(ProxyEvent::WakeUp(window_id));
+    });
+
     let renderer = render::Renderer::new(
       gl,
       device_pixel_ratio,
@@ -146,6 +174,10 @@ impl Window {
         self.event_handler.handle_event(event::Event::Empty);
       }
 
+      glutin::event::Event::NewEvents(glutin::event::StartCause::ResumeTimeReached { .. }) => {
+        self.event_handler.handle_event(event::Event::Empty);
+      }
+
       _ => {}
     };
   }
@@ -154,6 +186,11 @@ impl Window {
     self.event_handler.deinit();
   }
 
+  /// Returns when the next script timer is due, to be used with `ControlFlow::WaitUntil`.
+  pub fn next_timer(&self) -> Option<std::time::Instant> {
+    self.event_handler.next_timer()
+  }
+
   pub fn window(&self) -> &glutin::window::Window {
     self.event_handler.windowing.window()
   }
@@ -21,6 +21,7 @@ impl bindgen::callbacks::ParseCallbacks for BindgenCallbacks {
 
 fn main() {
   println!("cargo:rerun-if-changed=yoga/yoga/Yoga.h");
+  println!("cargo:rerun-if-changed=logger.cpp");
 
   cc::Build::new()
     .file("yoga/yoga/event/event.cpp")
@@ -35,6 +36,7 @@ fn main() {
     .file("yoga/yoga/YGStyle.cpp")
     .file("yoga/yoga/YGValue.cpp")
     .file("yoga/yoga/Yoga.cpp")
+    .file("logger.cpp")
     .flag_if_supported("-fno-omit-frame-pointer")
     .flag_if_supported("-fexceptions")
     .flag_if_supported("-fvisibility=hidden")
@@ -55,6 +57,8 @@ fn main() {
   let bindings = bindgen::Builder::default()
     .header("yoga/yoga/Yoga.h")
     .default_enum_style(bindgen::EnumVariation::Rust { non_exhaustive: false })
+    // Print options are flags that can be combined, which a Rust enum can't hold.
+    .constified_enum("YGPrintOptions")
     .whitelist_type("YG.*")
     .whitelist_var("YG.*")
     .whitelist_function("YG.*")
@@ -10,7 +10,13 @@ pub mod sys {
 }
 
 use serde::{Deserialize, Serialize};
-use std::{ffi::CStr, fmt, ops::Deref};
+use std::{
+  any::Any,
+  ffi::CStr,
+  fmt,
+  os::raw::c_char,
+  sync::{Arc, Mutex, Once},
+};
 
 #[allow(clippy::useless_attribute)]
 #[allow(clippy::wildcard_imports)]
@@ -46,6 +52,7 @@ yg_enum!(Display);
 yg_enum!(Edge);
 yg_enum!(ExperimentalFeature);
 yg_enum!(FlexDirection);
+yg_enum!(Gutter);
 yg_enum!(Justify);
 yg_enum!(LogLevel);
 yg_enum!(MeasureMode);
@@ -72,126 +79,847 @@ pub enum Value {
   Undefined,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl From<YGValue> for Value {
+  fn from(value: YGValue) -> Self {
+    match value.unit {
+      Unit::Point => Value::Px(value.value),
+      Unit::Percent => Value::Percent(value.value),
+      Unit::Auto => Value::Auto,
+      Unit::Undefined => Value::Undefined,
+    }
+  }
+}
+
+extern "C" {
+  fn frameui_yoga_set_logger(config: YGConfigRef);
+}
+
+/// Receives the messages formatted by the logger in `logger.cpp`.
+#[no_mangle]
+extern "C" fn frameui_yoga_log(level: LogLevel, message: *const c_char) {
+  let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+  let message = message.trim_end();
+  match level {
+    LogLevel::Error | LogLevel::Fatal => log::error!("{}", message),
+    LogLevel::Warn => log::warn!("{}", message),
+    LogLevel::Info => log::info!("{}", message),
+    LogLevel::Debug => log::debug!("{}", message),
+    LogLevel::Verbose => log::trace!("{}", message),
+  }
+}
+
+/// Routes messages of the default config, used by nodes created without one, to `log`.
+fn init_default_logger() {
+  static INIT: Once = Once::new();
+  INIT.call_once(|| unsafe { frameui_yoga_set_logger(YGConfigGetDefault()) });
+}
+
+/// An owned yoga config.
+///
+/// Configs are shared between nodes through an `Arc` and can only be changed
+/// before they are shared, so a layout never observes a config mid-update.
+#[derive(Debug)]
+pub struct Config {
+  inner: YGConfigRef,
+  point_scale_factor: f32,
+  use_web_defaults: bool,
+  use_legacy_stretch_behaviour: bool,
+  experimental_features: Vec<ExperimentalFeature>,
+}
+
+impl Config {
+  #[must_use]
+  pub fn new() -> Self {
+    let inner = unsafe { YGConfigNew() };
+    unsafe {
+      frameui_yoga_set_logger(inner);
+    }
+
+    Self {
+      inner,
+      point_scale_factor: 1.0,
+      use_web_defaults: false,
+      use_legacy_stretch_behaviour: false,
+      experimental_features: Vec::new(),
+    }
+  }
+
+  /// Returns the underlying yoga config for use with [`sys`].
+  #[must_use]
+  pub fn as_ptr(&self) -> YGConfigRef {
+    self.inner
+  }
+
+  #[must_use]
+  pub fn point_scale_factor(&self) -> f32 {
+    self.point_scale_factor
+  }
+
+  /// Sets the number of physical pixels per layout point, layout results are rounded to them.
+  pub fn set_point_scale_factor(&mut self, value: f32) {
+    self.point_scale_factor = value;
+    unsafe {
+      YGConfigSetPointScaleFactor(self.inner, value);
+    }
+  }
+
+  #[must_use]
+  pub fn use_web_defaults(&self) -> bool {
+    self.use_web_defaults
+  }
+
+  pub fn set_use_web_defaults(&mut self, value: bool) {
+    self.use_web_defaults = value;
+    unsafe {
+      YGConfigSetUseWebDefaults(self.inner, value);
+    }
+  }
+
+  #[must_use]
+  pub fn use_legacy_stretch_behaviour(&self) -> bool {
+    self.use_legacy_stretch_behaviour
+  }
+
+  pub fn set_use_legacy_stretch_behaviour(&mut self, value: bool) {
+    self.use_legacy_stretch_behaviour = value;
+    unsafe {
+      YGConfigSetUseLegacyStretchBehaviour(self.inner, value);
+    }
+  }
+
+  #[must_use]
+  pub fn is_experimental_feature_enabled(&self, feature: ExperimentalFeature) -> bool {
+    self.experimental_features.contains(&feature)
+  }
+
+  pub fn set_experimental_feature_enabled(&mut self, feature: ExperimentalFeature, enabled: bool) {
+    self.experimental_features.retain(|x| *x != feature);
+    if enabled {
+      self.experimental_features.push(feature);
+    }
+
+    unsafe {
+      YGConfigSetExperimentalFeatureEnabled(self.inner, feature, enabled);
+    }
+  }
+}
+
+impl Clone for Config {
+  fn clone(&self) -> Self {
+    let mut config = Self::new();
+    config.set_point_scale_factor(self.point_scale_factor);
+    config.set_use_web_defaults(self.use_web_defaults);
+    config.set_use_legacy_stretch_behaviour(self.use_legacy_stretch_behaviour);
+    for feature in &self.experimental_features {
+      config.set_experimental_feature_enabled(*feature, true);
+    }
+    config
+  }
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Drop for Config {
+  fn drop(&mut self) {
+    unsafe {
+      YGConfigFree(self.inner);
+    }
+  }
+}
+
+unsafe impl Send for Config {}
+unsafe impl Sync for Config {}
+
+/// The yoga node itself, kept alive by its [`Node`] and by the node it was inserted into.
+struct Shared {
+  inner: YGNodeRef,
+  // Yoga keeps a pointer to the config, so it lives as long as the node.
+  config: Mutex<Option<Arc<Config>>>,
+  children: Mutex<Vec<Arc<Shared>>>,
+}
+
+impl Shared {
+  fn new(inner: YGNodeRef, config: Option<Arc<Config>>) -> Arc<Self> {
+    Arc::new(Self {
+      inner,
+      config: Mutex::new(config),
+      children: Mutex::new(Vec::new()),
+    })
+  }
+}
+
+impl Drop for Shared {
+  fn drop(&mut self) {
+    // Detaches the children before they are freed along with `children`.
+    unsafe {
+      YGNodeFree(self.inner);
+    }
+  }
+}
+
+unsafe impl Send for Shared {}
+unsafe impl Sync for Shared {}
+
+/// A yoga node.
+///
+/// A parent owns the children inserted into it, a child is only freed once it was removed from its parent and
+/// its `Node` was dropped. Dropping the `Node` of a child that is still inserted keeps it in the layout, without
+/// its context.
 pub struct Node {
+  shared: Arc<Shared>,
   inner: YGNodeRef,
+  config: Option<Arc<Config>>,
+  // Boxed twice so yoga can hold a thin pointer to the context.
+  context: Option<Box<Box<dyn Any + Send + Sync>>>,
+}
+
+impl fmt::Debug for Node {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("Node")
+      .field("inner", &self.inner)
+      .field("config", &self.config)
+      .field("has_context", &self.context.is_some())
+      .finish()
+  }
 }
 
 impl Node {
   #[must_use]
-  pub unsafe fn new() -> Self {
-    Self { inner: YGNodeNew() }
+  pub fn new() -> Self {
+    init_default_logger();
+
+    Self::from_ptr(unsafe { YGNodeNew() }, None)
+  }
+
+  #[must_use]
+  pub fn new_with_config(config: &Arc<Config>) -> Self {
+    Self::from_ptr(unsafe { YGNodeNewWithConfig(config.inner) }, Some(config))
+  }
+
+  fn from_ptr(inner: YGNodeRef, config: Option<&Arc<Config>>) -> Self {
+    Self {
+      shared: Shared::new(inner, config.cloned()),
+      inner,
+      config: config.cloned(),
+      context: None,
+    }
+  }
+
+  /// Returns the config this node was given, if it doesn't use the yoga default.
+  #[must_use]
+  pub fn config(&self) -> Option<&Arc<Config>> {
+    self.config.as_ref()
+  }
+
+  pub fn set_config(&mut self, config: &Arc<Config>) {
+    unsafe {
+      YGNodeSetConfig(self.inner, config.inner);
+    }
+    *self.shared.config.lock().unwrap() = Some(Arc::clone(config));
+    self.config = Some(Arc::clone(config));
   }
 
-  pub unsafe fn free(&mut self) {
-    YGNodeFree(**self)
+  /// Attaches an arbitrary value to the node, replacing the previous one.
+  pub fn set_context<T: Any + Send + Sync>(&mut self, context: T) {
+    let mut context: Box<Box<dyn Any + Send + Sync>> = Box::new(Box::new(context));
+    unsafe {
+      YGNodeSetContext(self.inner, (&mut *context as *mut Box<dyn Any + Send + Sync>).cast());
+    }
+    self.context = Some(context);
   }
 
-  pub unsafe fn free_recursive(&mut self) {
-    YGNodeFreeRecursive(**self)
+  /// Returns the context of the node if it was set to a `T`.
+  #[must_use]
+  pub fn get_context<T: Any>(&self) -> Option<&T> {
+    self.context.as_ref().and_then(|x| x.downcast_ref())
   }
 
-  pub unsafe fn print(&self, options: PrintOptions) {
-    YGNodePrint(**self, std::mem::transmute(options.bits()));
+  pub fn clear_context(&mut self) {
+    unsafe {
+      YGNodeSetContext(self.inner, std::ptr::null_mut());
+    }
+    self.context = None;
   }
 
+  /// Returns the context of a raw node, as passed to measure and baseline functions.
+  ///
+  /// # Safety
+  ///
+  /// `node` must be a node owned by a live [`Node`] and the returned reference
+  /// must not outlive it or a call to one of its context setters.
   #[must_use]
-  pub unsafe fn child_count(&self) -> u32 {
-    YGNodeGetChildCount(**self)
+  pub unsafe fn context_from_ptr<'a, T: Any>(node: YGNodeRef) -> Option<&'a T> {
+    let context = YGNodeGetContext(node) as *const Box<dyn Any + Send + Sync>;
+    context.as_ref().and_then(|x| x.downcast_ref())
   }
 
+  /// Clones the style and layout of the node.
+  ///
+  /// The clone has no owner, children or context, but shares the config of the node.
   #[must_use]
-  pub unsafe fn get_child(&self, index: u32) -> Self {
-    Self {
-      inner: YGNodeGetChild(**self, index),
+  pub fn clone_node(&self) -> Node {
+    Self::clone_ptr(self.inner, self.config.as_ref())
+  }
+
+  /// Clones the node and every node below it.
+  ///
+  /// The clones are returned in pre-order, the first one is the clone of `self` and every
+  /// other clone is already inserted into the clone of its parent. Children use the config of
+  /// `self`, since the Rust side doesn't know about their owners.
+  #[must_use]
+  pub fn clone_subtree(&self) -> Vec<Node> {
+    let mut nodes = vec![self.clone_node()];
+    Self::clone_children(self.inner, 0, self.config.as_ref(), &mut nodes);
+    nodes
+  }
+
+  fn clone_ptr(node: YGNodeRef, config: Option<&Arc<Config>>) -> Node {
+    unsafe {
+      let inner = YGNodeClone(node);
+      // The clone still points at the children and context of the original,
+      // neither of which it owns.
+      YGNodeSetContext(inner, std::ptr::null_mut());
+      YGNodeRemoveAllChildren(inner);
+      YGNodeSetConfig(inner, config.map_or_else(|| YGConfigGetDefault(), |x| x.inner));
+
+      Self::from_ptr(inner, config)
+    }
+  }
+
+  fn clone_children(original: YGNodeRef, parent: usize, config: Option<&Arc<Config>>, nodes: &mut Vec<Node>) {
+    for i in 0..unsafe { YGNodeGetChildCount(original) } {
+      let child = unsafe { YGNodeGetChild(original, i) };
+      let mut clone = Self::clone_ptr(child, config);
+
+      nodes[parent].insert_child(&mut clone, i);
+
+      let index = nodes.len();
+      nodes.push(clone);
+      Self::clone_children(child, index, config, nodes);
     }
   }
 
-  // This should take &mut self, but that causes borrow issues when initializing dom...
-  pub unsafe fn insert_child(&self, child: YGNodeRef, index: u32) {
-    YGNodeInsertChild(**self, child, index);
+  /// Returns the underlying yoga node for use with [`sys`].
+  #[must_use]
+  pub fn as_ptr(&self) -> YGNodeRef {
+    self.inner
   }
 
-  pub unsafe fn set_width(&mut self, width: Value) {
-    match width {
-      Value::Px(v) => YGNodeStyleSetWidth(**self, v),
-      Value::Percent(v) => YGNodeStyleSetWidthPercent(**self, v),
-      Value::Auto => YGNodeStyleSetWidthAuto(**self),
-      Value::Undefined => YGNodeStyleSetWidth(**self, f32::NAN),
+  pub fn print(&self, options: PrintOptions) {
+    unsafe {
+      YGNodePrint(self.inner, options.bits() as YGPrintOptions);
     }
   }
 
-  pub unsafe fn set_height(&mut self, height: Value) {
-    match height {
-      Value::Px(v) => YGNodeStyleSetHeight(**self, v),
-      Value::Percent(v) => YGNodeStyleSetHeightPercent(**self, v),
-      Value::Auto => YGNodeStyleSetHeightAuto(**self),
-      Value::Undefined => YGNodeStyleSetHeight(**self, f32::NAN),
+  #[must_use]
+  pub fn child_count(&self) -> u32 {
+    unsafe { YGNodeGetChildCount(self.inner) }
+  }
+
+  /// Returns whether the node was inserted into another node.
+  #[must_use]
+  pub fn has_owner(&self) -> bool {
+    unsafe { !YGNodeGetOwner(self.inner).is_null() }
+  }
+
+  /// Inserts `child` at `index`, this node owns it until it's removed again.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `index` is past the last child, if `child` was already inserted into a node or if this node has a
+  /// measure function, yoga would abort or corrupt its memory otherwise.
+  pub fn insert_child(&mut self, child: &mut Node, index: u32) {
+    assert!(
+      index <= self.child_count(),
+      "child index {} out of range for {} children",
+      index,
+      self.child_count()
+    );
+    assert!(!child.has_owner(), "the child was already inserted into a node");
+    assert!(
+      unsafe { !YGNodeHasMeasureFunc(self.inner) },
+      "nodes with a measure function can't have children"
+    );
+
+    unsafe {
+      YGNodeInsertChild(self.inner, child.inner, index);
     }
+    self.shared.children.lock().unwrap().push(Arc::clone(&child.shared));
   }
 
-  pub unsafe fn set_margin(&mut self, edge: Edge, value: Value) {
-    match value {
-      Value::Px(v) => YGNodeStyleSetMargin(**self, edge, v),
-      Value::Percent(v) => YGNodeStyleSetMarginPercent(**self, edge, v),
-      Value::Auto => YGNodeStyleSetMarginAuto(**self, edge),
-      Value::Undefined => YGNodeStyleSetMargin(**self, edge, f32::NAN),
+  /// Removes `child` from this node, nothing happens if it isn't a child of this node.
+  pub fn remove_child(&mut self, child: &mut Node) {
+    unsafe {
+      YGNodeRemoveChild(self.inner, child.inner);
     }
+    self
+      .shared
+      .children
+      .lock()
+      .unwrap()
+      .retain(|x| !Arc::ptr_eq(x, &child.shared));
   }
 
-  pub unsafe fn set_padding(&mut self, edge: Edge, value: Value) {
-    match value {
-      Value::Px(v) => YGNodeStyleSetPadding(**self, edge, v),
-      Value::Percent(v) => YGNodeStyleSetPaddingPercent(**self, edge, v),
-      Value::Auto => unimplemented!(),
-      Value::Undefined => YGNodeStyleSetPadding(**self, edge, f32::NAN),
+  pub fn remove_all_children(&mut self) {
+    unsafe {
+      YGNodeRemoveAllChildren(self.inner);
     }
+    self.shared.children.lock().unwrap().clear();
   }
 
-  pub unsafe fn set_position_type(&mut self, position: PositionType) {
-    YGNodeStyleSetPositionType(**self, position);
+  pub fn set_width(&mut self, width: Value) {
+    unsafe {
+      match width {
+        Value::Px(v) => YGNodeStyleSetWidth(self.inner, v),
+        Value::Percent(v) => YGNodeStyleSetWidthPercent(self.inner, v),
+        Value::Auto => YGNodeStyleSetWidthAuto(self.inner),
+        Value::Undefined => YGNodeStyleSetWidth(self.inner, f32::NAN),
+      }
+    }
   }
 
-  pub unsafe fn set_display(&mut self, display: Display) {
-    YGNodeStyleSetDisplay(**self, display);
+  pub fn set_height(&mut self, height: Value) {
+    unsafe {
+      match height {
+        Value::Px(v) => YGNodeStyleSetHeight(self.inner, v),
+        Value::Percent(v) => YGNodeStyleSetHeightPercent(self.inner, v),
+        Value::Auto => YGNodeStyleSetHeightAuto(self.inner),
+        Value::Undefined => YGNodeStyleSetHeight(self.inner, f32::NAN),
+      }
+    }
   }
 
-  pub unsafe fn set_justify_content(&mut self, justify_content: Justify) {
-    YGNodeStyleSetJustifyContent(**self, justify_content);
+  pub fn set_margin(&mut self, edge: Edge, value: Value) {
+    unsafe {
+      match value {
+        Value::Px(v) => YGNodeStyleSetMargin(self.inner, edge, v),
+        Value::Percent(v) => YGNodeStyleSetMarginPercent(self.inner, edge, v),
+        Value::Auto => YGNodeStyleSetMarginAuto(self.inner, edge),
+        Value::Undefined => YGNodeStyleSetMargin(self.inner, edge, f32::NAN),
+      }
+    }
   }
 
-  pub unsafe fn calculate_layout(&mut self, available_width: f32, available_height: f32, owner_direction: Direction) {
-    YGNodeCalculateLayout(**self, available_width, available_height, owner_direction);
+  pub fn set_padding(&mut self, edge: Edge, value: Value) {
+    unsafe {
+      match value {
+        Value::Px(v) => YGNodeStyleSetPadding(self.inner, edge, v),
+        Value::Percent(v) => YGNodeStyleSetPaddingPercent(self.inner, edge, v),
+        // There is no `auto` padding, it's treated as unset.
+        Value::Auto | Value::Undefined => YGNodeStyleSetPadding(self.inner, edge, f32::NAN),
+      }
+    }
+  }
+
+  pub fn set_position_type(&mut self, position: PositionType) {
+    unsafe {
+      YGNodeStyleSetPositionType(self.inner, position);
+    }
+  }
+
+  /// Sets the inset of `edge`, used by relative and absolute positioning.
+  ///
+  /// Yoga has no `auto` inset, an unset edge already behaves like one, so
+  /// `Value::Auto` and `Value::Undefined` both clear the edge.
+  pub fn set_position(&mut self, edge: Edge, value: Value) {
+    unsafe {
+      match value {
+        Value::Px(v) => YGNodeStyleSetPosition(self.inner, edge, v),
+        Value::Percent(v) => YGNodeStyleSetPositionPercent(self.inner, edge, v),
+        Value::Auto | Value::Undefined => YGNodeStyleSetPosition(self.inner, edge, f32::NAN),
+      }
+    }
+  }
+
+  pub fn set_display(&mut self, display: Display) {
+    unsafe {
+      YGNodeStyleSetDisplay(self.inner, display);
+    }
+  }
+
+  pub fn set_justify_content(&mut self, justify_content: Justify) {
+    unsafe {
+      YGNodeStyleSetJustifyContent(self.inner, justify_content);
+    }
+  }
+
+  pub fn set_direction(&mut self, direction: Direction) {
+    unsafe {
+      YGNodeStyleSetDirection(self.inner, direction);
+    }
+  }
+
+  pub fn set_flex_direction(&mut self, flex_direction: FlexDirection) {
+    unsafe {
+      YGNodeStyleSetFlexDirection(self.inner, flex_direction);
+    }
+  }
+
+  pub fn set_flex_wrap(&mut self, wrap: Wrap) {
+    unsafe {
+      YGNodeStyleSetFlexWrap(self.inner, wrap);
+    }
+  }
+
+  pub fn set_overflow(&mut self, overflow: Overflow) {
+    unsafe {
+      YGNodeStyleSetOverflow(self.inner, overflow);
+    }
+  }
+
+  pub fn set_align_content(&mut self, align: Align) {
+    unsafe {
+      YGNodeStyleSetAlignContent(self.inner, align);
+    }
+  }
+
+  pub fn set_align_items(&mut self, align: Align) {
+    unsafe {
+      YGNodeStyleSetAlignItems(self.inner, align);
+    }
+  }
+
+  pub fn set_align_self(&mut self, align: Align) {
+    unsafe {
+      YGNodeStyleSetAlignSelf(self.inner, align);
+    }
+  }
+
+  pub fn set_flex(&mut self, flex: f32) {
+    unsafe {
+      YGNodeStyleSetFlex(self.inner, flex);
+    }
+  }
+
+  pub fn set_flex_grow(&mut self, flex_grow: f32) {
+    unsafe {
+      YGNodeStyleSetFlexGrow(self.inner, flex_grow);
+    }
+  }
+
+  pub fn set_flex_shrink(&mut self, flex_shrink: f32) {
+    unsafe {
+      YGNodeStyleSetFlexShrink(self.inner, flex_shrink);
+    }
+  }
+
+  pub fn set_flex_basis(&mut self, flex_basis: Value) {
+    unsafe {
+      match flex_basis {
+        Value::Px(v) => YGNodeStyleSetFlexBasis(self.inner, v),
+        Value::Percent(v) => YGNodeStyleSetFlexBasisPercent(self.inner, v),
+        Value::Auto => YGNodeStyleSetFlexBasisAuto(self.inner),
+        Value::Undefined => YGNodeStyleSetFlexBasis(self.inner, f32::NAN),
+      }
+    }
+  }
+
+  pub fn set_border(&mut self, edge: Edge, width: f32) {
+    unsafe {
+      YGNodeStyleSetBorder(self.inner, edge, width);
+    }
+  }
+
+  pub fn set_min_width(&mut self, min_width: Value) {
+    unsafe {
+      match min_width {
+        Value::Px(v) => YGNodeStyleSetMinWidth(self.inner, v),
+        Value::Percent(v) => YGNodeStyleSetMinWidthPercent(self.inner, v),
+        Value::Auto | Value::Undefined => YGNodeStyleSetMinWidth(self.inner, f32::NAN),
+      }
+    }
+  }
+
+  pub fn set_min_height(&mut self, min_height: Value) {
+    unsafe {
+      match min_height {
+        Value::Px(v) => YGNodeStyleSetMinHeight(self.inner, v),
+        Value::Percent(v) => YGNodeStyleSetMinHeightPercent(self.inner, v),
+        Value::Auto | Value::Undefined => YGNodeStyleSetMinHeight(self.inner, f32::NAN),
+      }
+    }
+  }
+
+  pub fn set_max_width(&mut self, max_width: Value) {
+    unsafe {
+      match max_width {
+        Value::Px(v) => YGNodeStyleSetMaxWidth(self.inner, v),
+        Value::Percent(v) => YGNodeStyleSetMaxWidthPercent(self.inner, v),
+        Value::Auto | Value::Undefined => YGNodeStyleSetMaxWidth(self.inner, f32::NAN),
+      }
+    }
+  }
+
+  pub fn set_max_height(&mut self, max_height: Value) {
+    unsafe {
+      match max_height {
+        Value::Px(v) => YGNodeStyleSetMaxHeight(self.inner, v),
+        Value::Percent(v) => YGNodeStyleSetMaxHeightPercent(self.inner, v),
+        Value::Auto | Value::Undefined => YGNodeStyleSetMaxHeight(self.inner, f32::NAN),
+      }
+    }
+  }
+
+  /// Sets the width to height ratio of the node, `NaN` unsets it.
+  pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+    unsafe {
+      YGNodeStyleSetAspectRatio(self.inner, aspect_ratio);
+    }
+  }
+
+  pub fn set_gap(&mut self, gutter: Gutter, gap: f32) {
+    unsafe {
+      YGNodeStyleSetGap(self.inner, gutter, gap);
+    }
+  }
+
+  /// Marks the node as needing a new layout.
+  ///
+  /// Yoga only accepts this for nodes with a measure function, setting a style
+  /// already dirties the node, so calls on other nodes are ignored.
+  pub fn mark_dirty(&mut self) {
+    unsafe {
+      if YGNodeHasMeasureFunc(self.inner) {
+        YGNodeMarkDirty(self.inner);
+      }
+    }
   }
 
   #[must_use]
-  pub unsafe fn get_top(&self) -> f32 {
-    YGNodeLayoutGetTop(**self)
+  pub fn is_dirty(&self) -> bool {
+    unsafe { YGNodeIsDirty(self.inner) }
   }
 
+  /// Returns whether the last layout pass changed the layout of this node.
+  ///
+  /// Yoga never clears this flag itself, use [`Node::set_has_new_layout`] once the new layout was consumed.
   #[must_use]
-  pub unsafe fn get_left(&self) -> f32 {
-    YGNodeLayoutGetLeft(**self)
+  pub fn has_new_layout(&self) -> bool {
+    unsafe { YGNodeGetHasNewLayout(self.inner) }
+  }
+
+  pub fn set_has_new_layout(&mut self, has_new_layout: bool) {
+    unsafe {
+      YGNodeSetHasNewLayout(self.inner, has_new_layout);
+    }
+  }
+
+  pub fn calculate_layout(&mut self, available_width: f32, available_height: f32, owner_direction: Direction) {
+    unsafe {
+      YGNodeCalculateLayout(self.inner, available_width, available_height, owner_direction);
+    }
+  }
+
+  #[must_use]
+  pub fn get_top(&self) -> f32 {
+    unsafe { YGNodeLayoutGetTop(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_left(&self) -> f32 {
+    unsafe { YGNodeLayoutGetLeft(self.inner) }
   }
 
   #[must_use]
-  pub unsafe fn get_width(&self) -> f32 {
-    YGNodeLayoutGetWidth(**self)
+  pub fn get_width(&self) -> f32 {
+    unsafe { YGNodeLayoutGetWidth(self.inner) }
   }
 
   #[must_use]
-  pub unsafe fn get_height(&self) -> f32 {
-    YGNodeLayoutGetHeight(**self)
+  pub fn get_height(&self) -> f32 {
+    unsafe { YGNodeLayoutGetHeight(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_right(&self) -> f32 {
+    unsafe { YGNodeLayoutGetRight(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_bottom(&self) -> f32 {
+    unsafe { YGNodeLayoutGetBottom(self.inner) }
+  }
+
+  /// Returns the resolved layout direction of the node.
+  #[must_use]
+  pub fn get_direction(&self) -> Direction {
+    unsafe { YGNodeLayoutGetDirection(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_had_overflow(&self) -> bool {
+    unsafe { YGNodeLayoutGetHadOverflow(self.inner) }
+  }
+
+  /// Returns the computed margin of `edge`, only physical edges and `Start`/`End` are valid.
+  #[must_use]
+  pub fn get_layout_margin(&self, edge: Edge) -> f32 {
+    unsafe { YGNodeLayoutGetMargin(self.inner, edge) }
+  }
+
+  #[must_use]
+  pub fn get_layout_border(&self, edge: Edge) -> f32 {
+    unsafe { YGNodeLayoutGetBorder(self.inner, edge) }
+  }
+
+  #[must_use]
+  pub fn get_layout_padding(&self, edge: Edge) -> f32 {
+    unsafe { YGNodeLayoutGetPadding(self.inner, edge) }
+  }
+
+  #[must_use]
+  pub fn get_style_direction(&self) -> Direction {
+    unsafe { YGNodeStyleGetDirection(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_style_flex_direction(&self) -> FlexDirection {
+    unsafe { YGNodeStyleGetFlexDirection(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_style_justify_content(&self) -> Justify {
+    unsafe { YGNodeStyleGetJustifyContent(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_style_align_content(&self) -> Align {
+    unsafe { YGNodeStyleGetAlignContent(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_style_align_items(&self) -> Align {
+    unsafe { YGNodeStyleGetAlignItems(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_style_align_self(&self) -> Align {
+    unsafe { YGNodeStyleGetAlignSelf(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_style_position_type(&self) -> PositionType {
+    unsafe { YGNodeStyleGetPositionType(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_style_flex_wrap(&self) -> Wrap {
+    unsafe { YGNodeStyleGetFlexWrap(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_style_overflow(&self) -> Overflow {
+    unsafe { YGNodeStyleGetOverflow(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_style_display(&self) -> Display {
+    unsafe { YGNodeStyleGetDisplay(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_style_flex_grow(&self) -> f32 {
+    unsafe { YGNodeStyleGetFlexGrow(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_style_flex_shrink(&self) -> f32 {
+    unsafe { YGNodeStyleGetFlexShrink(self.inner) }
+  }
+
+  #[must_use]
+  pub fn get_style_flex_basis(&self) -> Value {
+    unsafe { YGNodeStyleGetFlexBasis(self.inner) }.into()
+  }
+
+  #[must_use]
+  pub fn get_style_position(&self, edge: Edge) -> Value {
+    unsafe { YGNodeStyleGetPosition(self.inner, edge) }.into()
+  }
+
+  #[must_use]
+  pub fn get_style_margin(&self, edge: Edge) -> Value {
+    unsafe { YGNodeStyleGetMargin(self.inner, edge) }.into()
+  }
+
+  #[must_use]
+  pub fn get_style_padding(&self, edge: Edge) -> Value {
+    unsafe { YGNodeStyleGetPadding(self.inner, edge) }.into()
+  }
+
+  #[must_use]
+  pub fn get_style_border(&self, edge: Edge) -> f32 {
+    unsafe { YGNodeStyleGetBorder(self.inner, edge) }
+  }
+
+  #[must_use]
+  pub fn get_style_width(&self) -> Value {
+    unsafe { YGNodeStyleGetWidth(self.inner) }.into()
+  }
+
+  #[must_use]
+  pub fn get_style_height(&self) -> Value {
+    unsafe { YGNodeStyleGetHeight(self.inner) }.into()
+  }
+
+  #[must_use]
+  pub fn get_style_min_width(&self) -> Value {
+    unsafe { YGNodeStyleGetMinWidth(self.inner) }.into()
+  }
+
+  #[must_use]
+  pub fn get_style_min_height(&self) -> Value {
+    unsafe { YGNodeStyleGetMinHeight(self.inner) }.into()
+  }
+
+  #[must_use]
+  pub fn get_style_max_width(&self) -> Value {
+    unsafe { YGNodeStyleGetMaxWidth(self.inner) }.into()
+  }
+
+  #[must_use]
+  pub fn get_style_max_height(&self) -> Value {
+    unsafe { YGNodeStyleGetMaxHeight(self.inner) }.into()
+  }
+
+  #[must_use]
+  pub fn get_style_gap(&self, gutter: Gutter) -> f32 {
+    unsafe { YGNodeStyleGetGap(self.inner, gutter) }
+  }
+
+  /// Returns the aspect ratio of the node, `NaN` if it isn't set.
+  #[must_use]
+  pub fn get_style_aspect_ratio(&self) -> f32 {
+    unsafe { YGNodeStyleGetAspectRatio(self.inner) }
   }
 }
 
-unsafe impl Send for Node {}
-unsafe impl Sync for Node {}
+impl PartialEq for Node {
+  fn eq(&self, other: &Self) -> bool {
+    self.inner == other.inner
+  }
+}
 
-impl Deref for Node {
-  type Target = YGNodeRef;
+impl Eq for Node {}
 
-  fn deref(&self) -> &Self::Target {
-    &self.inner
+impl Default for Node {
+  fn default() -> Self {
+    Self::new()
   }
 }
+
+impl Drop for Node {
+  fn drop(&mut self) {
+    // The yoga node may outlive the context in its parent.
+    if self.context.is_some() {
+      self.clear_context();
+    }
+  }
+}
+
+unsafe impl Send for Node {}
+unsafe impl Sync for Node {}
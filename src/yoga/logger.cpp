@@ -0,0 +1,30 @@
+// Formats yoga log messages so they can be forwarded to the `log` crate,
+// Rust can't consume a `va_list` on stable.
+
+#include <cstdarg>
+#include <cstdio>
+#include <vector>
+
+#include <yoga/Yoga.h>
+
+extern "C" void frameui_yoga_log(YGLogLevel level, const char *message);
+
+static int frameui_yoga_logger(YGConfigRef, YGNodeRef, YGLogLevel level, const char *format, va_list args) {
+  va_list copy;
+  va_copy(copy, args);
+  int length = std::vsnprintf(nullptr, 0, format, copy);
+  va_end(copy);
+
+  if (length < 0) {
+    return length;
+  }
+
+  std::vector<char> message(length + 1);
+  std::vsnprintf(message.data(), message.size(), format, args);
+  frameui_yoga_log(level, message.data());
+  return length;
+}
+
+extern "C" void frameui_yoga_set_logger(YGConfigRef config) {
+  YGConfigSetLogger(config, frameui_yoga_logger);
+}
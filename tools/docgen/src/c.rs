@@ -13,11 +13,7 @@ pub struct Module<'tu> {
 impl Module<'_> {
   pub fn to_docs(&self) -> doc::Module {
     let mut children: Vec<_> = self.children.iter().map(|(_, x)| x.to_docs()).collect();
-    children.sort_by_key(|x| match x {
-      doc::Definition::Struct(x) => x.name.clone(),
-      doc::Definition::Typedef(x) => x.name.clone(),
-      doc::Definition::DataStruct(x) => x.name.clone(),
-    });
+    children.sort_by_key(|x| x.name().to_string());
 
     doc::Module {
       name: self.name.clone(),
@@ -31,6 +27,8 @@ pub enum Definition<'tu> {
   Struct(Struct<'tu>),
   Typedef(Typedef<'tu>),
   DataStruct(DataStruct<'tu>),
+  Enum(Enum<'tu>),
+  Constant(Constant<'tu>),
 }
 
 impl Definition<'_> {
@@ -39,6 +37,8 @@ impl Definition<'_> {
       Self::Struct(s) => doc::Definition::Struct(s.to_docs()),
       Self::Typedef(s) => doc::Definition::Typedef(s.to_docs()),
       Self::DataStruct(s) => doc::Definition::DataStruct(s.to_docs()),
+      Self::Enum(s) => doc::Definition::Enum(s.to_docs()),
+      Self::Constant(s) => doc::Definition::Constant(s.to_docs()),
     }
   }
 }
@@ -64,6 +64,8 @@ impl Typedef<'_> {
       declaration: self.entity.get_pretty_printer().print(),
       brief: self.entity.get_comment_brief(),
       description,
+      examples: Vec::new(),
+      links: Vec::new(),
     }
   }
 }
@@ -95,6 +97,8 @@ impl Struct<'_> {
       brief: self.entity.get_comment_brief(),
       description,
       methods,
+      examples: Vec::new(),
+      links: Vec::new(),
     }
   }
 }
@@ -148,6 +152,8 @@ impl Method<'_> {
       declaration,
       brief: self.entity.get_comment_brief(),
       description,
+      examples: Vec::new(),
+      links: Vec::new(),
     }
   }
 }
@@ -177,6 +183,8 @@ impl DataStruct<'_> {
       brief: self.entity.get_comment_brief(),
       description,
       fields,
+      examples: Vec::new(),
+      links: Vec::new(),
     }
   }
 }
@@ -193,6 +201,105 @@ impl Field<'_> {
       declaration: self.entity.get_pretty_printer().print(),
       brief: self.entity.get_comment_brief(),
       description: self.entity.get_comment(),
+      links: Vec::new(),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Enum<'tu> {
+  pub name: String,
+  pub entity: Entity<'tu>,
+  pub variants: Vec<Entity<'tu>>,
+}
+
+impl Enum<'_> {
+  pub fn to_docs(&self) -> doc::Enum {
+    let (description, _) = parse_comment(self.entity.get_comment().unwrap());
+
+    let description = if description.len() == 0 {
+      None
+    } else {
+      Some(description)
+    };
+
+    let variants = self
+      .variants
+      .iter()
+      .map(|x| {
+        let (description, _) = parse_comment(x.get_comment().unwrap_or_default());
+
+        doc::Variant {
+          name: x.get_name().unwrap(),
+          value: x.get_enum_constant_value().unwrap().0,
+          brief: x.get_comment_brief(),
+          description: if description.is_empty() {
+            None
+          } else {
+            Some(description)
+          },
+        }
+      })
+      .collect();
+
+    doc::Enum {
+      name: self.name.clone(),
+      brief: self.entity.get_comment_brief(),
+      description,
+      variants,
+      examples: Vec::new(),
+      links: Vec::new(),
+    }
+  }
+}
+
+/// A `#define` or a `const` variable.
+#[derive(Debug, Clone)]
+pub struct Constant<'tu> {
+  pub name: String,
+  pub entity: Entity<'tu>,
+  /// clang doesn't attach comments to macros, so they're read from the header.
+  pub comment: String,
+}
+
+impl Constant<'_> {
+  #[must_use]
+  pub fn is_macro(&self) -> bool {
+    self.entity.get_kind() == EntityKind::MacroDefinition
+  }
+
+  pub fn to_docs(&self) -> doc::Constant {
+    let (description, _) = parse_comment(self.comment.clone());
+
+    let description = if description.len() == 0 {
+      None
+    } else {
+      Some(description)
+    };
+
+    let declaration = if self.is_macro() {
+      let tokens: Vec<_> = self
+        .entity
+        .get_range()
+        .unwrap()
+        .tokenize()
+        .iter()
+        .map(Token::get_spelling)
+        .collect();
+      format!("#define {}", tokens.join(" "))
+    } else {
+      self.entity.get_pretty_printer().print()
+    };
+
+    doc::Constant {
+      name: self.name.clone(),
+      declaration,
+      brief: description
+        .as_ref()
+        .map(|x| x.trim().split("\n\n").next().unwrap().replace('\n', " ")),
+      description,
+      examples: Vec::new(),
+      links: Vec::new(),
     }
   }
 }
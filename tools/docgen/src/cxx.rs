@@ -1,8 +1,4 @@
-use clang::*;
-
-use std::collections::HashMap;
-
-use super::{c::*, doc, parse_comment};
+use super::c::*;
 
 fn uppercase_first(s: &str) -> String {
   let mut c = s.chars();
@@ -16,6 +12,30 @@ pub fn to_pascal_case(s: &str) -> String {
   s.split('_').map(|x| uppercase_first(x)).collect::<String>()
 }
 
+/// Prefixes the C API types in `ty` with `c_api::`, those are the only capitalized identifiers.
+fn qualify(ty: &str) -> String {
+  let mut out = String::new();
+  let mut ident = String::new();
+
+  for c in ty.chars().chain(std::iter::once(' ')) {
+    if c.is_ascii_alphanumeric() || c == '_' {
+      ident.push(c);
+      continue;
+    }
+
+    if ident.starts_with(|c: char| c.is_ascii_uppercase()) {
+      out += "c_api::";
+    }
+
+    out += &ident;
+    ident.clear();
+    out.push(c);
+  }
+
+  out.pop();
+  out
+}
+
 impl Module<'_> {
   pub fn to_cxx(&self) -> String {
     let children = self
@@ -41,7 +61,11 @@ impl Definition<'_> {
     match self {
       Self::Struct(s) => s.to_cxx(),
       Self::Typedef(s) => s.to_cxx(),
-      Self::DataStruct(s) => unimplemented!(),
+      Self::DataStruct(s) => s.to_cxx(),
+      Self::Enum(s) => format!("using {0} = c_api::{0};", s.name),
+      // Macros aren't scoped by the namespace, so they're usable as is.
+      Self::Constant(s) if s.is_macro() => String::new(),
+      Self::Constant(s) => format!("using c_api::{};", s.name),
     }
   }
 }
@@ -52,6 +76,13 @@ impl Typedef<'_> {
   }
 }
 
+impl DataStruct<'_> {
+  pub fn to_cxx(&self) -> String {
+    // Plain data is passed by value, so the C struct can be used as is.
+    format!("using {0} = c_api::{0};", self.name)
+  }
+}
+
 impl Struct<'_> {
   pub fn to_cxx(&self) -> String {
     let mut methods: Vec<_> = self.methods.iter().collect();
@@ -64,6 +95,11 @@ impl Struct<'_> {
         public:
           {}
 
+          {0}(const {0} &) = delete;
+          {0} &operator=(const {0} &) = delete;
+
+          {0}({0} &&other) : self(other.TakeInternalPointer()) {{}}
+
           c_api::{0} *GetInternalPointer() {{
             return self;
           }}
@@ -100,7 +136,7 @@ impl Method<'_> {
     };
 
     let name = to_pascal_case(&self.name);
-    let return_type = self.entity.get_result_type().unwrap().get_display_name();
+    let return_type = qualify(&self.entity.get_result_type().unwrap().get_display_name());
     let c_name = self.entity.get_name().unwrap();
 
     let mut iter = c_name.splitn(2, "_");
@@ -110,7 +146,7 @@ impl Method<'_> {
     let mut c_args = Vec::new();
     for arg in self.entity.get_arguments().unwrap() {
       let name = arg.get_display_name().unwrap();
-      let ty = arg.get_type().unwrap().get_display_name();
+      let ty = qualify(&arg.get_type().unwrap().get_display_name());
       if name == "self" && kind == MethodKind::StaticMethod {
         c_args.push(name);
         kind = MethodKind::Method;
@@ -147,13 +183,24 @@ impl Method<'_> {
         c_name = c_name,
       ),
 
-      _ => format!(
+      MethodKind::Method => format!(
         "
-          {static_keyword} {ret} {name}({args}) {{
+          {ret} {name}({args}) {{
             assert(self != nullptr);
             return c_api::{c_name}({c_args});
           }}",
-        static_keyword = if kind == MethodKind::StaticMethod { "static" } else { "" },
+        ret = return_type,
+        name = name,
+        args = args,
+        c_name = c_name,
+        c_args = c_args,
+      ),
+
+      MethodKind::StaticMethod => format!(
+        "
+          static {ret} {name}({args}) {{
+            return c_api::{c_name}({c_args});
+          }}",
         ret = return_type,
         name = name,
         args = args,
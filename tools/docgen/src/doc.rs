@@ -20,6 +20,21 @@ pub enum Definition {
   Typedef(Typedef),
   Struct(Struct),
   DataStruct(DataStruct),
+  Enum(Enum),
+  Constant(Constant),
+}
+
+impl Definition {
+  #[must_use]
+  pub fn name(&self) -> &str {
+    match self {
+      Self::Typedef(x) => &x.name,
+      Self::Struct(x) => &x.name,
+      Self::DataStruct(x) => &x.name,
+      Self::Enum(x) => &x.name,
+      Self::Constant(x) => &x.name,
+    }
+  }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -28,6 +43,8 @@ pub struct Typedef {
   pub declaration: String,
   pub brief: Option<String>,
   pub description: Option<String>,
+  pub examples: Vec<Example>,
+  pub links: Vec<Link>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -36,6 +53,8 @@ pub struct Struct {
   pub brief: Option<String>,
   pub description: Option<String>,
   pub methods: Vec<Method>,
+  pub examples: Vec<Example>,
+  pub links: Vec<Link>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -44,6 +63,8 @@ pub struct Method {
   pub declaration: String,
   pub brief: Option<String>,
   pub description: Option<String>,
+  pub examples: Vec<Example>,
+  pub links: Vec<Link>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -52,6 +73,8 @@ pub struct DataStruct {
   pub brief: Option<String>,
   pub description: Option<String>,
   pub fields: Vec<Field>,
+  pub examples: Vec<Example>,
+  pub links: Vec<Link>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -60,4 +83,48 @@ pub struct Field {
   pub declaration: String,
   pub brief: Option<String>,
   pub description: Option<String>,
+  pub links: Vec<Link>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Enum {
+  pub name: String,
+  pub brief: Option<String>,
+  pub description: Option<String>,
+  pub variants: Vec<Variant>,
+  pub examples: Vec<Example>,
+  pub links: Vec<Link>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Variant {
+  pub name: String,
+  pub value: i64,
+  pub brief: Option<String>,
+  pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Constant {
+  pub name: String,
+  pub declaration: String,
+  pub brief: Option<String>,
+  pub description: Option<String>,
+  pub examples: Vec<Example>,
+  pub links: Vec<Link>,
+}
+
+/// A documented definition mentioned by an item, see `Root::resolve_links`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Link {
+  pub name: String,
+  pub module: String,
+  pub item: String,
+}
+
+/// A fenced code block taken from a comment.
+#[derive(Debug, Clone, Serialize)]
+pub struct Example {
+  pub language: String,
+  pub code: String,
 }
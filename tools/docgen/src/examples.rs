@@ -0,0 +1,145 @@
+use std::{path::Path, process::Command};
+
+use super::doc::{self, Example};
+
+/// Moves the fenced code blocks out of `description`, untagged blocks are written in `language`.
+fn extract(description: &mut Option<String>, language: &str) -> Vec<Example> {
+  let text = match description.take() {
+    Some(text) => text,
+    None => return Vec::new(),
+  };
+
+  let mut examples = Vec::new();
+  let mut rest = String::new();
+  let mut current: Option<Example> = None;
+
+  for line in text.lines() {
+    let trimmed = line.trim();
+    match (&mut current, trimmed.strip_prefix("```")) {
+      (None, Some(info)) => {
+        let info = info.split(',').next().unwrap().trim();
+        current = Some(Example {
+          language: if info.is_empty() { language } else { info }.to_string(),
+          code: String::new(),
+        });
+      }
+
+      (Some(_), Some(_)) => examples.push(current.take().unwrap()),
+
+      (Some(example), None) => {
+        // C comments keep the space after `*`.
+        example.code += line.strip_prefix(' ').unwrap_or(line);
+        example.code += "\n";
+      }
+
+      (None, None) => {
+        rest += line;
+        rest += "\n";
+      }
+    }
+  }
+
+  // An unterminated block is left in the description.
+  if let Some(example) = current {
+    rest += &format!("```{}\n{}", example.language, example.code);
+  }
+
+  if !rest.trim().is_empty() {
+    *description = Some(format!("{}\n", rest.trim_end()));
+  }
+
+  examples
+}
+
+impl doc::Root {
+  /// Moves the code blocks of every comment into the `examples` of their item.
+  pub fn extract_examples(&mut self) {
+    let language = self.language.to_lowercase();
+
+    for module in &mut self.modules {
+      for child in &mut module.children {
+        match child {
+          doc::Definition::Typedef(x) => x.examples = extract(&mut x.description, &language),
+
+          doc::Definition::Struct(x) => {
+            x.examples = extract(&mut x.description, &language);
+            for method in &mut x.methods {
+              method.examples = extract(&mut method.description, &language);
+            }
+          }
+
+          doc::Definition::DataStruct(x) => x.examples = extract(&mut x.description, &language),
+          doc::Definition::Enum(x) => x.examples = extract(&mut x.description, &language),
+          doc::Definition::Constant(x) => x.examples = extract(&mut x.description, &language),
+        }
+      }
+    }
+  }
+
+  /// Compiles the C examples against `headers` with clang, returning the number of failed examples.
+  ///
+  /// Examples without a `main` are wrapped in one, so they can be written as plain statements.
+  pub fn check_examples(&self, headers: &[&Path], clang_args: &[&str]) -> usize {
+    let mut examples = Vec::new();
+    for module in &self.modules {
+      for child in &module.children {
+        match child {
+          doc::Definition::Typedef(x) => examples.extend(x.examples.iter().map(|e| (x.name.clone(), e))),
+          doc::Definition::DataStruct(x) => examples.extend(x.examples.iter().map(|e| (x.name.clone(), e))),
+          doc::Definition::Enum(x) => examples.extend(x.examples.iter().map(|e| (x.name.clone(), e))),
+          doc::Definition::Constant(x) => examples.extend(x.examples.iter().map(|e| (x.name.clone(), e))),
+          doc::Definition::Struct(x) => {
+            examples.extend(x.examples.iter().map(|e| (x.name.clone(), e)));
+            for method in &x.methods {
+              examples.extend(
+                method
+                  .examples
+                  .iter()
+                  .map(|e| (format!("{}_{}", x.name, method.name), e)),
+              );
+            }
+          }
+        }
+      }
+    }
+
+    let dir = std::env::temp_dir().join("docgen-examples");
+    std::fs::create_dir_all(&dir).unwrap();
+    let includes: String = headers
+      .iter()
+      .map(|x| format!("#include \"{}\"\n", std::fs::canonicalize(x).unwrap().display()))
+      .collect();
+
+    let mut failed = 0;
+    for (i, (name, example)) in examples.into_iter().filter(|(_, e)| e.language == "c").enumerate() {
+      let code = if example.code.contains("main(") {
+        example.code.clone()
+      } else {
+        format!("int main(void) {{\n{}\n  return 0;\n}}\n", example.code)
+      };
+
+      let path = dir.join(format!("{}-{}.c", name, i));
+      std::fs::write(&path, format!("{}\n{}", includes, code)).unwrap();
+
+      let status = Command::new("clang")
+        .arg("-fsyntax-only")
+        .args(clang_args)
+        .arg(&path)
+        .status();
+
+      match status {
+        Ok(status) if status.success() => {}
+        Ok(_) => {
+          eprintln!("example for `{}` failed to compile: {}", name, path.display());
+          failed += 1;
+        }
+        Err(e) => {
+          eprintln!("could not run clang: {}", e);
+          return failed + 1;
+        }
+      }
+    }
+
+    failed
+  }
+}
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use super::doc::{self, Link};
+
+fn identifiers(text: &str) -> impl Iterator<Item = &str> {
+  text
+    .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+    .filter(|x| !x.is_empty())
+}
+
+struct Resolver {
+  targets: HashMap<String, Link>,
+}
+
+impl Resolver {
+  /// Links every definition mentioned in `texts`, except `own` which is the page they're shown on.
+  fn resolve<'a>(&self, own: &str, texts: impl IntoIterator<Item = &'a Option<String>>) -> Vec<Link> {
+    let mut links: Vec<Link> = Vec::new();
+
+    for text in texts.into_iter().flatten() {
+      for ident in identifiers(text) {
+        if let Some(link) = self.targets.get(ident) {
+          if link.item != own && !links.contains(link) {
+            links.push(link.clone());
+          }
+        }
+      }
+    }
+
+    links
+  }
+}
+
+impl doc::Root {
+  /// Fills in the `links` of every item, linking the definitions mentioned by its declaration and comments.
+  pub fn resolve_links(&mut self) {
+    let mut targets = HashMap::new();
+    for module in &self.modules {
+      for child in &module.children {
+        let name = child.name().to_string();
+        targets.insert(
+          name.clone(),
+          Link {
+            name: name.clone(),
+            module: module.name.clone(),
+            item: name,
+          },
+        );
+      }
+    }
+
+    let resolver = Resolver { targets };
+    for module in &mut self.modules {
+      for child in &mut module.children {
+        match child {
+          doc::Definition::Typedef(x) => {
+            x.links = resolver.resolve(
+              &x.name,
+              &[Some(x.declaration.clone()), x.brief.clone(), x.description.clone()],
+            );
+          }
+
+          doc::Definition::Struct(x) => {
+            x.links = resolver.resolve(&x.name, &[x.brief.clone(), x.description.clone()]);
+            for method in &mut x.methods {
+              method.links = resolver.resolve(
+                &x.name,
+                &[
+                  Some(method.declaration.clone()),
+                  method.brief.clone(),
+                  method.description.clone(),
+                ],
+              );
+            }
+          }
+
+          doc::Definition::Enum(x) => {
+            x.links = resolver.resolve(&x.name, &[x.brief.clone(), x.description.clone()]);
+          }
+
+          doc::Definition::Constant(x) => {
+            x.links = resolver.resolve(
+              &x.name,
+              &[Some(x.declaration.clone()), x.brief.clone(), x.description.clone()],
+            );
+          }
+
+          doc::Definition::DataStruct(x) => {
+            x.links = resolver.resolve(&x.name, &[x.brief.clone(), x.description.clone()]);
+            for field in &mut x.fields {
+              field.links = resolver.resolve(
+                &x.name,
+                &[
+                  Some(field.declaration.clone()),
+                  field.brief.clone(),
+                  field.description.clone(),
+                ],
+              );
+            }
+          }
+        }
+      }
+    }
+  }
+}
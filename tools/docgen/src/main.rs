@@ -1,6 +1,11 @@
 use clang::*;
+use clap::{App, Arg};
 
-use std::collections::{BTreeMap, HashMap};
+use std::{
+  collections::{BTreeMap, HashMap},
+  io::prelude::*,
+  path::Path,
+};
 
 fn parse_comment(comment: String) -> (String, HashMap<String, Option<String>>) {
   let mut description = String::new();
@@ -30,21 +35,120 @@ fn parse_comment(comment: String) -> (String, HashMap<String, Option<String>>) {
   (description, config)
 }
 
+fn enum_variants(e: Entity) -> Vec<Entity> {
+  e.get_children()
+    .into_iter()
+    .filter(|x| x.get_kind() == EntityKind::EnumConstantDecl)
+    .collect()
+}
+
+/// Reads the `/** ... */` comment right above a macro definition.
+fn macro_comment(e: Entity) -> Option<String> {
+  let location = e.get_location()?.get_file_location();
+  let source = std::fs::read_to_string(location.file?.get_path()).ok()?;
+  let lines: Vec<_> = source.lines().take(location.line as usize - 1).collect();
+
+  if !lines.last()?.trim_end().ends_with("*/") {
+    return None;
+  }
+
+  let start = lines.iter().rposition(|x| x.trim_start().starts_with("/**"))?;
+  Some(lines[start..].join("\n"))
+}
+
 mod c;
 mod cxx;
 mod doc;
+mod examples;
+mod links;
+mod markdown;
+mod rust;
 
 fn main() {
+  let matches = App::new(env!("CARGO_PKG_NAME"))
+    .version(env!("CARGO_PKG_VERSION"))
+    .author(env!("CARGO_PKG_AUTHORS"))
+    .arg(
+      Arg::with_name("format")
+        .short("f")
+        .long("format")
+        .value_name("FORMAT")
+        .help("Sets the output format")
+        .possible_values(&["json", "markdown"])
+        .default_value("json"),
+    )
+    .arg(
+      Arg::with_name("check-examples")
+        .long("check-examples")
+        .help("Compiles the C examples with clang"),
+    )
+    .arg(
+      Arg::with_name("header")
+        .long("header")
+        .value_name("FILE")
+        .help("Adds a C API header to document")
+        .multiple(true)
+        .number_of_values(1)
+        .default_value("./include/project-a.h"),
+    )
+    .arg(
+      Arg::with_name("clang-arg")
+        .long("clang-arg")
+        .value_name("ARG")
+        .help("Passes an argument to clang when parsing the headers and compiling examples")
+        .multiple(true)
+        .number_of_values(1)
+        .allow_hyphen_values(true),
+    )
+    .arg(
+      Arg::with_name("out-dir")
+        .short("o")
+        .long("out-dir")
+        .value_name("DIR")
+        .help("Sets the output directory, defaults to `./doc/src` for JSON and `./doc/book/src` for Markdown"),
+    )
+    .arg(
+      Arg::with_name("cxx-header")
+        .long("cxx-header")
+        .value_name("FILE")
+        .help("Sets the generated C++ header")
+        .default_value("./include/project-a.hpp"),
+    )
+    .arg(
+      Arg::with_name("rustdoc")
+        .long("rustdoc")
+        .value_name("FILE")
+        .help("Adds a crate documented by `rustdoc --output-format json`")
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .get_matches();
+
+  let headers: Vec<_> = matches.values_of("header").unwrap().map(Path::new).collect();
+  let clang_args: Vec<_> = matches.values_of("clang-arg").into_iter().flatten().collect();
+
   let clang = Clang::new().unwrap();
   let index = Index::new(&clang, false, false);
-  let tu = index.parser("./include/project-a.h").parse().unwrap();
+  let tus: Vec<_> = headers
+    .iter()
+    .map(|header| {
+      index
+        .parser(*header)
+        .arguments(&clang_args)
+        .detailed_preprocessing_record(true)
+        .parse()
+        .unwrap()
+    })
+    .collect();
+
+  // Headers including each other declare the same entities, those are merged by name below.
+  let entities: Vec<_> = tus.iter().flat_map(|tu| tu.get_entity().get_children()).collect();
 
   let mut modules = BTreeMap::new();
 
-  let structs = tu
-    .get_entity()
-    .get_children()
-    .into_iter()
+  let structs = entities
+    .iter()
+    .copied()
     .filter(|e| e.get_kind() == EntityKind::TypedefDecl && !e.is_in_system_header())
     .collect::<Vec<_>>();
 
@@ -87,6 +191,18 @@ fn main() {
           }
         }
 
+        TypeKind::Enum => {
+          let variants = enum_variants(e.get_type().unwrap().get_canonical_type().get_declaration().unwrap());
+          entry.children.insert(
+            name.clone(),
+            c::Definition::Enum(c::Enum {
+              name,
+              entity: e,
+              variants,
+            }),
+          );
+        }
+
         _ => {
           entry
             .children
@@ -96,10 +212,80 @@ fn main() {
     }
   }
 
-  let fns = tu
-    .get_entity()
-    .get_children()
-    .into_iter()
+  // Enums with an explicit representation are declared separately from the integer typedef
+  // that is used in signatures, the enum documents the typedef's values.
+  let enums = entities
+    .iter()
+    .copied()
+    .filter(|e| e.get_kind() == EntityKind::EnumDecl && e.get_name().is_some() && !e.is_in_system_header())
+    .collect::<Vec<_>>();
+
+  for e in enums {
+    let (_, config) = parse_comment(e.get_comment().unwrap_or_default());
+    if let Some(module) = config.get("module") {
+      let module = module.clone().unwrap();
+      let name = e.get_name().unwrap();
+
+      modules
+        .entry(module.clone())
+        .or_insert(c::Module {
+          name: module,
+          children: BTreeMap::new(),
+        })
+        .children
+        .insert(
+          name.clone(),
+          c::Definition::Enum(c::Enum {
+            name,
+            entity: e,
+            variants: enum_variants(e),
+          }),
+        );
+    }
+  }
+
+  let constants = entities
+    .iter()
+    .copied()
+    .filter(|e| !e.is_in_system_header())
+    .filter_map(|e| match e.get_kind() {
+      EntityKind::MacroDefinition if !e.is_builtin_macro() && !e.is_function_like_macro() => {
+        macro_comment(e).map(|comment| (e, comment))
+      }
+
+      EntityKind::VarDecl if e.get_type().unwrap().is_const_qualified() => e.get_comment().map(|comment| (e, comment)),
+
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+
+  for (e, comment) in constants {
+    let (_, config) = parse_comment(comment.clone());
+    if let Some(module) = config.get("module") {
+      let module = module.clone().unwrap();
+      let name = e.get_name().unwrap();
+
+      modules
+        .entry(module.clone())
+        .or_insert(c::Module {
+          name: module,
+          children: BTreeMap::new(),
+        })
+        .children
+        .insert(
+          name.clone(),
+          c::Definition::Constant(c::Constant {
+            name,
+            entity: e,
+            comment,
+          }),
+        );
+    }
+  }
+
+  let fns = entities
+    .iter()
+    .copied()
     .filter(|e| e.get_kind() == EntityKind::FunctionDecl && !e.is_in_system_header())
     .collect::<Vec<_>>();
 
@@ -134,31 +320,28 @@ fn main() {
     }
   }
 
-  // {
-  //   let cxx: Vec<_> = modules.iter().map(|(_, x)| x.to_cxx()).collect();
-
-  //   let mut f = std::fs::File::create("./include/project-a.hpp").unwrap();
-  //   f.write_all(
-  //     b"#include <cassert>
+  {
+    let cxx_header = matches.value_of("cxx-header").unwrap();
+    let mut f = std::fs::File::create(cxx_header).unwrap();
+    f.write_all(b"#include <cassert>\n\nnamespace frameui {\n\nnamespace c_api {\n")
+      .unwrap();
 
-  //     namespace frameui {
+    for header in &headers {
+      writeln!(f, "#include \"{}\"", header.file_name().unwrap().to_string_lossy()).unwrap();
+    }
 
-  //     namespace c_api {
-  //       #include \"project-a.h\"
-  //     }",
-  //   )
-  //   .unwrap();
+    f.write_all(b"}").unwrap();
 
-  //   for module in cxx {
-  //     f.write_all(module.as_bytes()).unwrap();
-  //   }
+    for module in modules.values() {
+      f.write_all(module.to_cxx().as_bytes()).unwrap();
+    }
 
-  //   f.write_all(b"}").unwrap();
-  // }
+    f.write_all(b"}").unwrap();
 
-  let _ = std::process::Command::new("clang-format")
-    .args(&["--style=google", "-i", "include/project-a.hpp"])
-    .status();
+    let _ = std::process::Command::new("clang-format")
+      .args(&["--style=google", "-i", cxx_header])
+      .status();
+  }
 
   let mut modules: Vec<_> = modules.iter().map(|(_, x)| x.to_docs()).collect();
   modules.sort_by_key(|x| x.name.clone());
@@ -167,23 +350,77 @@ fn main() {
   keywords.insert("Struct".to_string(), "struct".to_string());
   keywords.insert("Typedef".to_string(), "typedef".to_string());
   keywords.insert("DataStruct".to_string(), "struct".to_string());
+  keywords.insert("Enum".to_string(), "enum".to_string());
+  keywords.insert("Constant".to_string(), "const".to_string());
 
-  let root = doc::Root {
+  let mut roots = vec![doc::Root {
     language: "C".to_string(),
     modules,
     keywords,
-  };
-
-  let json = serde_json::to_string(&root).unwrap();
+  }];
+
+  if let Some(files) = matches.values_of("rustdoc") {
+    let mut modules: Vec<_> = files
+      .flat_map(|file| rust::Crate::parse(&std::fs::read_to_string(file).unwrap()).to_docs())
+      .collect();
+    modules.sort_by_key(|x| x.name.clone());
+
+    let mut keywords = HashMap::new();
+    keywords.insert("Struct".to_string(), "struct".to_string());
+    keywords.insert("Typedef".to_string(), "type".to_string());
+    keywords.insert("DataStruct".to_string(), "struct".to_string());
+
+    roots.push(doc::Root {
+      language: "Rust".to_string(),
+      modules,
+      keywords,
+    });
+  }
 
-  // println!("{}", json);
+  for root in &mut roots {
+    root.extract_examples();
+    root.resolve_links();
+  }
 
-  // println!("{:#?}", modules["event"].to_docs());
+  if matches.is_present("check-examples") {
+    let failed = roots[0].check_examples(&headers, &clang_args);
+    if failed > 0 {
+      eprintln!("{} example(s) failed to compile", failed);
+      std::process::exit(1);
+    }
+  }
 
-  // let docs = modules["event"]["EventHandler"].to_docs();
-  // let json = serde_json::to_string(&docs).unwrap();
+  match matches.value_of("format").unwrap() {
+    "markdown" => {
+      let out_dir = Path::new(matches.value_of("out-dir").unwrap_or("./doc/book/src"));
+      let pages = roots.iter().flat_map(|x| x.to_markdown());
+      for page in pages.chain(std::iter::once(markdown::summary(&roots))) {
+        let path = out_dir.join(&page.path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, page.content).unwrap();
+      }
+    }
 
-  use std::io::prelude::*;
-  let mut f = std::fs::File::create("./doc/src/doc.json").unwrap();
-  f.write_all(json.as_bytes()).unwrap();
+    _ => {
+      let out_dir = Path::new(matches.value_of("out-dir").unwrap_or("./doc/src"));
+      for root in &roots {
+        let json = serde_json::to_string(root).unwrap();
+
+        // The C API predates the other languages and keeps its file names.
+        let (path, search_path) = match root.language.as_str() {
+          "C" => (out_dir.join("doc.json"), out_dir.join("search.json")),
+          language => (
+            out_dir.join(format!("{}.json", language.to_lowercase())),
+            out_dir.join(format!("{}-search.json", language.to_lowercase())),
+          ),
+        };
+
+        let mut f = std::fs::File::create(path).unwrap();
+        f.write_all(json.as_bytes()).unwrap();
+
+        let search = serde_json::to_string(&root.search_index()).unwrap();
+        std::fs::write(search_path, search).unwrap();
+      }
+    }
+  }
 }
@@ -0,0 +1,303 @@
+use std::fmt::Write;
+
+use super::doc;
+
+/// A generated Markdown file, `path` is relative to the output directory.
+#[derive(Debug, Clone)]
+pub struct Page {
+  pub path: String,
+  pub content: String,
+}
+
+// Comments without a description only hold the `module=...` config, which
+// clang still reports as the brief.
+fn table_brief(brief: &Option<String>, description: &Option<String>) -> String {
+  match (brief, description) {
+    (Some(brief), Some(_)) => brief.replace('\n', " ").replace('|', "\\|"),
+    _ => String::new(),
+  }
+}
+
+fn write_description(out: &mut String, description: &Option<String>) {
+  if let Some(description) = description {
+    for line in description.lines() {
+      writeln!(out, "{}", line.trim()).unwrap();
+    }
+    writeln!(out).unwrap();
+  }
+}
+
+/// Renders the `SUMMARY.md` mdBook expects, with one part per language.
+pub fn summary(roots: &[doc::Root]) -> Page {
+  let mut out = String::from("# Summary\n");
+
+  for root in roots {
+    let dir = root.dir();
+    writeln!(out, "\n# {}\n", root.language).unwrap();
+
+    for module in &root.modules {
+      writeln!(out, "- [{1}]({0}/{1}/index.md)", dir, module.name).unwrap();
+      for child in &module.children {
+        match child {
+          doc::Definition::Struct(doc::Struct { name, .. })
+          | doc::Definition::DataStruct(doc::DataStruct { name, .. })
+          | doc::Definition::Enum(doc::Enum { name, .. }) => {
+            writeln!(out, "  - [{2}]({0}/{1}/{2}.md)", dir, module.name, name).unwrap();
+          }
+
+          doc::Definition::Typedef(_) | doc::Definition::Constant(_) => {}
+        }
+      }
+    }
+  }
+
+  Page {
+    path: "SUMMARY.md".to_string(),
+    content: out,
+  }
+}
+
+impl doc::Root {
+  /// Renders one page per module and per struct, below a directory named after the language.
+  pub fn to_markdown(&self) -> Vec<Page> {
+    self
+      .modules
+      .iter()
+      .flat_map(|module| self.module_to_markdown(module))
+      .collect()
+  }
+
+  fn dir(&self) -> String {
+    self.language.to_lowercase()
+  }
+
+  fn keyword<'a>(&'a self, ty: &'a str) -> &'a str {
+    self.keywords.get(ty).map_or(ty, String::as_str)
+  }
+
+  // Pages are always one directory below the language, next to the other modules.
+  fn link_path(&self, link: &doc::Link) -> String {
+    let on_index = self
+      .modules
+      .iter()
+      .filter(|x| x.name == link.module)
+      .flat_map(|x| &x.children)
+      .any(|x| match x {
+        doc::Definition::Typedef(x) => x.name == link.item,
+        doc::Definition::Constant(x) => x.name == link.item,
+        _ => false,
+      });
+
+    if on_index {
+      format!("../{}/index.md#{}", link.module, link.item.to_lowercase())
+    } else {
+      format!("../{}/{}.md", link.module, link.item)
+    }
+  }
+
+  fn see_also(&self, links: &[doc::Link]) -> Option<String> {
+    if links.is_empty() {
+      return None;
+    }
+
+    let links: Vec<_> = links
+      .iter()
+      .map(|x| format!("[`{}`]({})", x.name, self.link_path(x)))
+      .collect();
+    Some(format!("See {}.", links.join(", ")))
+  }
+
+  fn code_block(&self, out: &mut String, code: &str) {
+    writeln!(out, "```{}\n{}\n```\n", self.dir(), code).unwrap();
+  }
+
+  fn write_examples(&self, out: &mut String, examples: &[doc::Example]) {
+    for example in examples {
+      writeln!(out, "```{}\n{}```\n", example.language, example.code).unwrap();
+    }
+  }
+
+  fn module_to_markdown(&self, module: &doc::Module) -> Vec<Page> {
+    let mut pages = Vec::new();
+    let mut index = format!("# Module `{}`\n\n", module.name);
+
+    index += "| Name | Kind | Description |\n| --- | --- | --- |\n";
+    for child in &module.children {
+      let (kind, name, link, brief) = match child {
+        doc::Definition::Struct(x) => (
+          "Struct",
+          &x.name,
+          format!("{}.md", x.name),
+          table_brief(&x.brief, &x.description),
+        ),
+        doc::Definition::DataStruct(x) => (
+          "DataStruct",
+          &x.name,
+          format!("{}.md", x.name),
+          table_brief(&x.brief, &x.description),
+        ),
+        doc::Definition::Enum(x) => (
+          "Enum",
+          &x.name,
+          format!("{}.md", x.name),
+          table_brief(&x.brief, &x.description),
+        ),
+        doc::Definition::Typedef(x) => (
+          "Typedef",
+          &x.name,
+          format!("#{}", x.name.to_lowercase()),
+          table_brief(&x.brief, &x.description),
+        ),
+        doc::Definition::Constant(x) => (
+          "Constant",
+          &x.name,
+          format!("#{}", x.name.to_lowercase()),
+          table_brief(&x.brief, &x.description),
+        ),
+      };
+
+      writeln!(index, "| [`{}`]({}) | {} | {} |", name, link, self.keyword(kind), brief).unwrap();
+    }
+
+    let typedefs: Vec<_> = module
+      .children
+      .iter()
+      .filter_map(|x| match x {
+        doc::Definition::Typedef(x) => Some(x),
+        _ => None,
+      })
+      .collect();
+
+    if !typedefs.is_empty() {
+      index += "\n## Typedefs\n\n";
+      for typedef in typedefs {
+        writeln!(index, "### {}\n", typedef.name).unwrap();
+        self.code_block(&mut index, &typedef.declaration);
+        write_description(&mut index, &typedef.description);
+        self.write_examples(&mut index, &typedef.examples);
+        write_description(&mut index, &self.see_also(&typedef.links));
+      }
+    }
+
+    let constants: Vec<_> = module
+      .children
+      .iter()
+      .filter_map(|x| match x {
+        doc::Definition::Constant(x) => Some(x),
+        _ => None,
+      })
+      .collect();
+
+    if !constants.is_empty() {
+      index += "\n## Constants\n\n";
+      for constant in constants {
+        writeln!(index, "### {}\n", constant.name).unwrap();
+        self.code_block(&mut index, &constant.declaration);
+        write_description(&mut index, &constant.description);
+        self.write_examples(&mut index, &constant.examples);
+        write_description(&mut index, &self.see_also(&constant.links));
+      }
+    }
+
+    pages.push(Page {
+      path: format!("{}/{}/index.md", self.dir(), module.name),
+      content: index,
+    });
+
+    for child in &module.children {
+      match child {
+        doc::Definition::Struct(x) => pages.push(self.struct_to_markdown(module, x)),
+        doc::Definition::DataStruct(x) => pages.push(self.data_struct_to_markdown(module, x)),
+        doc::Definition::Enum(x) => pages.push(self.enum_to_markdown(module, x)),
+        doc::Definition::Typedef(_) | doc::Definition::Constant(_) => {}
+      }
+    }
+
+    pages
+  }
+
+  fn struct_to_markdown(&self, module: &doc::Module, data: &doc::Struct) -> Page {
+    let mut out = format!("# {} `{}`\n\n", self.keyword("Struct"), data.name);
+    write_description(&mut out, &data.description);
+    self.write_examples(&mut out, &data.examples);
+    write_description(&mut out, &self.see_also(&data.links));
+
+    out += "## Methods\n\n| Method | Description |\n| --- | --- |\n";
+    for method in &data.methods {
+      writeln!(
+        out,
+        "| [`{0}`](#{0}) | {1} |",
+        method.name,
+        table_brief(&method.brief, &method.description)
+      )
+      .unwrap();
+    }
+
+    for method in &data.methods {
+      writeln!(out, "\n### {}\n", method.name).unwrap();
+      self.code_block(&mut out, &method.declaration);
+      write_description(&mut out, &method.description);
+      self.write_examples(&mut out, &method.examples);
+      write_description(&mut out, &self.see_also(&method.links));
+    }
+
+    Page {
+      path: format!("{}/{}/{}.md", self.dir(), module.name, data.name),
+      content: out,
+    }
+  }
+
+  fn data_struct_to_markdown(&self, module: &doc::Module, data: &doc::DataStruct) -> Page {
+    let mut out = format!("# {} `{}`\n\n", self.keyword("DataStruct"), data.name);
+    write_description(&mut out, &data.description);
+    self.write_examples(&mut out, &data.examples);
+    write_description(&mut out, &self.see_also(&data.links));
+
+    out += "## Fields\n\n| Field | Declaration | Description |\n| --- | --- | --- |\n";
+    for field in &data.fields {
+      writeln!(
+        out,
+        "| `{}` | `{}` | {} |",
+        field.name,
+        field.declaration,
+        field
+          .brief
+          .iter()
+          .chain(&self.see_also(&field.links))
+          .map(|x| x.replace('|', "\\|"))
+          .collect::<Vec<_>>()
+          .join(" ")
+      )
+      .unwrap();
+    }
+
+    Page {
+      path: format!("{}/{}/{}.md", self.dir(), module.name, data.name),
+      content: out,
+    }
+  }
+
+  fn enum_to_markdown(&self, module: &doc::Module, data: &doc::Enum) -> Page {
+    let mut out = format!("# {} `{}`\n\n", self.keyword("Enum"), data.name);
+    write_description(&mut out, &data.description);
+    self.write_examples(&mut out, &data.examples);
+    write_description(&mut out, &self.see_also(&data.links));
+
+    out += "## Variants\n\n| Variant | Value | Description |\n| --- | --- | --- |\n";
+    for variant in &data.variants {
+      writeln!(
+        out,
+        "| `{}` | `{}` | {} |",
+        variant.name,
+        variant.value,
+        table_brief(&variant.brief, &variant.description)
+      )
+      .unwrap();
+    }
+
+    Page {
+      path: format!("{}/{}/{}.md", self.dir(), module.name, data.name),
+      content: out,
+    }
+  }
+}
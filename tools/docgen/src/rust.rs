@@ -0,0 +1,252 @@
+use serde_json::Value;
+
+use std::collections::BTreeMap;
+
+use super::doc;
+
+/// A crate documented by `rustdoc --output-format json`.
+pub struct Crate {
+  root: String,
+  index: BTreeMap<String, Value>,
+}
+
+fn split_docs(docs: Option<&str>) -> (Option<String>, Option<String>) {
+  let docs = match docs.map(str::trim) {
+    Some(docs) if !docs.is_empty() => docs,
+    _ => return (None, None),
+  };
+
+  let brief = docs.split("\n\n").next().unwrap().replace('\n', " ");
+  (Some(brief), Some(docs.to_string()))
+}
+
+fn print_generic_args(args: &Value) -> String {
+  let args: Vec<_> = args["angle_bracketed"]["args"]
+    .as_array()
+    .map(|args| {
+      args
+        .iter()
+        .filter_map(|arg| match arg.get("type") {
+          Some(ty) => Some(print_type(ty)),
+          None => arg["lifetime"].as_str().map(str::to_string),
+        })
+        .collect()
+    })
+    .unwrap_or_default();
+
+  if args.is_empty() {
+    String::new()
+  } else {
+    format!("<{}>", args.join(", "))
+  }
+}
+
+fn print_type(ty: &Value) -> String {
+  let inner = &ty["inner"];
+  match ty["kind"].as_str().unwrap_or_default() {
+    "resolved_path" => format!(
+      "{}{}",
+      inner["name"].as_str().unwrap_or_default(),
+      print_generic_args(&inner["args"])
+    ),
+    "generic" | "primitive" => inner.as_str().unwrap_or_default().to_string(),
+    "tuple" => format!(
+      "({})",
+      inner
+        .as_array()
+        .map(|x| x.iter().map(print_type).collect::<Vec<_>>().join(", "))
+        .unwrap_or_default()
+    ),
+    "slice" => format!("[{}]", print_type(inner)),
+    "array" => format!(
+      "[{}; {}]",
+      print_type(&inner["type"]),
+      inner["len"].as_str().unwrap_or_default()
+    ),
+    "raw_pointer" => format!(
+      "*{} {}",
+      if inner["mutable"].as_bool().unwrap_or_default() {
+        "mut"
+      } else {
+        "const"
+      },
+      print_type(&inner["type"])
+    ),
+    "borrowed_ref" => format!(
+      "&{}{}{}",
+      inner["lifetime"]
+        .as_str()
+        .map(|x| format!("{} ", x))
+        .unwrap_or_default(),
+      if inner["mutable"].as_bool().unwrap_or_default() {
+        "mut "
+      } else {
+        ""
+      },
+      print_type(&inner["type"])
+    ),
+    "qualified_path" => format!(
+      "{}::{}",
+      print_type(&inner["self_type"]),
+      inner["name"].as_str().unwrap_or_default()
+    ),
+    "impl_trait" => "impl Trait".to_string(),
+    _ => "_".to_string(),
+  }
+}
+
+fn print_fn(name: &str, decl: &Value) -> String {
+  let inputs: Vec<_> = decl["inputs"]
+    .as_array()
+    .map(|inputs| {
+      inputs
+        .iter()
+        .map(|input| {
+          let name = input[0].as_str().unwrap_or_default();
+          let ty = &input[1];
+          match (name, print_type(ty).as_str()) {
+            ("self", "Self") => "self".to_string(),
+            ("self", "&Self") => "&self".to_string(),
+            ("self", "&mut Self") => "&mut self".to_string(),
+            (name, ty) => format!("{}: {}", name, ty),
+          }
+        })
+        .collect()
+    })
+    .unwrap_or_default();
+
+  let output = match &decl["output"] {
+    Value::Null => String::new(),
+    output => format!(" -> {}", print_type(output)),
+  };
+
+  format!("pub fn {}({}){}", name, inputs.join(", "), output)
+}
+
+impl Crate {
+  pub fn parse(source: &str) -> Crate {
+    let json: Value = serde_json::from_str(source).unwrap();
+    Crate {
+      root: json["root"].as_str().unwrap().to_string(),
+      index: serde_json::from_value(json["index"].clone()).unwrap(),
+    }
+  }
+
+  fn item(&self, id: &Value) -> Option<&Value> {
+    id.as_str().and_then(|id| self.index.get(id))
+  }
+
+  fn ids<'a>(&'a self, ids: &'a Value) -> impl Iterator<Item = &'a Value> + 'a {
+    ids
+      .as_array()
+      .into_iter()
+      .flatten()
+      .filter_map(move |id| self.item(id))
+      .filter(|item| item["visibility"] == "public")
+  }
+
+  /// Collects every public module with documentable items, named by their full path.
+  pub fn to_docs(&self) -> Vec<doc::Module> {
+    let mut modules = Vec::new();
+    if let Some(root) = self.index.get(&self.root) {
+      self.collect_module(
+        root,
+        root["name"].as_str().unwrap_or_default().to_string(),
+        &mut modules,
+      );
+    }
+
+    modules
+  }
+
+  fn collect_module(&self, module: &Value, path: String, modules: &mut Vec<doc::Module>) {
+    let mut children = Vec::new();
+
+    for item in self.ids(&module["inner"]["items"]) {
+      let name = item["name"].as_str().unwrap_or_default().to_string();
+      let (brief, description) = split_docs(item["docs"].as_str());
+
+      match item["kind"].as_str().unwrap_or_default() {
+        "module" => self.collect_module(item, format!("{}::{}", path, name), modules),
+
+        "struct" => {
+          let methods = self.methods(item);
+          if methods.is_empty() {
+            let fields = self
+              .ids(&item["inner"]["fields"])
+              .map(|field| {
+                let (brief, description) = split_docs(field["docs"].as_str());
+                let name = field["name"].as_str().unwrap_or_default().to_string();
+                doc::Field {
+                  declaration: format!("pub {}: {}", name, print_type(&field["inner"])),
+                  name,
+                  brief,
+                  description,
+                  links: Vec::new(),
+                }
+              })
+              .collect();
+
+            children.push(doc::Definition::DataStruct(doc::DataStruct {
+              name,
+              brief,
+              description,
+              fields,
+              examples: Vec::new(),
+              links: Vec::new(),
+            }));
+          } else {
+            children.push(doc::Definition::Struct(doc::Struct {
+              name,
+              brief,
+              description,
+              methods,
+              examples: Vec::new(),
+              links: Vec::new(),
+            }));
+          }
+        }
+
+        "typedef" => children.push(doc::Definition::Typedef(doc::Typedef {
+          declaration: format!("pub type {} = {};", name, print_type(&item["inner"]["type"])),
+          name,
+          brief,
+          description,
+          examples: Vec::new(),
+          links: Vec::new(),
+        })),
+
+        _ => {}
+      }
+    }
+
+    if !children.is_empty() {
+      modules.push(doc::Module { name: path, children });
+    }
+  }
+
+  // Only inherent methods, trait implementations are documented by their trait.
+  fn methods(&self, item: &Value) -> Vec<doc::Method> {
+    item["inner"]["impls"]
+      .as_array()
+      .into_iter()
+      .flatten()
+      .filter_map(|id| self.item(id))
+      .filter(|imp| imp["inner"]["trait"].is_null())
+      .flat_map(|imp| self.ids(&imp["inner"]["items"]))
+      .filter(|item| item["kind"] == "method" || item["kind"] == "function")
+      .map(|item| {
+        let name = item["name"].as_str().unwrap_or_default().to_string();
+        let (brief, description) = split_docs(item["docs"].as_str());
+        doc::Method {
+          declaration: print_fn(&name, &item["inner"]["decl"]),
+          name,
+          brief,
+          description,
+          examples: Vec::new(),
+          links: Vec::new(),
+        }
+      })
+      .collect()
+  }
+}
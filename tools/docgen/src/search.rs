@@ -0,0 +1,77 @@
+use serde::Serialize;
+
+use super::doc;
+
+/// An entry of the search index, `anchor` is the path of the entry's page below the language.
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+  pub name: String,
+  pub kind: String,
+  pub module: String,
+  pub brief: Option<String>,
+  pub anchor: String,
+}
+
+// See `markdown::table_brief`, the brief of an undescribed C item is its config line.
+fn brief(brief: &Option<String>, description: &Option<String>) -> Option<String> {
+  description.as_ref().and(brief.clone())
+}
+
+impl doc::Root {
+  /// Lists every definition and member, members are named after their parent, e.g. `EventHandler::drop`.
+  pub fn search_index(&self) -> Vec<Entry> {
+    let mut entries = Vec::new();
+
+    for module in &self.modules {
+      for child in &module.children {
+        let (kind, parent_brief) = match child {
+          doc::Definition::Typedef(x) => ("Typedef", brief(&x.brief, &x.description)),
+          doc::Definition::Struct(x) => ("Struct", brief(&x.brief, &x.description)),
+          doc::Definition::DataStruct(x) => ("DataStruct", brief(&x.brief, &x.description)),
+          doc::Definition::Enum(x) => ("Enum", brief(&x.brief, &x.description)),
+          doc::Definition::Constant(x) => ("Constant", brief(&x.brief, &x.description)),
+        };
+
+        let parent = child.name();
+        let anchor = format!("{}/{}", module.name, parent);
+        let member = |kind: &str, name: &str, brief| Entry {
+          name: format!("{}::{}", parent, name),
+          kind: kind.to_string(),
+          module: module.name.clone(),
+          brief,
+          anchor: format!("{}#{}", anchor, name),
+        };
+
+        let members: Vec<_> = match child {
+          doc::Definition::Struct(x) => x
+            .methods
+            .iter()
+            .map(|x| member("Method", &x.name, brief(&x.brief, &x.description)))
+            .collect(),
+          doc::Definition::DataStruct(x) => x
+            .fields
+            .iter()
+            .map(|x| member("Field", &x.name, x.brief.clone()))
+            .collect(),
+          doc::Definition::Enum(x) => x
+            .variants
+            .iter()
+            .map(|x| member("Variant", &x.name, brief(&x.brief, &x.description)))
+            .collect(),
+          doc::Definition::Typedef(_) | doc::Definition::Constant(_) => Vec::new(),
+        };
+
+        entries.push(Entry {
+          name: parent.to_string(),
+          kind: kind.to_string(),
+          module: module.name.clone(),
+          brief: parent_brief,
+          anchor,
+        });
+        entries.extend(members);
+      }
+    }
+
+    entries
+  }
+}